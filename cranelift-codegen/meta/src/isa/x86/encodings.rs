@@ -1196,6 +1196,7 @@ fn define_fpu_ops(
     let fdiv = shared.by_name("fdiv");
     let ffcmp = shared.by_name("ffcmp");
     let floor = shared.by_name("floor");
+    let fma = shared.by_name("fma");
     let fmul = shared.by_name("fmul");
     let fpromote = shared.by_name("fpromote");
     let fsub = shared.by_name("fsub");
@@ -1216,8 +1217,10 @@ fn define_fpu_ops(
     let rec_furm = r.template("furm");
     let rec_furmi_rnd = r.template("furmi_rnd");
     let rec_rfurm = r.template("rfurm");
+    let rec_vfma = r.template("vfma");
 
     // Predicates shorthands.
+    let use_fma = settings.predicate_by_name("use_fma");
     let use_sse41 = settings.predicate_by_name("use_sse41");
 
     // Floating-point constants equal to 0.0 can be encoded using either `xorps` or `xorpd`, for
@@ -1302,6 +1305,10 @@ fn define_fpu_ops(
     e.enc_both(x86_fmax.bind(F32), rec_fa.opcodes(&MAXSS));
     e.enc_both(x86_fmax.bind(F64), rec_fa.opcodes(&MAXSD));
 
+    // Fused multiply-add, only available in its VEX form.
+    e.enc64_isap(fma.bind(F32), rec_vfma.opcodes(&VFMADD231S), use_fma);
+    e.enc64_isap(fma.bind(F64), rec_vfma.opcodes(&VFMADD231S).w(), use_fma);
+
     // Comparisons.
     //
     // This only covers the condition codes in `supported_floatccs`, the rest are
@@ -1570,6 +1577,7 @@ fn define_simd(
     let fdiv = shared.by_name("fdiv");
     let fill = shared.by_name("fill");
     let fill_nop = shared.by_name("fill_nop");
+    let fma = shared.by_name("fma");
     let fmax = shared.by_name("fmax");
     let fmin = shared.by_name("fmin");
     let fmul = shared.by_name("fmul");
@@ -1586,6 +1594,7 @@ fn define_simd(
     let sadd_sat = shared.by_name("sadd_sat");
     let scalar_to_vector = shared.by_name("scalar_to_vector");
     let spill = shared.by_name("spill");
+    let splat = shared.by_name("splat");
     let sqrt = shared.by_name("sqrt");
     let sshr_imm = shared.by_name("sshr_imm");
     let ssub_sat = shared.by_name("ssub_sat");
@@ -1638,11 +1647,22 @@ fn define_simd(
     let rec_r_ib_unsigned_r = r.template("r_ib_unsigned_r");
     let rec_stacknull = r.recipe("stacknull");
     let rec_vconst = r.template("vconst");
+    let rec_vbroadcast_r = r.template("vbroadcast_r");
+    let rec_vbroadcast_rw = r.template("vbroadcast_rw");
     let rec_vconst_optimized = r.template("vconst_optimized");
+    let rec_vfa = r.template("vfa");
+    let rec_vfax = r.template("vfax");
+    let rec_vfma = r.template("vfma");
+    let rec_vfurm = r.template("vfurm");
+    let rec_vicscc_fpr = r.template("vicscc_fpr");
+    let rec_vpfcmp = r.template("vpfcmp");
 
     // Predicates shorthands.
     settings.predicate_by_name("all_ones_funcaddrs_and_not_is_pic");
     settings.predicate_by_name("not_all_ones_funcaddrs_and_not_is_pic");
+    let use_avx = settings.predicate_by_name("use_avx");
+    let use_avx2_simd = settings.predicate_by_name("use_avx2_simd");
+    let use_fma = settings.predicate_by_name("use_fma");
     let use_ssse3_simd = settings.predicate_by_name("use_ssse3_simd");
     let use_sse41_simd = settings.predicate_by_name("use_sse41_simd");
     let use_sse42_simd = settings.predicate_by_name("use_sse42_simd");
@@ -1657,6 +1677,21 @@ fn define_simd(
 
     let allowed_simd_type = |t: &LaneType| t.lane_bits() >= 8 && t.lane_bits() < 128;
 
+    // With AVX2, a splat is a single broadcast from the low lane of an XMM register, after moving
+    // an integer scalar there, so it doesn't need to be legalized.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        let splat = splat.bind(vector(ty, sse_vector_size));
+        let template = match (ty.is_float(), ty.lane_bits()) {
+            (true, 32) => rec_vfurm.opcodes(&VBROADCASTSS),
+            (true, _) => rec_vfurm.opcodes(&VPBROADCASTQ),
+            (false, 8) => rec_vbroadcast_r.opcodes(&VPBROADCASTB),
+            (false, 16) => rec_vbroadcast_r.opcodes(&VPBROADCASTW),
+            (false, 32) => rec_vbroadcast_r.opcodes(&VPBROADCASTD),
+            (false, _) => rec_vbroadcast_rw.opcodes(&VPBROADCASTQ),
+        };
+        e.enc64_isap(splat, template, use_avx2_simd);
+    }
+
     // PSHUFB, 8-bit shuffle using two XMM registers.
     for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
         let instruction = x86_pshufb.bind(vector(ty, sse_vector_size));
@@ -1867,25 +1902,50 @@ fn define_simd(
         e.enc_32_64_rec(bound_copy_nop, rec_stacknull, 0);
     }
 
+    // SIMD arithmetic: when AVX is available, the three-operand VEX forms are preferred over the
+    // two-operand SSE forms, which require their first input to be tied to the output. The VEX
+    // recipes may use all sixteen XMM registers, so they are only available on x86_64.
+
     // SIMD integer addition
     for (ty, opcodes) in &[(I8, &PADDB), (I16, &PADDW), (I32, &PADDD), (I64, &PADDQ)] {
         let iadd = iadd.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(iadd.clone(), rec_vfa.opcodes(*opcodes), use_avx);
         e.enc_32_64(iadd, rec_fa.opcodes(*opcodes));
     }
 
     // SIMD integer saturating addition
+    e.enc64_isap(
+        sadd_sat.bind(vector(I8, sse_vector_size)),
+        rec_vfa.opcodes(&PADDSB),
+        use_avx,
+    );
     e.enc_32_64(
         sadd_sat.bind(vector(I8, sse_vector_size)),
         rec_fa.opcodes(&PADDSB),
     );
+    e.enc64_isap(
+        sadd_sat.bind(vector(I16, sse_vector_size)),
+        rec_vfa.opcodes(&PADDSW),
+        use_avx,
+    );
     e.enc_32_64(
         sadd_sat.bind(vector(I16, sse_vector_size)),
         rec_fa.opcodes(&PADDSW),
     );
+    e.enc64_isap(
+        uadd_sat.bind(vector(I8, sse_vector_size)),
+        rec_vfa.opcodes(&PADDUSB),
+        use_avx,
+    );
     e.enc_32_64(
         uadd_sat.bind(vector(I8, sse_vector_size)),
         rec_fa.opcodes(&PADDUSB),
     );
+    e.enc64_isap(
+        uadd_sat.bind(vector(I16, sse_vector_size)),
+        rec_vfa.opcodes(&PADDUSW),
+        use_avx,
+    );
     e.enc_32_64(
         uadd_sat.bind(vector(I16, sse_vector_size)),
         rec_fa.opcodes(&PADDUSW),
@@ -1895,22 +1955,43 @@ fn define_simd(
     let isub = shared.by_name("isub");
     for (ty, opcodes) in &[(I8, &PSUBB), (I16, &PSUBW), (I32, &PSUBD), (I64, &PSUBQ)] {
         let isub = isub.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(isub.clone(), rec_vfa.opcodes(*opcodes), use_avx);
         e.enc_32_64(isub, rec_fa.opcodes(*opcodes));
     }
 
     // SIMD integer saturating subtraction
+    e.enc64_isap(
+        ssub_sat.bind(vector(I8, sse_vector_size)),
+        rec_vfa.opcodes(&PSUBSB),
+        use_avx,
+    );
     e.enc_32_64(
         ssub_sat.bind(vector(I8, sse_vector_size)),
         rec_fa.opcodes(&PSUBSB),
     );
+    e.enc64_isap(
+        ssub_sat.bind(vector(I16, sse_vector_size)),
+        rec_vfa.opcodes(&PSUBSW),
+        use_avx,
+    );
     e.enc_32_64(
         ssub_sat.bind(vector(I16, sse_vector_size)),
         rec_fa.opcodes(&PSUBSW),
     );
+    e.enc64_isap(
+        usub_sat.bind(vector(I8, sse_vector_size)),
+        rec_vfa.opcodes(&PSUBUSB),
+        use_avx,
+    );
     e.enc_32_64(
         usub_sat.bind(vector(I8, sse_vector_size)),
         rec_fa.opcodes(&PSUBUSB),
     );
+    e.enc64_isap(
+        usub_sat.bind(vector(I16, sse_vector_size)),
+        rec_vfa.opcodes(&PSUBUSW),
+        use_avx,
+    );
     e.enc_32_64(
         usub_sat.bind(vector(I16, sse_vector_size)),
         rec_fa.opcodes(&PSUBUSW),
@@ -1923,6 +2004,7 @@ fn define_simd(
         (I32, &PMULLD[..], Some(use_sse41_simd)),
     ] {
         let imul = imul.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(imul.clone(), rec_vfa.opcodes(opcodes), use_avx);
         e.enc_32_64_maybe_isap(imul, rec_fa.opcodes(opcodes), *isap);
     }
 
@@ -1932,18 +2014,22 @@ fn define_simd(
     for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
         // and
         let band = band.bind(vector(ty, sse_vector_size));
+        e.enc64_isap(band.clone(), rec_vfa.opcodes(&PAND), use_avx);
         e.enc_32_64(band, rec_fa.opcodes(&PAND));
 
         // and not (note flipped recipe operands to match band_not order)
         let band_not = band_not.bind(vector(ty, sse_vector_size));
+        e.enc64_isap(band_not.clone(), rec_vfax.opcodes(&PANDN), use_avx);
         e.enc_32_64(band_not, rec_fax.opcodes(&PANDN));
 
         // or
         let bor = bor.bind(vector(ty, sse_vector_size));
+        e.enc64_isap(bor.clone(), rec_vfa.opcodes(&POR), use_avx);
         e.enc_32_64(bor, rec_fa.opcodes(&POR));
 
         // xor
         let bxor = bxor.bind(vector(ty, sse_vector_size));
+        e.enc64_isap(bxor.clone(), rec_vfa.opcodes(&PXOR), use_avx);
         e.enc_32_64(bxor, rec_fa.opcodes(&PXOR));
 
         // ptest
@@ -1968,18 +2054,21 @@ fn define_simd(
     // SIMD shift left
     for (ty, opcodes) in &[(I16, &PSLLW), (I32, &PSLLD), (I64, &PSLLQ)] {
         let x86_psll = x86_psll.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(x86_psll.clone(), rec_vfa.opcodes(*opcodes), use_avx);
         e.enc_32_64(x86_psll, rec_fa.opcodes(*opcodes));
     }
 
     // SIMD shift right (logical)
     for (ty, opcodes) in &[(I16, &PSRLW), (I32, &PSRLD), (I64, &PSRLQ)] {
         let x86_psrl = x86_psrl.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(x86_psrl.clone(), rec_vfa.opcodes(*opcodes), use_avx);
         e.enc_32_64(x86_psrl, rec_fa.opcodes(*opcodes));
     }
 
     // SIMD shift right (arithmetic)
     for (ty, opcodes) in &[(I16, &PSRAW), (I32, &PSRAD)] {
         let x86_psra = x86_psra.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(x86_psra.clone(), rec_vfa.opcodes(*opcodes), use_avx);
         e.enc_32_64(x86_psra, rec_fa.opcodes(*opcodes));
     }
 
@@ -2011,6 +2100,11 @@ fn define_simd(
             let instruction = icmp
                 .bind(Immediate::IntCC(*cc))
                 .bind(vector(*ty, sse_vector_size));
+            e.enc64_isap(
+                instruction.clone(),
+                rec_vicscc_fpr.opcodes(opcodes),
+                use_avx,
+            );
             let template = rec_icscc_fpr.nonrex().opcodes(opcodes);
             e.enc_32_64_maybe_isap(instruction, template, *isa_predicate);
         }
@@ -2032,10 +2126,21 @@ fn define_simd(
         (I32, x86_pminu, &PMINUD[..], Some(use_sse41_simd)),
     ] {
         let inst = inst.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(inst.clone(), rec_vfa.opcodes(opcodes), use_avx);
         e.enc_32_64_maybe_isap(inst, rec_fa.opcodes(opcodes), *isa_predicate);
     }
 
     // SIMD float comparisons
+    e.enc64_isap(
+        fcmp.bind(vector(F32, sse_vector_size)),
+        rec_vpfcmp.opcodes(&CMPPS),
+        use_avx,
+    );
+    e.enc64_isap(
+        fcmp.bind(vector(F64, sse_vector_size)),
+        rec_vpfcmp.opcodes(&CMPPD),
+        use_avx,
+    );
     e.enc_both(
         fcmp.bind(vector(F32, sse_vector_size)),
         rec_pfcmp.opcodes(&CMPPS),
@@ -2061,12 +2166,25 @@ fn define_simd(
         (F64, fmax, &MAXPD[..]),
     ] {
         let inst = inst.bind(vector(*ty, sse_vector_size));
+        e.enc64_isap(inst.clone(), rec_vfa.opcodes(opcodes), use_avx);
         e.enc_both(inst, rec_fa.opcodes(opcodes));
     }
     for (ty, inst, opcodes) in &[(F32, sqrt, &SQRTPS[..]), (F64, sqrt, &SQRTPD[..])] {
         let inst = inst.bind(vector(*ty, sse_vector_size));
         e.enc_both(inst, rec_furm.opcodes(opcodes));
     }
+
+    // SIMD fused multiply-add
+    e.enc64_isap(
+        fma.bind(vector(F32, sse_vector_size)),
+        rec_vfma.opcodes(&VFMADD231P),
+        use_fma,
+    );
+    e.enc64_isap(
+        fma.bind(vector(F64, sse_vector_size)),
+        rec_vfma.opcodes(&VFMADD231P).w(),
+        use_fma,
+    );
 }
 
 #[inline(never)]
@@ -2212,6 +2330,8 @@ fn define_control_flow(
     let jump_table_base = shared.by_name("jump_table_base");
     let jump_table_entry = shared.by_name("jump_table_entry");
     let return_ = shared.by_name("return");
+    let return_call = shared.by_name("return_call");
+    let return_call_indirect = shared.by_name("return_call_indirect");
     let trap = shared.by_name("trap");
     let trapff = shared.by_name("trapff");
     let trapif = shared.by_name("trapif");
@@ -2232,6 +2352,9 @@ fn define_control_flow(
     let rec_jt_base = r.template("jt_base");
     let rec_jt_entry = r.template("jt_entry");
     let rec_ret = r.template("ret");
+    let rec_tail_call_id = r.template("tail_call_id");
+    let rec_tail_call_plt_id = r.template("tail_call_plt_id");
+    let rec_tail_call_r = r.template("tail_call_r");
     let rec_t8jccb_abcd = r.template("t8jccb_abcd");
     let rec_t8jccd_abcd = r.template("t8jccd_abcd");
     let rec_t8jccd_long = r.template("t8jccd_long");
@@ -2274,6 +2397,34 @@ fn define_control_flow(
     e.enc32(return_, rec_ret.opcodes(&RET_NEAR));
     e.enc64(return_, rec_ret.opcodes(&RET_NEAR));
 
+    // Tail calls jump to the callee once the epilogue has run. Like calls, 64-bit non-colocated
+    // non-PIC tail calls are legalized to func_addr+return_call_indirect.
+    e.enc32(return_call, rec_tail_call_id.opcodes(&JUMP_NEAR_RELATIVE));
+    let is_colocated_func = InstructionPredicate::new_is_colocated_func(&*formats.call, "func_ref");
+    e.enc64_instp(
+        return_call,
+        rec_tail_call_id.opcodes(&JUMP_NEAR_RELATIVE),
+        is_colocated_func,
+    );
+    e.enc64_isap(
+        return_call,
+        rec_tail_call_plt_id.opcodes(&JUMP_NEAR_RELATIVE),
+        is_pic,
+    );
+
+    e.enc32(
+        return_call_indirect.bind(I32),
+        rec_tail_call_r.opcodes(&JUMP_ABSOLUTE).rrr(4),
+    );
+    e.enc64(
+        return_call_indirect.bind(I64),
+        rec_tail_call_r.opcodes(&JUMP_ABSOLUTE).rrr(4).rex(),
+    );
+    e.enc64(
+        return_call_indirect.bind(I64),
+        rec_tail_call_r.opcodes(&JUMP_ABSOLUTE).rrr(4),
+    );
+
     // Branches.
     e.enc32(jump, rec_jmpb.opcodes(&JUMP_SHORT));
     e.enc64(jump, rec_jmpb.opcodes(&JUMP_SHORT));
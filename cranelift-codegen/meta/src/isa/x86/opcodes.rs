@@ -579,6 +579,30 @@ pub static UCOMISS: [u8; 2] = [0x0f, 0x2e];
 /// Raise invalid opcode instruction.
 pub static UNDEFINED2: [u8; 2] = [0x0f, 0x0b];
 
+/// Broadcast single-precision floating-point value from xmm2/m32 to all lanes of xmm1 (AVX2 for
+/// the register source).
+pub static VBROADCASTSS: [u8; 4] = [0x66, 0x0f, 0x38, 0x18];
+
+/// Multiply packed single- or double-precision floating-point values in xmm2 and xmm3/m128, add
+/// to xmm1 and put the result in xmm1, depending on VEX.W (FMA).
+pub static VFMADD231P: [u8; 4] = [0x66, 0x0f, 0x38, 0xb8];
+
+/// Multiply scalar single- or double-precision floating-point values in xmm2 and xmm3/m, add to
+/// xmm1 and put the result in xmm1, depending on VEX.W (FMA).
+pub static VFMADD231S: [u8; 4] = [0x66, 0x0f, 0x38, 0xb9];
+
+/// Broadcast a byte integer from xmm2/m8 to all lanes of xmm1 (AVX2).
+pub static VPBROADCASTB: [u8; 4] = [0x66, 0x0f, 0x38, 0x78];
+
+/// Broadcast a doubleword integer from xmm2/m32 to all lanes of xmm1 (AVX2).
+pub static VPBROADCASTD: [u8; 4] = [0x66, 0x0f, 0x38, 0x58];
+
+/// Broadcast a quadword integer from xmm2/m64 to all lanes of xmm1 (AVX2).
+pub static VPBROADCASTQ: [u8; 4] = [0x66, 0x0f, 0x38, 0x59];
+
+/// Broadcast a word integer from xmm2/m16 to all lanes of xmm1 (AVX2).
+pub static VPBROADCASTW: [u8; 4] = [0x66, 0x0f, 0x38, 0x79];
+
 /// imm{16,32} XOR r/m{16,32,64}, possibly sign-extended.
 pub static XOR_IMM: [u8; 1] = [0x81];
 
@@ -82,8 +82,8 @@ impl<'builder> RecipeGroup<'builder> {
 // opcode format is indicated by the recipe name prefix.
 //
 // The match case below does not include the REX prefix which goes after the mandatory prefix.
-// VEX/XOP and EVEX prefixes are not yet supported. Encodings using any of these prefixes are
-// represented by separate recipes.
+// Encodings using a VEX prefix are represented by separate `Vex*` recipes, which reuse the pp and
+// mm bits below. XOP and EVEX prefixes are not yet supported.
 //
 // The encoding bits are:
 //
@@ -102,7 +102,7 @@ impl<'builder> RecipeGroup<'builder> {
 // 15:    REX.W bit (or VEX.W/E)
 //
 // There is some redundancy between bits 8-11 and the recipe names, but we have enough bits, and
-// the pp+mm format is ready for supporting VEX prefixes.
+// the pp+mm format maps directly onto the fields of a VEX prefix.
 //
 // TODO Cranelift doesn't actually require recipe to have different encoding sizes anymore, so this
 // could be simplified.
@@ -161,6 +161,9 @@ pub enum RexRecipeKind {
     /// Because such a Recipe has a non-constant instruction size, it must have
     /// a special `compute_size` handler for the inferrable-REX case.
     InferRex,
+
+    /// The Recipe uses a three-byte VEX prefix instead of the mandatory and REX prefixes.
+    Vex,
 }
 
 impl Default for RexRecipeKind {
@@ -260,6 +263,10 @@ impl<'builder> Template<'builder> {
             self.rex_kind != RexRecipeKind::AlwaysEmitRex,
             "Template requires REX prefix."
         );
+        assert!(
+            self.rex_kind != RexRecipeKind::Vex,
+            "Template uses a VEX prefix."
+        );
         let mut copy = self.clone();
         copy.rex_kind = RexRecipeKind::NeverEmitRex;
         copy
@@ -269,6 +276,10 @@ impl<'builder> Template<'builder> {
             self.rex_kind != RexRecipeKind::NeverEmitRex,
             "Template requires no REX prefix."
         );
+        assert!(
+            self.rex_kind != RexRecipeKind::Vex,
+            "Template uses a VEX prefix."
+        );
         if let Some(prefixed) = &self.when_prefixed {
             let mut ret = prefixed.rex();
             // Forward specialized parameters.
@@ -286,6 +297,10 @@ impl<'builder> Template<'builder> {
             self.rex_kind != RexRecipeKind::NeverEmitRex,
             "Template requires no REX prefix."
         );
+        assert!(
+            self.rex_kind != RexRecipeKind::Vex,
+            "Template uses a VEX prefix."
+        );
         assert!(
             self.when_prefixed.is_none(),
             "infer_rex used with when_prefixed()."
@@ -321,9 +336,16 @@ impl<'builder> Template<'builder> {
 
                 ("DynRex".to_string() + opcode, 0)
             }
+            RexRecipeKind::Vex => ("Vex".to_string() + opcode, 0),
         };
 
-        let size_addendum = self.op_bytes.len() as u64 + rex_prefix_size;
+        let size_addendum = if self.rex_kind == RexRecipeKind::Vex {
+            // The mandatory prefix and the opcode map are part of the VEX prefix, so only the
+            // three VEX bytes and the opcode byte remain.
+            4
+        } else {
+            self.op_bytes.len() as u64 + rex_prefix_size
+        };
         self.recipe.base_size += size_addendum;
 
         // Branch ranges are relative to the end of the instruction.
@@ -574,6 +596,106 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // XX /r with FPR ins and outs, in the three-operand VEX form, which doesn't overwrite the
+    // first input.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfa", &formats.binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        put_vex(bits, out_reg0, in_reg0, in_reg1, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .rex_kind(RexRecipeKind::Vex),
+    );
+
+    // XX /r with FPR ins and outs, in the three-operand VEX form with input operands swapped.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfax", &formats.binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        put_vex(bits, out_reg0, in_reg1, in_reg0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .rex_kind(RexRecipeKind::Vex),
+    );
+
+    // XX /r with three FPR ins, the last of which is also the output, in the VEX form used by
+    // fused multiply-add instructions.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfma", &formats.ternary, 1)
+                .operands_in(vec![fpr, fpr, fpr])
+                .operands_out(vec![2])
+                .emit(
+                    r#"
+                        put_vex(bits, in_reg2, in_reg0, in_reg1, sink);
+                        modrm_rr(in_reg1, in_reg2, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .rex_kind(RexRecipeKind::Vex),
+    );
+
+    // XX /r with an FPR in and out, in the VEX form. The unused vvvv field must be all ones, which
+    // is what register 0 encodes to.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfurm", &formats.unary, 1)
+                .operands_in(vec![fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        put_vex(bits, out_reg0, 0, in_reg0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .rex_kind(RexRecipeKind::Vex),
+    );
+
+    // XX /r broadcasting a GPR to all lanes of an FPR: the GPR is first moved to the low lane of
+    // the output with MOVD (MOVQ for the `w` variant), which always has a REX prefix so that the
+    // size doesn't depend on the registers, and is then broadcast from there in the VEX form.
+    for &(name, rex_w) in &[("vbroadcast_r", 0), ("vbroadcast_rw", 1)] {
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new(name, &formats.unary, 6)
+                    .operands_in(vec![gpr])
+                    .operands_out(vec![fpr])
+                    .clobbers_flags(false)
+                    .emit(format!(
+                        r#"
+                        sink.put1(0x66);
+                        sink.put1(rex2(in_reg0, out_reg0) | ({} << 3));
+                        sink.put1(0x0f);
+                        sink.put1(0x6e);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                        put_vex(bits, out_reg0, 0, out_reg0, sink);
+                        modrm_rr(out_reg0, out_reg0, sink);
+                    "#,
+                        rex_w
+                    )),
+                regs,
+            )
+            .rex_kind(RexRecipeKind::Vex),
+        );
+    }
+
     // XX /r with FPR ins and outs. A form with a byte immediate.
     {
         recipes.add_template_recipe(
@@ -2402,9 +2524,62 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // Tail calls. The frame of the caller has been torn down by the epilogue, so these are plain
+    // jumps to the callee.
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tail_call_id", &formats.call, 4).emit(
+            r#"
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            // The addend adjusts for the difference between the end of the
+            // instruction and the beginning of the immediate field.
+            sink.reloc_external(Reloc::X86CallPCRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tail_call_plt_id", &formats.call, 4).emit(
+            r#"
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            sink.reloc_external(Reloc::X86CallPLTRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tail_call_r", &formats.call_indirect, 1)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
+    // Functions whose callee pops the stack arguments return with `ret imm16`, whose opcode is
+    // one less than the plain `ret`.
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("ret", &formats.multiary, 0)
-            .emit("{{PUT_OP}}(bits, BASE_REX, sink);"),
+            .compute_size("size_plus_callee_popped_bytes")
+            .emit(
+                r#"
+                    let popped = callee_popped_bytes(func);
+                    if popped == 0 {
+                        {{PUT_OP}}(bits, BASE_REX, sink);
+                    } else {
+                        {{PUT_OP}}(bits - 1, BASE_REX, sink);
+                        sink.put2(popped);
+                    }
+                "#,
+            ),
     );
 
     // Branches.
@@ -3070,6 +3245,24 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // Three-operand VEX form of `icscc_fpr`.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vicscc_fpr", &formats.int_compare, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        // Comparison instruction.
+                        put_vex(bits, out_reg0, in_reg0, in_reg1, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .rex_kind(RexRecipeKind::Vex),
+    );
+
     {
         let is_small_imm =
             InstructionPredicate::new_is_signed_int(&*formats.int_compare_imm, "imm", 8, 0);
@@ -3217,6 +3410,42 @@ pub(crate) fn define<'shared>(
                 "#,
                 ),
         );
+
+        // Three-operand VEX form of `pfcmp`.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vpfcmp", &formats.float_compare, 2)
+                    .operands_in(vec![fpr, fpr])
+                    .operands_out(vec![fpr])
+                    .inst_predicate(supported_floatccs_predicate(
+                        &supported_floatccs[..],
+                        &*formats.float_compare,
+                    ))
+                    .emit(
+                        r#"
+                        // Comparison instruction.
+                        put_vex(bits, out_reg0, in_reg0, in_reg1, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                        // Add immediate byte indicating what type of comparison.
+                        use crate::ir::condcodes::FloatCC::*;
+                        let imm = match cond {
+                            Equal                      => 0x00,
+                            LessThan                   => 0x01,
+                            LessThanOrEqual            => 0x02,
+                            Unordered                  => 0x03,
+                            NotEqual                   => 0x04,
+                            UnorderedOrGreaterThanOrEqual => 0x05,
+                            UnorderedOrGreaterThan => 0x06,
+                            Ordered                    => 0x07,
+                            _ => panic!("{} not supported by vpfcmp", cond),
+                        };
+                        sink.put1(imm);
+                    "#,
+                    ),
+                regs,
+            )
+            .rex_kind(RexRecipeKind::Vex),
+        );
     }
 
     recipes.add_template_recipe(
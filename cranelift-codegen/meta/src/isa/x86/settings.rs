@@ -6,10 +6,11 @@ pub(crate) fn define(shared: &SettingGroup) -> SettingGroup {
     // CPUID.01H:ECX
     let has_sse3 = settings.add_bool("has_sse3", "SSE3: CPUID.01H:ECX.SSE3[bit 0]", false);
     let has_ssse3 = settings.add_bool("has_ssse3", "SSSE3: CPUID.01H:ECX.SSSE3[bit 9]", false);
+    let has_fma = settings.add_bool("has_fma", "FMA: CPUID.01H:ECX.FMA[bit 12]", false);
     let has_sse41 = settings.add_bool("has_sse41", "SSE4.1: CPUID.01H:ECX.SSE4_1[bit 19]", false);
     let has_sse42 = settings.add_bool("has_sse42", "SSE4.2: CPUID.01H:ECX.SSE4_2[bit 20]", false);
     let has_popcnt = settings.add_bool("has_popcnt", "POPCNT: CPUID.01H:ECX.POPCNT[bit 23]", false);
-    settings.add_bool("has_avx", "AVX: CPUID.01H:ECX.AVX[bit 28]", false);
+    let has_avx = settings.add_bool("has_avx", "AVX: CPUID.01H:ECX.AVX[bit 28]", false);
 
     // CPUID.(EAX=07H, ECX=0H):EBX
     let has_bmi1 = settings.add_bool(
@@ -17,6 +18,11 @@ pub(crate) fn define(shared: &SettingGroup) -> SettingGroup {
         "BMI1: CPUID.(EAX=07H, ECX=0H):EBX.BMI1[bit 3]",
         false,
     );
+    let has_avx2 = settings.add_bool(
+        "has_avx2",
+        "AVX2: CPUID.(EAX=07H, ECX=0H):EBX.AVX2[bit 5]",
+        false,
+    );
     let has_bmi2 = settings.add_bool(
         "has_bmi2",
         "BMI2: CPUID.(EAX=07H, ECX=0H):EBX.BMI2[bit 8]",
@@ -48,6 +54,12 @@ pub(crate) fn define(shared: &SettingGroup) -> SettingGroup {
         "use_sse42_simd",
         predicate!(shared_enable_simd && has_sse41 && has_sse42),
     );
+    settings.add_predicate("use_avx", predicate!(shared_enable_simd && has_avx));
+    settings.add_predicate(
+        "use_avx2_simd",
+        predicate!(shared_enable_simd && has_avx && has_avx2),
+    );
+    settings.add_predicate("use_fma", predicate!(has_avx && has_fma));
 
     settings.add_predicate("use_popcnt", predicate!(has_popcnt && has_sse42));
     settings.add_predicate("use_bmi1", predicate!(has_bmi1));
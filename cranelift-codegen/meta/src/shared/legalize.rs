@@ -82,6 +82,7 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
     let ifcmp_imm = insts.by_name("ifcmp_imm");
     let imul = insts.by_name("imul");
     let imul_imm = insts.by_name("imul_imm");
+    let ineg = insts.by_name("ineg");
     let ireduce = insts.by_name("ireduce");
     let irsub_imm = insts.by_name("irsub_imm");
     let ishl = insts.by_name("ishl");
@@ -132,6 +133,7 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
 
     // Custom expansions for calls.
     expand.custom_legalize(insts.by_name("call"), "expand_call");
+    expand.custom_legalize(insts.by_name("return_call"), "expand_call");
 
     // Custom expansions that need to change the CFG.
     // TODO: Add sufficient XForm syntax that we don't need to hand-code these.
@@ -212,6 +214,21 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
     // embedded as part of arguments), so use a custom legalization for now.
     narrow.custom_legalize(iconst, "narrow_iconst");
 
+    // Shifts and rotates by a variable amount need to combine bits from both halves.
+    narrow.custom_legalize(ishl, "narrow_shift");
+    narrow.custom_legalize(ushr, "narrow_shift");
+    narrow.custom_legalize(sshr, "narrow_shift");
+    narrow.custom_legalize(rotl, "narrow_rotate");
+    narrow.custom_legalize(rotr, "narrow_rotate");
+
+    // Division is done by a library call, after checking the conditions that make it trap.
+    narrow.custom_legalize(udiv, "narrow_divrem");
+    narrow.custom_legalize(sdiv, "narrow_divrem");
+    narrow.custom_legalize(urem, "narrow_divrem");
+    narrow.custom_legalize(srem, "narrow_divrem");
+
+    narrow.custom_legalize(br_icmp, "expand_br_icmp");
+
     {
         let inst = uextend.bind(I128).bind(I64);
         narrow.legalize(
@@ -234,6 +251,28 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         );
     }
 
+    for &ty in &[I8, I16, I32] {
+        let inst = uextend.bind(I128).bind(ty);
+        narrow.legalize(
+            def!(a = inst(x)),
+            vec![
+                def!(al = uextend.I64(x)),
+                def!(ah = iconst(Literal::constant(&imm.imm64, 0))),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        let inst = sextend.bind(I128).bind(ty);
+        narrow.legalize(
+            def!(a = inst(x)),
+            vec![
+                def!(al = sextend.I64(x)),
+                def!(ah = sshr_imm(al, Literal::constant(&imm.imm64, 63))),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+    }
+
     for &bin_op in &[band, bor, bxor, band_not, bor_not, bxor_not] {
         narrow.legalize(
             def!(a = bin_op(x, y)),
@@ -313,12 +352,85 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         ],
     );
 
+    let intcc_eq = Literal::enumerator_for(&imm.intcc, "eq");
+    let imm64_0 = Literal::constant(&imm.imm64, 0);
+    let imm64_64 = Literal::constant(&imm.imm64, 64);
+
+    // If the upper half is all zeros, count the leading zeros of the lower half instead.
+    narrow.legalize(
+        def!(a = clz.I128(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(c = icmp_imm(intcc_eq, xh, imm64_0)),
+            def!(e1 = clz(xh)),
+            def!(e2 = clz(xl)),
+            def!(e3 = iadd_imm(e2, imm64_64)),
+            def!(e4 = select(c, e3, e1)),
+            def!(a = uextend(e4)),
+        ],
+    );
+
+    // If the lower half is all zeros, count the trailing zeros of the upper half instead.
+    narrow.legalize(
+        def!(a = ctz.I128(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(c = icmp_imm(intcc_eq, xl, imm64_0)),
+            def!(e1 = ctz(xl)),
+            def!(e2 = ctz(xh)),
+            def!(e3 = iadd_imm(e2, imm64_64)),
+            def!(e4 = select(c, e3, e1)),
+            def!(a = uextend(e4)),
+        ],
+    );
+
+    narrow.legalize(
+        def!(a = ineg.I128(x)),
+        vec![def!(a1 = iconst(imm64_0)), def!(a = isub(a1, x))],
+    );
+
+    // Operations with immediates are expanded into the wide operation with an `iconst`, which is
+    // then narrowed like any other.
+    for &(inst_imm, inst) in &[
+        (iadd_imm, iadd),
+        (imul_imm, imul),
+        (sdiv_imm, sdiv),
+        (udiv_imm, udiv),
+        (srem_imm, srem),
+        (urem_imm, urem),
+        (band_imm, band),
+        (bor_imm, bor),
+        (bxor_imm, bxor),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = irsub_imm.I128(y, x)),
+        vec![def!(a1 = iconst(x)), def!(a = isub(a1, y))],
+    );
+
+    for &(inst_imm, inst) in &[
+        (rotl_imm, rotl),
+        (rotr_imm, rotr),
+        (ishl_imm, ishl),
+        (sshr_imm, sshr),
+        (ushr_imm, ushr),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst.I32(y)), def!(a = inst(x, a1))],
+        );
+    }
+
     // TODO(ryzokuken): benchmark this and decide if branching is a faster
     // approach than evaluating boolean expressions.
 
     narrow.custom_legalize(icmp_imm, "narrow_icmp_imm");
 
-    let intcc_eq = Literal::enumerator_for(&imm.intcc, "eq");
     let intcc_ne = Literal::enumerator_for(&imm.intcc, "ne");
     for &(int_ty, int_ty_half) in &[(I64, I32), (I128, I64)] {
         narrow.legalize(
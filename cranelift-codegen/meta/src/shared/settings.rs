@@ -16,17 +16,46 @@ pub(crate) fn define() -> SettingGroup {
         vec!["none", "speed", "speed_and_size"],
     );
 
+    settings.add_enum(
+        "regalloc",
+        r#"
+        Register allocator used by the MachInst backends:
+
+        - backtracking: the backtracking allocator from regalloc.rs, which produces the best code.
+        - linear_scan: the linear scan allocator from regalloc.rs, which is faster but
+          less mature.
+
+        The result of either allocator can be checked with `regalloc_checker`.
+        "#,
+        vec!["backtracking", "linear_scan"],
+    );
+
     settings.add_bool(
         "enable_verifier",
         r#"
         Run the Cranelift IR verifier at strategic times during compilation.
 
+        The MachInst backends also verify their VCode after lowering and after
+        register allocation.
+
         This makes compilation slower but catches many bugs. The verifier is always enabled by
         default, which is useful during development.
         "#,
         true,
     );
 
+    settings.add_bool(
+        "regalloc_checker",
+        r#"
+        Check the result of the MachInst register allocator with a symbolic checker,
+        whichever allocator `regalloc` selects.
+
+        The checker is slow, and only catches bugs in the register allocator or in the
+        register uses that the backends report. It also runs whenever `enable_verifier` is set.
+        "#,
+        false,
+    );
+
     // Note that Cranelift doesn't currently need an is_pie flag, because PIE is
     // just PIC where symbols can't be pre-empted, which can be expressed with the
     // `colocated` flag on external functions and global values.
@@ -208,7 +237,7 @@ pub(crate) fn define() -> SettingGroup {
             The log2 of the size of the stack guard region.
 
             Stack frames larger than this size will have stack overflow checked
-            by calling the probestack function.
+            by calling the probestack function, or by probing each page inline.
 
             The default is 12, which translates to a size of 4096.
             "#,
@@ -223,5 +252,30 @@ pub(crate) fn define() -> SettingGroup {
         true,
     );
 
+    // Loop optimization options.
+
+    settings.add_bool(
+        "enable_loop_optimizations",
+        r#"
+            Enable the loop optimizations when `opt_level` is not `none`.
+
+            This runs strength reduction of induction variable multiplications, and with
+            `opt_level` set to `speed`, also unrolls small loops with a constant trip count.
+            "#,
+        false,
+    );
+
+    settings.add_bool(
+        "enable_bounds_check_elimination",
+        r#"
+            Enable the elimination of heap bounds checks when `opt_level` is not `none`.
+
+            `heap_addr` instructions whose index is known to be in bounds, from the loop
+            induction variables or from a dominating access to the same heap, are expanded
+            without a bounds check.
+            "#,
+        false,
+    );
+
     settings.build()
 }
@@ -9,8 +9,9 @@ use std::ops::RangeInclusive;
 /// styles of opcodes and prefixes. The opcode format is indicated by the
 /// recipe name prefix.
 ///
-/// VEX/XOP and EVEX prefixes are not yet supported.
-/// Encodings using any of these prefixes are represented by separate recipes.
+/// Encodings using a VEX prefix are represented by separate recipes, which use
+/// the pp and mm bits below for the corresponding VEX fields. XOP and EVEX
+/// prefixes are not yet supported.
 ///
 /// The encoding bits are:
 ///
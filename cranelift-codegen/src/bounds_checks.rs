@@ -0,0 +1,269 @@
+//! Elimination of redundant heap bounds checks.
+//!
+//! The legalizer turns every `heap_addr` instruction into a bounds check followed by an address
+//! computation, unless the offset-guard pages of a static heap make the check unnecessary. This
+//! pass finds the `heap_addr` instructions that are known to be in bounds, and expands them
+//! early without a check:
+//!
+//! - A check is redundant when it is dominated by a check of the same heap and index for an
+//!   access at least as large. Checks of the same heap and index in a block that are not
+//!   separated by any side effect are merged, by widening the first one to the largest access.
+//! - A check is redundant when the index is known to lie in a range for which the access is always
+//!   in bounds. Ranges are derived from constants, masks, and the induction variables of loops
+//!   with a constant trip count.
+
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::induction::{
+    find_induction_vars, header_edges, initial_value, is_induction_type, single_block_latch,
+    trip_count,
+};
+use crate::ir::dfg::ValueDef;
+use crate::ir::immediates::Uimm32;
+use crate::ir::{DataFlowGraph, Function, Heap, HeapStyle, Inst, InstructionData, Opcode, Value};
+use crate::isa::TargetIsa;
+use crate::legalizer::expand_heap_addr_unchecked;
+use crate::loop_analysis::LoopAnalysis;
+use crate::timing;
+use alloc::vec::Vec;
+
+/// A `heap_addr` instruction.
+struct HeapAccess {
+    inst: Inst,
+    heap: Heap,
+    index: Value,
+    size: u32,
+}
+
+/// Can an instruction with this opcode be observed if it is moved after a trap?
+fn has_side_effects(opcode: Opcode) -> bool {
+    opcode.can_store()
+        || opcode.is_call()
+        || opcode.is_branch()
+        || opcode.other_side_effects()
+        || (opcode.can_trap() && !opcode.can_load() && opcode != Opcode::HeapAddr)
+}
+
+/// Ranges of unsigned values that the index of a heap access can take.
+struct IndexRanges {
+    /// Ranges of the induction variables of loops with a constant trip count.
+    params: FxHashMap<Value, (u64, u64)>,
+}
+
+impl IndexRanges {
+    fn new(
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        domtree: &DominatorTree,
+        loop_analysis: &LoopAnalysis,
+    ) -> Self {
+        let mut params = FxHashMap();
+        for lp in loop_analysis.loops() {
+            let header = loop_analysis.loop_header(lp);
+            let latch = match single_block_latch(func, cfg, loop_analysis, lp) {
+                Some(latch) => latch,
+                None => continue,
+            };
+            let ivs = find_induction_vars(func, cfg, domtree, loop_analysis, lp);
+            let trips = match trip_count(func, cfg, domtree, header, &latch, &ivs) {
+                Some(trips) => trips,
+                None => continue,
+            };
+            let edges = header_edges(func, cfg, domtree, header).unwrap();
+            for iv in &ivs {
+                let init = match initial_value(func, &edges, iv) {
+                    Some(init) => init,
+                    None => continue,
+                };
+                // The parameter takes the values `init + k * step` for `k` in `0..trips`. Only
+                // keep the ranges which don't wrap around.
+                let mask = type_mask(func.dfg.value_type(iv.param).bits());
+                let first = i128::from(init as u64 & mask);
+                let last = first + i128::from(trips - 1) * i128::from(iv.step);
+                if last < 0 || last > i128::from(mask) {
+                    continue;
+                }
+                let (lo, hi) = if first <= last {
+                    (first, last)
+                } else {
+                    (last, first)
+                };
+                params.insert(iv.param, (lo as u64, hi as u64));
+            }
+        }
+        Self { params }
+    }
+
+    /// Get the range of unsigned values that `value` can take, if known.
+    fn range(&self, dfg: &DataFlowGraph, value: Value) -> Option<(u64, u64)> {
+        let value = dfg.resolve_aliases(value);
+        let ty = dfg.value_type(value);
+        if !is_induction_type(ty) {
+            return None;
+        }
+        let mask = type_mask(ty.bits());
+        let inst = match dfg.value_def(value) {
+            ValueDef::Result(inst, _) => inst,
+            ValueDef::Param(..) => return self.params.get(&value).cloned(),
+        };
+        match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                let c = imm as u64 & mask;
+                Some((c, c))
+            }
+            InstructionData::Unary {
+                opcode: Opcode::Uextend,
+                arg,
+            } => self.range(dfg, arg),
+            InstructionData::BinaryImm {
+                opcode: Opcode::BandImm,
+                arg,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                let (_, hi) = self.range(dfg, arg).unwrap_or((0, mask));
+                Some((0, hi.min(imm as u64 & mask)))
+            }
+            InstructionData::BinaryImm {
+                opcode: Opcode::UshrImm,
+                arg,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                let shift = imm as u64 & u64::from(ty.bits() - 1);
+                let (lo, hi) = self.range(dfg, arg).unwrap_or((0, mask));
+                Some((lo >> shift, hi >> shift))
+            }
+            InstructionData::BinaryImm {
+                opcode: Opcode::IaddImm,
+                arg,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                let (lo, hi) = self.range(dfg, arg)?;
+                let lo = i128::from(lo) + i128::from(imm);
+                let hi = i128::from(hi) + i128::from(imm);
+                if lo < 0 || hi > i128::from(mask) {
+                    None
+                } else {
+                    Some((lo as u64, hi as u64))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Is `access` always in bounds?
+    fn in_bounds(&self, func: &Function, access: &HeapAccess) -> bool {
+        let limit: u64 = match func.heaps[access.heap].style {
+            HeapStyle::Static { bound } => bound.into(),
+            // The bound of a dynamic heap is never smaller than its minimum size.
+            HeapStyle::Dynamic { .. } => func.heaps[access.heap].min_size.into(),
+        };
+        match self.range(&func.dfg, access.index) {
+            Some((_, hi)) => u128::from(hi) + u128::from(access.size) <= u128::from(limit),
+            None => false,
+        }
+    }
+}
+
+/// Mask of the bits of a `bits`-wide integer.
+fn type_mask(bits: u16) -> u64 {
+    u64::max_value() >> (64 - bits)
+}
+
+/// Collect the `heap_addr` instructions of `func`, merging the checks of the same heap and index
+/// in a block that are not separated by side effects.
+fn collect_heap_accesses(func: &mut Function) -> Vec<HeapAccess> {
+    let mut accesses: Vec<HeapAccess> = Vec::new();
+    for block in func.layout.blocks() {
+        // The first access to each heap and index since the last side effect in this block.
+        let mut first_access: FxHashMap<(Heap, Value), usize> = FxHashMap();
+        for inst in func.layout.block_insts(block) {
+            match func.dfg[inst] {
+                InstructionData::HeapAddr {
+                    opcode: Opcode::HeapAddr,
+                    heap,
+                    arg,
+                    imm,
+                } => {
+                    let index = func.dfg.resolve_aliases(arg);
+                    let size: u32 = imm.into();
+                    if let Some(&first) = first_access.get(&(heap, index)) {
+                        let first = &mut accesses[first];
+                        first.size = first.size.max(size);
+                    } else {
+                        first_access.insert((heap, index), accesses.len());
+                    }
+                    accesses.push(HeapAccess {
+                        inst,
+                        heap,
+                        index,
+                        size,
+                    });
+                }
+                ref data if has_side_effects(data.opcode()) => first_access.clear(),
+                _ => {}
+            }
+        }
+    }
+
+    // Write back the widened checks.
+    for access in &accesses {
+        if let InstructionData::HeapAddr { ref mut imm, .. } = func.dfg[access.inst] {
+            *imm = Uimm32::from(access.size);
+        }
+    }
+    accesses
+}
+
+/// Expand the `heap_addr` instructions of `func` that are known to be in bounds without a bounds
+/// check, using the dominator tree and the loop analysis.
+pub fn do_bounds_check_elimination(
+    isa: &dyn TargetIsa,
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) {
+    let _tt = timing::bounds_checks();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let accesses = collect_heap_accesses(func);
+    if accesses.is_empty() {
+        return;
+    }
+
+    let mut by_index: FxHashMap<(Heap, Value), Vec<usize>> = FxHashMap();
+    for (i, access) in accesses.iter().enumerate() {
+        by_index
+            .entry((access.heap, access.index))
+            .or_insert_with(Vec::new)
+            .push(i);
+    }
+
+    let ranges = IndexRanges::new(func, cfg, domtree, loop_analysis);
+    let mut unchecked = Vec::new();
+    for access in &accesses {
+        let dominated = by_index[&(access.heap, access.index)].iter().any(|&other| {
+            let other = &accesses[other];
+            other.inst != access.inst
+                && other.size >= access.size
+                && domtree.dominates(other.inst, access.inst, &func.layout)
+        });
+        if dominated || ranges.in_bounds(func, access) {
+            unchecked.push(access.inst);
+        }
+    }
+
+    for inst in unchecked {
+        expand_heap_addr_unchecked(inst, func, isa);
+    }
+}
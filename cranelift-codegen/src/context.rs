@@ -13,10 +13,11 @@ use crate::binemit::{
     relax_branches, shrink_instructions, CodeInfo, FrameUnwindKind, FrameUnwindSink,
     MemoryCodeSink, RelocSink, StackmapSink, TrapSink,
 };
+use crate::bounds_checks::do_bounds_check_elimination;
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::Function;
+use crate::ir::{types, Function};
 use crate::isa::TargetIsa;
 use crate::legalize_function;
 use crate::legalizer::simple_legalize;
@@ -27,12 +28,14 @@ use crate::nan_canonicalization::do_nan_canonicalization;
 use crate::postopt::do_postopt;
 use crate::redundant_reload_remover::RedundantReloadRemover;
 use crate::regalloc;
-use crate::result::CodegenResult;
+use crate::result::{CodegenError, CodegenResult};
 use crate::settings::{FlagsOrIsa, OptLevel};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
+use crate::strength_reduction::do_strength_reduction;
 use crate::timing;
 use crate::unreachable_code::eliminate_unreachable_code;
+use crate::unroll::do_loop_unrolling;
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
 use crate::verifier::{verify_context, verify_locations, VerifierErrors, VerifierResult};
 use alloc::vec::Vec;
@@ -60,6 +63,9 @@ pub struct Context {
 
     /// Result of MachBackend compilation, if computed.
     pub mach_compile_result: Option<MachCompileResult>,
+
+    /// Should a MachBackend produce a disassembly of the compiled code in `mach_compile_result`?
+    pub want_disasm: bool,
 }
 
 impl Context {
@@ -84,6 +90,7 @@ impl Context {
             loop_analysis: LoopAnalysis::new(),
             redundant_reload_remover: RedundantReloadRemover::new(),
             mach_compile_result: None,
+            want_disasm: false,
         }
     }
 
@@ -143,6 +150,17 @@ impl Context {
         self.compute_cfg();
         if opt_level != OptLevel::None {
             self.preopt(isa)?;
+            self.compute_domtree();
+            self.compute_loop_analysis();
+            if isa.flags().enable_loop_optimizations() {
+                self.strength_reduce(isa)?;
+                if opt_level == OptLevel::Speed {
+                    self.unroll_loops(isa)?;
+                }
+            }
+            if isa.flags().enable_bounds_check_elimination() {
+                self.eliminate_bounds_checks(isa)?;
+            }
         }
         if isa.flags().enable_nan_canonicalization() {
             self.canonicalize_nans(isa)?;
@@ -165,7 +183,7 @@ impl Context {
 
         if let Some(backend) = isa.get_mach_backend() {
             let func = std::mem::replace(&mut self.func, Function::new());
-            let result = backend.compile_function(func, false)?;
+            let result = backend.compile_function(func, self.want_disasm)?;
             let info = result.code_info();
             self.mach_compile_result = Some(result);
             Ok(info)
@@ -222,12 +240,23 @@ impl Context {
     ///
     /// Only some calling conventions (e.g. Windows fastcall) will have unwind information.
     /// This is a no-op if the function has no unwind information.
+    ///
+    /// For functions compiled by a `MachBackend`, the unwind information comes from the
+    /// compilation result instead of the function layout.
     pub fn emit_unwind_info(
         &self,
         isa: &dyn TargetIsa,
         kind: FrameUnwindKind,
         sink: &mut dyn FrameUnwindSink,
     ) {
+        if let Some(ref result) = &self.mach_compile_result {
+            if let Some(ref info) = &result.unwind_info {
+                if info.kind == kind {
+                    sink.bytes(&info.data);
+                }
+            }
+            return;
+        }
         isa.emit_unwind_info(&self.func, kind, sink);
     }
 
@@ -295,8 +324,20 @@ impl Context {
     }
 
     /// Run the legalizer for `isa` on the function.
+    ///
+    /// Functions using `i128` values are only supported by the legacy backends; the new backends
+    /// reject them with `CodegenError::Unsupported`.
     pub fn legalize(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         if isa.get_mach_backend().is_some() {
+            // The new backends have neither a register-pair ABI nor lowerings for `i128`, and
+            // `simple_legalize` doesn't narrow.
+            let dfg = &self.func.dfg;
+            if dfg.values().any(|v| dfg.value_type(v) == types::I128) {
+                return Err(CodegenError::Unsupported(
+                    "i128 values with the new backends".into(),
+                ));
+            }
+
             // Run some specific legalizations only.
             simple_legalize(&mut self.func, &mut self.cfg, isa);
             Ok(())
@@ -358,6 +399,50 @@ impl Context {
         self.verify_if(isa)
     }
 
+    /// Perform strength reduction of induction variable multiplications in loops.
+    pub fn strength_reduce<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_strength_reduction(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        );
+        self.verify_if(fisa)
+    }
+
+    /// Unroll small loops with a constant trip count.
+    ///
+    /// The control flow graph, dominator tree and loop analysis are recomputed if any loop was
+    /// unrolled.
+    pub fn unroll_loops<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        if do_loop_unrolling(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        ) {
+            self.compute_cfg();
+            self.compute_domtree();
+            self.compute_loop_analysis();
+        }
+        self.verify_if(fisa)
+    }
+
+    /// Expand the `heap_addr` instructions which are known to be in bounds without a bounds check.
+    pub fn eliminate_bounds_checks(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        do_bounds_check_elimination(
+            isa,
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        );
+        self.verify_if(isa)
+    }
+
     /// Perform unreachable code elimination.
     pub fn eliminate_unreachable_code<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
     where
@@ -0,0 +1,394 @@
+//! Induction variable analysis.
+//!
+//! A basic induction variable is a loop header block parameter that is incremented by the same
+//! constant along every back edge of the loop. Such parameters are found by looking at the
+//! arguments passed to the header by each branch, as reported by the `LoopAnalysis`.
+
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::{BlockPredecessor, ControlFlowGraph};
+use crate::ir::condcodes::IntCC;
+use crate::ir::dfg::ValueDef;
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{Block, DataFlowGraph, Function, Inst, InstructionData, Opcode, Type, Value};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use alloc::vec::Vec;
+
+/// Maximum number of iterations evaluated when computing a trip count.
+const MAX_EVALUATED_TRIP_COUNT: u64 = 1 << 16;
+
+/// A basic induction variable of a loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InductionVar {
+    /// The loop header parameter holding the current value of the induction variable.
+    pub param: Value,
+    /// Index of `param` in the list of the header's parameters.
+    pub index: usize,
+    /// The constant added to `param` on every iteration of the loop.
+    pub step: i64,
+}
+
+/// The branches targeting a loop header, split between the ones entering the loop and the back
+/// edges.
+pub struct HeaderEdges {
+    /// Branches from outside the loop to the loop header.
+    pub entries: Vec<Inst>,
+    /// Branches from inside the loop back to the loop header.
+    pub back_edges: Vec<Inst>,
+}
+
+/// Collect the branches to `header`.
+///
+/// Returns `None` if the header is the entry block, or if one of its predecessors can't pass
+/// arguments to it (e.g. a jump table).
+pub fn header_edges(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    header: Block,
+) -> Option<HeaderEdges> {
+    if func.layout.entry_block() == Some(header) {
+        return None;
+    }
+    let mut entries = Vec::new();
+    let mut back_edges = Vec::new();
+    for BlockPredecessor { inst, .. } in cfg.pred_iter(header) {
+        match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, _) if dest == header => {}
+            _ => return None,
+        }
+        if domtree.dominates(header, inst, &func.layout) {
+            back_edges.push(inst);
+        } else {
+            entries.push(inst);
+        }
+    }
+    if entries.is_empty() || back_edges.is_empty() {
+        return None;
+    }
+    Some(HeaderEdges {
+        entries,
+        back_edges,
+    })
+}
+
+/// Get the `index`-th block argument passed by the branch `inst`.
+pub fn branch_arg(dfg: &DataFlowGraph, inst: Inst, index: usize) -> Value {
+    match dfg.analyze_branch(inst) {
+        BranchInfo::SingleDest(_, args) => dfg.resolve_aliases(args[index]),
+        _ => panic!("{} doesn't pass block arguments", dfg[inst].opcode()),
+    }
+}
+
+/// If `value` is defined by an `iconst` instruction, return its immediate.
+pub fn iconst_value(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    if let ValueDef::Result(inst, _) = dfg.value_def(dfg.resolve_aliases(value)) {
+        if let InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } = dfg[inst]
+        {
+            return Some(imm.into());
+        }
+    }
+    None
+}
+
+/// Can an induction variable have type `ty`?
+pub fn is_induction_type(ty: Type) -> bool {
+    ty.is_int() && ty.lane_count() == 1 && ty.bits() <= 64
+}
+
+/// If `value` is computed as `base` plus a constant, return that constant.
+fn constant_increment(dfg: &DataFlowGraph, value: Value, base: Value) -> Option<i64> {
+    let inst = match dfg.value_def(value) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return None,
+    };
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if dfg.resolve_aliases(arg) == base => Some(imm.into()),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } => {
+            let lhs = dfg.resolve_aliases(args[0]);
+            let rhs = dfg.resolve_aliases(args[1]);
+            if lhs == base {
+                iconst_value(dfg, rhs)
+            } else if rhs == base {
+                iconst_value(dfg, lhs)
+            } else {
+                None
+            }
+        }
+        InstructionData::Binary {
+            opcode: Opcode::Isub,
+            args,
+        } if dfg.resolve_aliases(args[0]) == base => {
+            iconst_value(dfg, args[1]).map(i64::wrapping_neg)
+        }
+        _ => None,
+    }
+}
+
+/// Find the basic induction variables of the loop `lp`.
+///
+/// A header parameter is an induction variable if every back edge passes it the value of the
+/// parameter incremented by the same constant.
+pub fn find_induction_vars(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) -> Vec<InductionVar> {
+    let header = loop_analysis.loop_header(lp);
+    let edges = match header_edges(func, cfg, domtree, header) {
+        Some(edges) => edges,
+        None => return Vec::new(),
+    };
+
+    let mut ivs = Vec::new();
+    for (index, &param) in func.dfg.block_params(header).iter().enumerate() {
+        if !is_induction_type(func.dfg.value_type(param)) {
+            continue;
+        }
+        let mut step = None;
+        for &inst in &edges.back_edges {
+            let arg = branch_arg(&func.dfg, inst, index);
+            match (step, constant_increment(&func.dfg, arg, param)) {
+                (None, Some(inc)) => step = Some(inc),
+                (Some(s), Some(inc)) if s == inc => {}
+                _ => {
+                    step = None;
+                    break;
+                }
+            }
+        }
+        if let Some(step) = step {
+            ivs.push(InductionVar { param, index, step });
+        }
+    }
+    ivs
+}
+
+/// The terminators of a single-block loop: a conditional branch followed by a jump, one of them
+/// being the back edge to the header.
+pub struct LoopLatch {
+    /// The conditional branch terminating the header.
+    pub cond_branch: Inst,
+    /// The jump terminating the header.
+    pub jump: Inst,
+    /// Which of `cond_branch` and `jump` is the back edge to the header.
+    pub back_edge: Inst,
+    /// Which of `cond_branch` and `jump` exits the loop.
+    pub exit_edge: Inst,
+}
+
+/// If `lp` consists of its header only, return the header's terminators.
+pub fn single_block_latch(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) -> Option<LoopLatch> {
+    let header = loop_analysis.loop_header(lp);
+
+    // The header must be the only block of the loop.
+    if cfg
+        .succ_iter(header)
+        .any(|succ| succ != header && loop_analysis.is_in_loop(succ, lp))
+    {
+        return None;
+    }
+
+    let jump = func.layout.last_inst(header)?;
+    let cond_branch = func.layout.prev_inst(jump)?;
+    if func.dfg[jump].opcode() != Opcode::Jump {
+        return None;
+    }
+    match func.dfg[cond_branch].opcode() {
+        Opcode::Brz | Opcode::Brnz => {}
+        _ => return None,
+    }
+
+    let (back_edge, exit_edge) = match (
+        func.dfg[cond_branch].branch_destination(),
+        func.dfg[jump].branch_destination(),
+    ) {
+        (Some(taken), Some(other)) if taken == header && other != header => (cond_branch, jump),
+        (Some(taken), Some(other)) if taken != header && other == header => (jump, cond_branch),
+        _ => return None,
+    };
+
+    Some(LoopLatch {
+        cond_branch,
+        jump,
+        back_edge,
+        exit_edge,
+    })
+}
+
+/// If all the entries into the loop pass the same constant to `iv`, return it.
+pub fn initial_value(func: &Function, edges: &HeaderEdges, iv: &InductionVar) -> Option<i64> {
+    let mut init = None;
+    for &entry in &edges.entries {
+        let value = iconst_value(&func.dfg, branch_arg(&func.dfg, entry, iv.index))?;
+        if init.map_or(false, |init| init != value) {
+            return None;
+        }
+        init = Some(value);
+    }
+    init
+}
+
+/// Evaluate the integer comparison `cond` on `bits`-wide operands `x` and `y`.
+fn eval_icmp(cond: IntCC, x: u64, y: u64, bits: u16) -> Option<bool> {
+    let shift = 64 - u32::from(bits);
+    let (ux, uy) = ((x << shift) >> shift, (y << shift) >> shift);
+    let (sx, sy) = (
+        ((x << shift) as i64) >> shift,
+        ((y << shift) as i64) >> shift,
+    );
+    Some(match cond {
+        IntCC::Equal => ux == uy,
+        IntCC::NotEqual => ux != uy,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => ux < uy,
+        IntCC::UnsignedGreaterThanOrEqual => ux >= uy,
+        IntCC::UnsignedGreaterThan => ux > uy,
+        IntCC::UnsignedLessThanOrEqual => ux <= uy,
+        IntCC::Overflow | IntCC::NotOverflow => return None,
+    })
+}
+
+/// Compute the number of times the header of a single-block loop is executed, if it is a
+/// constant.
+///
+/// The exit test must compare one of the induction variables `ivs`, before or after its
+/// increment, against a constant.
+pub fn trip_count(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    header: Block,
+    latch: &LoopLatch,
+    ivs: &[InductionVar],
+) -> Option<u64> {
+    let dfg = &func.dfg;
+    let cond_value = dfg.resolve_aliases(dfg.inst_args(latch.cond_branch)[0]);
+    let cond_inst = match dfg.value_def(cond_value) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return None,
+    };
+    let (cond, lhs, rhs) = match dfg[cond_inst] {
+        InstructionData::IntCompareImm {
+            opcode: Opcode::IcmpImm,
+            cond,
+            arg,
+            imm,
+        } => {
+            let imm: i64 = imm.into();
+            (cond, arg, imm)
+        }
+        InstructionData::IntCompare {
+            opcode: Opcode::Icmp,
+            cond,
+            args,
+        } => (cond, args[0], iconst_value(dfg, args[1])?),
+        _ => return None,
+    };
+    let lhs = dfg.resolve_aliases(lhs);
+
+    // The compared value must be an induction variable, either before or after its increment.
+    let edges = header_edges(func, cfg, domtree, header)?;
+    let (iv, compares_next) = ivs.iter().find_map(|iv| {
+        if lhs == iv.param {
+            Some((iv, false))
+        } else if lhs == branch_arg(dfg, latch.back_edge, iv.index) {
+            Some((iv, true))
+        } else {
+            None
+        }
+    })?;
+
+    let bits = dfg.value_type(iv.param).bits();
+    let taken_when = dfg[latch.cond_branch].opcode() == Opcode::Brnz;
+    let taken_loops = latch.back_edge == latch.cond_branch;
+    let mut current = initial_value(func, &edges, iv)? as u64;
+    let mut count = 1;
+    loop {
+        let next = current.wrapping_add(iv.step as u64);
+        let tested = if compares_next { next } else { current };
+        let taken = eval_icmp(cond, tested, rhs as u64, bits)? == taken_when;
+        if taken != taken_loops {
+            return Some(count);
+        }
+        count += 1;
+        if count > MAX_EVALUATED_TRIP_COUNT {
+            return None;
+        }
+        current = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::condcodes::IntCC;
+    use crate::ir::{types, InstBuilder};
+
+    #[test]
+    fn counted_loop() {
+        let mut func = Function::new();
+        let block0 = func.dfg.make_block();
+        let block1 = func.dfg.make_block();
+        let block2 = func.dfg.make_block();
+        let v0 = func.dfg.append_block_param(block0, types::I32);
+        let iv = func.dfg.append_block_param(block1, types::I32);
+        let acc = func.dfg.append_block_param(block1, types::I32);
+
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_block(block0);
+            let zero = cur.ins().iconst(types::I32, 0);
+            cur.ins().jump(block1, &[zero, v0]);
+
+            cur.insert_block(block1);
+            let acc2 = cur.ins().iadd(acc, iv);
+            let iv2 = cur.ins().iadd_imm(iv, 4);
+            let cond = cur.ins().icmp_imm(IntCC::UnsignedLessThan, iv2, 64);
+            cur.ins().brnz(cond, block1, &[iv2, acc2]);
+            cur.ins().jump(block2, &[]);
+
+            cur.insert_block(block2);
+            cur.ins().return_(&[acc2]);
+        }
+
+        let mut loop_analysis = LoopAnalysis::new();
+        let mut cfg = ControlFlowGraph::new();
+        let mut domtree = DominatorTree::new();
+        cfg.compute(&func);
+        domtree.compute(&func, &cfg);
+        loop_analysis.compute(&func, &cfg, &domtree);
+
+        let lp = loop_analysis.loops().next().unwrap();
+        let ivs = find_induction_vars(&func, &cfg, &domtree, &loop_analysis, lp);
+        assert_eq!(
+            ivs,
+            [InductionVar {
+                param: iv,
+                index: 0,
+                step: 4
+            }]
+        );
+    }
+}
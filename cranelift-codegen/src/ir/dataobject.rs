@@ -0,0 +1,85 @@
+//! Data objects.
+//!
+//! A data object is a named piece of memory that sits alongside the functions of a module. It is
+//! not part of any `Function`, but having a common representation lets the textual IR describe
+//! whole compilation units.
+
+use crate::binemit::{Addend, CodeOffset};
+use crate::ir::{ExternalName, Linkage};
+use crate::write::write_data_object;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// How a data object is initialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DataInit {
+    /// No contents; the data object is only declared here.
+    Uninitialized,
+    /// Initialize the data with all zeros.
+    Zeros {
+        /// The size of the data.
+        size: usize,
+    },
+    /// Initialize the data with the specified contents.
+    Bytes {
+        /// The contents, which also implies the size of the data.
+        contents: Vec<u8>,
+    },
+}
+
+impl DataInit {
+    /// Return the size of the data to be initialized, or `None` if it has no contents.
+    pub fn size(&self) -> Option<usize> {
+        match *self {
+            Self::Uninitialized => None,
+            Self::Zeros { size } => Some(size),
+            Self::Bytes { ref contents } => Some(contents.len()),
+        }
+    }
+}
+
+/// A data object declaration, with its initializer and the addresses written into it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataObject {
+    /// Name of the data object.
+    pub name: ExternalName,
+
+    /// Where the data object is defined and who can see it.
+    pub linkage: Linkage,
+
+    /// Can the data be modified at runtime?
+    pub writable: bool,
+
+    /// Required alignment in bytes, if any.
+    pub align: Option<u8>,
+
+    /// Initial contents of the data object.
+    pub init: DataInit,
+
+    /// Function addresses to write at specified offsets.
+    pub function_relocs: Vec<(CodeOffset, ExternalName)>,
+
+    /// Data addresses to write at specified offsets.
+    pub data_relocs: Vec<(CodeOffset, ExternalName, Addend)>,
+}
+
+impl DataObject {
+    /// Create a read-only data object with no contents.
+    pub fn new(name: ExternalName, linkage: Linkage) -> Self {
+        Self {
+            name,
+            linkage,
+            writable: false,
+            align: None,
+            init: DataInit::Uninitialized,
+            function_relocs: Vec::new(),
+            data_relocs: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for DataObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_data_object(f, self)
+    }
+}
@@ -531,7 +531,7 @@ impl DataFlowGraph {
         self.results[inst].clear(&mut self.value_lists);
 
         // Get the call signature if this is a function call.
-        if let Some(sig) = self.call_signature(inst) {
+        if let Some(sig) = self.call_results_signature(inst) {
             // Create result values corresponding to the call return types.
             debug_assert_eq!(
                 self.insts[inst].opcode().constraints().num_fixed_results(),
@@ -690,6 +690,17 @@ impl DataFlowGraph {
         }
     }
 
+    /// Get the call signature whose return types are the results of `inst`.
+    /// Returns `None` if `inst` is not a call instruction, or is a tail call, which returns
+    /// directly to the caller of the current function and so has no results.
+    pub fn call_results_signature(&self, inst: Inst) -> Option<SigRef> {
+        if self.insts[inst].opcode().is_terminator() {
+            None
+        } else {
+            self.call_signature(inst)
+        }
+    }
+
     /// Check if `inst` is a branch.
     pub fn analyze_branch(&self, inst: Inst) -> BranchInfo {
         self.insts[inst].analyze_branch(&self.value_lists)
@@ -716,7 +727,7 @@ impl DataFlowGraph {
         }
 
         // Not a fixed result, try to extract a return type from the call signature.
-        self.call_signature(inst).and_then(|sigref| {
+        self.call_results_signature(inst).and_then(|sigref| {
             self.signatures[sigref]
                 .returns
                 .get(result_idx - num_fixed_results)
@@ -993,7 +1004,7 @@ impl DataFlowGraph {
         reuse: &[Value],
     ) -> usize {
         // Get the call signature if this is a function call.
-        if let Some(sig) = self.call_signature(inst) {
+        if let Some(sig) = self.call_results_signature(inst) {
             assert_eq!(
                 self.insts[inst].opcode().constraints().num_fixed_results(),
                 0
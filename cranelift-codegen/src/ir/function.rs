@@ -14,7 +14,7 @@ use crate::ir::{
 };
 use crate::ir::{BlockOffsets, FrameLayout, InstEncodings, SourceLocs, StackSlots, ValueLocations};
 use crate::ir::{DataFlowGraph, ExternalName, Layout, Signature};
-use crate::ir::{JumpTableOffsets, JumpTables};
+use crate::ir::{JumpTableOffsets, JumpTables, Linkage};
 use crate::isa::{CallConv, EncInfo, Encoding, Legalize, TargetIsa};
 use crate::regalloc::{EntryRegDiversions, RegDiversions};
 use crate::value_label::ValueLabelsRanges;
@@ -319,6 +319,9 @@ pub struct DisplayFunctionAnnotations<'a> {
 
     /// Enable value labels annotations.
     pub value_ranges: Option<&'a ValueLabelsRanges>,
+
+    /// Linkage to print in the function header, as a module would declare it.
+    pub linkage: Option<Linkage>,
 }
 
 impl<'a> From<Option<&'a dyn TargetIsa>> for DisplayFunctionAnnotations<'a> {
@@ -326,6 +329,7 @@ impl<'a> From<Option<&'a dyn TargetIsa>> for DisplayFunctionAnnotations<'a> {
         DisplayFunctionAnnotations {
             isa,
             value_ranges: None,
+            linkage: None,
         }
     }
 }
@@ -46,6 +46,22 @@ pub enum LibCall {
     Memset,
     /// libc.memmove
     Memmove,
+    /// udiv.i64
+    UdivI64,
+    /// sdiv.i64
+    SdivI64,
+    /// urem.i64
+    UremI64,
+    /// srem.i64
+    SremI64,
+    /// udiv.i128
+    UdivI128,
+    /// sdiv.i128
+    SdivI128,
+    /// urem.i128
+    UremI128,
+    /// srem.i128
+    SremI128,
 }
 
 impl fmt::Display for LibCall {
@@ -71,6 +87,14 @@ impl FromStr for LibCall {
             "Memcpy" => Ok(Self::Memcpy),
             "Memset" => Ok(Self::Memset),
             "Memmove" => Ok(Self::Memmove),
+            "UdivI64" => Ok(Self::UdivI64),
+            "SdivI64" => Ok(Self::SdivI64),
+            "UremI64" => Ok(Self::UremI64),
+            "SremI64" => Ok(Self::SremI64),
+            "UdivI128" => Ok(Self::UdivI128),
+            "SdivI128" => Ok(Self::SdivI128),
+            "UremI128" => Ok(Self::UremI128),
+            "SremI128" => Ok(Self::SremI128),
             _ => Err(()),
         }
     }
@@ -97,6 +121,20 @@ impl LibCall {
                 Opcode::Nearest => Self::NearestF64,
                 _ => return None,
             },
+            types::I64 => match opcode {
+                Opcode::Udiv => Self::UdivI64,
+                Opcode::Sdiv => Self::SdivI64,
+                Opcode::Urem => Self::UremI64,
+                Opcode::Srem => Self::SremI64,
+                _ => return None,
+            },
+            types::I128 => match opcode {
+                Opcode::Udiv => Self::UdivI128,
+                Opcode::Sdiv => Self::SdivI128,
+                Opcode::Urem => Self::UremI128,
+                Opcode::Srem => Self::SremI128,
+                _ => return None,
+            },
             _ => return None,
         })
     }
@@ -220,5 +258,6 @@ mod tests {
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("SdivI128".parse(), Ok(LibCall::SdivI128));
     }
 }
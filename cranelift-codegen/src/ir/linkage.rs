@@ -0,0 +1,116 @@
+//! Symbol linkage.
+
+use core::fmt;
+use core::str::FromStr;
+
+/// Linkage refers to where an entity is defined and who can see it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Linkage {
+    /// Defined outside of a module.
+    Import,
+    /// Defined inside the module, but not visible outside it.
+    Local,
+    /// Defined inside the module, visible outside it, and may be preempted.
+    Preemptible,
+    /// Defined inside the module, and visible outside it.
+    Export,
+}
+
+impl Linkage {
+    /// Combine the linkages of two declarations of the same entity.
+    pub fn merge(a: Self, b: Self) -> Self {
+        match a {
+            Self::Export => Self::Export,
+            Self::Preemptible => match b {
+                Self::Export => Self::Export,
+                _ => Self::Preemptible,
+            },
+            Self::Local => match b {
+                Self::Export => Self::Export,
+                Self::Preemptible => Self::Preemptible,
+                _ => Self::Local,
+            },
+            Self::Import => b,
+        }
+    }
+
+    /// Test whether this linkage can have a definition.
+    pub fn is_definable(self) -> bool {
+        match self {
+            Self::Import => false,
+            Self::Local | Self::Preemptible | Self::Export => true,
+        }
+    }
+
+    /// Test whether this linkage will have a definition that cannot be preempted.
+    pub fn is_final(self) -> bool {
+        match self {
+            Self::Import | Self::Preemptible => false,
+            Self::Local | Self::Export => true,
+        }
+    }
+}
+
+impl fmt::Display for Linkage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Self::Import => "import",
+            Self::Local => "local",
+            Self::Preemptible => "preemptible",
+            Self::Export => "export",
+        })
+    }
+}
+
+impl FromStr for Linkage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "import" => Ok(Self::Import),
+            "local" => Ok(Self::Local),
+            "preemptible" => Ok(Self::Preemptible),
+            "export" => Ok(Self::Export),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn roundtrip() {
+        for &linkage in &[
+            Linkage::Import,
+            Linkage::Local,
+            Linkage::Preemptible,
+            Linkage::Export,
+        ] {
+            assert_eq!(linkage.to_string().parse(), Ok(linkage));
+        }
+        assert_eq!("static".parse::<Linkage>(), Err(()));
+    }
+
+    #[test]
+    fn merge() {
+        assert_eq!(
+            Linkage::merge(Linkage::Import, Linkage::Local),
+            Linkage::Local
+        );
+        assert_eq!(
+            Linkage::merge(Linkage::Local, Linkage::Preemptible),
+            Linkage::Preemptible
+        );
+        assert_eq!(
+            Linkage::merge(Linkage::Preemptible, Linkage::Import),
+            Linkage::Preemptible
+        );
+        assert_eq!(
+            Linkage::merge(Linkage::Export, Linkage::Local),
+            Linkage::Export
+        );
+    }
+}
@@ -2,6 +2,7 @@
 
 mod builder;
 pub mod constant;
+mod dataobject;
 pub mod dfg;
 pub mod entities;
 mod extfunc;
@@ -15,6 +16,7 @@ pub mod instructions;
 pub mod jumptable;
 pub mod layout;
 pub(crate) mod libcall;
+mod linkage;
 mod memflags;
 mod progpoint;
 mod sourceloc;
@@ -31,6 +33,7 @@ pub use crate::ir::builder::{
     InsertBuilder, InstBuilder, InstBuilderBase, InstInserterBase, ReplaceBuilder,
 };
 pub use crate::ir::constant::{ConstantData, ConstantOffset, ConstantPool};
+pub use crate::ir::dataobject::{DataInit, DataObject};
 pub use crate::ir::dfg::{DataFlowGraph, ValueDef};
 pub use crate::ir::entities::{
     Block, Constant, FuncRef, GlobalValue, Heap, Immediate, Inst, JumpTable, SigRef, StackSlot,
@@ -50,6 +53,7 @@ pub use crate::ir::instructions::{
 pub use crate::ir::jumptable::JumpTableData;
 pub use crate::ir::layout::Layout;
 pub use crate::ir::libcall::{get_probestack_funcref, LibCall};
+pub use crate::ir::linkage::Linkage;
 pub use crate::ir::memflags::MemFlags;
 pub use crate::ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
 pub use crate::ir::sourceloc::SourceLoc;
@@ -10,6 +10,7 @@ use crate::ir::Type;
 use crate::isa::arm64::inst::*;
 use crate::isa::arm64::*;
 use crate::machinst::*;
+use crate::settings;
 
 use alloc::vec::Vec;
 
@@ -17,10 +18,15 @@ use regalloc::{RealReg, Reg, RegClass, Set, SpillSlot, Writable};
 
 use log::debug;
 
+/// Frames which need up to this many stack probes are probed inline; bigger
+/// ones call the probestack function.
+const PROBESTACK_MAX_INLINE_PROBES: u64 = 4;
+
 #[derive(Clone, Debug)]
 enum ABIArg {
     Reg(RealReg),
-    Stack, // TODO
+    /// Offset from SP on entry to the function.
+    Stack(i64),
 }
 
 #[derive(Clone, Debug)]
@@ -38,27 +44,30 @@ struct ABISig {
 impl ABISig {
     fn from_func_sig(sig: &ir::Signature) -> ABISig {
         // Compute args and retvals from signature.
+        // The first eight integer arguments are passed in x0-x7, the rest on
+        // the stack, in 8-byte slots.
         let mut args = vec![];
         let mut next_xreg = 0;
+        let mut next_stack = 0;
         for param in &sig.params {
-            if in_int_reg(param.value_type) && next_xreg < 8 {
-                let x = next_xreg;
+            match &param.purpose {
+                &ir::ArgumentPurpose::VMContext
+                | &ir::ArgumentPurpose::StackLimit
+                | &ir::ArgumentPurpose::Normal => {}
+                _ => panic!(
+                    "Unsupported argument purpose {:?} in signature: {:?}",
+                    param.purpose, sig
+                ),
+            }
+            if !in_int_reg(param.value_type) {
+                panic!("Unsupported argument type in signature: {:?}", sig);
+            }
+            if next_xreg < 8 {
+                args.push(ABIArg::Reg(xreg(next_xreg).to_real_reg()));
                 next_xreg += 1;
-                let reg = xreg(x).to_real_reg();
-                match &param.purpose {
-                    &ir::ArgumentPurpose::VMContext | &ir::ArgumentPurpose::Normal => {
-                        args.push(ABIArg::Reg(reg));
-                    }
-                    _ => panic!(
-                        "Unsupported argument purpose {:?} in signature: {:?}",
-                        param.purpose, sig
-                    ),
-                }
             } else {
-                panic!(
-                    "Unsupported argument type or argument count in signature: {:?}",
-                    sig
-                );
+                args.push(ABIArg::Stack(next_stack));
+                next_stack += 8;
             }
         }
 
@@ -89,6 +98,8 @@ pub struct ARM64ABIBody {
     stackslots_size: usize,            // total stack size of all stackslots
     clobbered: Set<Writable<RealReg>>, // clobbered registers, from regalloc.
     spillslots: Option<usize>,         // total number of spillslots, from regalloc.
+    stack_limit: Option<ABIArg>,       // the stack limit argument, if any.
+    flags: settings::Flags,
 }
 
 fn in_int_reg(ty: types::Type) -> bool {
@@ -101,10 +112,14 @@ fn in_int_reg(ty: types::Type) -> bool {
 
 impl ARM64ABIBody {
     /// Create a new body ABI instance.
-    pub fn new(f: &ir::Function) -> ARM64ABIBody {
+    pub fn new(f: &ir::Function, flags: settings::Flags) -> ARM64ABIBody {
         //println!("ARM64 ABI: func signature {:?}", f.signature);
 
         let sig = ABISig::from_func_sig(&f.signature);
+        let stack_limit = f
+            .signature
+            .special_param_index(ir::ArgumentPurpose::StackLimit)
+            .map(|idx| sig.args[idx].clone());
 
         // Compute stackslot locations and total stackslot size.
         let mut stack_offset: usize = 0;
@@ -123,6 +138,8 @@ impl ARM64ABIBody {
             stackslots_size: stack_offset,
             clobbered: Set::empty(),
             spillslots: None,
+            stack_limit,
+            flags,
         }
     }
 }
@@ -159,6 +176,131 @@ fn store_stack(fp_offset: i64, from_reg: Reg, ty: Type) -> Inst {
     }
 }
 
+// Get a sequence of instructions which computes `sp - amount` into the
+// spilltmp register.
+fn gen_sp_minus_into_spilltmp(amount: u64, insts: &mut Vec<Inst>) {
+    if let Some(imm12) = Imm12::maybe_from_u64(amount) {
+        insts.push(Inst::AluRRImm12 {
+            alu_op: ALUOp::Sub64,
+            rd: writable_spilltmp_reg(),
+            rn: stack_reg(),
+            imm12,
+        });
+    } else {
+        insts.push(Inst::ULoad64 {
+            rd: writable_spilltmp_reg(),
+            mem: MemArg::label(MemLabel::ConstantData(u64_constant(amount))),
+        });
+        // The extended-register form of SUB, because the shifted-register
+        // form reads register 31 as XZR rather than SP.
+        insts.push(Inst::AluRRRExtend {
+            alu_op: ALUOp::Sub64,
+            rd: writable_spilltmp_reg(),
+            rn: stack_reg(),
+            rm: spilltmp_reg(),
+            extendop: ExtendOp::UXTX,
+        });
+    }
+}
+
+// Get a sequence of instructions which traps with a stack overflow if
+// allocating a frame of `frame_size` bytes would take SP to or below the
+// stack limit passed in `limit`.  This runs at the start of the prologue,
+// where x15 and x16 are free.
+fn gen_stack_limit_check(limit: &ABIArg, frame_size: u64, insts: &mut Vec<Inst>) {
+    let limit = match limit {
+        &ABIArg::Reg(reg) => reg.to_reg(),
+        &ABIArg::Stack(offset) => {
+            // Nothing has been pushed yet, so SP is still as it was on entry.
+            insts.push(Inst::ULoad64 {
+                rd: writable_tmp2_reg(),
+                mem: MemArg::reg_maybe_offset(stack_reg(), offset, I64).unwrap(),
+            });
+            tmp2_reg()
+        }
+    };
+    // spilltmp = limit + frame_size
+    if let Some(imm12) = Imm12::maybe_from_u64(frame_size) {
+        insts.push(Inst::AluRRImm12 {
+            alu_op: ALUOp::Add64,
+            rd: writable_spilltmp_reg(),
+            rn: limit,
+            imm12,
+        });
+    } else {
+        insts.push(Inst::ULoad64 {
+            rd: writable_spilltmp_reg(),
+            mem: MemArg::label(MemLabel::ConstantData(u64_constant(frame_size))),
+        });
+        insts.push(Inst::AluRRR {
+            alu_op: ALUOp::Add64,
+            rd: writable_spilltmp_reg(),
+            rn: spilltmp_reg(),
+            rm: limit,
+        });
+    }
+    // cmp sp, spilltmp
+    insts.push(Inst::AluRRRExtend {
+        alu_op: ALUOp::SubS64,
+        rd: writable_zero_reg(),
+        rn: stack_reg(),
+        rm: spilltmp_reg(),
+        extendop: ExtendOp::UXTX,
+    });
+    insts.push(Inst::TrapIf {
+        kind: CondBrKind::Cond(Cond::Ls),
+        trap_info: (ir::SourceLoc::default(), ir::TrapCode::StackOverflow),
+    });
+}
+
+// Get a sequence of instructions which touches every page of a frame of
+// `frame_size` bytes below SP, from the top down, so that a guard page below
+// the stack can't be skipped over.
+fn gen_inline_probestack(frame_size: u64, guard_size: u64, insts: &mut Vec<Inst>) {
+    for page in 1..=(frame_size / guard_size) {
+        gen_sp_minus_into_spilltmp(page * guard_size, insts);
+        insts.push(Inst::Store64 {
+            rd: zero_reg(),
+            mem: MemArg::UnsignedOffset(spilltmp_reg(), UImm12Scaled::zero(I64)),
+        });
+    }
+}
+
+// Get a sequence of instructions which probes a frame of `frame_size` bytes
+// below SP by calling the probestack function.  It takes the size in x15 and
+// preserves the other registers, except x16, x17 and LR, so it is called once
+// FP and LR are saved.  It doesn't allocate the frame.  As on x64, the call
+// goes through a register unless the code is PIC, since the function may be
+// out of reach of a direct branch.
+fn gen_probestack_call(frame_size: u64, flags: &settings::Flags, insts: &mut Vec<Inst>) {
+    let probestack = ir::ExternalName::LibCall(ir::LibCall::Probestack);
+    insts.push(Inst::ULoad64 {
+        rd: writable_spilltmp_reg(),
+        mem: MemArg::label(MemLabel::ConstantData(u64_constant(frame_size))),
+    });
+    let mut uses = Set::empty();
+    uses.insert(spilltmp_reg());
+    let mut defs = Set::empty();
+    defs.insert(writable_link_reg());
+    if flags.is_pic() {
+        insts.push(Inst::Call {
+            dest: probestack,
+            uses,
+            defs,
+        });
+    } else {
+        insts.push(Inst::ULoad64 {
+            rd: writable_tmp2_reg(),
+            mem: MemArg::label(MemLabel::ExtName(probestack, 0)),
+        });
+        insts.push(Inst::CallInd {
+            rn: tmp2_reg(),
+            uses,
+            defs,
+        });
+    }
+}
+
 fn is_callee_save(r: RealReg) -> bool {
     match r.get_class() {
         RegClass::I64 => {
@@ -211,6 +353,10 @@ fn get_caller_saves_set() -> Set<Writable<Reg>> {
 }
 
 impl ABIBody<Inst> for ARM64ABIBody {
+    fn flags(&self) -> &settings::Flags {
+        &self.flags
+    }
+
     fn liveins(&self) -> Set<RealReg> {
         let mut set: Set<RealReg> = Set::empty();
         for arg in &self.sig.args {
@@ -248,7 +394,8 @@ impl ABIBody<Inst> for ARM64ABIBody {
             &ABIArg::Reg(r) => {
                 return Inst::gen_move(into_reg, r.to_reg());
             }
-            _ => unimplemented!(),
+            // FP is 16 bytes below SP on entry, under the saved FP and LR.
+            &ABIArg::Stack(offset) => load_stack(16 + offset, into_reg, I64),
         }
     }
 
@@ -318,6 +465,28 @@ impl ABIBody<Inst> for ARM64ABIBody {
         let total_stacksize = self.stackslots_size + 8 * self.spillslots.unwrap();
         let total_stacksize = (total_stacksize + 15) & !15; // 16-align the stack.
 
+        // The whole frame: FP and LR, the stack and spill slots, and the
+        // clobbered registers, saved in pairs.
+        let clobbered = get_callee_saves(self.clobbered.to_vec());
+        let frame_size = (16 + total_stacksize + 16 * ((clobbered.len() + 1) / 2)) as u64;
+
+        // Check the frame against the stack limit, and probe it, before
+        // anything is stored to it.  Bigger frames are probed by calling the
+        // probestack function, which needs LR saved first; only FP and LR are
+        // stored before then, at the top of the frame.
+        if let Some(ref limit) = self.stack_limit {
+            gen_stack_limit_check(limit, frame_size, &mut insts);
+        }
+        let guard_size = 1u64 << self.flags.probestack_size_log2();
+        let mut probestack_call = false;
+        if self.flags.enable_probestack() && frame_size > guard_size {
+            if frame_size / guard_size <= PROBESTACK_MAX_INLINE_PROBES {
+                gen_inline_probestack(frame_size, guard_size, &mut insts);
+            } else {
+                probestack_call = true;
+            }
+        }
+
         // stp fp (x29), lr (x30), [sp, #-16]!
         insts.push(Inst::StoreP64 {
             rt: fp_reg(),
@@ -338,6 +507,9 @@ impl ABIBody<Inst> for ARM64ABIBody {
                 shift12: false,
             },
         });
+        if probestack_call {
+            gen_probestack_call(frame_size, &self.flags, &mut insts);
+        }
 
         if total_stacksize > 0 {
             // sub sp, sp, #total_stacksize
@@ -368,7 +540,6 @@ impl ABIBody<Inst> for ARM64ABIBody {
         }
 
         // Save clobbered registers.
-        let clobbered = get_callee_saves(self.clobbered.to_vec());
         for reg_pair in clobbered.chunks(2) {
             let (r1, r2) = if reg_pair.len() == 2 {
                 // .to_reg().to_reg(): Writable<RealReg> --> RealReg --> Reg
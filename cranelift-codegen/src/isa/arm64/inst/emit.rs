@@ -745,6 +745,34 @@ impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
                 // Unconditional part.
                 sink.put4(enc_jump26(0b000101, not_taken.as_off26().unwrap_or(0)));
             }
+            &Inst::TrapIf {
+                kind,
+                trap_info: (srcloc, code),
+            } => {
+                // Branch over the `udf` (two instructions on) if the trap
+                // condition is false.
+                match kind.invert() {
+                    CondBrKind::Zero(reg) => {
+                        sink.put4(enc_cmpbr(0b1_011010_0, 2, reg));
+                    }
+                    CondBrKind::NotZero(reg) => {
+                        sink.put4(enc_cmpbr(0b1_011010_1, 2, reg));
+                    }
+                    CondBrKind::Cond(c) => {
+                        sink.put4(enc_cbr(0b01010100, 2, 0b0, c.bits()));
+                    }
+                }
+                sink.add_trap(srcloc, code);
+                // udf #0
+                sink.put4(0x00000000);
+            }
+            &Inst::Udf {
+                trap_info: (srcloc, code),
+            } => {
+                sink.add_trap(srcloc, code);
+                // udf #0
+                sink.put4(0x00000000);
+            }
             &Inst::Nop => {}
             &Inst::Nop4 => {
                 sink.put4(0xd503201f);
@@ -756,6 +784,7 @@ impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::ir::{SourceLoc, TrapCode};
     use crate::isa::test_utils;
 
     #[test]
@@ -2351,6 +2380,30 @@ mod test {
             "blr x10",
         ));
 
+        insns.push((
+            Inst::TrapIf {
+                kind: CondBrKind::Cond(Cond::Ls),
+                trap_info: (SourceLoc::default(), TrapCode::StackOverflow),
+            },
+            "4800005400000000",
+            "b.hi 8 ; udf",
+        ));
+        insns.push((
+            Inst::TrapIf {
+                kind: CondBrKind::NotZero(xreg(8)),
+                trap_info: (SourceLoc::default(), TrapCode::StackOverflow),
+            },
+            "480000B400000000",
+            "cbz x8, 8 ; udf",
+        ));
+        insns.push((
+            Inst::Udf {
+                trap_info: (SourceLoc::default(), TrapCode::Interrupt),
+            },
+            "00000000",
+            "udf",
+        ));
+
         let rru = create_reg_universe();
         for (insn, expected_encoding, expected_printing) in insns {
             println!(
@@ -10,7 +10,7 @@ use crate::ir::constant::{ConstantData, ConstantOffset};
 use crate::ir::types::{
     B1, B128, B16, B32, B64, B8, F32, F64, FFLAGS, I128, I16, I32, I64, I8, IFLAGS,
 };
-use crate::ir::{ExternalName, GlobalValue, Opcode, Type};
+use crate::ir::{ExternalName, GlobalValue, Opcode, SourceLoc, TrapCode, Type};
 use crate::machinst::*;
 
 use regalloc::Map as RegallocMap;
@@ -338,6 +338,17 @@ pub enum Inst {
         not_taken: BranchTarget,
         kind: CondBrKind,
     },
+
+    /// A trap if the given condition holds: a conditional branch over an
+    /// undefined instruction. Unlike the branches above, this doesn't end a
+    /// basic block.
+    TrapIf {
+        kind: CondBrKind,
+        trap_info: (SourceLoc, TrapCode),
+    },
+
+    /// An unconditional trap: an undefined instruction.
+    Udf { trap_info: (SourceLoc, TrapCode) },
 }
 
 impl Inst {
@@ -523,13 +534,14 @@ fn arm64_get_regs(inst: &Inst) -> InstRegUses {
         }
         &Inst::CondBr { ref kind, .. }
         | &Inst::CondBrLowered { ref kind, .. }
-        | &Inst::CondBrLoweredCompound { ref kind, .. } => match kind {
+        | &Inst::CondBrLoweredCompound { ref kind, .. }
+        | &Inst::TrapIf { ref kind, .. } => match kind {
             CondBrKind::Zero(rt) | CondBrKind::NotZero(rt) => {
                 iru.used.insert(*rt);
             }
             CondBrKind::Cond(_) => {}
         },
-        &Inst::Nop | Inst::Nop4 => {}
+        &Inst::Nop | Inst::Nop4 | Inst::Udf { .. } => {}
     }
 
     // Enforce the invariant that if a register is in the 'modify' set, it
@@ -838,6 +850,11 @@ fn arm64_map_regs(
             not_taken,
             kind: map_br(u, &kind),
         },
+        &mut Inst::TrapIf { kind, trap_info } => Inst::TrapIf {
+            kind: map_br(u, &kind),
+            trap_info,
+        },
+        &mut Inst::Udf { trap_info } => Inst::Udf { trap_info },
         &mut Inst::Nop => Inst::Nop,
         &mut Inst::Nop4 => Inst::Nop4,
     };
@@ -884,6 +901,13 @@ impl MachInst for Inst {
         }
     }
 
+    fn is_trap(&self) -> bool {
+        match self {
+            &Inst::Udf { .. } => true,
+            _ => false,
+        }
+    }
+
     fn gen_move(to_reg: Writable<Reg>, from_reg: Reg) -> Inst {
         Inst::mov(to_reg, from_reg)
     }
@@ -1412,6 +1436,15 @@ impl Inst {
                 };
                 first.show_rru(mb_rru) + " ; " + &second.show_rru(mb_rru)
             }
+            &Inst::TrapIf { ref kind, .. } => {
+                // The branch skips the `udf` when the trap condition is false.
+                match kind.invert() {
+                    CondBrKind::Zero(reg) => format!("cbz {}, 8 ; udf", reg.show_rru(mb_rru)),
+                    CondBrKind::NotZero(reg) => format!("cbnz {}, 8 ; udf", reg.show_rru(mb_rru)),
+                    CondBrKind::Cond(c) => format!("b.{} 8 ; udf", c.show_rru(mb_rru)),
+                }
+            }
+            &Inst::Udf { .. } => "udf".to_string(),
         }
     }
 }
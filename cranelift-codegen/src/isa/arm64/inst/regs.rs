@@ -134,6 +134,18 @@ pub fn writable_spilltmp_reg() -> Writable<Reg> {
     Writable::from_reg(spilltmp_reg())
 }
 
+/// Get a reference to the second temp register, x16 (IP0). The allocator may
+/// use it, so it is only used where no value can be live in it, such as at the
+/// start of the prologue.
+pub fn tmp2_reg() -> Reg {
+    xreg(16)
+}
+
+/// Get a writable reference to the tmp2 reg.
+pub fn writable_tmp2_reg() -> Writable<Reg> {
+    Writable::from_reg(tmp2_reg())
+}
+
 /// Create the register universe for ARM64.
 pub fn create_reg_universe() -> RealRegUniverse {
     let mut regs = vec![];
@@ -5,9 +5,10 @@
 use crate::ir::condcodes::IntCC;
 use crate::ir::types::*;
 use crate::ir::Inst as IRInst;
-use crate::ir::{Block, InstructionData, Opcode, Type};
+use crate::ir::{Block, InstructionData, Opcode, TrapCode, Type};
 use crate::machinst::lower::*;
 use crate::machinst::*;
+use crate::result::{CodegenError, CodegenResult};
 
 use crate::isa::arm64::abi::*;
 use crate::isa::arm64::inst::*;
@@ -654,7 +655,7 @@ pub fn condcode_is_signed(cc: IntCC) -> bool {
 // Top-level instruction lowering entry point, for one instruction.
 
 /// Actually codegen an instruction's results into registers.
-fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
+fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) -> CodegenResult<()> {
     let op = ctx.data(insn).opcode();
     let inputs: SmallVec<[InsnInput; 4]> = (0..ctx.num_inputs(insn))
         .map(|i| InsnInput { insn, input: i })
@@ -1349,14 +1350,23 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             unimplemented!()
         }
 
-        Opcode::Debugtrap
-        | Opcode::Trap
-        | Opcode::Trapz
-        | Opcode::Trapnz
-        | Opcode::Trapif
-        | Opcode::Trapff
-        | Opcode::ResumableTrap
-        | Opcode::Safepoint => {
+        Opcode::Trap | Opcode::ResumableTrap => {
+            let trap_info = (ctx.srcloc(insn), inst_trapcode(ctx.data(insn)).unwrap());
+            ctx.emit(Inst::Udf { trap_info });
+        }
+
+        Opcode::Trapz | Opcode::Trapnz => {
+            let trap_info = (ctx.srcloc(insn), inst_trapcode(ctx.data(insn)).unwrap());
+            let rt = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend64);
+            let kind = if op == Opcode::Trapz {
+                CondBrKind::Zero(rt)
+            } else {
+                CondBrKind::NotZero(rt)
+            };
+            ctx.emit(Inst::TrapIf { kind, trap_info });
+        }
+
+        Opcode::Debugtrap | Opcode::Trapif | Opcode::Trapff | Opcode::Safepoint => {
             panic!("trap support not implemented!");
         }
 
@@ -1412,6 +1422,13 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             }
         }
 
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            // TODO
+            return Err(CodegenError::Unsupported(
+                "tail calls with the arm64 backend".into(),
+            ));
+        }
+
         Opcode::GetPinnedReg
         | Opcode::SetPinnedReg
         | Opcode::Spill
@@ -1548,6 +1565,8 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             panic!("x86-specific opcode in supposedly arch-neutral IR!");
         }
     }
+
+    Ok(())
 }
 
 //=============================================================================
@@ -1603,6 +1622,16 @@ fn ldst_offset(data: &InstructionData) -> Option<i32> {
     }
 }
 
+fn inst_trapcode(data: &InstructionData) -> Option<TrapCode> {
+    match data {
+        &InstructionData::Trap { code, .. }
+        | &InstructionData::CondTrap { code, .. }
+        | &InstructionData::IntCondTrap { code, .. }
+        | &InstructionData::FloatCondTrap { code, .. } => Some(code),
+        _ => None,
+    }
+}
+
 fn inst_condcode(data: &InstructionData) -> Option<IntCC> {
     match data {
         &InstructionData::IntCond { cond, .. }
@@ -1632,8 +1661,8 @@ fn maybe_input_insn<C: LowerCtx<Inst>>(c: &mut C, input: InsnInput, op: Opcode)
 impl LowerBackend for Arm64Backend {
     type MInst = Inst;
 
-    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) {
-        lower_insn_to_regs(ctx, ir_inst);
+    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
+        lower_insn_to_regs(ctx, ir_inst)
     }
 
     fn lower_branch_group<C: LowerCtx<Inst>>(
@@ -4,10 +4,14 @@
 
 use crate::binemit::{CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink};
 use crate::ir::Function;
-use crate::machinst::{compile, MachBackend, MachCompileResult, ShowWithRRU, VCode};
+use crate::isa::Builder as IsaBuilder;
+use crate::isa::TargetIsa;
+use crate::machinst::{
+    compile, MachBackend, MachCompileResult, ShowWithRRU, TargetIsaAdapter, VCode,
+};
 use crate::machinst::{ABIBody, ABICall};
 use crate::result::CodegenResult;
-use crate::settings;
+use crate::settings as shared_settings;
 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
@@ -18,49 +22,87 @@ use regalloc::RealRegUniverse;
 use target_lexicon::Triple;
 
 // New backend:
-mod abi;
-mod inst;
+pub(crate) mod abi;
+pub(crate) mod inst;
 mod lower;
+pub mod settings;
 
 use inst::create_reg_universe;
 
 /// An ARM64 backend.
 pub struct Arm64Backend {
-    flags: settings::Flags,
+    triple: Triple,
+    flags: shared_settings::Flags,
+    isa_flags: settings::Flags,
 }
 
 impl Arm64Backend {
-    /// Create a new ARM64 backend.
-    pub fn new() -> Arm64Backend {
+    /// Create a new ARM64 backend with the given shared and ISA-specific flags.
+    pub fn new_with_flags(
+        triple: Triple,
+        flags: shared_settings::Flags,
+        isa_flags: settings::Flags,
+    ) -> Arm64Backend {
         Arm64Backend {
-            flags: settings::Flags::new(settings::builder()),
+            triple,
+            flags,
+            isa_flags,
         }
     }
 
-    fn compile_vcode(&self, mut func: Function) -> VCode<inst::Inst> {
+    /// Get the ISA-specific flags of this backend.
+    pub fn isa_flags(&self) -> &settings::Flags {
+        &self.isa_flags
+    }
+
+    fn compile_vcode(&self, mut func: Function) -> CodegenResult<VCode<inst::Inst>> {
         // This performs lowering to VCode, register-allocates the code, computes
         // block layout and finalizes branches. The result is ready for binary emission.
-        let abi = Box::new(abi::ARM64ABIBody::new(&func));
+        let abi = Box::new(abi::ARM64ABIBody::new(&func, self.flags.clone()));
         compile::compile::<Arm64Backend>(&mut func, self, abi)
     }
 }
 
+/// Get an ISA builder for creating ARM64 targets.
+pub fn isa_builder(triple: Triple) -> IsaBuilder {
+    IsaBuilder {
+        triple,
+        setup: Some(settings::builder()),
+        constructor: Some(isa_constructor),
+        wrapped: None,
+    }
+}
+
+fn isa_constructor(
+    triple: Triple,
+    shared_flags: shared_settings::Flags,
+    builder: shared_settings::Builder,
+) -> Box<dyn TargetIsa> {
+    let isa_flags = settings::Flags::new(&shared_flags, builder);
+    let backend = Arm64Backend::new_with_flags(triple, shared_flags, isa_flags);
+    Box::new(TargetIsaAdapter::new(backend))
+}
+
 impl MachBackend for Arm64Backend {
     fn compile_function(
         &self,
         func: Function,
         want_disasm: bool,
     ) -> CodegenResult<MachCompileResult> {
-        let vcode = self.compile_vcode(func);
+        let vcode = self.compile_vcode(func)?;
         let sections = vcode.emit();
 
         let disasm = if want_disasm {
-            Some(vcode.show_rru(Some(&create_reg_universe())))
+            Some(vcode.show_disasm(Some(&create_reg_universe())))
         } else {
             None
         };
 
-        Ok(MachCompileResult { sections, disasm })
+        Ok(MachCompileResult {
+            sections,
+            disasm,
+            unwind_info: vcode.unwind_info(),
+        })
     }
 
     fn name(&self) -> &'static str {
@@ -68,10 +110,10 @@ impl MachBackend for Arm64Backend {
     }
 
     fn triple(&self) -> Triple {
-        FromStr::from_str("arm64").unwrap()
+        self.triple.clone()
     }
 
-    fn flags(&self) -> &settings::Flags {
+    fn flags(&self) -> &shared_settings::Flags {
         &self.flags
     }
 
@@ -86,8 +128,10 @@ mod test {
     use crate::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
     use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::types::*;
-    use crate::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
+    use crate::ir::{AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, Signature};
     use crate::isa::CallConv;
+    use crate::result::CodegenError;
+    use crate::Context;
 
     #[test]
     fn test_compile_function() {
@@ -106,7 +150,13 @@ mod test {
         let v1 = pos.ins().iadd(arg0, v0);
         pos.ins().return_(&[v1]);
 
-        let backend = Arm64Backend::new();
+        let shared_flags = shared_settings::Flags::new(shared_settings::builder());
+        let isa_flags = settings::Flags::new(&shared_flags, settings::builder());
+        let backend = Arm64Backend::new_with_flags(
+            Triple::from_str("arm64").unwrap(),
+            shared_flags,
+            isa_flags,
+        );
         let sections = backend.compile_function(func, false).unwrap().sections;
         let code = &sections.sections[0].data;
 
@@ -124,4 +174,63 @@ mod test {
 
         assert_eq!(code, &golden);
     }
+
+    #[test]
+    fn test_tail_call_unsupported() {
+        let name = ExternalName::testcase("test0");
+        let mut sig = Signature::new(CallConv::Tail);
+        sig.params.push(AbiParam::new(I64));
+        sig.returns.push(AbiParam::new(I64));
+        let mut func = Function::with_name_signature(name, sig.clone());
+        let callee = func.import_signature(sig);
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: callee,
+            colocated: false,
+        });
+
+        let bb0 = func.dfg.make_block();
+        let arg0 = func.dfg.append_block_param(bb0, I64);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(bb0);
+        pos.ins().return_call(callee, &[arg0]);
+
+        let shared_flags = shared_settings::Flags::new(shared_settings::builder());
+        let isa_flags = settings::Flags::new(&shared_flags, settings::builder());
+        let backend = Arm64Backend::new_with_flags(
+            Triple::from_str("arm64").unwrap(),
+            shared_flags,
+            isa_flags,
+        );
+        match backend.compile_function(func, false) {
+            Err(CodegenError::Unsupported(_)) => {}
+            _ => panic!("expected tail calls to be unsupported"),
+        }
+    }
+
+    #[test]
+    fn test_i128_unsupported() {
+        let name = ExternalName::testcase("test0");
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I128));
+        sig.returns.push(AbiParam::new(I128));
+        let mut func = Function::with_name_signature(name, sig);
+
+        let bb0 = func.dfg.make_block();
+        let arg0 = func.dfg.append_block_param(bb0, I128);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(bb0);
+        let v0 = pos.ins().iadd(arg0, arg0);
+        pos.ins().return_(&[v0]);
+
+        let isa = isa_builder(Triple::from_str("arm64").unwrap())
+            .finish(shared_settings::Flags::new(shared_settings::builder()));
+        let mut context = Context::for_function(func);
+        match context.compile(&*isa) {
+            Err(CodegenError::Unsupported(_)) => {}
+            _ => panic!("expected i128 to be unsupported"),
+        }
+    }
 }
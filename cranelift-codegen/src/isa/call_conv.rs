@@ -21,6 +21,8 @@ pub enum CallConv {
     BaldrdashWindows,
     /// Specialized convention for the probestack function
     Probestack,
+    /// Convention supporting guaranteed tail calls, where the callee pops its stack arguments
+    Tail,
 }
 
 impl CallConv {
@@ -57,6 +59,16 @@ impl CallConv {
         }
     }
 
+    /// Does the callee pop its stack arguments when returning?
+    ///
+    /// This is required to make tail calls to functions with more stack arguments than the caller.
+    pub fn callee_pops_stack_args(self) -> bool {
+        match self {
+            Self::Tail => true,
+            _ => false,
+        }
+    }
+
     /// Is the calling convention extending the Baldrdash ABI?
     pub fn extends_baldrdash(self) -> bool {
         match self {
@@ -76,6 +88,7 @@ impl fmt::Display for CallConv {
             Self::BaldrdashSystemV => "baldrdash_system_v",
             Self::BaldrdashWindows => "baldrdash_windows",
             Self::Probestack => "probestack",
+            Self::Tail => "tail",
         })
     }
 }
@@ -91,6 +104,7 @@ impl str::FromStr for CallConv {
             "baldrdash_system_v" => Ok(Self::BaldrdashSystemV),
             "baldrdash_windows" => Ok(Self::BaldrdashWindows),
             "probestack" => Ok(Self::Probestack),
+            "tail" => Ok(Self::Tail),
             _ => Err(()),
         }
     }
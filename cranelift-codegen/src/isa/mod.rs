@@ -56,7 +56,7 @@ use crate::binemit;
 use crate::flowgraph;
 use crate::ir;
 pub use crate::isa::enc_tables::Encodings;
-use crate::machinst::{MachBackend, TargetIsaAdapter};
+use crate::machinst::MachBackend;
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::settings;
@@ -65,6 +65,7 @@ use crate::timing;
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use core::fmt;
+use core::str::FromStr;
 use target_lexicon::{triple, Architecture, PointerWidth, Triple};
 use thiserror::Error;
 
@@ -81,7 +82,7 @@ mod x64;
 mod arm32;
 
 #[cfg(feature = "arm64")]
-mod arm64;
+pub(crate) mod arm64;
 
 mod call_conv;
 mod constraints;
@@ -129,35 +130,106 @@ macro_rules! isa_builder {
     }};
 }
 
-/// Look for an ISA for the given `triple`.
+/// Which of the available backends to use for a target.
+///
+/// Only x86-64 currently has more than one backend: the legacy `x86` backend, which is based on
+/// encodings and legalizations, and the new `x64` backend, which is based on `MachInst`s. Having
+/// both in one binary allows comparing them, or falling back to the legacy one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendVariant {
+    /// The default backend for the target: the legacy one, if the target has one.
+    Any,
+    /// A legacy backend, based on encodings and legalizations.
+    Legacy,
+    /// A new backend, based on `MachInst`s.
+    MachInst,
+}
+
+impl Default for BackendVariant {
+    fn default() -> Self {
+        BackendVariant::Any
+    }
+}
+
+impl fmt::Display for BackendVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            BackendVariant::Any => "any",
+            BackendVariant::Legacy => "legacy",
+            BackendVariant::MachInst => "machinst",
+        })
+    }
+}
+
+impl FromStr for BackendVariant {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(BackendVariant::Any),
+            "legacy" => Ok(BackendVariant::Legacy),
+            "machinst" => Ok(BackendVariant::MachInst),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Look for an ISA for the given `triple`, using its default backend.
 /// Return a builder that can create a corresponding `TargetIsa`.
+///
+/// Use `lookup_variant` to choose the new backend for targets that have several.
 pub fn lookup(triple: Triple) -> Result<IsaBackend, LookupError> {
+    lookup_variant(triple, BackendVariant::Any)
+}
+
+/// Look for an ISA for the given `triple`, using the given backend `variant`.
+/// Return a builder that can create a corresponding `TargetIsa`.
+pub fn lookup_variant(triple: Triple, variant: BackendVariant) -> Result<IsaBackend, LookupError> {
+    use BackendVariant::*;
     match triple.architecture {
-        Architecture::Riscv32 | Architecture::Riscv64 => isa_builder!(riscv, "riscv", triple),
-        #[cfg(not(feature = "new-x64"))]
-        Architecture::I386 | Architecture::I586 | Architecture::I686 | Architecture::X86_64 => {
+        Architecture::Riscv32 | Architecture::Riscv64 if variant != MachInst => {
+            isa_builder!(riscv, "riscv", triple)
+        }
+        Architecture::I386 | Architecture::I586 | Architecture::I686 if variant != MachInst => {
             isa_builder!(x86, "x86", triple)
         }
-        Architecture::Arm { .. } => isa_builder!(arm32, "arm32", triple),
-        // ARM64 uses the new backend.
-        Architecture::Aarch64 { .. } => Ok(IsaBackend::Builder(Builder::wrap(Box::new(
-            TargetIsaAdapter::new(arm64::Arm64Backend::new()),
-        )))),
-        // X86_64 uses the new backend.  Bwaha!
-        #[cfg(feature = "new-x64")]
-        Architecture::X86_64 => Ok(IsaBackend::Builder(Builder::wrap(Box::new(
-            TargetIsaAdapter::new(x64::X64Backend::new()),
-        )))),
+        Architecture::X86_64 => match variant {
+            // The new backend is only used when asked for explicitly.
+            Any | Legacy => isa_builder!(x86, "x86", triple),
+            MachInst => isa_builder!(x64, "new-x64", triple),
+        },
+        Architecture::Arm { .. } if variant != MachInst => isa_builder!(arm32, "arm32", triple),
+        // ARM64 only has the new backend.
+        Architecture::Aarch64 { .. } if variant != Legacy => isa_builder!(arm64, "arm64", triple),
         _ => Err(LookupError::Unsupported),
     }
 }
 
-/// Return a MachBackend if supported.
-pub fn lookup_mach_backend(triple: Triple) -> Result<Box<dyn MachBackend>, LookupError> {
+/// Return a MachBackend if supported, configured with the given shared flags and the default
+/// ISA-specific flags.
+pub fn lookup_mach_backend(
+    triple: Triple,
+    flags: settings::Flags,
+) -> Result<Box<dyn MachBackend>, LookupError> {
     match triple.architecture {
-        Architecture::Aarch64 { .. } => Ok(Box::new(arm64::Arm64Backend::new())),
+        #[cfg(feature = "arm64")]
+        Architecture::Aarch64 { .. } => {
+            let isa_flags = arm64::settings::Flags::new(&flags, arm64::settings::builder());
+            Ok(Box::new(arm64::Arm64Backend::new_with_flags(
+                triple, flags, isa_flags,
+            )))
+        }
         #[cfg(feature = "new-x64")]
-        Architecture::X86_64 => Ok(Box::new(x64::X64Backend::new())),
+        Architecture::X86_64 => {
+            let isa_flags = x86::settings::Flags::new(&flags, x86::settings::builder());
+            Ok(Box::new(x64::X64Backend::new_with_flags(
+                triple, flags, isa_flags,
+            )))
+        }
+        #[cfg(not(feature = "arm64"))]
+        Architecture::Aarch64 { .. } => Err(LookupError::SupportDisabled),
+        #[cfg(not(feature = "new-x64"))]
+        Architecture::X86_64 => Err(LookupError::SupportDisabled),
         _ => Err(LookupError::Unsupported),
     }
 }
@@ -165,7 +237,6 @@ pub fn lookup_mach_backend(triple: Triple) -> Result<Box<dyn MachBackend>, Looku
 /// Look for a supported ISA with the given `name`.
 /// Return a builder that can create a corresponding `TargetIsa`.
 pub fn lookup_by_name(name: &str) -> Result<IsaBackend, LookupError> {
-    use alloc::str::FromStr;
     lookup(triple!(name))
 }
 
@@ -465,3 +536,32 @@ pub trait TargetIsa: fmt::Display + Send + Sync {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_mach_backend(variant: BackendVariant) -> bool {
+        let flags = settings::Flags::new(settings::builder());
+        lookup_variant(triple!("x86_64"), variant)
+            .unwrap()
+            .as_builder()
+            .finish(flags)
+            .get_mach_backend()
+            .is_some()
+    }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn any_x86_64_backend_is_legacy() {
+        // Compiling in the new backend doesn't change the default.
+        assert!(!is_mach_backend(BackendVariant::Any));
+        assert!(!is_mach_backend(BackendVariant::Legacy));
+    }
+
+    #[test]
+    #[cfg(feature = "new-x64")]
+    fn x86_64_new_backend_is_explicit() {
+        assert!(is_mach_backend(BackendVariant::MachInst));
+    }
+}
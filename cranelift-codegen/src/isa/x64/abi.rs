@@ -3,6 +3,8 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
 
+#[cfg(feature = "unwind")]
+use crate::binemit::FrameUnwindKind;
 use crate::ir;
 use crate::ir::types;
 use crate::ir::types::*;
@@ -10,17 +12,31 @@ use crate::ir::StackSlot;
 use crate::ir::Type;
 use crate::isa::x64::inst::*;
 use crate::isa::x64::*;
+#[cfg(feature = "unwind")]
+use crate::isa::x86::unwind::{UnwindCode, UnwindInfo};
+use crate::isa::CallConv;
+#[cfg(feature = "unwind")]
+use crate::isa::RegUnit;
 use crate::machinst::*;
+use crate::settings;
 
 use alloc::vec::Vec;
 
 use regalloc::{RealReg, Reg, RegClass, Set, SpillSlot, Writable};
 
-// Clone of arm64 version
+/// The Windows fastcall ABI reserves 32 bytes of "shadow space" above the return address of
+/// every call, where the callee may save the four register arguments.
+const WIN_SHADOW_STACK_SPACE: usize = 32;
+
+/// Frames which need up to this many stack probes are probed inline; bigger
+/// ones call the probestack function.
+const PROBESTACK_MAX_INLINE_PROBES: usize = 4;
+
 #[derive(Clone, Debug)]
 enum ABIArg {
     Reg(RealReg),
-    Stack, // TODO
+    /// Passed on the stack, at this offset from the frame pointer.
+    Stack(u32),
 }
 
 // Clone of arm64 version
@@ -32,6 +48,7 @@ enum ABIRet {
 
 // Clone of arm64 version
 pub struct X64ABIBody {
+    call_conv: CallConv,
     args: Vec<ABIArg>,
     rets: Vec<ABIRet>,
     stackslots: Vec<usize>,            // offsets to each stackslot
@@ -42,6 +59,15 @@ pub struct X64ABIBody {
     // epilogue.  Amount by which RSP is adjusted downwards to allocate the
     // spill area.
     spill_area_sizeB: Option<usize>,
+    // Size of the shadow space reserved at the bottom of the frame, for the
+    // calls made by Windows fastcall functions.
+    outgoing_shadow_sizeB: usize,
+    // The Windows x64 unwind information for the prologue, computed along
+    // with it.
+    unwind_info: Option<MachUnwindInfo>,
+    // Index of the argument holding the stack limit, if any.
+    stack_limit_arg: Option<usize>,
+    flags: settings::Flags,
 }
 
 // Clone of arm64 version
@@ -73,38 +99,258 @@ fn get_intreg_for_retval_ELF(idx: usize) -> Option<Reg> {
     }
 }
 
+// Windows fastcall assigns argument registers by position, whatever the
+// types of the preceding arguments.
+fn get_intreg_for_arg_fastcall(idx: usize) -> Option<Reg> {
+    match idx {
+        0 => Some(reg_RCX()),
+        1 => Some(reg_RDX()),
+        2 => Some(reg_R8()),
+        3 => Some(reg_R9()),
+        _ => None,
+    }
+}
+
+fn get_intreg_for_retval_fastcall(idx: usize) -> Option<Reg> {
+    match idx {
+        0 => Some(reg_RAX()),
+        _ => None,
+    }
+}
+
+fn get_intreg_for_arg(call_conv: CallConv, idx: usize) -> Option<Reg> {
+    if call_conv.extends_windows_fastcall() {
+        get_intreg_for_arg_fastcall(idx)
+    } else {
+        get_intreg_for_arg_ELF(idx)
+    }
+}
+
+fn get_intreg_for_retval(call_conv: CallConv, idx: usize) -> Option<Reg> {
+    if call_conv.extends_windows_fastcall() {
+        get_intreg_for_retval_fastcall(idx)
+    } else {
+        get_intreg_for_retval_ELF(idx)
+    }
+}
+
 fn is_callee_save_ELF(r: RealReg) -> bool {
     match r.get_class() {
         RegClass::I64 => match r.get_hw_encoding() as u8 {
             ENC_RBX | ENC_RBP | ENC_R12 | ENC_R13 | ENC_R14 | ENC_R15 => true,
             _ => false,
         },
+        // All the XMM registers are caller-saved.
+        RegClass::V128 => false,
         _ => unimplemented!(),
     }
 }
 
-// Clone of arm64 version
-fn get_callee_saves(regs: Vec<Writable<RealReg>>) -> Vec<Writable<RealReg>> {
-    regs.into_iter()
-        .filter(|r| is_callee_save_ELF(r.to_reg()))
-        .collect()
+fn is_callee_save_fastcall(r: RealReg) -> bool {
+    match r.get_class() {
+        RegClass::I64 => match r.get_hw_encoding() as u8 {
+            ENC_RBX | ENC_RBP | ENC_RSI | ENC_RDI | ENC_R12 | ENC_R13 | ENC_R14 | ENC_R15 => true,
+            _ => false,
+        },
+        // XMM6-XMM15 are callee-saved.
+        RegClass::V128 => r.get_hw_encoding() >= 6,
+        _ => unimplemented!(),
+    }
+}
+
+// Clone of arm64 version.  The registers are sorted, so that the prologue
+// and epilogue don't depend on the order of the clobbered set.
+fn get_callee_saves(call_conv: CallConv, regs: Vec<Writable<RealReg>>) -> Vec<Writable<RealReg>> {
+    let mut regs: Vec<Writable<RealReg>> = if call_conv.extends_windows_fastcall() {
+        regs.into_iter()
+            .filter(|r| is_callee_save_fastcall(r.to_reg()))
+            .collect()
+    } else {
+        regs.into_iter()
+            .filter(|r| is_callee_save_ELF(r.to_reg()))
+            .collect()
+    };
+    regs.sort_by_key(|r| r.to_reg().get_index());
+    regs
+}
+
+// Get a sequence of instructions which traps with a stack overflow if
+// allocating `frame_size` more bytes would take RSP to or below the stack
+// limit passed in `limit`.  This runs at the start of the prologue, where
+// R11 is free in all the calling conventions.
+fn gen_stack_limit_check(limit: &ABIArg, frame_size: usize, insts: &mut Vec<Inst>) {
+    let w_r11 = Writable::<Reg>::from_reg(reg_R11());
+    match limit {
+        &ABIArg::Reg(reg) => insts.push(i_Mov_R_R(true, reg.to_reg(), w_r11)),
+        &ABIArg::Stack(offset) => insts.push(i_Mov64_M_R(ip_Addr_IR(offset, reg_RBP()), w_r11)),
+    }
+    if frame_size > 0 {
+        insts.push(i_Alu_RMI_R(
+            true,
+            RMI_R_Op::Add,
+            ip_RMI_I(frame_size as u32),
+            w_r11,
+        ));
+    }
+    insts.push(i_Cmp_RMI_R(8, ip_RMI_R(reg_R11()), reg_RSP()));
+    insts.push(i_TrapIf(
+        CC::BE,
+        ir::SourceLoc::default(),
+        ir::TrapCode::StackOverflow,
+    ));
+}
+
+// Get a sequence of instructions which touches every page of a frame of
+// `frame_size` bytes below RSP, from the top down, so that a guard page below
+// the stack can't be skipped over.  The value stored doesn't matter, so this
+// stores RSP, which saves needing a register.
+fn gen_inline_probestack(frame_size: usize, guard_size: usize, insts: &mut Vec<Inst>) {
+    for page in 1..=(frame_size / guard_size) {
+        let offset = -((page * guard_size) as i32);
+        insts.push(i_Mov_R_M(
+            4,
+            reg_RSP(),
+            ip_Addr_IR(offset as u32, reg_RSP()),
+        ));
+    }
+}
+
+// Get a sequence of instructions which allocates a frame of `frame_size`
+// bytes by calling the probestack function.  It takes the size in RAX and
+// preserves the other registers.  As for the old x86 backend, the call goes
+// through R11 unless the code is PIC, since the function may be out of reach
+// of a 32-bit displacement.
+fn gen_probestack_call(frame_size: usize, flags: &settings::Flags, insts: &mut Vec<Inst>) {
+    let w_rax = Writable::<Reg>::from_reg(reg_RAX());
+    let w_r11 = Writable::<Reg>::from_reg(reg_R11());
+    let probestack = ir::ExternalName::LibCall(ir::LibCall::Probestack);
+    insts.push(i_Imm_R(false, frame_size as u64, w_rax));
+    let mut uses = Set::empty();
+    uses.insert(reg_RAX());
+    let mut defs = Set::empty();
+    defs.insert(w_rax);
+    if flags.is_pic() {
+        insts.push(i_CallKnown(probestack, uses, defs));
+    } else {
+        insts.push(i_LoadExtName(probestack, w_r11));
+        insts.push(i_CallUnknown(ip_RM_R(reg_R11())));
+    }
+    // If the probestack function doesn't adjust RSP, do it ourselves.
+    if !flags.probestack_func_adjusts_sp() {
+        insts.push(i_Alu_RMI_R(
+            true,
+            RMI_R_Op::Sub,
+            ip_RMI_R(reg_RAX()),
+            Writable::<Reg>::from_reg(reg_RSP()),
+        ));
+    }
+}
+
+// Describe a Windows fastcall prologue for the unwinder: the pushes of
+// callee-saved integer registers, the allocation of the frame, and the saves
+// of callee-saved XMM registers, each at the offset where it completes.
+// There is no frame register: the frame is described relative to RSP, which
+// the function body doesn't move.
+#[cfg(feature = "unwind")]
+fn fastcall_unwind_info(prologue: &[Inst], flags: &settings::Flags) -> MachUnwindInfo {
+    let mut sink = MachSectionSize::new(0);
+    let mut consts = MachSectionSize::new(0);
+    let mut unwind_codes = vec![];
+    // The frame size passed to the probestack function, if it's called.
+    let mut probestack_size = None;
+    for inst in prologue {
+        inst.emit(&mut sink, &mut consts);
+        if sink.size() > 255 {
+            panic!("function prologues cannot exceed 255 bytes in size for Windows x64");
+        }
+        let offset = sink.size() as u8;
+        match inst {
+            Inst::Push64 {
+                src: RMI::R { reg },
+            } => unwind_codes.push(UnwindCode::PushRegister {
+                offset,
+                reg: reg.get_hw_encoding() as RegUnit,
+            }),
+            Inst::Alu_RMI_R {
+                op: RMI_R_Op::Sub,
+                src: RMI::I { simm32 },
+                dst,
+                ..
+            } if *dst == reg_RSP() && *simm32 > 0 => unwind_codes.push(UnwindCode::StackAlloc {
+                offset,
+                size: *simm32,
+            }),
+            Inst::Imm_R { simm64, dst, .. } if *dst == reg_RAX() => {
+                probestack_size = Some(*simm64 as u32);
+            }
+            Inst::CallKnown { .. } | Inst::CallUnknown { .. }
+                if flags.probestack_func_adjusts_sp() =>
+            {
+                unwind_codes.push(UnwindCode::StackAlloc {
+                    offset,
+                    size: probestack_size.unwrap(),
+                })
+            }
+            Inst::Alu_RMI_R {
+                op: RMI_R_Op::Sub,
+                src: RMI::R { reg },
+                dst,
+                ..
+            } if *dst == reg_RSP() && *reg == reg_RAX() => {
+                unwind_codes.push(UnwindCode::StackAlloc {
+                    offset,
+                    size: probestack_size.unwrap(),
+                })
+            }
+            Inst::MovUPS_R_M {
+                src,
+                addr: Addr::IR { simm32, base },
+            } if *base == reg_RSP() => unwind_codes.push(UnwindCode::SaveXmm {
+                offset,
+                reg: src.get_hw_encoding(),
+                stack_offset: *simm32,
+            }),
+            _ => {}
+        }
+    }
+    MachUnwindInfo {
+        kind: FrameUnwindKind::Fastcall,
+        data: UnwindInfo::new(sink.size() as u8, unwind_codes).to_bytes(),
+    }
 }
 
 impl X64ABIBody {
     /// Create a new body ABI instance.
-    pub fn new(f: &ir::Function) -> X64ABIBody {
+    pub fn new(f: &ir::Function, flags: settings::Flags) -> X64ABIBody {
         println!("X64 ABI: func signature {:?}", f.signature);
+        let call_conv = f.signature.call_conv;
+        let is_fastcall = call_conv.extends_windows_fastcall();
 
-        // Compute args and retvals from signature.
+        // Compute args and retvals from signature.  Stack arguments start
+        // above the saved RBP and the return address, and for Windows
+        // fastcall, above the shadow space for the register arguments too.
         let mut args = vec![];
         let mut next_int_arg = 0;
+        let shadow_space = if is_fastcall {
+            WIN_SHADOW_STACK_SPACE
+        } else {
+            0
+        };
+        let mut next_stack_arg = (16 + shadow_space) as u32;
         for param in &f.signature.params {
             let mut ok = false;
-            if &param.purpose == &ir::ArgumentPurpose::Normal && in_int_reg(param.value_type) {
-                if let Some(reg) = get_intreg_for_arg_ELF(next_int_arg) {
+            let purpose_ok = match &param.purpose {
+                &ir::ArgumentPurpose::Normal | &ir::ArgumentPurpose::StackLimit => true,
+                _ => false,
+            };
+            if purpose_ok && in_int_reg(param.value_type) {
+                if let Some(reg) = get_intreg_for_arg(call_conv, next_int_arg) {
                     args.push(ABIArg::Reg(reg.to_real_reg()));
-                    ok = true;
+                } else {
+                    args.push(ABIArg::Stack(next_stack_arg));
+                    next_stack_arg += 8;
                 }
+                ok = true;
                 next_int_arg += 1;
             }
             if !ok {
@@ -117,7 +363,7 @@ impl X64ABIBody {
         for ret in &f.signature.returns {
             let mut ok = false;
             if &ret.purpose == &ir::ArgumentPurpose::Normal && in_int_reg(ret.value_type) {
-                if let Some(reg) = get_intreg_for_retval_ELF(next_int_retval) {
+                if let Some(reg) = get_intreg_for_retval(call_conv, next_int_retval) {
                     rets.push(ABIRet::Reg(reg.to_real_reg()));
                     ok = true;
                 }
@@ -139,7 +385,15 @@ impl X64ABIBody {
             stackslots.push(off);
         }
 
+        // Functions which make calls must provide the shadow space for them.
+        let outgoing_shadow_sizeB = if is_fastcall && !f.is_leaf() {
+            WIN_SHADOW_STACK_SPACE
+        } else {
+            0
+        };
+
         X64ABIBody {
+            call_conv,
             args,
             rets,
             stackslots,
@@ -147,11 +401,21 @@ impl X64ABIBody {
             clobbered: Set::empty(),
             spillslots: None,
             spill_area_sizeB: None,
+            outgoing_shadow_sizeB,
+            unwind_info: None,
+            stack_limit_arg: f
+                .signature
+                .special_param_index(ir::ArgumentPurpose::StackLimit),
+            flags,
         }
     }
 }
 
 impl ABIBody<Inst> for X64ABIBody {
+    fn flags(&self) -> &settings::Flags {
+        &self.flags
+    }
+
     fn num_args(&self) -> usize {
         unimplemented!()
     }
@@ -189,14 +453,14 @@ impl ABIBody<Inst> for X64ABIBody {
     }
 
     fn gen_copy_arg_to_reg(&self, idx: usize, to_reg: Writable<Reg>) -> Inst {
-        if let Some(from_reg) = get_intreg_for_arg_ELF(idx) {
-            return i_Mov_R_R(/*is64=*/ true, from_reg, to_reg);
+        match &self.args[idx] {
+            &ABIArg::Reg(from_reg) => i_Mov_R_R(/*is64=*/ true, from_reg.to_reg(), to_reg),
+            &ABIArg::Stack(offset) => i_Mov64_M_R(ip_Addr_IR(offset, reg_RBP()), to_reg),
         }
-        unimplemented!()
     }
 
     fn gen_copy_reg_to_retval(&self, idx: usize, from_reg: Reg) -> Inst {
-        if let Some(to_reg) = get_intreg_for_retval_ELF(idx) {
+        if let Some(to_reg) = get_intreg_for_retval(self.call_conv, idx) {
             return i_Mov_R_R(
                 /*is64=*/ true,
                 from_reg,
@@ -252,30 +516,25 @@ impl ABIBody<Inst> for X64ABIBody {
         let w_rbp = Writable::<Reg>::from_reg(r_rbp);
         let w_rsp = Writable::<Reg>::from_reg(r_rsp);
 
-        // The "traditional" pre-preamble
-        // RSP before the call will be 0 % 16.  So here, it is 8 % 16.
-        insts.push(i_Push64(ip_RMI_R(r_rbp)));
-        // RSP is now 0 % 16
-        insts.push(i_Mov_R_R(true, r_rsp, w_rbp));
-
-        // Save callee saved registers that we trash.  Keep track of how much
-        // space we've used, so as to know what we have to do to get the base
-        // of the spill area 0 % 16.
-        let mut callee_saved_used = 0;
-        let clobbered = get_callee_saves(self.clobbered.to_vec());
+        // Callee saved registers that we trash.  XMM registers can't be
+        // pushed; they are stored once the frame has been allocated.
+        let clobbered = get_callee_saves(self.call_conv, self.clobbered.to_vec());
+        let mut gpr_saves = vec![];
+        let mut xmm_saves = vec![];
         for reg in clobbered {
-            let r_reg = reg.to_reg();
+            let r_reg = reg.to_reg().to_reg();
             match r_reg.get_class() {
-                RegClass::I64 => {
-                    insts.push(i_Push64(ip_RMI_R(r_reg.to_reg())));
-                    callee_saved_used += 8;
-                }
+                RegClass::I64 => gpr_saves.push(r_reg),
+                RegClass::V128 => xmm_saves.push(r_reg),
                 _ => unimplemented!(),
             }
         }
+        // Keep track of how much space the pushes use, so as to know what we
+        // have to do to get the base of the spill area 0 % 16.
+        let callee_saved_used = 8 * gpr_saves.len();
 
-        // Allocate the frame.  Now, be careful: RSP may now not be 0 % 16.
-        // If it isn't, increase total_stacksize to compensate.  Because
+        // Size the frame.  Now, be careful: RSP may now not be 0 % 16.  If it
+        // isn't, increase total_stacksize to compensate.  Because
         // total_stacksize is 0 % 16, this ensures that RSP after this
         // subtraction, is still 16 aligned.
         //
@@ -288,10 +547,50 @@ impl ABIBody<Inst> for X64ABIBody {
             // Really there should be no other cases, though.
             _ => panic!("gen_prologue(x86): total_stacksize is not 8-aligned"),
         }
+        // Below the spill area, in this order: the save area for XMM
+        // registers, and the shadow space for calls.  Both are multiples of 16
+        // bytes, so RSP stays 16 aligned.
+        spill_area_sizeB += 16 * xmm_saves.len() + self.outgoing_shadow_sizeB;
         if spill_area_sizeB > 0x7FFF_FFFF {
             panic!("gen_prologue(x86): total_stacksize >= 2G");
         }
-        if spill_area_sizeB >= 0 {
+
+        // The "traditional" pre-preamble
+        // RSP before the call will be 0 % 16.  So here, it is 8 % 16.
+        insts.push(i_Push64(ip_RMI_R(r_rbp)));
+        // RSP is now 0 % 16
+        insts.push(i_Mov_R_R(true, r_rsp, w_rbp));
+
+        // Check the rest of the frame against the stack limit before any of
+        // it is used.
+        if let Some(idx) = self.stack_limit_arg {
+            gen_stack_limit_check(
+                &self.args[idx],
+                callee_saved_used + spill_area_sizeB,
+                &mut insts,
+            );
+        }
+
+        for reg in gpr_saves {
+            insts.push(i_Push64(ip_RMI_R(reg)));
+        }
+
+        // Allocate the frame, probing it first if it's bigger than the guard
+        // region.
+        let guard_size = 1usize << self.flags.probestack_size_log2();
+        if self.flags.enable_probestack() && spill_area_sizeB > guard_size {
+            if spill_area_sizeB / guard_size <= PROBESTACK_MAX_INLINE_PROBES {
+                gen_inline_probestack(spill_area_sizeB, guard_size, &mut insts);
+                insts.push(i_Alu_RMI_R(
+                    true,
+                    RMI_R_Op::Sub,
+                    ip_RMI_I(spill_area_sizeB as u32),
+                    w_rsp,
+                ));
+            } else {
+                gen_probestack_call(spill_area_sizeB, &self.flags, &mut insts);
+            }
+        } else {
             // FIXME JRS 2020Feb16: handle spill_area_size >= 2G?
             insts.push(i_Alu_RMI_R(
                 true,
@@ -300,10 +599,21 @@ impl ABIBody<Inst> for X64ABIBody {
                 w_rsp,
             ));
         }
+        for (i, reg) in xmm_saves.into_iter().enumerate() {
+            let offset = self.outgoing_shadow_sizeB + 16 * i;
+            insts.push(i_MovUPS_R_M(reg, ip_Addr_IR(offset as u32, r_rsp)));
+        }
         debug_assert!(self.spill_area_sizeB.is_none());
         // Stash this value.  We'll need it for the epilogue.
         self.spill_area_sizeB = Some(spill_area_sizeB);
 
+        #[cfg(feature = "unwind")]
+        {
+            if self.call_conv.extends_windows_fastcall() {
+                self.unwind_info = Some(fastcall_unwind_info(&insts, &self.flags));
+            }
+        }
+
         insts
     }
 
@@ -315,6 +625,20 @@ impl ABIBody<Inst> for X64ABIBody {
         let w_rsp = Writable::<Reg>::from_reg(r_rsp);
 
         // Undo what we did in the prologue.
+        let clobbered = get_callee_saves(self.call_conv, self.clobbered.to_vec());
+
+        // Restore the XMM registers, before their save area goes away.
+        let xmm_saves = clobbered
+            .iter()
+            .map(|reg| reg.to_reg().to_reg())
+            .filter(|reg| reg.get_class() == RegClass::V128);
+        for (i, reg) in xmm_saves.enumerate() {
+            let offset = self.outgoing_shadow_sizeB + 16 * i;
+            insts.push(i_MovUPS_M_R(
+                ip_Addr_IR(offset as u32, r_rsp),
+                Writable::<Reg>::from_reg(reg),
+            ));
+        }
 
         // Clear the spill area and the 16-alignment padding below it.
         debug_assert!(self.spill_area_sizeB.is_some());
@@ -331,7 +655,6 @@ impl ABIBody<Inst> for X64ABIBody {
 
         // Restore regs.
         let mut tmp_insts = vec![];
-        let clobbered = get_callee_saves(self.clobbered.to_vec());
         for w_real_reg in clobbered {
             match w_real_reg.to_reg().get_class() {
                 RegClass::I64 => {
@@ -340,6 +663,7 @@ impl ABIBody<Inst> for X64ABIBody {
                         w_real_reg.to_reg().to_reg(),
                     )))
                 }
+                RegClass::V128 => {}
                 _ => unimplemented!(),
             }
         }
@@ -357,6 +681,10 @@ impl ABIBody<Inst> for X64ABIBody {
         insts
     }
 
+    fn unwind_info(&self) -> Option<MachUnwindInfo> {
+        self.unwind_info.clone()
+    }
+
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32 {
         // We allocate in terms of 8-byte slots.
         match (rc, ty) {
@@ -375,3 +703,90 @@ impl ABIBody<Inst> for X64ABIBody {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{ExternalName, Function, Signature};
+    use alloc::string::String;
+    use regalloc::RealRegUniverse;
+
+    fn clobber(universe: &RealRegUniverse, name: &str) -> Writable<RealReg> {
+        let &(rreg, _) = universe.regs.iter().find(|(_, n)| n == name).unwrap();
+        Writable::from_reg(rreg)
+    }
+
+    // No CLIF function can use an XMM register in this backend yet, so the
+    // saves of callee-saved XMM registers are checked directly.
+    #[test]
+    fn fastcall_xmm_saves() {
+        let func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::WindowsFastcall),
+        );
+        let mut abi = X64ABIBody::new(&func, settings::Flags::new(settings::builder()));
+        let universe = create_reg_universe();
+        let mut clobbered = Set::empty();
+        for name in &["%rbx", "%xmm6", "%xmm15"] {
+            clobbered.insert(clobber(&universe, name));
+        }
+        abi.set_num_spillslots(0);
+        abi.set_clobbered(clobbered);
+
+        let show = |insts: Vec<Inst>| -> Vec<String> {
+            insts
+                .iter()
+                .map(|inst| inst.show_rru(Some(&universe)))
+                .collect()
+        };
+        assert_eq!(
+            show(abi.gen_prologue()),
+            vec![
+                "pushq   %rbp",
+                "movq    %rsp, %rbp",
+                "pushq   %rbx",
+                "subq    $40, %rsp",
+                "movups  %xmm6, 0(%rsp)",
+                "movups  %xmm15, 16(%rsp)",
+            ]
+        );
+        assert_eq!(
+            show(abi.gen_epilogue()),
+            vec![
+                "movups  0(%rsp), %xmm6",
+                "movups  16(%rsp), %xmm15",
+                "addq    $40, %rsp",
+                "popq    %rbx",
+                "popq    %rbp",
+                "ret",
+            ]
+        );
+
+        // Each code is at the offset where its instruction ends.
+        #[cfg(feature = "unwind")]
+        {
+            let expected = UnwindInfo::new(
+                19,
+                vec![
+                    UnwindCode::PushRegister { offset: 1, reg: 5 },
+                    UnwindCode::PushRegister { offset: 5, reg: 3 },
+                    UnwindCode::StackAlloc {
+                        offset: 9,
+                        size: 40,
+                    },
+                    UnwindCode::SaveXmm {
+                        offset: 13,
+                        reg: 6,
+                        stack_offset: 0,
+                    },
+                    UnwindCode::SaveXmm {
+                        offset: 19,
+                        reg: 15,
+                        stack_offset: 16,
+                    },
+                ],
+            );
+            assert_eq!(abi.unwind_info().unwrap().data, expected.to_bytes());
+        }
+    }
+}
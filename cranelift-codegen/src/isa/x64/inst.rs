@@ -37,6 +37,8 @@ use smallvec::SmallVec;
 pub const ENC_RBX: u8 = 3;
 pub const ENC_RSP: u8 = 4;
 pub const ENC_RBP: u8 = 5;
+pub const ENC_RSI: u8 = 6;
+pub const ENC_RDI: u8 = 7;
 pub const ENC_R12: u8 = 12;
 pub const ENC_R13: u8 = 13;
 pub const ENC_R14: u8 = 14;
@@ -87,13 +89,13 @@ fn info_RBX() -> (RealReg, String) {
 
 fn info_RSI() -> (RealReg, String) {
     (
-        Reg::new_real(RegClass::I64, /*enc=*/ 6, /*index=*/ 5).to_real_reg(),
+        Reg::new_real(RegClass::I64, ENC_RSI, /*index=*/ 5).to_real_reg(),
         "%rsi".to_string(),
     )
 }
 fn info_RDI() -> (RealReg, String) {
     (
-        Reg::new_real(RegClass::I64, /*enc=*/ 7, /*index=*/ 6).to_real_reg(),
+        Reg::new_real(RegClass::I64, ENC_RDI, /*index=*/ 6).to_real_reg(),
         "%rdi".to_string(),
     )
 }
@@ -275,6 +277,9 @@ pub fn reg_R8() -> Reg {
 pub fn reg_R9() -> Reg {
     info_R9().0.to_reg()
 }
+pub fn reg_R11() -> Reg {
+    info_R11().0.to_reg()
+}
 
 pub fn reg_RSP() -> Reg {
     info_RSP().0.to_reg()
@@ -606,6 +611,29 @@ impl fmt::Debug for ShiftKind {
     }
 }
 
+// These indicate the form of a bit count: population count, leading zeroes
+// or trailing zeroes.
+#[derive(Clone)]
+pub enum BitCountKind {
+    Popcnt,
+    Lzcnt,
+    Tzcnt,
+}
+impl BitCountKind {
+    fn to_string(&self) -> String {
+        match self {
+            BitCountKind::Popcnt => "popcnt".to_string(),
+            BitCountKind::Lzcnt => "lzcnt".to_string(),
+            BitCountKind::Tzcnt => "tzcnt".to_string(),
+        }
+    }
+}
+impl fmt::Debug for BitCountKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.to_string())
+    }
+}
+
 // These indicate condition code tests.  Not all are represented since not all
 // are useful in compiler-generated code.
 #[derive(Copy, Clone)]
@@ -812,6 +840,9 @@ pub enum Inst {
     /// A plain 64-bit integer load, since MovZX_M_R can't represent that
     Mov64_M_R { addr: Addr, dst: Reg },
 
+    /// movabsq $name, reg -- loads the address of an external symbol
+    LoadExtName { name: ExternalName, dst: Reg },
+
     /// movs (bl bq wl wq lq) addr reg (good for all SX loads)
     MovSX_M_R {
         extMode: ExtMode,
@@ -834,6 +865,14 @@ pub enum Inst {
         dst: Reg,
     },
 
+    /// (popcnt lzcnt tzcnt) (l q) reg reg
+    BitCount_R_R {
+        is64: bool,
+        kind: BitCountKind,
+        src: Reg,
+        dst: Reg,
+    },
+
     /// cmp (b w l q) (reg addr imm) reg
     Cmp_RMI_R {
         size: u8, // 1, 2, 4 or 8
@@ -847,6 +886,26 @@ pub enum Inst {
     /// popq reg
     Pop64 { dst: Reg },
 
+    /// movups reg addr -- stores a whole XMM register, eg to save a callee-saved one
+    MovUPS_R_M { src: Reg, addr: Addr },
+
+    /// movups addr reg -- loads a whole XMM register, eg to restore a callee-saved one
+    MovUPS_M_R { addr: Addr, dst: Reg },
+
+    /// jcond-not $next ; ud2 -- traps if the condition holds.  This doesn't
+    /// end a basic block.
+    TrapIf {
+        cc: CC,
+        srcloc: SourceLoc,
+        trap_code: TrapCode,
+    },
+
+    /// ud2 -- an unconditional trap.
+    Ud2 {
+        srcloc: SourceLoc,
+        trap_code: TrapCode,
+    },
+
     /// call simm32
     CallKnown {
         dest: ExternalName,
@@ -998,6 +1057,18 @@ pub fn i_Shift_R(
     }
 }
 
+pub fn i_BitCount_R_R(is64: bool, kind: BitCountKind, src: Reg, wdst: Writable<Reg>) -> Inst {
+    let dst = wdst.to_reg();
+    debug_assert!(src.get_class() == RegClass::I64);
+    debug_assert!(dst.get_class() == RegClass::I64);
+    Inst::BitCount_R_R {
+        is64,
+        kind,
+        src,
+        dst,
+    }
+}
+
 pub fn i_Cmp_RMI_R(
     size: u8, // 1, 2, 4 or 8
     src: RMI,
@@ -1016,9 +1087,38 @@ pub fn i_Pop64(wdst: Writable<Reg>) -> Inst {
     Inst::Pop64 { dst: wdst.to_reg() }
 }
 
-//pub fn i_CallKnown(target: FuncRef) -> Inst {
-//    Inst::CallKnown { target }
-//}
+pub fn i_MovUPS_R_M(src: Reg, addr: Addr) -> Inst {
+    debug_assert!(src.get_class() == RegClass::V128);
+    Inst::MovUPS_R_M { src, addr }
+}
+
+pub fn i_MovUPS_M_R(addr: Addr, wdst: Writable<Reg>) -> Inst {
+    let dst = wdst.to_reg();
+    debug_assert!(dst.get_class() == RegClass::V128);
+    Inst::MovUPS_M_R { addr, dst }
+}
+
+pub fn i_LoadExtName(name: ExternalName, wdst: Writable<Reg>) -> Inst {
+    let dst = wdst.to_reg();
+    debug_assert!(dst.get_class() == RegClass::I64);
+    Inst::LoadExtName { name, dst }
+}
+
+pub fn i_TrapIf(cc: CC, srcloc: SourceLoc, trap_code: TrapCode) -> Inst {
+    Inst::TrapIf {
+        cc,
+        srcloc,
+        trap_code,
+    }
+}
+
+pub fn i_Ud2(srcloc: SourceLoc, trap_code: TrapCode) -> Inst {
+    Inst::Ud2 { srcloc, trap_code }
+}
+
+pub fn i_CallKnown(dest: ExternalName, uses: Set<Reg>, defs: Set<Writable<Reg>>) -> Inst {
+    Inst::CallKnown { dest, uses, defs }
+}
 
 pub fn i_CallUnknown(dest: RM) -> Inst {
     Inst::CallUnknown { dest }
@@ -1189,6 +1289,17 @@ fn x64_show_rru(inst: &Inst, mb_rru: Option<&RealRegUniverse>) -> String {
                 )
             }
         }
+        Inst::BitCount_R_R {
+            is64,
+            kind,
+            src,
+            dst,
+        } => format!(
+            "{} {}, {}",
+            ljustify2(kind.to_string(), suffixLQ(*is64)),
+            show_ireg_sized(*src, mb_rru, sizeLQ(*is64)),
+            show_ireg_sized(*dst, mb_rru, sizeLQ(*is64))
+        ),
         Inst::Cmp_RMI_R { size, src, dst } => format!(
             "{} {}, {}",
             ljustify2("cmp".to_string(), suffixBWLQ(*size)),
@@ -1199,9 +1310,30 @@ fn x64_show_rru(inst: &Inst, mb_rru: Option<&RealRegUniverse>) -> String {
             format!("{} {}", ljustify("pushq".to_string()), src.show_rru(mb_rru))
         }
         Inst::Pop64 { dst } => format!("{} {}", ljustify("popq".to_string()), dst.show_rru(mb_rru)),
-        //
-        //Inst::CallKnown { target } => format!("{} {:?}", ljustify("call".to_string()), target),
-        Inst::CallKnown { .. } => "**CallKnown**".to_string(),
+        Inst::MovUPS_R_M { src, addr } => format!(
+            "{} {}, {}",
+            ljustify("movups".to_string()),
+            src.show_rru(mb_rru),
+            addr.show_rru(mb_rru)
+        ),
+        Inst::MovUPS_M_R { addr, dst } => format!(
+            "{} {}, {}",
+            ljustify("movups".to_string()),
+            addr.show_rru(mb_rru),
+            dst.show_rru(mb_rru)
+        ),
+        Inst::LoadExtName { name, dst } => format!(
+            "{} ${}, {}",
+            ljustify("movabsq".to_string()),
+            name,
+            dst.show_rru(mb_rru)
+        ),
+        Inst::TrapIf { cc, .. } => format!(
+            "{} $next ; ud2",
+            ljustify2("j".to_string(), cc.invert().to_string())
+        ),
+        Inst::Ud2 { .. } => "ud2".to_string(),
+        Inst::CallKnown { dest, .. } => format!("{} {}", ljustify("call".to_string()), dest),
         //
         Inst::CallUnknown { dest } => format!(
             "{} *{}",
@@ -1361,6 +1493,15 @@ fn x64_get_regs(inst: &Inst) -> InstRegUses {
             }
             iru.modified.insert(Writable::from_reg(*dst));
         }
+        Inst::BitCount_R_R {
+            is64: _,
+            kind: _,
+            src,
+            dst,
+        } => {
+            iru.used.insert(*src);
+            iru.defined.insert(Writable::from_reg(*dst));
+        }
         Inst::Cmp_RMI_R { size: _, src, dst } => {
             src.get_regs(&mut iru.used);
             iru.used.insert(*dst); // yes, really |iru.used|
@@ -1372,13 +1513,26 @@ fn x64_get_regs(inst: &Inst) -> InstRegUses {
         Inst::Pop64 { dst } => {
             iru.defined.insert(Writable::from_reg(*dst));
         }
+        Inst::MovUPS_R_M { src, addr } => {
+            iru.used.insert(*src);
+            addr.get_regs(&mut iru.used);
+        }
+        Inst::MovUPS_M_R { addr, dst } => {
+            addr.get_regs(&mut iru.used);
+            iru.defined.insert(Writable::from_reg(*dst));
+        }
+        Inst::LoadExtName { name: _, dst } => {
+            iru.defined.insert(Writable::from_reg(*dst));
+        }
+        Inst::TrapIf { .. } => {}
+        Inst::Ud2 { .. } => {}
         Inst::CallKnown {
             dest: _,
-            uses: _,
-            defs: _,
+            uses,
+            defs,
         } => {
-            // FIXME add arg regs (iru.used) and caller-saved regs (iru.defined)
-            unimplemented!();
+            iru.used.union(uses);
+            iru.defined.union(defs);
         }
         Inst::CallUnknown { dest } => {
             dest.get_regs(&mut iru.used);
@@ -1543,6 +1697,15 @@ fn x64_map_regs(
         } => {
             apply_maps(dst, pre_map, post_map);
         }
+        Inst::BitCount_R_R {
+            is64: _,
+            kind: _,
+            ref mut src,
+            ref mut dst,
+        } => {
+            apply_map(src, pre_map);
+            apply_map(dst, post_map);
+        }
         Inst::Cmp_RMI_R {
             size: _,
             ref mut src,
@@ -1557,6 +1720,28 @@ fn x64_map_regs(
         Inst::Pop64 { ref mut dst } => {
             apply_map(dst, post_map);
         }
+        Inst::MovUPS_R_M {
+            ref mut src,
+            ref mut addr,
+        } => {
+            apply_map(src, pre_map);
+            addr.apply_map(pre_map);
+        }
+        Inst::MovUPS_M_R {
+            ref mut addr,
+            ref mut dst,
+        } => {
+            addr.apply_map(pre_map);
+            apply_map(dst, post_map);
+        }
+        Inst::LoadExtName {
+            name: _,
+            ref mut dst,
+        } => {
+            apply_map(dst, post_map);
+        }
+        Inst::TrapIf { .. } => {}
+        Inst::Ud2 { .. } => {}
         Inst::CallKnown {
             dest: _,
             uses: _,
@@ -1618,6 +1803,14 @@ fn iregEnc(reg: Reg) -> u8 {
     reg.get_hw_encoding()
 }
 
+#[inline(always)]
+// Same, for a real register of class V128.
+fn xregEnc(reg: Reg) -> u8 {
+    debug_assert!(reg.is_real());
+    debug_assert!(reg.get_class() == RegClass::V128);
+    reg.get_hw_encoding()
+}
+
 // F_*: these flags describe special handling of the insn to be generated.  Be
 // careful with these.  It is easy to create nonsensical combinations.
 const F_NONE: u32 = 0;
@@ -1633,6 +1826,10 @@ const F_CLEAR_REX_W: u32 = 2;
 // a 16-bit operation.  Normally this will be used together with F_CLEAR_REX_W.
 const F_PREFIX_66: u32 = 4;
 
+// Add an 0xF3 prefix.  This is a mandatory prefix for some instructions (eg
+// POPCNT), and so has to come before the REX prefix.
+const F_PREFIX_F3: u32 = 8;
+
 // This is the core 'emit' function for instructions that reference memory.
 //
 // For an instruction that has as operands a register |encG| and a memory
@@ -1664,6 +1861,7 @@ fn emit_REX_OPCODES_MODRM_SIB_IMM_encG_memE<O: MachSectionOutput>(
     // 64-bit integer registers, because they are part of an address
     // expression.  But |encG| can be derived from a register of any class.
     let prefix66 = (flags & F_PREFIX_66) != 0;
+    let prefixF3 = (flags & F_PREFIX_F3) != 0;
     let clearRexW = (flags & F_CLEAR_REX_W) != 0;
     let retainRedundant = (flags & F_RETAIN_REDUNDANT_REX) != 0;
     // The operand-size override, if requested.  This indicates a 16-bit
@@ -1671,6 +1869,10 @@ fn emit_REX_OPCODES_MODRM_SIB_IMM_encG_memE<O: MachSectionOutput>(
     if prefix66 {
         sink.put1(0x66);
     }
+    // The mandatory prefix, if requested.
+    if prefixF3 {
+        sink.put1(0xF3);
+    }
     match memE {
         Addr::IR { simm32, base: regE } => {
             // First, cook up the REX byte.  This is easy.
@@ -1784,12 +1986,17 @@ fn emit_REX_OPCODES_MODRM_encG_encE<O: MachSectionOutput>(
     // integer-to-FP conversion insn, one might be RegClass::I64 and the other
     // RegClass::V128.
     let prefix66 = (flags & F_PREFIX_66) != 0;
+    let prefixF3 = (flags & F_PREFIX_F3) != 0;
     let clearRexW = (flags & F_CLEAR_REX_W) != 0;
     let retainRedundant = (flags & F_RETAIN_REDUNDANT_REX) != 0;
     // The operand-size override
     if prefix66 {
         sink.put1(0x66);
     }
+    // The mandatory prefix
+    if prefixF3 {
+        sink.put1(0xF3);
+    }
     // The rex byte
     let w = if clearRexW { 0 } else { 1 };
     let r = (encG >> 3) & 1;
@@ -2180,6 +2387,22 @@ fn x64_emit<O: MachSectionOutput>(inst: &Inst, sink: &mut O) {
                 sink.put1(*nBits);
             }
         }
+        Inst::BitCount_R_R {
+            is64,
+            kind,
+            src,
+            dst,
+        } => {
+            // POPCNT/LZCNT/TZCNT reg32, reg32 is F3 (REX.W==0) 0F B8/BD/BC /r
+            // POPCNT/LZCNT/TZCNT reg64, reg64 is F3 (REX.W==1) 0F B8/BD/BC /r
+            let opcode = match kind {
+                BitCountKind::Popcnt => 0x0FB8,
+                BitCountKind::Lzcnt => 0x0FBD,
+                BitCountKind::Tzcnt => 0x0FBC,
+            };
+            let flags = if *is64 { F_NONE } else { F_CLEAR_REX_W };
+            emit_REX_OPCODES_MODRM_regG_regE(sink, opcode, 2, *dst, *src, flags | F_PREFIX_F3);
+        }
         Inst::Cmp_RMI_R {
             size,
             src: srcE,
@@ -2281,9 +2504,60 @@ fn x64_emit<O: MachSectionOutput>(inst: &Inst, sink: &mut O) {
             }
             sink.put1(0x58 + (encDst & 7));
         }
-        //
-        // ** Inst::CallKnown
-        //
+        Inst::MovUPS_R_M { src, addr } => {
+            // MOVUPS is (REX.W==0) 0F 11 /r
+            emit_REX_OPCODES_MODRM_SIB_IMM_encG_memE(
+                sink,
+                0x0F11,
+                2,
+                xregEnc(*src),
+                addr,
+                F_CLEAR_REX_W,
+            )
+        }
+        Inst::MovUPS_M_R { addr, dst } => {
+            // MOVUPS is (REX.W==0) 0F 10 /r
+            emit_REX_OPCODES_MODRM_SIB_IMM_encG_memE(
+                sink,
+                0x0F10,
+                2,
+                xregEnc(*dst),
+                addr,
+                F_CLEAR_REX_W,
+            )
+        }
+        Inst::LoadExtName { name, dst } => {
+            // MOVABSQ $imm64, reg64 is REX.W B8+r io, with the address
+            // filled in by a relocation.
+            let encDst = iregEnc(*dst);
+            sink.put1(0x48 | ((encDst >> 3) & 1));
+            sink.put1(0xB8 | (encDst & 7));
+            sink.add_reloc(Reloc::Abs8, name, 0);
+            sink.put8(0);
+        }
+        Inst::TrapIf {
+            cc,
+            srcloc,
+            trap_code,
+        } => {
+            // Jump over the UD2 (2 bytes) with the inverted condition.
+            sink.put1(0x70 + cc.invert().get_enc());
+            sink.put1(2);
+            sink.add_trap(*srcloc, *trap_code);
+            sink.put1(0x0F);
+            sink.put1(0x0B);
+        }
+        Inst::Ud2 { srcloc, trap_code } => {
+            sink.add_trap(*srcloc, *trap_code);
+            sink.put1(0x0F);
+            sink.put1(0x0B);
+        }
+        Inst::CallKnown { dest, .. } => {
+            // CALL rel32 is E8 cd, relative to the end of the instruction.
+            sink.put1(0xE8);
+            sink.add_reloc(Reloc::X86CallPLTRel4, dest, -4);
+            sink.put4(0);
+        }
         Inst::CallUnknown { dest } => {
             match dest {
                 RM::R { reg } => {
@@ -2438,6 +2712,13 @@ impl MachInst for Inst {
         }
     }
 
+    fn is_trap(&self) -> bool {
+        match self {
+            &Inst::Ud2 { .. } => true,
+            _ => false,
+        }
+    }
+
     fn gen_move(dst_reg: Writable<Reg>, src_reg: Reg) -> Inst {
         let rcD = dst_reg.to_reg().get_class();
         let rcS = src_reg.get_class();
@@ -2568,6 +2849,8 @@ impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
 //       cargo test isa::x64::inst::test_x64_insn_encoding_and_printing \
 //                  -- --nocapture)
 
+#[cfg(test)]
+use crate::ir::LibCall;
 #[cfg(test)]
 use crate::isa::test_utils;
 
@@ -2591,6 +2874,9 @@ fn test_x64_insn_encoding_and_printing() {
     let r13 = info_R13().0.to_reg();
     let r14 = info_R14().0.to_reg();
     let r15 = info_R15().0.to_reg();
+    let xmm6 = info_XMM6().0.to_reg();
+    let xmm10 = info_XMM10().0.to_reg();
+    let xmm15 = info_XMM15().0.to_reg();
 
     // And Writable<> versions of the same:
     let w_rax = Writable::<Reg>::from_reg(info_RAX().0.to_reg());
@@ -2603,12 +2889,14 @@ fn test_x64_insn_encoding_and_printing() {
     let _w_rbp = Writable::<Reg>::from_reg(info_RBP().0.to_reg());
     let w_r8 = Writable::<Reg>::from_reg(info_R8().0.to_reg());
     let w_r9 = Writable::<Reg>::from_reg(info_R9().0.to_reg());
-    let _w_r10 = Writable::<Reg>::from_reg(info_R10().0.to_reg());
+    let w_r10 = Writable::<Reg>::from_reg(info_R10().0.to_reg());
     let w_r11 = Writable::<Reg>::from_reg(info_R11().0.to_reg());
     let w_r12 = Writable::<Reg>::from_reg(info_R12().0.to_reg());
     let w_r13 = Writable::<Reg>::from_reg(info_R13().0.to_reg());
     let w_r14 = Writable::<Reg>::from_reg(info_R14().0.to_reg());
     let w_r15 = Writable::<Reg>::from_reg(info_R15().0.to_reg());
+    let w_xmm6 = Writable::<Reg>::from_reg(info_XMM6().0.to_reg());
+    let w_xmm9 = Writable::<Reg>::from_reg(info_XMM9().0.to_reg());
 
     let mut insns = Vec::<(Inst, &str, &str)>::new();
 
@@ -4297,6 +4585,39 @@ fn test_x64_insn_encoding_and_printing() {
         "sarq    $63, %r13",
     ));
 
+    // ========================================================
+    // BitCount_R_R
+    insns.push((
+        i_BitCount_R_R(false, BitCountKind::Popcnt, rbx, w_rsi),
+        "F30FB8F3",
+        "popcntl %ebx, %esi",
+    ));
+    insns.push((
+        i_BitCount_R_R(false, BitCountKind::Popcnt, r9, w_r12),
+        "F3450FB8E1",
+        "popcntl %r9d, %r12d",
+    ));
+    insns.push((
+        i_BitCount_R_R(true, BitCountKind::Popcnt, rbx, w_rsi),
+        "F3480FB8F3",
+        "popcntq %rbx, %rsi",
+    ));
+    insns.push((
+        i_BitCount_R_R(true, BitCountKind::Lzcnt, r11, w_rdi),
+        "F3490FBDFB",
+        "lzcntq  %r11, %rdi",
+    ));
+    insns.push((
+        i_BitCount_R_R(false, BitCountKind::Tzcnt, rdx, w_r10),
+        "F3440FBCD2",
+        "tzcntl  %edx, %r10d",
+    ));
+    insns.push((
+        i_BitCount_R_R(true, BitCountKind::Tzcnt, rax, w_rcx),
+        "F3480FBCC8",
+        "tzcntq  %rax, %rcx",
+    ));
+
     // ========================================================
     // Cmp_RMI_R
     insns.push((
@@ -4616,7 +4937,72 @@ fn test_x64_insn_encoding_and_printing() {
     insns.push((i_Pop64(w_r15), "415F", "popq    %r15"));
 
     // ========================================================
-    // CallKnown skipped for now
+    // MovUPS_R_M
+    insns.push((
+        i_MovUPS_R_M(xmm6, ip_Addr_IR(32, rsp)),
+        "0F11742420",
+        "movups  %xmm6, 32(%rsp)",
+    ));
+    insns.push((
+        i_MovUPS_R_M(xmm15, ip_Addr_IR(0, rsp)),
+        "440F113C24",
+        "movups  %xmm15, 0(%rsp)",
+    ));
+    insns.push((
+        i_MovUPS_R_M(xmm10, ip_Addr_IR(160, r12)),
+        "450F119424A0000000",
+        "movups  %xmm10, 160(%r12)",
+    ));
+
+    // ========================================================
+    // MovUPS_M_R
+    insns.push((
+        i_MovUPS_M_R(ip_Addr_IR(32, rsp), w_xmm6),
+        "0F10742420",
+        "movups  32(%rsp), %xmm6",
+    ));
+    insns.push((
+        i_MovUPS_M_R(ip_Addr_IR(-16i32 as u32, rbp), w_xmm9),
+        "440F104DF0",
+        "movups  -16(%rbp), %xmm9",
+    ));
+
+    // ========================================================
+    // LoadExtName
+    insns.push((
+        i_LoadExtName(ExternalName::LibCall(LibCall::Probestack), w_rax),
+        "48B80000000000000000",
+        "movabsq $%Probestack, %rax",
+    ));
+    insns.push((
+        i_LoadExtName(ExternalName::testcase("test0"), w_r11),
+        "49BB0000000000000000",
+        "movabsq $%test0, %r11",
+    ));
+
+    // ========================================================
+    // TrapIf
+    insns.push((
+        i_TrapIf(CC::BE, SourceLoc::default(), TrapCode::StackOverflow),
+        "77020F0B",
+        "jnbe    $next ; ud2",
+    ));
+
+    // ========================================================
+    // Ud2
+    insns.push((
+        i_Ud2(SourceLoc::default(), TrapCode::UnreachableCodeReached),
+        "0F0B",
+        "ud2",
+    ));
+
+    // ========================================================
+    // CallKnown
+    insns.push((
+        i_CallKnown(ExternalName::testcase("test0"), Set::empty(), Set::empty()),
+        "E800000000",
+        "call    %test0",
+    ));
 
     // ========================================================
     // CallUnknown
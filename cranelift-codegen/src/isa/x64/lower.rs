@@ -6,16 +6,18 @@
 use crate::ir::condcodes::IntCC;
 //zz use crate::ir::types::*;
 use crate::ir::Inst as IRInst;
-use crate::ir::{Block, InstructionData, Opcode, Type};
+use crate::ir::{Block, InstructionData, Opcode, TrapCode, Type};
 
 use crate::ir::types;
 use crate::ir::types::*;
 
 use crate::machinst::lower::*;
 use crate::machinst::*;
+use crate::result::CodegenResult;
 
 use crate::isa::x64::inst::*;
 use crate::isa::x64::X64Backend;
+use crate::isa::x86::settings as x86_settings;
 
 use regalloc::{RealReg, Reg, RegClass, VirtualReg, Writable};
 
@@ -71,6 +73,17 @@ fn iri_to_u64_immediate<'a>(ctx: Ctx<'a>, iri: IRInst) -> Option<u64> {
     }
 }
 
+// Clone of arm64 version.  TODO: de-clone, re-name?
+fn inst_trapcode(data: &InstructionData) -> Option<TrapCode> {
+    match data {
+        &InstructionData::Trap { code, .. }
+        | &InstructionData::CondTrap { code, .. }
+        | &InstructionData::IntCondTrap { code, .. }
+        | &InstructionData::FloatCondTrap { code, .. } => Some(code),
+        _ => None,
+    }
+}
+
 // Clone of arm64 version.  TODO: de-clone, re-name?
 fn inst_condcode(data: &InstructionData) -> IntCC {
     match data {
@@ -105,8 +118,13 @@ fn intCC_to_x64_CC(cc: IntCC) -> CC {
 //=============================================================================
 // Top-level instruction lowering entry point, for one instruction.
 
-/// Actually codegen an instruction's results into registers.
-fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
+/// Actually codegen an instruction's results into registers.  |isa_flags|
+/// says which instruction set extensions may be used.
+fn lower_insn_to_regs<'a>(
+    ctx: Ctx<'a>,
+    iri: IRInst,
+    isa_flags: &x86_settings::Flags,
+) -> CodegenResult<()> {
     let op = ctx.data(iri).opcode();
     let ty = if ctx.num_outputs(iri) == 1 {
         Some(ctx.output_ty(iri, 0))
@@ -248,7 +266,28 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
             unimplemented = true;
         }
 
-        Opcode::Clz | Opcode::Cls | Opcode::Ctz | Opcode::Popcnt => {
+        Opcode::Clz | Opcode::Ctz | Opcode::Popcnt => {
+            // Only the forms from instruction set extensions are done, since
+            // BSR/BSF leave the destination undefined for a zero input.
+            // TODO: 8- and 16-bit inputs.
+            let kind = match op {
+                Opcode::Clz if isa_flags.use_lzcnt() => Some(BitCountKind::Lzcnt),
+                Opcode::Ctz if isa_flags.use_bmi1() => Some(BitCountKind::Tzcnt),
+                Opcode::Popcnt if isa_flags.use_popcnt() => Some(BitCountKind::Popcnt),
+                _ => None,
+            };
+            let tyD = ty.unwrap();
+            match kind {
+                Some(kind) if tyD == types::I32 || tyD == types::I64 => {
+                    let regS = ctx.input(iri, 0);
+                    let regD = ctx.output(iri, 0);
+                    ctx.emit(i_BitCount_R_R(tyD == types::I64, kind, regS, regD));
+                }
+                _ => unimplemented = true,
+            }
+        }
+
+        Opcode::Cls => {
             // TODO
             unimplemented = true;
         }
@@ -461,15 +500,30 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
             unimplemented = true;
         }
 
-        Opcode::Trap => {
-            unimplemented = true;
+        Opcode::Trap | Opcode::ResumableTrap => {
+            let trap_code = inst_trapcode(ctx.data(iri)).unwrap();
+            ctx.emit(i_Ud2(ctx.srcloc(iri), trap_code));
         }
 
-        Opcode::Trapz | Opcode::Trapnz | Opcode::Trapif | Opcode::Trapff => {
-            unimplemented = true;
+        Opcode::Trapz | Opcode::Trapnz => {
+            let tyS = ctx.input_ty(iri, 0);
+            if is_int_ty(tyS) {
+                let rS = ctx.input(iri, 0);
+                let cc = match op {
+                    Opcode::Trapz => CC::Z,
+                    Opcode::Trapnz => CC::NZ,
+                    _ => unreachable!(),
+                };
+                let trap_code = inst_trapcode(ctx.data(iri)).unwrap();
+                let sizeB = int_ty_to_sizeB(tyS);
+                ctx.emit(i_Cmp_RMI_R(sizeB, ip_RMI_I(0), rS));
+                ctx.emit(i_TrapIf(cc, ctx.srcloc(iri), trap_code));
+            } else {
+                unimplemented = true;
+            }
         }
 
-        Opcode::ResumableTrap => {
+        Opcode::Trapif | Opcode::Trapff => {
             unimplemented = true;
         }
 
@@ -512,6 +566,11 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
             unimplemented = true;
         }
 
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            // TODO
+            unimplemented = true;
+        }
+
         Opcode::GetPinnedReg
         | Opcode::SetPinnedReg
         | Opcode::Spill
@@ -652,6 +711,8 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
     if unimplemented {
         panic!("lower_insn_to_regs(x64): can't reduce: {:?}", ctx.data(iri));
     }
+
+    Ok(())
 }
 
 //=============================================================================
@@ -660,8 +721,8 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
 impl LowerBackend for X64Backend {
     type MInst = Inst;
 
-    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) {
-        lower_insn_to_regs(ctx, ir_inst);
+    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
+        lower_insn_to_regs(ctx, ir_inst, self.isa_flags())
     }
 
     fn lower_branch_group<C: LowerCtx<Inst>>(
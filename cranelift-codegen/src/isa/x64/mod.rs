@@ -4,9 +4,14 @@
 
 use crate::binemit::{CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink};
 use crate::ir::Function;
-use crate::machinst::{compile, MachBackend, MachCompileResult, ShowWithRRU, VCode};
+use crate::isa::x86::settings as x86_settings;
+use crate::isa::Builder as IsaBuilder;
+use crate::isa::TargetIsa;
+use crate::machinst::{
+    compile, MachBackend, MachCompileResult, ShowWithRRU, TargetIsaAdapter, VCode,
+};
 use crate::result::CodegenResult;
-use crate::settings;
+use crate::settings as shared_settings;
 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
@@ -25,44 +30,82 @@ use inst::create_reg_universe;
 
 /// An X64 backend.
 pub struct X64Backend {
-    flags: settings::Flags,
+    triple: Triple,
+    flags: shared_settings::Flags,
+    isa_flags: x86_settings::Flags,
 }
 
 impl X64Backend {
-    /// Create a new X64 backend.
-    pub fn new() -> X64Backend {
+    /// Create a new X64 backend with the given shared and ISA-specific flags. The ISA-specific
+    /// flags are the same CPUID flags as those of the legacy x86 backend.
+    pub fn new_with_flags(
+        triple: Triple,
+        flags: shared_settings::Flags,
+        isa_flags: x86_settings::Flags,
+    ) -> X64Backend {
         X64Backend {
-            flags: settings::Flags::new(settings::builder()),
+            triple,
+            flags,
+            isa_flags,
         }
     }
 
-    fn compile_vcode(&self, mut func: Function) -> VCode<inst::Inst> {
+    /// Get the ISA-specific flags of this backend.
+    pub fn isa_flags(&self) -> &x86_settings::Flags {
+        &self.isa_flags
+    }
+
+    fn compile_vcode(&self, mut func: Function) -> CodegenResult<VCode<inst::Inst>> {
         // This performs lowering to VCode, register-allocates the code, computes
         // block layout and finalizes branches. The result is ready for binary emission.
-        let abi = Box::new(abi::X64ABIBody::new(&func));
+        let abi = Box::new(abi::X64ABIBody::new(&func, self.flags.clone()));
         compile::compile::<X64Backend>(&mut func, self, abi)
     }
 }
 
+/// Get an ISA builder for creating x86_64 targets with the new backend.
+pub fn isa_builder(triple: Triple) -> IsaBuilder {
+    IsaBuilder {
+        triple,
+        setup: Some(x86_settings::builder()),
+        constructor: Some(isa_constructor),
+        wrapped: None,
+    }
+}
+
+fn isa_constructor(
+    triple: Triple,
+    shared_flags: shared_settings::Flags,
+    builder: shared_settings::Builder,
+) -> Box<dyn TargetIsa> {
+    let isa_flags = x86_settings::Flags::new(&shared_flags, builder);
+    let backend = X64Backend::new_with_flags(triple, shared_flags, isa_flags);
+    Box::new(TargetIsaAdapter::new(backend))
+}
+
 impl MachBackend for X64Backend {
     fn compile_function(
         &self,
         func: Function,
         want_disasm: bool,
     ) -> CodegenResult<MachCompileResult> {
-        let vcode = self.compile_vcode(func);
+        let vcode = self.compile_vcode(func)?;
         let sections = vcode.emit();
 
         let disasm = if want_disasm {
-            Some(vcode.show_rru(Some(&create_reg_universe())))
+            Some(vcode.show_disasm(Some(&create_reg_universe())))
         } else {
             None
         };
 
-        Ok(MachCompileResult { sections, disasm })
+        Ok(MachCompileResult {
+            sections,
+            disasm,
+            unwind_info: vcode.unwind_info(),
+        })
     }
 
-    fn flags(&self) -> &settings::Flags {
+    fn flags(&self) -> &shared_settings::Flags {
         &self.flags
     }
 
@@ -71,10 +114,55 @@ impl MachBackend for X64Backend {
     }
 
     fn triple(&self) -> Triple {
-        FromStr::from_str("x86_64").unwrap()
+        self.triple.clone()
     }
 
     fn reg_universe(&self) -> RealRegUniverse {
         create_reg_universe()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::*;
+    use crate::ir::{AbiParam, ExternalName, InstBuilder, Signature};
+    use crate::isa::CallConv;
+    use crate::settings::Configurable;
+
+    #[test]
+    fn test_popcnt_uses_isa_flags() {
+        let name = ExternalName::testcase("test0");
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        sig.returns.push(AbiParam::new(I32));
+        let mut func = Function::with_name_signature(name, sig);
+
+        let bb0 = func.dfg.make_block();
+        let arg0 = func.dfg.append_block_param(bb0, I32);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(bb0);
+        let v0 = pos.ins().popcnt(arg0);
+        pos.ins().return_(&[v0]);
+
+        let shared_flags = shared_settings::Flags::new(shared_settings::builder());
+        let mut isa_builder = x86_settings::builder();
+        isa_builder.enable("has_popcnt").unwrap();
+        isa_builder.enable("has_sse42").unwrap();
+        let isa_flags = x86_settings::Flags::new(&shared_flags, isa_builder);
+        let backend = X64Backend::new_with_flags(
+            Triple::from_str("x86_64").unwrap(),
+            shared_flags,
+            isa_flags,
+        );
+        let disasm = backend
+            .compile_function(func, true)
+            .unwrap()
+            .disasm
+            .unwrap();
+
+        assert!(disasm.contains("popcntl"), "{}", disasm);
+    }
+}
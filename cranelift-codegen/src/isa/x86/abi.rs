@@ -20,9 +20,10 @@ use crate::ir::{
 };
 use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
-use crate::result::CodegenResult;
+use crate::result::{CodegenError, CodegenResult};
 use crate::stack_layout::layout_stack;
 use alloc::borrow::Cow;
+use alloc::vec::Vec;
 use core::i32;
 use std::boxed::Box;
 use target_lexicon::{PointerWidth, Triple};
@@ -72,6 +73,8 @@ struct Args {
     fpr_limit: usize,
     fpr_used: usize,
     offset: u32,
+    /// Number of upcoming split halves that must be passed on the stack.
+    stack_halves: usize,
     call_conv: CallConv,
     shared_flags: shared_settings::Flags,
     #[allow(dead_code)]
@@ -102,6 +105,7 @@ impl Args {
             fpr_limit,
             fpr_used: 0,
             offset,
+            stack_halves: 0,
             call_conv,
             shared_flags: shared_flags.clone(),
             isa_flags: isa_flags.clone(),
@@ -125,6 +129,16 @@ impl ArgAssigner for Args {
 
         // Large integers and booleans are broken down to fit in a register.
         if !ty.is_float() && ty.bits() > u16::from(self.pointer_bits) {
+            // The System V ABI passes a 128-bit integer in a pair of registers, or entirely in a
+            // 16-byte aligned stack slot when fewer than two registers are left.
+            if self.pointer_bits == 64
+                && ty.bits() == 128
+                && !self.call_conv.extends_windows_fastcall()
+                && self.gpr_used + 2 > self.gpr.len()
+            {
+                self.stack_halves = 2;
+                self.offset = (self.offset + 15) & !15;
+            }
             return ValueConversion::IntSplit.into();
         }
 
@@ -163,7 +177,7 @@ impl ArgAssigner for Args {
         }
 
         // Try to use a GPR.
-        if !ty.is_float() && self.gpr_used < self.gpr.len() {
+        if !ty.is_float() && self.stack_halves == 0 && self.gpr_used < self.gpr.len() {
             let reg = self.gpr[self.gpr_used] as RegUnit;
             self.gpr_used += 1;
             return ArgumentLoc::Reg(reg).into();
@@ -189,6 +203,7 @@ impl ArgAssigner for Args {
         // Assign a stack location.
         let loc = ArgumentLoc::Stack(self.offset as i32);
         self.offset += u32::from(self.pointer_bytes);
+        self.stack_halves = self.stack_halves.saturating_sub(1);
         debug_assert!(self.offset <= i32::MAX as u32);
         loc.into()
     }
@@ -331,6 +346,34 @@ pub fn legalize_signature(
     }
 }
 
+/// Get the size of the stack area holding the arguments of the legalized signature `sig`, on a
+/// target with `word_size` byte stack slots.
+pub fn stack_args_size(sig: &ir::Signature, word_size: u32) -> u32 {
+    sig.params
+        .iter()
+        .filter_map(|param| match param.location {
+            ArgumentLoc::Stack(offset) => Some(offset as u32 + word_size),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Get the number of bytes of stack arguments that `func` pops when returning.
+pub fn callee_popped_bytes(func: &ir::Function) -> u16 {
+    let sig = &func.signature;
+    if !sig.call_conv.callee_pops_stack_args() || !sig.params.iter().any(|p| p.location.is_stack())
+    {
+        return 0;
+    }
+    // The type of the frame pointer parameter added by the prologue is the word size.
+    let word_size = sig
+        .special_param_index(ArgumentPurpose::FramePointer)
+        .map(|index| sig.params[index].value_type.bytes())
+        .expect("functions popping their stack arguments need a frame pointer");
+    stack_args_size(sig, word_size) as u16
+}
+
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
     if ty.is_int() || ty.is_bool() || ty.is_ref() {
@@ -438,9 +481,11 @@ fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterS
 }
 
 pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    insert_callee_pops_adjustments(func, isa);
     match func.signature.call_conv {
-        // For now, just translate fast and cold as system_v.
-        CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
+        // For now, just translate fast and cold as system_v. The tail calling convention only
+        // differs in how the stack arguments are popped.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV | CallConv::Tail => {
             system_v_prologue_epilogue(func, isa)
         }
         CallConv::WindowsFastcall => fastcall_prologue_epilogue(func, isa),
@@ -451,11 +496,54 @@ pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> Codege
     }
 }
 
+/// Re-allocate the stack arguments popped by callees after each call, so that the stack pointer
+/// stays constant in the body of `func`.
+fn insert_callee_pops_adjustments(func: &mut ir::Function, isa: &dyn TargetIsa) {
+    let word_size = u32::from(isa.pointer_bytes());
+    let mut adjustments = Vec::new();
+    for block in func.layout.blocks() {
+        for inst in func.layout.block_insts(block) {
+            if func.dfg[inst].opcode().is_terminator() {
+                continue;
+            }
+            if let Some(sig_ref) = func.dfg.call_signature(inst) {
+                let sig = &func.dfg.signatures[sig_ref];
+                if sig.call_conv.callee_pops_stack_args() {
+                    let size = stack_args_size(sig, word_size);
+                    if size > 0 {
+                        adjustments.push((inst, size));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut pos = EncCursor::new(func, isa);
+    for (inst, size) in adjustments {
+        pos.goto_after_inst(inst);
+        pos.ins().adjust_sp_down_imm(Imm64::new(i64::from(size)));
+    }
+}
+
+/// Does `func` contain a `return_call` or `return_call_indirect` instruction?
+fn has_tail_calls(func: &ir::Function) -> bool {
+    func.layout.blocks().any(|block| {
+        func.layout.last_inst(block).map_or(false, |inst| {
+            func.dfg[inst].opcode().is_call() && func.dfg[inst].opcode().is_terminator()
+        })
+    })
+}
+
 fn baldrdash_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
     debug_assert!(
         !isa.flags().enable_probestack(),
         "baldrdash does not expect cranelift to emit stack probes"
     );
+    if has_tail_calls(func) {
+        return Err(CodegenError::Unsupported(
+            "tail calls with the baldrdash calling conventions".into(),
+        ));
+    }
 
     let word_size = StackSize::from(isa.pointer_bytes());
     let shadow_store_size = if func.signature.call_conv.extends_windows_fastcall() {
@@ -508,6 +596,13 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
     if isa.triple().pointer_width().unwrap() != PointerWidth::U64 {
         panic!("TODO: windows-fastcall: x86-32 not implemented yet");
     }
+    // The tail call epilogue neither skips the shadow space nor preserves the callee-saved XMM
+    // registers.
+    if has_tail_calls(func) {
+        return Err(CodegenError::Unsupported(
+            "tail calls with the windows_fastcall calling convention".into(),
+        ));
+    }
 
     let csrs = callee_saved_gprs_used(isa, func);
 
@@ -565,9 +660,7 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         &csrs,
         isa,
         prologue_cfa_state,
-    );
-
-    Ok(())
+    )
 }
 
 /// Insert a System V-compatible prologue and epilogue.
@@ -626,9 +719,7 @@ fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         &csrs,
         isa,
         prologue_cfa_state,
-    );
-
-    Ok(())
+    )
 }
 
 /// Insert the prologue for a given function.
@@ -822,7 +913,7 @@ fn insert_stack_check(pos: &mut EncCursor, stack_size: i64, stack_limit_arg: ir:
     );
 }
 
-/// Find all `return` instructions and insert epilogues before them.
+/// Find all `return` and tail call instructions and insert epilogues before them.
 fn insert_common_epilogues(
     pos: &mut EncCursor,
     stack_size: i64,
@@ -830,12 +921,13 @@ fn insert_common_epilogues(
     csrs: &RegisterSet,
     isa: &dyn TargetIsa,
     cfa_state: Option<CFAState>,
-) {
+) -> CodegenResult<()> {
     while let Some(block) = pos.next_block() {
         pos.goto_last_inst(block);
         if let Some(inst) = pos.current_inst() {
-            if pos.func.dfg[inst].opcode().is_return() {
-                let is_last = pos.func.layout.last_block() == Some(block);
+            let opcode = pos.func.dfg[inst].opcode();
+            let is_last = pos.func.layout.last_block() == Some(block);
+            if opcode.is_return() {
                 insert_common_epilogue(
                     inst,
                     stack_size,
@@ -846,9 +938,12 @@ fn insert_common_epilogues(
                     is_last,
                     cfa_state.clone(),
                 );
+            } else if opcode.is_call() {
+                insert_tail_call_epilogue(inst, stack_size, pos, reg_type, csrs, isa, is_last)?;
             }
         }
     }
+    Ok(())
 }
 
 /// Insert an epilogue given a specific `return` instruction.
@@ -947,6 +1042,127 @@ fn insert_common_epilogue(
     }
 }
 
+/// Insert an epilogue given a specific `return_call` or `return_call_indirect` instruction.
+///
+/// The stack arguments of the callee are stored at the bottom of the frame, like for any other
+/// call. Once the callee-saved registers and the frame pointer are restored, they are moved to the
+/// incoming arguments area of the current function. When the callee pops its stack arguments,
+/// the return address is moved along so that they end where the incoming arguments end, which
+/// allows tail calls to functions with more stack arguments than the current one.
+///
+/// The registers used here are neither argument registers nor callee-saved registers in the
+/// System V-based conventions, which are the only ones tail calls are supported from.
+fn insert_tail_call_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    reg_type: ir::types::Type,
+    csrs: &RegisterSet,
+    isa: &dyn TargetIsa,
+    is_last: bool,
+) -> CodegenResult<()> {
+    let word_size = isa.pointer_bytes() as i32;
+    let sig_ref = pos.func.dfg.call_signature(inst).expect("tail call");
+    let incoming_size = stack_args_size(&pos.func.signature, word_size as u32) as i32;
+    let outgoing_size = stack_args_size(&pos.func.dfg.signatures[sig_ref], word_size as u32) as i32;
+
+    // How far up the return address is moved.
+    let shift = if pos.func.signature.call_conv.callee_pops_stack_args() {
+        incoming_size - outgoing_size
+    } else if outgoing_size <= incoming_size {
+        0
+    } else {
+        // Our caller will only pop its own stack arguments, there is no room for more.
+        return Err(CodegenError::ImplLimitExceeded);
+    };
+
+    // Distance from the stack pointer to the incoming arguments.
+    let frame_size = stack_size as i32 + (csrs.iter(GPR).len() as i32 + 2) * word_size;
+
+    let (base_reg, copy_reg, ret_addr_reg, copy_type) = if word_size == 8 {
+        (
+            RU::r11 as RegUnit,
+            FPR.unit(14),
+            FPR.unit(15),
+            ir::types::F64,
+        )
+    } else {
+        (RU::rcx as RegUnit, FPR.unit(6), FPR.unit(7), ir::types::F32)
+    };
+    let flags = ir::MemFlags::trusted();
+
+    // The callee address may live in a callee-saved register, move it out of the way.
+    if pos.func.dfg[inst].opcode() == ir::Opcode::ReturnCallIndirect {
+        let callee = pos.func.dfg.inst_args(inst)[0];
+        let callee_copy = pos.ins().copy(callee);
+        pos.func.locations[callee_copy] = ir::ValueLoc::Reg(RU::rax as RegUnit);
+        pos.func.dfg.inst_args_mut(inst)[0] = callee_copy;
+    }
+
+    let base = pos.ins().copy_to_ssa(reg_type, RU::rsp as RegUnit);
+    pos.func.locations[base] = ir::ValueLoc::Reg(base_reg);
+
+    // Restore the callee-saved registers pushed by the prologue, and the frame pointer, before
+    // the stack arguments overwrite them.
+    for (i, reg) in csrs.iter(GPR).enumerate() {
+        let offset = frame_size - (i as i32 + 3) * word_size;
+        let csr = pos.ins().load(reg_type, flags, base, offset);
+        pos.func.locations[csr] = ir::ValueLoc::Reg(reg);
+    }
+    let ret_addr = pos
+        .ins()
+        .load(copy_type, flags, base, frame_size - word_size);
+    pos.func.locations[ret_addr] = ir::ValueLoc::Reg(ret_addr_reg);
+    let fp = pos
+        .ins()
+        .load(reg_type, flags, base, frame_size - 2 * word_size);
+    pos.func.locations[fp] = ir::ValueLoc::Reg(RU::rbp as RegUnit);
+    let fp_load_inst = pos.built_inst();
+
+    // The destination is above the source, so copy from the top down.
+    let dest = frame_size + shift;
+    for offset in (0..outgoing_size).step_by(word_size as usize).rev() {
+        let arg = pos.ins().load(copy_type, flags, base, offset);
+        pos.func.locations[arg] = ir::ValueLoc::Reg(copy_reg);
+        pos.ins().store(flags, arg, base, dest + offset);
+    }
+    pos.ins().store(flags, ret_addr, base, dest - word_size);
+    let adjust_sp_inst = pos
+        .ins()
+        .adjust_sp_up_imm(Imm64::new(i64::from(dest - word_size)));
+
+    if let Some(ref mut frame_layout) = pos.func.frame_layout {
+        // Once the frame pointer is restored, the call frame can only be found from the stack
+        // pointer, which ends up pointing at the return address.
+        let new_cfa = FrameLayoutChange::CallFrameAddressAt {
+            reg: RU::rsp as RegUnit,
+            offset: frame_size as isize,
+        };
+        let new_cfa = if is_last {
+            vec![new_cfa]
+        } else {
+            vec![FrameLayoutChange::Preserve, new_cfa]
+        };
+        frame_layout
+            .instructions
+            .insert(fp_load_inst, new_cfa.into_boxed_slice());
+        frame_layout.instructions.insert(
+            adjust_sp_inst,
+            vec![FrameLayoutChange::CallFrameAddressAt {
+                reg: RU::rsp as RegUnit,
+                offset: word_size as isize,
+            }]
+            .into_boxed_slice(),
+        );
+        if !is_last {
+            frame_layout
+                .instructions
+                .insert(inst, vec![FrameLayoutChange::Restore].into_boxed_slice());
+        }
+    }
+    Ok(())
+}
+
 #[cfg(feature = "unwind")]
 pub fn emit_unwind_info(
     func: &ir::Function,
@@ -1,5 +1,6 @@
 //! Emitting binary x86 machine code.
 
+use super::abi::callee_popped_bytes;
 use super::enc_tables::{needs_offset, needs_sib_byte};
 use super::registers::RU;
 use crate::binemit::{bad_encoding, CodeSink, Reloc};
@@ -206,6 +207,29 @@ fn put_rexmp3<CS: CodeSink + ?Sized>(bits: u16, rex: u8, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit a three-byte VEX prefix (C4 RXBmmmmm WvvvvLpp) followed by the opcode byte.
+//
+// `reg` and `rm` are the registers that go in the ModR/M byte, and `vvvv` is the additional source
+// register. The two-byte VEX form is never used, so the size doesn't depend on the registers.
+fn put_vex<CS: CodeSink + ?Sized>(
+    bits: u16,
+    reg: RegUnit,
+    vvvv: RegUnit,
+    rm: RegUnit,
+    sink: &mut CS,
+) {
+    let enc = EncodingBits::from(bits);
+    debug_assert_ne!(enc.mm(), 0, "Invalid encoding bits for Vex*");
+    // The R, X, B and vvvv fields are stored inverted. L is 0 for 128-bit vectors.
+    let r = (!(reg >> 3) & 1) as u8;
+    let b = (!(rm >> 3) & 1) as u8;
+    let vvvv = (!vvvv & 0xf) as u8;
+    sink.put1(0xc4);
+    sink.put1((r << 7) | (1 << 6) | (b << 5) | enc.mm());
+    sink.put1((enc.rex_w() << 7) | (vvvv << 3) | enc.pp());
+    sink.put1(enc.opcode_byte());
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
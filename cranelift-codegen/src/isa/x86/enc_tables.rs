@@ -1,5 +1,6 @@
 //! Encoding tables for x86 ISAs.
 
+use super::abi::callee_popped_bytes;
 use super::registers::*;
 use crate::bitset::BitSet;
 use crate::cursor::{Cursor, FuncCursor};
@@ -82,6 +83,15 @@ fn size_plus_maybe_offset_for_inreg_1(
     let needs_offset = test_input(1, inst, divert, func, needs_offset);
     sizing.base_size + if needs_offset { 1 } else { 0 }
 }
+fn size_plus_callee_popped_bytes(
+    sizing: &RecipeSizing,
+    _enc: Encoding,
+    _inst: Inst,
+    _divert: &RegDiversions,
+    func: &Function,
+) -> u8 {
+    sizing.base_size + if callee_popped_bytes(func) > 0 { 2 } else { 0 }
+}
 fn size_plus_maybe_sib_for_inreg_0(
     sizing: &RecipeSizing,
     _enc: Encoding,
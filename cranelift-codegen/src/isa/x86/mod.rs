@@ -8,7 +8,7 @@ mod fde;
 mod registers;
 pub mod settings;
 #[cfg(feature = "unwind")]
-mod unwind;
+pub(crate) mod unwind;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
@@ -1,7 +1,7 @@
 //! Unwind information for x64 Windows.
 
 use super::registers::RU;
-use crate::binemit::FrameUnwindSink;
+use crate::binemit::{FrameUnwindOffset, FrameUnwindSink, Reloc};
 use crate::ir::{Function, InstructionData, Opcode};
 use crate::isa::{CallConv, RegUnit, TargetIsa};
 use alloc::vec::Vec;
@@ -11,6 +11,8 @@ use byteorder::{ByteOrder, LittleEndian};
 const SMALL_ALLOC_MAX_SIZE: u32 = 128;
 /// Maximum (inclusive) size of a "large" stack allocation that can represented in 16-bits
 const LARGE_ALLOC_16BIT_MAX_SIZE: u32 = 524280;
+/// Maximum (inclusive) stack offset of an XMM register save that can be represented in 16-bits
+const SAVE_XMM_16BIT_MAX_OFFSET: u32 = 1048560;
 
 fn write_u8(sink: &mut dyn FrameUnwindSink, v: u8) {
     sink.bytes(&[v]);
@@ -34,10 +36,26 @@ fn write_u32<T: ByteOrder>(sink: &mut dyn FrameUnwindSink, v: u32) {
 /// Only what is needed to describe the prologues generated by the Cranelift x86 ISA are represented here.
 /// Note: the Cranelift x86 ISA RU enum matches the Windows unwind GPR encoding values.
 #[derive(Debug, PartialEq, Eq)]
-enum UnwindCode {
-    PushRegister { offset: u8, reg: RegUnit },
-    StackAlloc { offset: u8, size: u32 },
-    SetFramePointer { offset: u8, sp_offset: u8 },
+pub(crate) enum UnwindCode {
+    PushRegister {
+        offset: u8,
+        reg: RegUnit,
+    },
+    StackAlloc {
+        offset: u8,
+        size: u32,
+    },
+    SetFramePointer {
+        offset: u8,
+        sp_offset: u8,
+    },
+    /// Save all of XMM register number `reg` at `stack_offset` bytes above the stack pointer, as
+    /// it is after the prologue's stack allocation.
+    SaveXmm {
+        offset: u8,
+        reg: u8,
+        stack_offset: u32,
+    },
 }
 
 impl UnwindCode {
@@ -47,6 +65,8 @@ impl UnwindCode {
             LargeStackAlloc,
             SmallStackAlloc,
             SetFramePointer,
+            SaveXmm128 = 8,
+            SaveXmm128Far,
         }
 
         match self {
@@ -83,6 +103,23 @@ impl UnwindCode {
                     (*sp_offset << 4) | (UnwindOperation::SetFramePointer as u8),
                 );
             }
+            Self::SaveXmm {
+                offset,
+                reg,
+                stack_offset,
+            } => {
+                // Saves must be 16-byte aligned, and the 16-bit form is scaled by 16
+                assert!((*stack_offset % 16) == 0);
+
+                write_u8(sink, *offset);
+                if *stack_offset <= SAVE_XMM_16BIT_MAX_OFFSET {
+                    write_u8(sink, (*reg << 4) | (UnwindOperation::SaveXmm128 as u8));
+                    write_u16::<LittleEndian>(sink, (*stack_offset / 16) as u16);
+                } else {
+                    write_u8(sink, (*reg << 4) | (UnwindOperation::SaveXmm128Far as u8));
+                    write_u32::<LittleEndian>(sink, *stack_offset);
+                }
+            }
         };
     }
 
@@ -97,6 +134,13 @@ impl UnwindCode {
                     3
                 }
             }
+            Self::SaveXmm { stack_offset, .. } => {
+                if *stack_offset <= SAVE_XMM_16BIT_MAX_OFFSET {
+                    2
+                } else {
+                    3
+                }
+            }
             _ => 1,
         }
     }
@@ -116,6 +160,18 @@ pub struct UnwindInfo {
 }
 
 impl UnwindInfo {
+    /// Create the unwind information for a prologue of `prologue_size` bytes that doesn't set up a
+    /// frame register, from its `unwind_codes` in prologue order.
+    pub(crate) fn new(prologue_size: u8, unwind_codes: Vec<UnwindCode>) -> Self {
+        Self {
+            flags: 0, // this assumes cranelift functions have no SEH handlers
+            prologue_size,
+            frame_register: None,
+            frame_register_offset: 0,
+            unwind_codes,
+        }
+    }
+
     pub fn try_from_func(
         func: &Function,
         isa: &dyn TargetIsa,
@@ -280,12 +336,32 @@ impl UnwindInfo {
         // Ensure the correct number of bytes was emitted
         assert_eq!(sink.len() - offset, size);
     }
+
+    /// Emit the unwind information into a new buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        struct VecSink(Vec<u8>);
+        impl FrameUnwindSink for VecSink {
+            fn len(&self) -> FrameUnwindOffset {
+                self.0.len()
+            }
+            fn bytes(&mut self, b: &[u8]) {
+                self.0.extend_from_slice(b);
+            }
+            fn reloc(&mut self, _: Reloc, _: FrameUnwindOffset) {
+                panic!("UNWIND_INFO has no relocations");
+            }
+            fn set_entry_offset(&mut self, _: FrameUnwindOffset) {}
+        }
+
+        let mut sink = VecSink(Vec::with_capacity(self.size()));
+        self.emit(&mut sink);
+        sink.0
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::binemit::{FrameUnwindOffset, Reloc};
     use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::{ExternalName, InstBuilder, Signature, StackSlotData, StackSlotKind};
     use crate::isa::{lookup, CallConv};
@@ -519,6 +595,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_save_xmm() {
+        let unwind = UnwindInfo::new(
+            0x15,
+            vec![
+                UnwindCode::PushRegister {
+                    offset: 0x01,
+                    reg: RU::rbp.into(),
+                },
+                UnwindCode::StackAlloc {
+                    offset: 0x08,
+                    size: 64,
+                },
+                UnwindCode::SaveXmm {
+                    offset: 0x0D,
+                    reg: 6,
+                    stack_offset: 32,
+                },
+                UnwindCode::SaveXmm {
+                    offset: 0x15,
+                    reg: 15,
+                    stack_offset: 0x100000,
+                },
+            ],
+        );
+
+        assert_eq!(unwind.size(), 20);
+
+        assert_eq!(
+            unwind.to_bytes(),
+            [
+                0x01, // Version and flags (version 1, no flags)
+                0x15, // Prologue size
+                0x07, // Unwind code count (3 for far xmm save, 2 for xmm save, 1 for stack alloc, 1 for push reg)
+                0x00, // Frame register + offset (no frame register)
+                0x15, // Prolog offset
+                0xF9, // Operation 9 (save far xmm register), reg = 15 (XMM15)
+                0x00, // Byte 1 of stack offset
+                0x00, // Byte 2 of stack offset
+                0x10, // Byte 3 of stack offset
+                0x00, // Byte 4 of stack offset (offset is unscaled 0x100000)
+                0x0D, // Prolog offset
+                0x68, // Operation 8 (save xmm register), reg = 6 (XMM6)
+                0x02, // Byte 1 of stack offset
+                0x00, // Byte 2 of stack offset (offset is 2 * 16 = 32)
+                0x08, // Prolog offset
+                0x72, // Operation 2 (small stack alloc), size = 0x7 slots (e.g. (0x7 * 8) + 8 = 64 bytes)
+                0x01, // Prolog offset
+                0x50, // Operation 0 (push nonvolatile register), reg = 5 (RBP)
+                0x00, // Padding byte
+                0x00, // Padding byte
+            ]
+        );
+    }
+
     fn create_function(call_conv: CallConv, stack_slot: Option<StackSlotData>) -> Function {
         let mut func =
             Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
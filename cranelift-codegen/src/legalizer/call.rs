@@ -1,15 +1,17 @@
 //! Legalization of calls.
 //!
 //! This module exports the `expand_call` function which transforms a `call`
-//! instruction into `func_addr` and `call_indirect` instructions.
+//! instruction into `func_addr` and `call_indirect` instructions, and a
+//! `return_call` instruction into `func_addr` and `return_call_indirect`.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::{self, InstBuilder};
 use crate::isa::TargetIsa;
 
-/// Expand a `call` instruction. This lowers it to a `call_indirect`, which
-/// is only done if the ABI doesn't support direct calls.
+/// Expand a `call` or `return_call` instruction. This lowers it to a
+/// `call_indirect` or `return_call_indirect`, which is only done if the ABI
+/// doesn't support direct calls.
 pub fn expand_call(
     inst: ir::Inst,
     func: &mut ir::Function,
@@ -17,14 +19,18 @@ pub fn expand_call(
     isa: &dyn TargetIsa,
 ) {
     // Unpack the instruction.
-    let (func_ref, old_args) = match func.dfg[inst] {
+    let (opcode, func_ref, old_args) = match func.dfg[inst] {
         ir::InstructionData::Call {
             opcode,
             ref args,
             func_ref,
         } => {
-            debug_assert_eq!(opcode, ir::Opcode::Call);
-            (func_ref, args.clone())
+            let indirect = match opcode {
+                ir::Opcode::Call => ir::Opcode::CallIndirect,
+                ir::Opcode::ReturnCall => ir::Opcode::ReturnCallIndirect,
+                _ => panic!("Unexpected call opcode: {}", opcode),
+            };
+            (indirect, func_ref, args.clone())
         }
         _ => panic!("Wanted call: {}", func.dfg.display_inst(inst, None)),
     };
@@ -50,5 +56,5 @@ pub fn expand_call(
 
     func.dfg
         .replace(inst)
-        .CallIndirect(ir::Opcode::CallIndirect, ptr_ty, sig, new_args);
+        .CallIndirect(opcode, ptr_ty, sig, new_args);
 }
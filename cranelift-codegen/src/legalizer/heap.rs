@@ -1,7 +1,8 @@
 //! Legalization of heaps.
 //!
 //! This module exports the `expand_heap_addr` function which transforms a `heap_addr`
-//! instruction into code that depends on the kind of heap referenced.
+//! instruction into code that depends on the kind of heap referenced, and the
+//! `expand_heap_addr_unchecked` function for accesses which are known to be in bounds.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
@@ -47,6 +48,22 @@ pub fn expand_heap_addr(
     }
 }
 
+/// Expand a `heap_addr` instruction which is known to be in bounds, without a bounds check.
+pub fn expand_heap_addr_unchecked(inst: ir::Inst, func: &mut ir::Function, isa: &dyn TargetIsa) {
+    let (heap, offset) = match func.dfg[inst] {
+        ir::InstructionData::HeapAddr {
+            opcode, heap, arg, ..
+        } => {
+            debug_assert_eq!(opcode, ir::Opcode::HeapAddr);
+            (heap, arg)
+        }
+        _ => panic!("Wanted heap_addr: {}", func.dfg.display_inst(inst, None)),
+    };
+    let offset_ty = func.dfg.value_type(offset);
+    let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    compute_addr(isa, inst, heap, addr_ty, offset, offset_ty, func);
+}
+
 /// Expand a `heap_addr` for a dynamic heap.
 fn dynamic_addr(
     isa: &dyn TargetIsa,
@@ -163,10 +180,15 @@ fn compute_addr(
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
-    // Convert `offset` to `addr_ty`.
+    // Convert `offset` to `addr_ty`. A 64-bit index into a heap on a 32-bit target has already
+    // been bounds checked above, so it fits in `addr_ty` and can simply be truncated.
     if offset_ty != addr_ty {
         let labels_value = offset;
-        offset = pos.ins().uextend(addr_ty, offset);
+        offset = if offset_ty.bits() > addr_ty.bits() {
+            pos.ins().ireduce(addr_ty, offset)
+        } else {
+            pos.ins().uextend(addr_ty, offset)
+        };
         if let Some(values_labels) = pos.func.dfg.values_labels.as_mut() {
             values_labels.insert(
                 offset,
@@ -16,7 +16,7 @@
 use crate::bitset::BitSet;
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::types::{I32, I64};
+use crate::ir::types::{I128, I32, I64};
 use crate::ir::{self, InstBuilder, MemFlags};
 use crate::isa::TargetIsa;
 use crate::predicates;
@@ -38,6 +38,8 @@ use self::heap::expand_heap_addr;
 use self::libcall::expand_as_libcall;
 use self::table::expand_table_addr;
 
+pub use self::heap::expand_heap_addr_unchecked;
+
 enum LegalizeInstResult {
     Done,
     Legalized,
@@ -106,6 +108,14 @@ fn legalize_inst(
         pos.func.dfg.change_to_alias(resh, xh);
 
         return LegalizeInstResult::Legalized;
+    } else if opcode == ir::Opcode::Ireduce {
+        // The controlling type of `ireduce` is its result type, which is legal, so the ISA's
+        // legalization actions would pick an expansion rather than narrowing the argument.
+        let arg = pos.func.dfg.inst_args(inst)[0];
+        if pos.func.dfg.value_type(arg).bits() > isa.pointer_bits().into() {
+            narrow_ireduce(inst, pos.func, cfg, isa);
+            return LegalizeInstResult::Legalized;
+        }
     }
 
     match pos.func.update_encoding(inst, isa) {
@@ -689,6 +699,14 @@ fn narrow_iconst(
         return;
     }
 
+    if isa.pointer_bits() == 64 && ty == I128 {
+        // The immediate is sign-extended to the full width.
+        let low = pos.ins().iconst(I64, imm);
+        let high = pos.ins().iconst(I64, imm >> 63);
+        pos.func.dfg.replace(inst).iconcat(low, high);
+        return;
+    }
+
     unimplemented!("missing encoding or legalization for iconst.{:?}", ty);
 }
 
@@ -719,9 +737,10 @@ fn narrow_icmp_imm(
     let imm_low = pos
         .ins()
         .iconst(ty_half, imm & ((1u128 << ty_half.bits()) - 1) as i64);
+    // The immediate is sign-extended to the full width, so a 64-bit half only holds sign bits.
     let imm_high = pos
         .ins()
-        .iconst(ty_half, imm.wrapping_shr(ty_half.bits().into()));
+        .iconst(ty_half, imm >> u32::from(ty_half.bits()).min(63));
     let (arg_low, arg_high) = pos.ins().isplit(arg);
 
     match cond {
@@ -755,3 +774,189 @@ fn narrow_icmp_imm(
         _ => unimplemented!("missing legalization for condition {:?}", cond),
     }
 }
+
+/// Narrow a shift by a variable amount into shifts of both halves.
+///
+/// The shifts of the halves only look at the amount modulo the width of a half, so the result is
+/// selected based on whether the full amount crosses over into the other half.
+fn narrow_shift(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    use crate::ir::condcodes::IntCC;
+
+    let (opcode, x, amt) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("unexpected instruction in narrow_shift"),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+    let ty_half = ty.half_width().expect("Can't narrow shift");
+    let half_bits = i64::from(ty_half.bits());
+
+    // Bring the shift amount to the type of the halves; only its low bits matter.
+    let amt_ty = pos.func.dfg.value_type(amt);
+    let amt = if amt_ty == ty {
+        pos.ins().isplit(amt).0
+    } else if amt_ty.bits() < ty_half.bits() {
+        pos.ins().uextend(ty_half, amt)
+    } else if amt_ty.bits() > ty_half.bits() {
+        pos.ins().ireduce(ty_half, amt)
+    } else {
+        amt
+    };
+
+    let (xl, xh) = pos.ins().isplit(x);
+
+    // `(half_bits - 1) - amt`, modulo `half_bits`. Shifting by one and then by this amount moves
+    // bits between the halves without needing a shift by `half_bits` when `amt` is zero.
+    let inv = pos.ins().bnot(amt);
+    let crosses = pos.ins().band_imm(amt, half_bits);
+    let crosses = pos.ins().icmp_imm(IntCC::NotEqual, crosses, 0);
+
+    let (low, high) = match opcode {
+        ir::Opcode::Ishl => {
+            let low = pos.ins().ishl(xl, amt);
+            let high = pos.ins().ishl(xh, amt);
+            let carry = pos.ins().ushr_imm(xl, 1);
+            let carry = pos.ins().ushr(carry, inv);
+            let high = pos.ins().bor(high, carry);
+            let zero = pos.ins().iconst(ty_half, 0);
+            (
+                pos.ins().select(crosses, zero, low),
+                pos.ins().select(crosses, low, high),
+            )
+        }
+        ir::Opcode::Ushr | ir::Opcode::Sshr => {
+            let (high, fill) = if opcode == ir::Opcode::Ushr {
+                (pos.ins().ushr(xh, amt), pos.ins().iconst(ty_half, 0))
+            } else {
+                (
+                    pos.ins().sshr(xh, amt),
+                    pos.ins().sshr_imm(xh, half_bits - 1),
+                )
+            };
+            let low = pos.ins().ushr(xl, amt);
+            let carry = pos.ins().ishl_imm(xh, 1);
+            let carry = pos.ins().ishl(carry, inv);
+            let low = pos.ins().bor(low, carry);
+            (
+                pos.ins().select(crosses, high, low),
+                pos.ins().select(crosses, fill, high),
+            )
+        }
+        _ => panic!("unexpected instruction in narrow_shift"),
+    };
+
+    pos.func.dfg.replace(inst).iconcat(low, high);
+}
+
+/// Expand a division or remainder of integers that need to be narrowed as a library call.
+///
+/// Library routines don't trap, so the checks for division by zero and for signed overflow are
+/// inserted before the call.
+fn narrow_divrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    use crate::ir::condcodes::IntCC;
+
+    let (opcode, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("unexpected instruction in narrow_divrem"),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+
+    let is_zero = pos.ins().icmp_imm(IntCC::Equal, y, 0);
+    pos.ins()
+        .trapnz(is_zero, ir::TrapCode::IntegerDivisionByZero);
+
+    if opcode == ir::Opcode::Sdiv {
+        // `MIN / -1` overflows.
+        let ty_half = ty.half_width().expect("Can't narrow division");
+        let min_low = pos.ins().iconst(ty_half, 0);
+        let min_high = pos.ins().iconst(ty_half, 1i64 << (ty_half.bits() - 1));
+        let min = pos.ins().iconcat(min_low, min_high);
+        let is_min = pos.ins().icmp(IntCC::Equal, x, min);
+        let is_neg_one = pos.ins().icmp_imm(IntCC::Equal, y, -1);
+        let overflow = pos.ins().band(is_min, is_neg_one);
+        pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+    }
+
+    // Every type that can be narrowed (`i64` and `i128`) has a library call for all four
+    // operations.
+    let expanded = expand_as_libcall(inst, pos.func, isa);
+    debug_assert!(expanded, "missing library call for {}.{}", opcode, ty);
+}
+
+/// Express a rotate by a variable amount as a pair of shifts, which are narrowed in turn.
+fn narrow_rotate(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (opcode, x, amt) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("unexpected instruction in narrow_rotate"),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+    let rev_amt = pos.ins().irsub_imm(amt, i64::from(ty.bits()));
+
+    let (a, b) = match opcode {
+        ir::Opcode::Rotl => (pos.ins().ishl(x, amt), pos.ins().ushr(x, rev_amt)),
+        ir::Opcode::Rotr => (pos.ins().ushr(x, amt), pos.ins().ishl(x, rev_amt)),
+        _ => panic!("unexpected instruction in narrow_rotate"),
+    };
+    pos.func.dfg.replace(inst).bor(a, b);
+}
+
+/// Reduce an integer that needs to be narrowed by only keeping its lower half.
+fn narrow_ireduce(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let arg = match func.dfg[inst] {
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Ireduce,
+            arg,
+        } => arg,
+        _ => panic!("Expected ireduce: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+    let arg_ty = pos.func.dfg.value_type(arg);
+    let ty_half = arg_ty
+        .half_width()
+        .unwrap_or_else(|| panic!("missing encoding or legalization for ireduce.{}", ty));
+
+    let (low, _) = pos.ins().isplit(arg);
+    if ty == ty_half {
+        let result = pos.func.dfg.first_result(inst);
+        pos.func.dfg.clear_results(inst);
+        pos.remove_inst();
+        pos.func.dfg.change_to_alias(result, low);
+    } else {
+        pos.func.dfg.replace(inst).ireduce(ty, low);
+    }
+}
@@ -82,11 +82,13 @@ pub use crate::entity::packed_option;
 
 mod abi;
 mod bitset;
+mod bounds_checks;
 mod constant_hash;
 mod context;
 mod dce;
 mod divconst_magic_numbers;
 mod fx;
+mod induction;
 mod iterators;
 mod legalizer;
 mod licm;
@@ -102,8 +104,10 @@ mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
 mod stack_layout;
+mod strength_reduction;
 mod topo_order;
 mod unreachable_code;
+mod unroll;
 mod value_label;
 
 pub use crate::result::{CodegenError, CodegenResult};
@@ -3,11 +3,15 @@
 use crate::ir;
 use crate::ir::StackSlot;
 use crate::machinst::*;
+use crate::settings;
 use regalloc::{Reg, Set, SpillSlot, VirtualReg, Writable};
 
 /// Trait implemented by an object that tracks ABI-related state (e.g., stack
 /// layout) and can generate code while emitting the *body* of a function.
 pub trait ABIBody<I: VCodeInst> {
+    /// Get the settings controlling this function's compilation.
+    fn flags(&self) -> &settings::Flags;
+
     /// Get the liveins of the function.
     fn liveins(&self) -> Set<RealReg>;
 
@@ -78,6 +82,13 @@ pub trait ABIBody<I: VCodeInst> {
     /// likely closely related.
     fn gen_epilogue(&self) -> Vec<I>;
 
+    /// Get the unwind information describing the frame set up by the
+    /// prologue, if the calling convention needs any. May only be called after
+    /// `gen_prologue`.
+    fn unwind_info(&self) -> Option<MachUnwindInfo> {
+        None
+    }
+
     /// Get the spill-slot size.
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32;
 
@@ -0,0 +1,256 @@
+//! A symbolic checker for the result of register allocation.
+//!
+//! The checker tracks which virtual register's value each real register and spill slot holds,
+//! starting from the entry block and keeping, at the start of each block, only what all of its
+//! predecessors agree on. Every virtual register a lowered instruction reads must then be found
+//! in the real register allocated to it. A lowered instruction clobbers every real register it
+//! writes, and leaves the virtual registers it defines in their allocated registers; the spills,
+//! reloads and moves inserted by the allocator copy one location to another.
+
+use crate::machinst::*;
+use crate::result::{CodegenError, CodegenResult};
+use crate::HashMap;
+
+use regalloc::{RealReg, RealRegUniverse, RegAllocResult, VirtualReg};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A location that can hold the value of a virtual register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Location {
+    Reg(RealReg),
+    Slot(u32),
+}
+
+/// The virtual register whose value each location holds, where that is known.
+type State = HashMap<Location, VirtualReg>;
+
+/// Check the instructions that the register allocator produced for `vcode`.
+///
+/// `result` must come from a `RegallocVCode` that recorded allocations.
+pub fn check_regalloc<I: VCodeInst + ShowWithRRU>(
+    vcode: &VCode<I>,
+    result: &RegAllocResult<RegallocVCode<I>>,
+    universe: &RealRegUniverse,
+) -> CodegenResult<()> {
+    let ranges = block_ranges(result.target_map.elems(), result.insns.len());
+
+    // Compute the state at the start of each reachable block.
+    let mut entry_states: Vec<Option<State>> = vec![None; vcode.num_blocks()];
+    entry_states[vcode.entry() as usize] = Some(State::default());
+    let mut worklist = vec![vcode.entry()];
+    while let Some(block) = worklist.pop() {
+        let mut state = entry_states[block as usize].clone().unwrap();
+        let (start, end) = ranges[block as usize];
+        for insn in &result.insns[start..end] {
+            let _ = step(&mut state, insn);
+        }
+        for &succ in vcode.succs(block) {
+            let merged = match &entry_states[succ as usize] {
+                Some(old) => meet(old, &state),
+                None => state.clone(),
+            };
+            if entry_states[succ as usize].as_ref() != Some(&merged) {
+                entry_states[succ as usize] = Some(merged);
+                worklist.push(succ);
+            }
+        }
+    }
+
+    // Then check every instruction against the state before it.
+    for (block, entry_state) in entry_states.into_iter().enumerate() {
+        let mut state = match entry_state {
+            Some(state) => state,
+            None => continue,
+        };
+        let (start, end) = ranges[block];
+        for i in start..end {
+            let insn = &result.insns[i];
+            if let Err((vreg, rreg)) = step(&mut state, insn) {
+                return Err(CodegenError::RegAlloc(format!(
+                    "vcode block {} inst {}: {}: {:?} is not in {}, which holds {:?}",
+                    block,
+                    i,
+                    insn.inst.show_rru(Some(universe)),
+                    vreg,
+                    rreg.to_reg().show_rru(Some(universe)),
+                    state.get(&Location::Reg(rreg)),
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keep the locations that hold the same virtual register in both states.
+fn meet(a: &State, b: &State) -> State {
+    a.iter()
+        .filter(|&(loc, vreg)| b.get(loc) == Some(vreg))
+        .map(|(loc, vreg)| (*loc, *vreg))
+        .collect()
+}
+
+/// Update `state` across `insn`. Fails with the first virtual register that `insn` reads from a
+/// real register that doesn't hold it, leaving `state` untouched.
+fn step<I: VCodeInst>(
+    state: &mut State,
+    insn: &RegallocInst<I>,
+) -> Result<(), (VirtualReg, RealReg)> {
+    match insn.origin {
+        RegallocOrigin::Lowered(_) => {
+            let allocs = insn
+                .allocs
+                .as_ref()
+                .expect("register allocations were not recorded");
+            for &(vreg, rreg) in &allocs.uses {
+                if state.get(&Location::Reg(rreg)) != Some(&vreg) {
+                    return Err((vreg, rreg));
+                }
+            }
+            let regs = insn.inst.get_regs();
+            for reg in regs.defined.iter().chain(regs.modified.iter()) {
+                if let Some(rreg) = reg.to_reg().as_real_reg() {
+                    state.remove(&Location::Reg(rreg));
+                }
+            }
+            for &(vreg, rreg) in &allocs.defs {
+                state.insert(Location::Reg(rreg), vreg);
+            }
+        }
+        RegallocOrigin::Spill { to_slot, from_reg } => copy(
+            state,
+            Location::Reg(from_reg),
+            Location::Slot(to_slot.get()),
+        ),
+        RegallocOrigin::Reload { to_reg, from_slot } => copy(
+            state,
+            Location::Slot(from_slot.get()),
+            Location::Reg(to_reg),
+        ),
+        RegallocOrigin::Move { to_reg, from_reg } => {
+            copy(state, Location::Reg(from_reg), Location::Reg(to_reg))
+        }
+    }
+    Ok(())
+}
+
+fn copy(state: &mut State, from: Location, to: Location) {
+    match state.get(&from).cloned() {
+        Some(vreg) => state.insert(to, vreg),
+        None => state.remove(&to),
+    };
+}
+
+#[cfg(all(test, feature = "arm64"))]
+mod tests {
+    use super::*;
+    use crate::ir::{ExternalName, Function, Signature};
+    use crate::isa::arm64::abi::ARM64ABIBody;
+    use crate::isa::arm64::inst::Inst;
+    use crate::isa::arm64::inst::*;
+    use crate::isa::CallConv;
+    use crate::settings;
+    use alloc::boxed::Box;
+    use regalloc::{allocate_registers, InstIx, RegAllocAlgorithm, RegClass, Writable};
+
+    /// Build VCode with one block per element of `blocks`, the first being the entry.
+    fn vcode(blocks: Vec<Vec<Inst>>) -> VCode<Inst> {
+        let func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::SystemV),
+        );
+        let flags = settings::Flags::new(settings::builder());
+        let mut builder = VCodeBuilder::new(Box::new(ARM64ABIBody::new(&func, flags)));
+        builder.set_entry(0);
+        for insns in blocks {
+            for insn in insns {
+                builder.push(insn);
+            }
+            builder.end_ir_inst();
+            builder.end_bb();
+        }
+        builder.build()
+    }
+
+    fn vreg(index: usize) -> Reg {
+        Reg::new_virtual(RegClass::I64, index as u32)
+    }
+
+    fn add(rd: Reg, rn: Reg, rm: Reg) -> Inst {
+        Inst::AluRRR {
+            alu_op: ALUOp::Add64,
+            rd: Writable::from_reg(rd),
+            rn,
+            rm,
+        }
+    }
+
+    fn movz(rd: Reg, value: u64) -> Inst {
+        Inst::MovZ {
+            rd: Writable::from_reg(rd),
+            imm: MoveWideConst::maybe_from_u64(value).unwrap(),
+        }
+    }
+
+    fn message(result: CodegenResult<()>) -> String {
+        match result {
+            Err(CodegenError::RegAlloc(message)) => message,
+            _ => panic!("expected a register allocation error"),
+        }
+    }
+
+    fn allocate(
+        algorithm: RegAllocAlgorithm,
+    ) -> (VCode<Inst>, RegAllocResult<RegallocVCode<Inst>>) {
+        let vcode = vcode(vec![
+            vec![
+                movz(vreg(0), 1),
+                movz(vreg(1), 2),
+                Inst::Jump {
+                    dest: BranchTarget::Block(1),
+                },
+            ],
+            vec![add(xreg(0), vreg(0), vreg(1)), Inst::Ret {}],
+        ]);
+        let mut regalloc_vcode = RegallocVCode::new(vcode, true);
+        let result =
+            allocate_registers(&mut regalloc_vcode, algorithm, &create_reg_universe()).unwrap();
+        (regalloc_vcode.into_vcode(), result)
+    }
+
+    #[test]
+    fn valid() {
+        let universe = create_reg_universe();
+        for algorithm in vec![
+            RegAllocAlgorithm::Backtracking,
+            RegAllocAlgorithm::LinearScan,
+        ] {
+            let (vcode, result) = allocate(algorithm);
+            assert!(check_regalloc(&vcode, &result, &universe).is_ok());
+        }
+    }
+
+    #[test]
+    fn clobbered() {
+        let universe = create_reg_universe();
+        let (vcode, mut result) = allocate(RegAllocAlgorithm::Backtracking);
+
+        // Overwrite the register allocated to v0 right after its definition.
+        let (v0, r0) = result.insns[0].allocs.as_ref().unwrap().defs[0];
+        let mut clobber = result.insns[1].clone();
+        clobber.inst = movz(r0.to_reg(), 3);
+        clobber.allocs = Some(RegallocAllocs::default());
+        result.insns.insert(1, clobber);
+        let block1 = &mut result.target_map.elems_mut()[1];
+        *block1 = InstIx::new(block1.get() + 1);
+
+        assert!(
+            message(check_regalloc(&vcode, &result, &universe)).ends_with(&format!(
+                "{:?} is not in {}, which holds None",
+                v0,
+                r0.to_reg().show_rru(Some(&universe))
+            ))
+        );
+    }
+}
@@ -2,6 +2,8 @@
 
 use crate::ir::Function;
 use crate::machinst::*;
+use crate::result::{CodegenError, CodegenResult};
+use crate::settings::{self, Regalloc};
 
 use log::debug;
 use regalloc::{allocate_registers, RegAllocAlgorithm};
@@ -12,20 +14,33 @@ pub fn compile<B: LowerBackend>(
     f: &mut Function,
     b: &B,
     abi: Box<dyn ABIBody<B::MInst>>,
-) -> VCode<B::MInst>
+) -> CodegenResult<VCode<B::MInst>>
 where
     B::MInst: ShowWithRRU,
 {
+    let options = RegallocOptions::from_flags(abi.flags());
+    let verify = abi.flags().enable_verifier();
+
     // This lowers the CL IR.
-    let mut vcode = Lower::new(f, abi).lower(b);
+    let vcode = Lower::new(f, abi).lower(b)?;
 
     let universe = &B::MInst::reg_universe();
 
     debug!("vcode from lowering: \n{}", vcode.show_rru(Some(universe)));
 
+    if verify {
+        verify_vcode(&vcode, universe)?;
+    }
+
     // Perform register allocation.
-    let result = allocate_registers(&mut vcode, RegAllocAlgorithm::Backtracking, universe)
-        .expect("register allocation");
+    let mut regalloc_vcode = RegallocVCode::new(vcode, options.run_checker);
+    let result = allocate_registers(&mut regalloc_vcode, options.algorithm(), universe)
+        .map_err(|err| CodegenError::RegAlloc(format!("{:?}", err)))?;
+    let mut vcode = regalloc_vcode.into_vcode();
+
+    if options.run_checker {
+        check_regalloc(&vcode, &result, universe)?;
+    }
 
     // Reorder vcode into final order and copy out final instruction sequence
     // all at once. This also inserts prologues/epilogues.
@@ -33,6 +48,10 @@ where
 
     debug!("vcode after regalloc:\n{}", vcode.show_rru(Some(universe)));
 
+    if verify {
+        verify_vcode_regalloc(&vcode, universe)?;
+    }
+
     vcode.remove_redundant_branches();
 
     debug!(
@@ -47,5 +66,83 @@ where
 
     //println!("{}\n", vcode.show_rru(Some(&B::MInst::reg_universe())));
 
-    vcode
+    Ok(vcode)
+}
+
+/// How to run the register allocator, as selected by the shared settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RegallocOptions {
+    /// The allocator to use.
+    regalloc: Regalloc,
+    /// Check the allocation with `check_regalloc`.
+    run_checker: bool,
+}
+
+impl RegallocOptions {
+    fn from_flags(flags: &settings::Flags) -> Self {
+        Self {
+            regalloc: flags.regalloc(),
+            run_checker: flags.regalloc_checker() || flags.enable_verifier(),
+        }
+    }
+
+    /// The regalloc.rs algorithm implementing these options.
+    fn algorithm(self) -> RegAllocAlgorithm {
+        match self.regalloc {
+            Regalloc::Backtracking => RegAllocAlgorithm::Backtracking,
+            Regalloc::LinearScan => RegAllocAlgorithm::LinearScan,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Configurable;
+
+    fn options(settings: &[(&str, &str)]) -> RegallocOptions {
+        let mut builder = settings::builder();
+        for &(name, value) in settings {
+            builder.set(name, value).unwrap();
+        }
+        RegallocOptions::from_flags(&settings::Flags::new(builder))
+    }
+
+    #[test]
+    fn regalloc_options() {
+        let unchecked = |regalloc| RegallocOptions {
+            regalloc,
+            run_checker: false,
+        };
+        let checked = |regalloc| RegallocOptions {
+            regalloc,
+            run_checker: true,
+        };
+
+        assert_eq!(
+            options(&[("enable_verifier", "false")]),
+            unchecked(Regalloc::Backtracking)
+        );
+        assert_eq!(
+            options(&[("enable_verifier", "false"), ("regalloc", "linear_scan")]),
+            unchecked(Regalloc::LinearScan)
+        );
+
+        // The verifier, which is on by default, runs the checker for both allocators.
+        assert_eq!(options(&[]), checked(Regalloc::Backtracking));
+        assert_eq!(
+            options(&[("regalloc", "linear_scan")]),
+            checked(Regalloc::LinearScan)
+        );
+
+        // So does `regalloc_checker`, without the verifier.
+        assert_eq!(
+            options(&[
+                ("enable_verifier", "false"),
+                ("regalloc", "linear_scan"),
+                ("regalloc_checker", "true"),
+            ]),
+            checked(Regalloc::LinearScan)
+        );
+    }
 }
@@ -6,14 +6,15 @@ use crate::binemit::CodeSink;
 use crate::dce::has_side_effect;
 use crate::entity::SecondaryMap;
 use crate::ir::{
-    Block, ExternalName, Function, GlobalValueData, Inst, InstructionData, Opcode, Signature, Type,
-    Value, ValueDef,
+    Block, ExternalName, Function, GlobalValueData, Inst, InstructionData, Opcode, Signature,
+    SourceLoc, Type, Value, ValueDef,
 };
 use crate::isa::registers::RegUnit;
 use crate::machinst::{
     ABIBody, BlockIndex, MachInst, MachInstEmit, VCode, VCodeBuilder, VCodeInst,
 };
 use crate::num_uses::NumUses;
+use crate::result::CodegenResult;
 
 use regalloc::Function as RegallocFunction;
 use regalloc::{RealReg, Reg, RegClass, VirtualReg, Writable};
@@ -72,6 +73,9 @@ pub trait LowerCtx<I> {
     fn call_sig<'b>(&'b self, ir_inst: Inst) -> Option<&'b Signature>;
     /// Get the symbol name and offset for a symbol_value instruction.
     fn symbol_value<'b>(&'b self, ir_inst: Inst) -> Option<(&'b ExternalName, i64)>;
+    /// Get the source location of an IR instruction, to record with the trap
+    /// sites of the machine instructions it is lowered to.
+    fn srcloc(&self, ir_inst: Inst) -> SourceLoc;
 }
 
 /// A machine backend.
@@ -81,8 +85,8 @@ pub trait LowerBackend {
 
     /// Lower a single instruction. Instructions are lowered in reverse order.
     /// This function need not handle branches; those are always passed to
-    /// `lower_branch_group` below.
-    fn lower<C: LowerCtx<Self::MInst>>(&self, ctx: &mut C, inst: Inst);
+    /// `lower_branch_group` below. Fails if the instruction can't be lowered for this backend.
+    fn lower<C: LowerCtx<Self::MInst>>(&self, ctx: &mut C, inst: Inst) -> CodegenResult<()>;
 
     /// Lower a block-terminating group of branches (which together can be seen as one
     /// N-way branch), given a vcode BlockIndex for each target.
@@ -214,7 +218,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
     }
 
     /// Lower the function.
-    pub fn lower<B: LowerBackend<MInst = I>>(mut self, backend: &B) -> VCode<I> {
+    pub fn lower<B: LowerBackend<MInst = I>>(mut self, backend: &B) -> CodegenResult<VCode<I>> {
         // Work backward (reverse block order, reverse through each block), skipping insns with zero
         // uses.
         let mut bbs: SmallVec<[Block; 16]> = self.f.layout.blocks().collect();
@@ -263,6 +267,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
             // If this is a return block, produce the return value setup.
             let last_insn = self.f.layout.block_insts(*bb).last().unwrap();
             if self.f.dfg[last_insn].opcode().is_return() {
+                self.vcode.set_srcloc(self.f.srclocs[last_insn]);
                 self.gen_retval_setup();
                 self.vcode.end_ir_inst();
             }
@@ -283,6 +288,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
                         let fallthrough = fallthrough.map(|bb| self.vcode.bb_to_bindex(bb));
                         branches.reverse();
                         targets.reverse();
+                        self.vcode.set_srcloc(self.f.srclocs[branches[0]]);
                         backend.lower_branch_group(
                             &mut self,
                             &branches[..],
@@ -299,7 +305,8 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
                     let num_uses = self.num_uses[inst];
                     let side_effect = has_side_effect(self.f, inst);
                     if side_effect || num_uses > 0 {
-                        backend.lower(&mut self, inst);
+                        self.vcode.set_srcloc(self.f.srclocs[inst]);
+                        backend.lower(&mut self, inst)?;
                         self.vcode.end_ir_inst();
                     } else {
                         // If we're skipping the instruction, we need to dec-ref
@@ -322,6 +329,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
                 let fallthrough = fallthrough.map(|bb| self.vcode.bb_to_bindex(bb));
                 branches.reverse();
                 targets.reverse();
+                self.vcode.set_srcloc(self.f.srclocs[branches[0]]);
                 backend.lower_branch_group(&mut self, &branches[..], &targets[..], fallthrough);
                 self.vcode.end_ir_inst();
                 branches.clear();
@@ -387,14 +395,16 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
                 .push(I::gen_jump(self.vcode.bb_to_bindex(orig_block)));
 
             // End the IR inst and block. (We lower this as if it were one IR instruction so that
-            // we can emit machine instructions in forward order.)
+            // we can emit machine instructions in forward order.) The copies and the jump belong
+            // to the branch that created the edge.
+            self.vcode.set_srcloc(self.f.srclocs[inst]);
             self.vcode.end_ir_inst();
             let blocknum = self.vcode.end_bb();
             assert!(blocknum == edge_block);
         }
 
         // Now that we've emitted all instructions into the VCodeBuilder, let's build the VCode.
-        self.vcode.build()
+        Ok(self.vcode.build())
     }
 
     /// Reduce the use-count of an IR instruction. Use this when, e.g., isel incorporates the
@@ -578,6 +588,11 @@ impl<'a, I: VCodeInst> LowerCtx<I> for Lower<'a, I> {
             _ => None,
         }
     }
+
+    /// Get the source location of an IR instruction.
+    fn srcloc(&self, ir_inst: Inst) -> SourceLoc {
+        self.f.srclocs[ir_inst]
+    }
 }
 
 fn branch_target(inst: &InstructionData) -> Option<Block> {
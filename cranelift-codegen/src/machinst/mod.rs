@@ -97,7 +97,8 @@
 #![allow(unused_imports)]
 
 use crate::binemit::{
-    CodeInfo, CodeOffset, CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink,
+    CodeInfo, CodeOffset, CodeSink, FrameUnwindKind, MemoryCodeSink, RelocSink, StackmapSink,
+    TrapSink,
 };
 use crate::entity::EntityRef;
 use crate::entity::SecondaryMap;
@@ -136,6 +137,10 @@ pub mod sections;
 pub use sections::*;
 pub mod adapter;
 pub use adapter::*;
+pub mod verifier;
+pub use verifier::*;
+pub mod checker;
+pub use checker::*;
 
 /// A machine instruction.
 pub trait MachInst: Clone + Debug {
@@ -158,6 +163,12 @@ pub trait MachInst: Clone + Debug {
     /// (ret/uncond/cond) and target if applicable.
     fn is_term(&self) -> MachTerminator;
 
+    /// Does this instruction trap unconditionally? A block may end in such an
+    /// instruction rather than in a terminator.
+    fn is_trap(&self) -> bool {
+        false
+    }
+
     /// Generate a move.
     fn gen_move(to_reg: Writable<Reg>, from_reg: Reg) -> Self;
 
@@ -230,13 +241,27 @@ pub trait MachInstEmit<O: MachSectionOutput> {
     fn emit(&self, code: &mut O, consts: &mut O);
 }
 
+/// Unwind information for a compiled function, already encoded in the format
+/// given by `kind`.
+#[derive(Clone, Debug)]
+pub struct MachUnwindInfo {
+    /// The format of `data`.
+    pub kind: FrameUnwindKind,
+    /// The encoded unwind information.
+    pub data: Vec<u8>,
+}
+
 /// The result of a `MachBackend::compile_function()` call. Contains machine
 /// code (as bytes) and a disassembly, if requested.
 pub struct MachCompileResult {
     /// Machine code.
     pub sections: MachSections,
-    /// Disassembly, if requested.
+    /// Disassembly, if requested. This lists the final instructions with their
+    /// offsets, machine code bytes, relocations, trap sites and source
+    /// locations.
     pub disasm: Option<String>,
+    /// Unwind information, if the function's calling convention needs any.
+    pub unwind_info: Option<MachUnwindInfo>,
 }
 
 impl MachCompileResult {
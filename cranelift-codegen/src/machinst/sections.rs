@@ -4,7 +4,7 @@
 //! caller at the end of compilation.
 
 use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc, RelocSink, StackmapSink, TrapSink};
-use crate::ir::ExternalName;
+use crate::ir::{ExternalName, SourceLoc, TrapCode};
 
 use alloc::vec::Vec;
 
@@ -106,6 +106,9 @@ pub trait MachSectionOutput {
     /// Add a relocation at the current offset.
     fn add_reloc(&mut self, kind: Reloc, name: &ExternalName, addend: Addend);
 
+    /// Add a trap site at the current offset.
+    fn add_trap(&mut self, srcloc: SourceLoc, code: TrapCode);
+
     /// Align up to the given alignment.
     fn align_to(&mut self, align_to: CodeOffset) {
         assert!(align_to.is_power_of_two());
@@ -129,6 +132,8 @@ pub struct MachSection {
     pub data: Vec<u8>,
     /// Any relocations referring to this section.
     pub relocs: Vec<MachReloc>,
+    /// Any trap sites in this section.
+    pub traps: Vec<MachTrap>,
 }
 
 impl MachSection {
@@ -139,6 +144,7 @@ impl MachSection {
             length_limit,
             data: vec![],
             relocs: vec![],
+            traps: vec![],
         }
     }
 
@@ -149,6 +155,7 @@ impl MachSection {
         assert!(sink.offset() == self.start_offset);
 
         let mut next_reloc = 0;
+        let mut next_trap = 0;
         for (idx, byte) in self.data.iter().enumerate() {
             if next_reloc < self.relocs.len() {
                 let reloc = &self.relocs[next_reloc];
@@ -157,6 +164,12 @@ impl MachSection {
                     next_reloc += 1;
                 }
             }
+            while next_trap < self.traps.len() && self.traps[next_trap].offset == idx as CodeOffset
+            {
+                let trap = &self.traps[next_trap];
+                sink.trap(trap.code, trap.srcloc);
+                next_trap += 1;
+            }
             sink.put1(*byte);
         }
     }
@@ -186,6 +199,14 @@ impl MachSectionOutput for MachSection {
             addend,
         });
     }
+
+    fn add_trap(&mut self, srcloc: SourceLoc, code: TrapCode) {
+        self.traps.push(MachTrap {
+            offset: self.data.len() as CodeOffset,
+            srcloc,
+            code,
+        });
+    }
 }
 
 /// A MachSectionOutput implementation that records only size.
@@ -227,6 +248,8 @@ impl MachSectionOutput for MachSectionSize {
     }
 
     fn add_reloc(&mut self, _: Reloc, _: &ExternalName, _: Addend) {}
+
+    fn add_trap(&mut self, _: SourceLoc, _: TrapCode) {}
 }
 
 /// A relocation resulting from a compilation.
@@ -241,3 +264,14 @@ pub struct MachReloc {
     /// The addend to add to the symbol value.
     pub addend: i64,
 }
+
+/// A trap site resulting from a compilation.
+pub struct MachTrap {
+    /// The offset at which the trap instruction occurs, *relative to the
+    /// containing section*.
+    pub offset: CodeOffset,
+    /// The original source location.
+    pub srcloc: SourceLoc,
+    /// The trap code.
+    pub code: TrapCode,
+}
@@ -18,7 +18,7 @@
 //! backend pipeline.
 
 use crate::binemit::Reloc;
-use crate::ir;
+use crate::ir::{self, SourceLoc};
 use crate::machinst::*;
 
 use regalloc::Function as RegallocFunction;
@@ -60,6 +60,10 @@ pub struct VCode<I: VCodeInst> {
     /// Lowered machine instructions in order corresponding to the original IR.
     insts: Vec<I>,
 
+    /// Source location of each instruction in `insts`. Instructions that don't come from an IR
+    /// instruction, such as the prologue or register allocator spills, have the default location.
+    srclocs: Vec<SourceLoc>,
+
     /// Entry block.
     entry: BlockIndex,
 
@@ -115,13 +119,16 @@ pub struct VCodeBuilder<I: VCodeInst> {
     /// In-progress VCode.
     vcode: VCode<I>,
 
-    /// Current basic block instructions, in reverse order (because blocks are
-    /// built bottom-to-top).
-    bb_insns: SmallVec<[I; 32]>,
+    /// Current basic block instructions and their source locations, in reverse
+    /// order (because blocks are built bottom-to-top).
+    bb_insns: SmallVec<[(I, SourceLoc); 32]>,
 
     /// Current IR-inst instructions, in forward order.
     ir_inst_insns: SmallVec<[I; 4]>,
 
+    /// Source location of the current IR inst.
+    cur_srcloc: SourceLoc,
+
     /// Start of succs for the current block in the concatenated succs list.
     succ_start: usize,
 }
@@ -134,6 +141,7 @@ impl<I: VCodeInst> VCodeBuilder<I> {
             vcode,
             bb_insns: SmallVec::new(),
             ir_inst_insns: SmallVec::new(),
+            cur_srcloc: SourceLoc::default(),
             succ_start: 0,
         }
     }
@@ -177,12 +185,19 @@ impl<I: VCodeInst> VCodeBuilder<I> {
         self.vcode.entry = block;
     }
 
+    /// Set the source location of the instructions pushed for the current IR
+    /// instruction. This is reset when the IR instruction ends.
+    pub fn set_srcloc(&mut self, srcloc: SourceLoc) {
+        self.cur_srcloc = srcloc;
+    }
+
     /// End the current IR instruction. Must be called after pushing any
     /// instructions and prior to ending the basic block.
     pub fn end_ir_inst(&mut self) {
         while let Some(i) = self.ir_inst_insns.pop() {
-            self.bb_insns.push(i);
+            self.bb_insns.push((i, self.cur_srcloc));
         }
+        self.cur_srcloc = SourceLoc::default();
     }
 
     /// End the current basic block. Must be called after emitting vcode insts
@@ -192,8 +207,9 @@ impl<I: VCodeInst> VCodeBuilder<I> {
         let block_num = self.vcode.block_ranges.len() as BlockIndex;
         // Push the instructions.
         let start_idx = self.vcode.insts.len() as InsnIndex;
-        while let Some(i) = self.bb_insns.pop() {
+        while let Some((i, srcloc)) = self.bb_insns.pop() {
             self.vcode.insts.push(i);
+            self.vcode.srclocs.push(srcloc);
         }
         let end_idx = self.vcode.insts.len() as InsnIndex;
         // Add the instruction index range to the list of blocks.
@@ -231,7 +247,7 @@ impl<I: VCodeInst> VCodeBuilder<I> {
     }
 }
 
-fn block_ranges(indices: &[InstIx], len: usize) -> Vec<(usize, usize)> {
+pub(crate) fn block_ranges(indices: &[InstIx], len: usize) -> Vec<(usize, usize)> {
     let v = indices
         .iter()
         .map(|iix| iix.get() as usize)
@@ -290,6 +306,7 @@ impl<I: VCodeInst> VCode<I> {
             liveouts: abi.liveouts(),
             vreg_types: vec![],
             insts: vec![],
+            srclocs: vec![],
             entry: 0,
             block_ranges: vec![],
             block_succ_range: vec![],
@@ -326,10 +343,17 @@ impl<I: VCodeInst> VCode<I> {
         &self.block_succs[start..end]
     }
 
+    /// Get the unwind information for the function's frame, if its ABI needs
+    /// any. Only available once the prologue has been generated, after
+    /// register allocation.
+    pub fn unwind_info(&self) -> Option<MachUnwindInfo> {
+        self.abi.unwind_info()
+    }
+
     /// Take the results of register allocation, with a sequence of
     /// instructions including spliced fill/reload/move instructions, and replace
     /// the VCode with them.
-    pub fn replace_insns_from_regalloc(&mut self, result: RegAllocResult<Self>) {
+    pub fn replace_insns_from_regalloc(&mut self, result: RegAllocResult<RegallocVCode<I>>) {
         self.final_block_order = compute_final_block_order(self);
 
         // Record the spillslot count and clobbered registers for the ABI/stack
@@ -343,6 +367,7 @@ impl<I: VCodeInst> VCode<I> {
         let block_ranges: Vec<(usize, usize)> =
             block_ranges(result.target_map.elems(), result.insns.len());
         let mut final_insns = vec![];
+        let mut final_srclocs = vec![];
         let mut final_block_ranges = vec![(0, 0); self.num_blocks()];
 
         for block in &self.final_block_order {
@@ -352,10 +377,11 @@ impl<I: VCodeInst> VCode<I> {
             if *block == self.entry {
                 // Start with the prologue.
                 final_insns.extend(self.abi.gen_prologue().into_iter());
+                final_srclocs.resize(final_insns.len(), SourceLoc::default());
             }
 
             for i in start..end {
-                let insn = &result.insns[i];
+                let insn = &result.insns[i].inst;
 
                 // Elide redundant moves at this point (we only know what is
                 // redundant once registers are allocated).
@@ -363,6 +389,13 @@ impl<I: VCodeInst> VCode<I> {
                     continue;
                 }
 
+                // Instructions inserted by the register allocator get the
+                // default source location.
+                let srcloc = match result.insns[i].origin {
+                    RegallocOrigin::Lowered(iix) => self.srclocs[iix.get() as usize],
+                    _ => SourceLoc::default(),
+                };
+
                 // Whenever encountering a return instruction, replace it
                 // with the epilogue.
                 let is_ret = insn.is_term() == MachTerminator::Ret;
@@ -371,12 +404,17 @@ impl<I: VCodeInst> VCode<I> {
                 } else {
                     final_insns.push(insn.clone());
                 }
+
+                // The epilogue gets the source location of the return it
+                // replaces.
+                final_srclocs.resize(final_insns.len(), srcloc);
             }
             let final_end = final_insns.len() as InsnIndex;
             final_block_ranges[*block as usize] = (final_start, final_end);
         }
 
         self.insts = final_insns;
+        self.srclocs = final_srclocs;
         self.block_ranges = final_block_ranges;
     }
 
@@ -506,6 +544,112 @@ impl<I: VCodeInst> VCode<I> {
 
         sections
     }
+
+    /// Produce a listing of the final machine code, with the offset and bytes
+    /// of each instruction and the relocations, trap sites and source location
+    /// that belong to it.
+    ///
+    /// Each block starts with a `Block N:` line, followed by one line per
+    /// instruction in the same format as the listing `clif-util` prints for
+    /// the other backends:
+    ///
+    /// ```text
+    ///   0008: 40 00 00 b5 00 00 00 00  cbnz x0, 8 ; udf  ; trap heap_oob  ; @0010
+    /// ```
+    ///
+    /// The annotations are `; reloc KIND NAME+ADDEND`, `; trap CODE` and the
+    /// source location, in that order. The listing ends with the size and
+    /// offset of the constant pool, if there is one.
+    pub fn show_disasm(&self, mb_rru: Option<&RealRegUniverse>) -> String
+    where
+        I: MachInstEmit<MachSection> + ShowWithRRU,
+    {
+        // Emit the code again, in the same way as `emit`, to find out which
+        // relocations and traps each instruction produces.
+        let mut sections = MachSections::new();
+        let code_idx = sections.add_section(0, self.code_size);
+        let const_idx = sections.add_section(self.constants_start, self.constants_size);
+        let (code_section, const_section) = sections.two_sections(code_idx, const_idx);
+
+        let mut s = String::new();
+        for block in &self.final_block_order {
+            let new_offset = I::align_basic_block(code_section.cur_offset_from_start());
+            while new_offset > code_section.cur_offset_from_start() {
+                let nop = I::gen_nop((new_offset - code_section.cur_offset_from_start()) as usize);
+                show_emitted(
+                    &mut s,
+                    &nop,
+                    SourceLoc::default(),
+                    code_section,
+                    const_section,
+                    mb_rru,
+                );
+            }
+
+            s.push_str(&format!("Block {}:\n", block));
+            let (start, end) = self.block_ranges[*block as usize];
+            for iix in start..end {
+                let iix = iix as usize;
+                show_emitted(
+                    &mut s,
+                    &self.insts[iix],
+                    self.srclocs[iix],
+                    code_section,
+                    const_section,
+                    mb_rru,
+                );
+            }
+        }
+
+        if self.constants_size > 0 {
+            s.push_str(&format!(
+                "Constant pool: {} bytes at {:04x}\n",
+                self.constants_size, self.constants_start
+            ));
+        }
+        s
+    }
+}
+
+/// Emit `insn` and append a line to `s` showing its offset, its bytes, the
+/// instruction and its annotations.
+fn show_emitted<I: MachInstEmit<MachSection> + ShowWithRRU>(
+    s: &mut String,
+    insn: &I,
+    srcloc: SourceLoc,
+    code_section: &mut MachSection,
+    const_section: &mut MachSection,
+    mb_rru: Option<&RealRegUniverse>,
+) {
+    let offset = code_section.cur_offset_from_start();
+    let num_relocs = code_section.relocs.len();
+    let num_traps = code_section.traps.len();
+    insn.emit(code_section, const_section);
+
+    let bytes = code_section.data[(offset - code_section.start_offset) as usize..]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    s.push_str(&format!(
+        "  {:04x}: {:<24} {}",
+        offset,
+        bytes,
+        insn.show_rru(mb_rru)
+    ));
+    for reloc in &code_section.relocs[num_relocs..] {
+        s.push_str(&format!(
+            "  ; reloc {} {}{:+}",
+            reloc.kind, reloc.name, reloc.addend
+        ));
+    }
+    for trap in &code_section.traps[num_traps..] {
+        s.push_str(&format!("  ; trap {}", trap.code));
+    }
+    if !srcloc.is_default() {
+        s.push_str(&format!("  ; {}", srcloc));
+    }
+    s.push('\n');
 }
 
 impl<I: VCodeInst> RegallocFunction for VCode<I> {
@@ -604,6 +748,242 @@ impl<I: VCodeInst> RegallocFunction for VCode<I> {
     }
 }
 
+/// An instruction as seen by the register allocator, tagged with where it comes from.
+#[derive(Clone)]
+pub struct RegallocInst<I: VCodeInst> {
+    /// The instruction, with registers allocated once the register allocator is done.
+    pub inst: I,
+    /// Where the instruction comes from.
+    pub origin: RegallocOrigin,
+    /// The real registers allocated to the virtual registers of a lowered instruction, if the
+    /// allocation is to be checked.
+    pub allocs: Option<RegallocAllocs>,
+}
+
+/// Where an instruction seen by the register allocator comes from.
+#[derive(Clone)]
+pub enum RegallocOrigin {
+    /// The instruction at this index in the lowered VCode.
+    Lowered(InstIx),
+    /// A spill inserted by the register allocator.
+    Spill {
+        /// The spill slot written.
+        to_slot: SpillSlot,
+        /// The register read.
+        from_reg: RealReg,
+    },
+    /// A reload inserted by the register allocator.
+    Reload {
+        /// The register written.
+        to_reg: RealReg,
+        /// The spill slot read.
+        from_slot: SpillSlot,
+    },
+    /// A move inserted by the register allocator.
+    Move {
+        /// The register written.
+        to_reg: RealReg,
+        /// The register read.
+        from_reg: RealReg,
+    },
+}
+
+/// The real registers allocated to the virtual registers of an instruction.
+#[derive(Clone, Default)]
+pub struct RegallocAllocs {
+    /// The virtual registers read or modified, and their registers before the instruction.
+    pub uses: Vec<(VirtualReg, RealReg)>,
+    /// The virtual registers written or modified, and their registers after the instruction.
+    pub defs: Vec<(VirtualReg, RealReg)>,
+}
+
+impl<I: VCodeInst> RegallocInst<I> {
+    fn inserted(inst: I, origin: RegallocOrigin) -> Self {
+        Self {
+            inst,
+            origin,
+            allocs: None,
+        }
+    }
+}
+
+/// The VCode handed to the register allocator.
+///
+/// The allocator rewrites the registers of a tagged copy of the instructions, so that every
+/// instruction of its result can be traced back to the lowered instruction it came from, if any.
+pub struct RegallocVCode<I: VCodeInst> {
+    vcode: VCode<I>,
+    insts: Vec<RegallocInst<I>>,
+}
+
+impl<I: VCodeInst> RegallocVCode<I> {
+    /// Prepare `vcode` for register allocation. With `record_allocs`, the registers allocated to
+    /// each lowered instruction are recorded, for `check_regalloc`.
+    pub fn new(vcode: VCode<I>, record_allocs: bool) -> Self {
+        let insts = vcode
+            .insts
+            .iter()
+            .enumerate()
+            .map(|(i, inst)| RegallocInst {
+                inst: inst.clone(),
+                origin: RegallocOrigin::Lowered(InstIx::new(i as u32)),
+                allocs: if record_allocs {
+                    Some(RegallocAllocs::default())
+                } else {
+                    None
+                },
+            })
+            .collect();
+        Self { vcode, insts }
+    }
+
+    /// Get back the lowered VCode, without allocated registers.
+    pub fn into_vcode(self) -> VCode<I> {
+        self.vcode
+    }
+}
+
+impl<I: VCodeInst> RegallocFunction for RegallocVCode<I> {
+    type Inst = RegallocInst<I>;
+
+    fn insns(&self) -> &[RegallocInst<I>] {
+        &self.insts[..]
+    }
+
+    fn insns_mut(&mut self) -> &mut [RegallocInst<I>] {
+        &mut self.insts[..]
+    }
+
+    fn get_insn(&self, insn: InstIx) -> &RegallocInst<I> {
+        &self.insts[insn.get() as usize]
+    }
+
+    fn get_insn_mut(&mut self, insn: InstIx) -> &mut RegallocInst<I> {
+        &mut self.insts[insn.get() as usize]
+    }
+
+    fn blocks(&self) -> MyRange<BlockIx> {
+        self.vcode.blocks()
+    }
+
+    fn entry_block(&self) -> BlockIx {
+        self.vcode.entry_block()
+    }
+
+    fn block_insns(&self, block: BlockIx) -> MyRange<InstIx> {
+        self.vcode.block_insns(block)
+    }
+
+    fn block_succs(&self, block: BlockIx) -> Vec<BlockIx> {
+        self.vcode.block_succs(block)
+    }
+
+    fn is_ret(&self, insn: InstIx) -> bool {
+        self.vcode.is_ret(insn)
+    }
+
+    fn get_regs(&self, insn: &RegallocInst<I>) -> InstRegUses {
+        insn.inst.get_regs()
+    }
+
+    fn map_regs(
+        insn: &mut RegallocInst<I>,
+        pre_map: &RegallocMap<VirtualReg, RealReg>,
+        post_map: &RegallocMap<VirtualReg, RealReg>,
+    ) {
+        if let Some(allocs) = &mut insn.allocs {
+            let allocated = |reg: Reg, map: &RegallocMap<VirtualReg, RealReg>| {
+                let vreg = reg.as_virtual_reg()?;
+                map.get(&vreg).map(|rreg| (vreg, *rreg))
+            };
+            let regs = insn.inst.get_regs();
+            let mods = || regs.modified.iter().map(|r| r.to_reg());
+            let uses = regs.used.iter().cloned().chain(mods());
+            let defs = regs.defined.iter().map(|r| r.to_reg()).chain(mods());
+            allocs
+                .uses
+                .extend(uses.filter_map(|reg| allocated(reg, pre_map)));
+            allocs
+                .defs
+                .extend(defs.filter_map(|reg| allocated(reg, post_map)));
+        }
+        insn.inst.map_regs(pre_map, post_map);
+    }
+
+    fn is_move(&self, insn: &RegallocInst<I>) -> Option<(Writable<Reg>, Reg)> {
+        insn.inst.is_move()
+    }
+
+    fn get_spillslot_size(&self, regclass: RegClass, vreg: VirtualReg) -> u32 {
+        self.vcode.get_spillslot_size(regclass, vreg)
+    }
+
+    fn gen_spill(
+        &self,
+        to_slot: SpillSlot,
+        from_reg: RealReg,
+        vreg: VirtualReg,
+    ) -> RegallocInst<I> {
+        RegallocInst::inserted(
+            self.vcode.gen_spill(to_slot, from_reg, vreg),
+            RegallocOrigin::Spill { to_slot, from_reg },
+        )
+    }
+
+    fn gen_reload(
+        &self,
+        to_reg: Writable<RealReg>,
+        from_slot: SpillSlot,
+        vreg: VirtualReg,
+    ) -> RegallocInst<I> {
+        RegallocInst::inserted(
+            self.vcode.gen_reload(to_reg, from_slot, vreg),
+            RegallocOrigin::Reload {
+                to_reg: to_reg.to_reg(),
+                from_slot,
+            },
+        )
+    }
+
+    fn gen_move(
+        &self,
+        to_reg: Writable<RealReg>,
+        from_reg: RealReg,
+        vreg: VirtualReg,
+    ) -> RegallocInst<I> {
+        RegallocInst::inserted(
+            self.vcode.gen_move(to_reg, from_reg, vreg),
+            RegallocOrigin::Move {
+                to_reg: to_reg.to_reg(),
+                from_reg,
+            },
+        )
+    }
+
+    fn maybe_direct_reload(
+        &self,
+        insn: &RegallocInst<I>,
+        reg: VirtualReg,
+        slot: SpillSlot,
+    ) -> Option<RegallocInst<I>> {
+        // The reloading instruction replaces the original one.
+        let inst = insn.inst.maybe_direct_reload(reg, slot)?;
+        Some(RegallocInst {
+            inst,
+            origin: insn.origin.clone(),
+            allocs: insn.allocs.clone(),
+        })
+    }
+
+    fn func_liveins(&self) -> RegallocSet<RealReg> {
+        self.vcode.func_liveins()
+    }
+
+    fn func_liveouts(&self) -> RegallocSet<RealReg> {
+        self.vcode.func_liveouts()
+    }
+}
+
 // N.B.: Debug impl assumes that VCode has already been through all compilation
 // passes, and so has a final block order and offsets.
 
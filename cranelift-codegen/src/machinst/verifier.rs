@@ -0,0 +1,446 @@
+//! A verifier for VCode.
+//!
+//! After lowering, it checks:
+//!
+//! - Every reachable block ends in a terminator or a trap, and the successors recorded for each
+//!   block are exactly the targets of its terminators, in order.
+//! - Every virtual register is defined before it is used, along all paths from the entry block.
+//! - `get_regs` and `map_regs` agree: mapping the registers reported by `get_regs` rewrites all the
+//!   virtual registers of an instruction, using the pre-instruction map for uses and the
+//!   post-instruction map for defs.
+//!
+//! After register allocation, it checks the block structure again, and that no virtual registers
+//! remain.
+
+use crate::ir::entities::AnyEntity;
+use crate::machinst::*;
+use crate::verifier::{VerifierErrors, VerifierResult};
+use crate::HashSet;
+
+use regalloc::Function as RegallocFunction;
+use regalloc::{BlockIx, InstIx, RealReg, RealRegUniverse, Reg, VirtualReg};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Verify `vcode` as produced by lowering, before register allocation.
+pub fn verify_vcode<I: VCodeInst + ShowWithRRU>(
+    vcode: &VCode<I>,
+    universe: &RealRegUniverse,
+) -> VerifierResult<()> {
+    let mut errors = VerifierErrors::default();
+    verify_blocks(vcode, universe, &mut errors);
+    verify_defs_before_uses(vcode, universe, &mut errors);
+    for insn in vcode.insns() {
+        verify_map_regs(insn, universe, &mut errors);
+    }
+    errors.as_result().map_err(|()| errors)
+}
+
+/// Verify `vcode` after its registers have been allocated.
+pub fn verify_vcode_regalloc<I: VCodeInst + ShowWithRRU>(
+    vcode: &VCode<I>,
+    universe: &RealRegUniverse,
+) -> VerifierResult<()> {
+    let mut errors = VerifierErrors::default();
+    verify_blocks(vcode, universe, &mut errors);
+    for insn in vcode.insns() {
+        let regs = insn.get_regs();
+        let all = regs
+            .used
+            .iter()
+            .cloned()
+            .chain(regs.defined.iter().map(|r| r.to_reg()))
+            .chain(regs.modified.iter().map(|r| r.to_reg()));
+        for reg in all {
+            if reg.is_virtual() {
+                errors.report((
+                    AnyEntity::Function,
+                    insn.show_rru(Some(universe)),
+                    format!("virtual register {:?} left after register allocation", reg),
+                ));
+            }
+        }
+    }
+    errors.as_result().map_err(|()| errors)
+}
+
+/// Check that blocks end in terminators which agree with the recorded successors.
+///
+/// Only blocks reachable from the entry are checked. Register allocation drops the others.
+fn verify_blocks<I: VCodeInst + ShowWithRRU>(
+    vcode: &VCode<I>,
+    universe: &RealRegUniverse,
+    errors: &mut VerifierErrors,
+) {
+    let num_blocks = vcode.num_blocks() as BlockIndex;
+    let mut reachable = vec![false; num_blocks as usize];
+    let mut stack = vec![vcode.entry()];
+    while let Some(block) = stack.pop() {
+        if block >= num_blocks || reachable[block as usize] {
+            continue;
+        }
+        reachable[block as usize] = true;
+        stack.extend(vcode.succs(block));
+    }
+
+    for block in (0..num_blocks).filter(|&block| reachable[block as usize]) {
+        let insns = vcode.block_insns(BlockIx::new(block));
+        if insns.len() == 0 {
+            errors.report((
+                AnyEntity::Function,
+                format!("vcode block {} is empty", block),
+            ));
+            continue;
+        }
+
+        let mut targets = Vec::new();
+        for iix in insns {
+            let insn = vcode.get_insn(iix);
+            let insn_targets = match insn.is_term() {
+                MachTerminator::None | MachTerminator::Ret => vec![],
+                MachTerminator::Uncond(target) => vec![target],
+                MachTerminator::Cond(taken, not_taken) => vec![taken, not_taken],
+            };
+            if insn_targets.iter().any(|&target| target >= num_blocks) {
+                errors.report((
+                    AnyEntity::Function,
+                    context(universe, block, iix, insn),
+                    "branch to a nonexistent block",
+                ));
+            }
+            targets.extend(insn_targets);
+        }
+
+        let last = InstIx::new(insns.first().get() + insns.len() as u32 - 1);
+        let last_insn = vcode.get_insn(last);
+        if last_insn.is_term() == MachTerminator::None && !last_insn.is_trap() {
+            errors.report((
+                AnyEntity::Function,
+                context(universe, block, last, last_insn),
+                "block does not end in a terminator",
+            ));
+        }
+        if targets[..] != vcode.succs(block)[..] {
+            errors.report((
+                AnyEntity::Function,
+                format!("vcode block {}", block),
+                format!(
+                    "successors {:?} don't match the branch targets {:?}",
+                    vcode.succs(block),
+                    targets
+                ),
+            ));
+        }
+    }
+}
+
+/// Check that every virtual register is defined along all paths to its uses.
+fn verify_defs_before_uses<I: VCodeInst + ShowWithRRU>(
+    vcode: &VCode<I>,
+    universe: &RealRegUniverse,
+    errors: &mut VerifierErrors,
+) {
+    // The registers defined on all paths to the start of each block. `None` means no path has
+    // been seen yet, so the block is unreachable once the analysis is done.
+    let num_blocks = vcode.num_blocks();
+    let mut defined_in: Vec<Option<HashSet<VirtualReg>>> = vec![None; num_blocks];
+    defined_in[vcode.entry() as usize] = Some(HashSet::new());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in 0..num_blocks {
+            let mut defined = match defined_in[block] {
+                Some(ref defined) => defined.clone(),
+                None => continue,
+            };
+            for iix in vcode.block_insns(BlockIx::new(block as BlockIndex)) {
+                let regs = vcode.get_insn(iix).get_regs();
+                let defs = regs.defined.iter().chain(regs.modified.iter());
+                defined.extend(defs.filter_map(|r| r.to_reg().as_virtual_reg()));
+            }
+            // Branches to nonexistent blocks are reported by `verify_blocks`.
+            let succs = vcode.succs(block as BlockIndex).iter();
+            for &succ in succs.filter(|&&succ| (succ as usize) < num_blocks) {
+                let succ_in = &mut defined_in[succ as usize];
+                let new_in = match *succ_in {
+                    None => defined.clone(),
+                    Some(ref old) => old.intersection(&defined).cloned().collect(),
+                };
+                if succ_in.as_ref() != Some(&new_in) {
+                    *succ_in = Some(new_in);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for block in 0..num_blocks {
+        let mut defined = match defined_in[block] {
+            Some(ref defined) => defined.clone(),
+            None => continue,
+        };
+        for iix in vcode.block_insns(BlockIx::new(block as BlockIndex)) {
+            let insn = vcode.get_insn(iix);
+            let regs = insn.get_regs();
+            let uses = regs
+                .used
+                .iter()
+                .cloned()
+                .chain(regs.modified.iter().map(|r| r.to_reg()));
+            for vreg in uses.filter_map(|r| r.as_virtual_reg()) {
+                if !defined.contains(&vreg) {
+                    errors.report((
+                        AnyEntity::Function,
+                        context(universe, block as BlockIndex, iix, insn),
+                        format!("{:?} is used before it is defined", vreg),
+                    ));
+                }
+            }
+            let defs = regs.defined.iter().chain(regs.modified.iter());
+            defined.extend(defs.filter_map(|r| r.to_reg().as_virtual_reg()));
+        }
+    }
+}
+
+/// Check that `map_regs` rewrites exactly the virtual registers that `get_regs` reports.
+fn verify_map_regs<I: VCodeInst + ShowWithRRU>(
+    insn: &I,
+    universe: &RealRegUniverse,
+    errors: &mut VerifierErrors,
+) {
+    let regs = insn.get_regs();
+    let used: Vec<Reg> = regs.used.iter().cloned().collect();
+    let defined: Vec<Reg> = regs.defined.iter().map(|r| r.to_reg()).collect();
+    let modified: Vec<Reg> = regs.modified.iter().map(|r| r.to_reg()).collect();
+
+    // Give every virtual register distinct real registers before and after the instruction,
+    // avoiding the real registers the instruction already mentions.
+    let mut taken: HashSet<RealReg> = used
+        .iter()
+        .chain(&defined)
+        .chain(&modified)
+        .filter_map(|r| r.as_real_reg())
+        .collect();
+    let mut pre_map = RegallocMap::default();
+    let mut post_map = RegallocMap::default();
+    for vreg in used
+        .iter()
+        .chain(&defined)
+        .chain(&modified)
+        .filter_map(|r| r.as_virtual_reg())
+    {
+        if pre_map.contains_key(&vreg) {
+            continue;
+        }
+        let mut free = universe
+            .regs
+            .iter()
+            .map(|&(rreg, _)| rreg)
+            .filter(|rreg| rreg.get_class() == vreg.get_class() && !taken.contains(rreg));
+        let (pre, post) = match (free.next(), free.next()) {
+            (Some(pre), Some(post)) => (pre, post),
+            // Not enough registers to tell the maps apart; skip the check.
+            _ => return,
+        };
+        taken.insert(pre);
+        taken.insert(post);
+        pre_map.insert(vreg, pre);
+        post_map.insert(vreg, post);
+    }
+
+    let map = |regs: &[Reg], map: &RegallocMap<VirtualReg, RealReg>| -> HashSet<Reg> {
+        regs.iter()
+            .map(|&r| match r.as_virtual_reg() {
+                Some(vreg) => map[&vreg].to_reg(),
+                None => r,
+            })
+            .collect()
+    };
+    let expected_used = map(&used, &pre_map);
+    let expected_defined = map(&defined, &post_map);
+    let expected_modified = map(&modified, &pre_map);
+
+    let mut mapped = insn.clone();
+    mapped.map_regs(&pre_map, &post_map);
+    let regs = mapped.get_regs();
+    let actual_used: HashSet<Reg> = regs.used.iter().cloned().collect();
+    let actual_defined: HashSet<Reg> = regs.defined.iter().map(|r| r.to_reg()).collect();
+    let actual_modified: HashSet<Reg> = regs.modified.iter().map(|r| r.to_reg()).collect();
+
+    if actual_used != expected_used
+        || actual_defined != expected_defined
+        || actual_modified != expected_modified
+    {
+        errors.report((
+            AnyEntity::Function,
+            insn.show_rru(Some(universe)),
+            format!(
+                "map_regs doesn't agree with get_regs: mapping gives {}",
+                mapped.show_rru(Some(universe))
+            ),
+        ));
+    }
+}
+
+/// Describe an instruction for an error message.
+fn context<I: ShowWithRRU>(
+    universe: &RealRegUniverse,
+    block: BlockIndex,
+    iix: InstIx,
+    insn: &I,
+) -> String {
+    format!(
+        "vcode block {} inst {}: {}",
+        block,
+        iix.get(),
+        insn.show_rru(Some(universe))
+    )
+}
+
+#[cfg(all(test, feature = "arm64"))]
+mod tests {
+    use super::*;
+    use crate::ir::{ExternalName, Function, Signature, SourceLoc, TrapCode};
+    use crate::isa::arm64::abi::ARM64ABIBody;
+    use crate::isa::arm64::inst::Inst;
+    use crate::isa::arm64::inst::*;
+    use crate::isa::CallConv;
+    use crate::settings;
+    use alloc::boxed::Box;
+    use regalloc::{RegClass, Writable};
+
+    /// Build VCode with one block per element of `blocks`, the first being the entry.
+    fn vcode(blocks: Vec<Vec<Inst>>) -> VCode<Inst> {
+        let func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::SystemV),
+        );
+        let flags = settings::Flags::new(settings::builder());
+        let mut builder = VCodeBuilder::new(Box::new(ARM64ABIBody::new(&func, flags)));
+        builder.set_entry(0);
+        for insns in blocks {
+            for insn in insns {
+                builder.push(insn);
+            }
+            builder.end_ir_inst();
+            builder.end_bb();
+        }
+        builder.build()
+    }
+
+    fn vreg(index: usize) -> Reg {
+        Reg::new_virtual(RegClass::I64, index as u32)
+    }
+
+    fn add(rd: Reg, rn: Reg, rm: Reg) -> Inst {
+        Inst::AluRRR {
+            alu_op: ALUOp::Add64,
+            rd: Writable::from_reg(rd),
+            rn,
+            rm,
+        }
+    }
+
+    fn messages(result: VerifierResult<()>) -> Vec<String> {
+        result
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        let vcode = vcode(vec![
+            vec![
+                add(vreg(0), xreg(0), xreg(1)),
+                Inst::Jump {
+                    dest: BranchTarget::Block(1),
+                },
+            ],
+            vec![add(xreg(0), vreg(0), vreg(0)), Inst::Ret {}],
+        ]);
+        let universe = create_reg_universe();
+        assert_eq!(verify_vcode(&vcode, &universe), Ok(()));
+    }
+
+    #[test]
+    fn use_before_def() {
+        let vcode = vcode(vec![vec![add(xreg(0), vreg(0), xreg(1)), Inst::Ret {}]]);
+        let universe = create_reg_universe();
+        assert_eq!(
+            messages(verify_vcode(&vcode, &universe)),
+            vec![format!("{:?} is used before it is defined", vreg(0))]
+        );
+    }
+
+    #[test]
+    fn def_on_one_path_only() {
+        let vcode = vcode(vec![
+            vec![Inst::CondBr {
+                taken: BranchTarget::Block(1),
+                not_taken: BranchTarget::Block(2),
+                kind: CondBrKind::Zero(xreg(0)),
+            }],
+            vec![
+                add(vreg(0), xreg(0), xreg(1)),
+                Inst::Jump {
+                    dest: BranchTarget::Block(2),
+                },
+            ],
+            vec![add(xreg(0), vreg(0), xreg(1)), Inst::Ret {}],
+        ]);
+        let universe = create_reg_universe();
+        assert_eq!(
+            messages(verify_vcode(&vcode, &universe)),
+            vec![format!("{:?} is used before it is defined", vreg(0))]
+        );
+    }
+
+    #[test]
+    fn bad_successor() {
+        let vcode = vcode(vec![vec![Inst::Jump {
+            dest: BranchTarget::Block(5),
+        }]]);
+        let universe = create_reg_universe();
+        assert_eq!(
+            messages(verify_vcode(&vcode, &universe)),
+            vec!["branch to a nonexistent block"]
+        );
+    }
+
+    #[test]
+    fn missing_terminator() {
+        let vcode = vcode(vec![vec![add(xreg(0), xreg(0), xreg(1))]]);
+        let universe = create_reg_universe();
+        assert_eq!(
+            messages(verify_vcode(&vcode, &universe)),
+            vec!["block does not end in a terminator"]
+        );
+    }
+
+    #[test]
+    fn ends_in_trap() {
+        let vcode = vcode(vec![vec![Inst::Udf {
+            trap_info: (SourceLoc::default(), TrapCode::User(0)),
+        }]]);
+        let universe = create_reg_universe();
+        assert_eq!(verify_vcode(&vcode, &universe), Ok(()));
+    }
+
+    #[test]
+    fn unmapped_vreg() {
+        let vcode = vcode(vec![vec![add(xreg(0), vreg(0), xreg(1)), Inst::Ret {}]]);
+        let universe = create_reg_universe();
+        assert_eq!(
+            messages(verify_vcode_regalloc(&vcode, &universe)),
+            vec![format!(
+                "virtual register {:?} left after register allocation",
+                vreg(0)
+            )]
+        );
+    }
+}
@@ -530,7 +530,7 @@ impl<'a> Context<'a> {
             }
         }
 
-        if let Some(sig) = call_sig {
+        if let Some(sig) = self.cur.func.dfg.call_results_signature(inst) {
             self.program_output_abi(
                 sig,
                 defs,
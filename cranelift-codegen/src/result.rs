@@ -1,6 +1,7 @@
 //! Result and error types representing the outcome of compiling a function.
 
 use crate::verifier::VerifierErrors;
+use alloc::string::String;
 use thiserror::Error;
 
 /// A compilation error.
@@ -30,6 +31,18 @@ pub enum CodegenError {
     /// is exceeded, compilation fails.
     #[error("Code for function is too large")]
     CodeTooLarge,
+
+    /// The function uses a feature that isn't supported by the target or calling convention.
+    #[error("Unsupported feature: {0}")]
+    Unsupported(String),
+
+    /// The register allocator of a MachInst backend failed.
+    ///
+    /// This includes errors found by the register allocator's checker, which runs when the
+    /// verifier is enabled. Such errors always represent a bug in Cranelift or in the allocator.
+    /// The allocator's error is kept as a message, since its type can't be compared.
+    #[error("Register allocation failed: {0}")]
+    RegAlloc(String),
 }
 
 /// A convenient alias for a `Result` that uses `CodegenError` as the error type.
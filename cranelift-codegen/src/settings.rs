@@ -399,10 +399,12 @@ mod tests {
             f.to_string(),
             "[shared]\n\
              opt_level = \"none\"\n\
+             regalloc = \"backtracking\"\n\
              libcall_call_conv = \"isa_default\"\n\
              baldrdash_prologue_words = 0\n\
              probestack_size_log2 = 12\n\
              enable_verifier = true\n\
+             regalloc_checker = false\n\
              is_pic = false\n\
              use_colocated_libcalls = false\n\
              avoid_div_traps = false\n\
@@ -416,7 +418,9 @@ mod tests {
              emit_all_ones_funcaddrs = false\n\
              enable_probestack = true\n\
              probestack_func_adjusts_sp = false\n\
-             enable_jump_tables = true\n"
+             enable_jump_tables = true\n\
+             enable_loop_optimizations = false\n\
+             enable_bounds_check_elimination = false\n"
         );
         assert_eq!(f.opt_level(), super::OptLevel::None);
         assert_eq!(f.enable_simd(), false);
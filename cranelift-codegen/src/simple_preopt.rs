@@ -23,7 +23,7 @@ use crate::timing;
 /// Replaces the unique result of the instruction inst to an alias of the given value, and
 /// replaces the instruction with a nop. Can be used only on instructions producing one unique
 /// result, otherwise will assert.
-fn replace_single_result_with_alias(dfg: &mut DataFlowGraph, inst: Inst, value: Value) {
+pub(crate) fn replace_single_result_with_alias(dfg: &mut DataFlowGraph, inst: Inst, value: Value) {
     // Replace the result value by an alias.
     let results = dfg.detach_results(inst);
     debug_assert!(results.len(&dfg.value_lists) == 1);
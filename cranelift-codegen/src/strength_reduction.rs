@@ -0,0 +1,134 @@
+//! Strength reduction of induction variable multiplications.
+//!
+//! A multiplication `imul iv, k` of a basic induction variable `iv` by a constant `k` is itself
+//! an induction variable: it starts at `init * k` and is incremented by `step * k` on every
+//! iteration. This pass materializes it as a new loop header parameter, so that the loop body
+//! only needs an addition instead of a multiplication.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::induction::{branch_arg, find_induction_vars, header_edges, iconst_value};
+use crate::ir::{DataFlowGraph, Function, Inst, InstBuilder, InstructionData, Opcode, Value};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::simple_preopt::replace_single_result_with_alias;
+use crate::timing;
+use alloc::vec::Vec;
+
+/// If `inst` multiplies `iv` by a constant, return that constant.
+fn multiplier(dfg: &DataFlowGraph, inst: Inst, iv: Value) -> Option<i64> {
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        } if dfg.resolve_aliases(arg) == iv => Some(imm.into()),
+        InstructionData::Binary {
+            opcode: Opcode::Imul,
+            args,
+        } => {
+            let lhs = dfg.resolve_aliases(args[0]);
+            let rhs = dfg.resolve_aliases(args[1]);
+            if lhs == iv {
+                iconst_value(dfg, rhs)
+            } else if rhs == iv {
+                iconst_value(dfg, lhs)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Move `pos` to the first branch of the block ending with `branch`, as nothing can be inserted
+/// between the branches of a block.
+fn goto_branches(pos: &mut FuncCursor, branch: Inst) {
+    let block = pos.func.layout.inst_block(branch).unwrap();
+    let first = pos
+        .func
+        .layout
+        .block_insts(block)
+        .find(|&inst| pos.func.dfg[inst].opcode().is_branch())
+        .unwrap();
+    pos.goto_inst(first);
+}
+
+/// Performs strength reduction of the multiplications of induction variables by constants, in
+/// every loop found by `loop_analysis`.
+pub fn do_strength_reduction(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) {
+    let _tt = timing::loop_strength_reduction();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    for lp in loop_analysis.loops() {
+        reduce_loop(func, cfg, domtree, loop_analysis, lp);
+    }
+}
+
+fn reduce_loop(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) {
+    let ivs = find_induction_vars(func, cfg, domtree, loop_analysis, lp);
+    if ivs.is_empty() {
+        return;
+    }
+    let header = loop_analysis.loop_header(lp);
+    let edges = header_edges(func, cfg, domtree, header).unwrap();
+
+    // The derived induction variables we have already created, keyed by the basic induction
+    // variable and the multiplier.
+    let mut derived: FxHashMap<(Value, i64), Value> = FxHashMap();
+
+    // Collect the candidate multiplications first, as we'll be inserting instructions.
+    let mut candidates = Vec::new();
+    let mut pos = FuncCursor::new(func);
+    while let Some(block) = pos.next_block() {
+        if !loop_analysis.is_in_loop(block, lp) {
+            continue;
+        }
+        while let Some(inst) = pos.next_inst() {
+            for iv in &ivs {
+                if let Some(k) = multiplier(&pos.func.dfg, inst, iv.param) {
+                    candidates.push((inst, *iv, k));
+                    break;
+                }
+            }
+        }
+    }
+
+    for (inst, iv, k) in candidates {
+        let ty = pos.func.dfg.value_type(iv.param);
+        let reduced = match derived.get(&(iv.param, k)) {
+            Some(&reduced) => reduced,
+            None => {
+                let reduced = pos.func.dfg.append_block_param(header, ty);
+                for &entry in &edges.entries {
+                    let init = branch_arg(&pos.func.dfg, entry, iv.index);
+                    goto_branches(&mut pos, entry);
+                    let scaled = pos.ins().imul_imm(init, k);
+                    pos.func.dfg.append_inst_arg(entry, scaled);
+                }
+                for &back_edge in &edges.back_edges {
+                    goto_branches(&mut pos, back_edge);
+                    let next = pos.ins().iadd_imm(reduced, iv.step.wrapping_mul(k));
+                    pos.func.dfg.append_inst_arg(back_edge, next);
+                }
+                derived.insert((iv.param, k), reduced);
+                reduced
+            }
+        };
+        replace_single_result_with_alias(&mut pos.func.dfg, inst, reduced);
+    }
+}
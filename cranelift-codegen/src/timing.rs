@@ -61,6 +61,9 @@ define_passes! {
     legalize: "Legalization",
     gvn: "Global value numbering",
     licm: "Loop invariant code motion",
+    loop_strength_reduction: "Loop strength reduction",
+    loop_unrolling: "Loop unrolling",
+    bounds_checks: "Bounds check elimination",
     unreachable_code: "Remove unreachable blocks",
 
     regalloc: "Register allocation",
@@ -0,0 +1,270 @@
+//! Unrolling of small loops with a constant trip count.
+//!
+//! This pass handles single-block loops, whose header ends with a conditional branch and a jump,
+//! one of them being the back edge. When the loop is controlled by an induction variable with a
+//! constant initial value that is compared against a constant, the trip count is computed by
+//! evaluating the exit condition. Loops whose unrolled size is small enough are then fully
+//! unrolled into a chain of straight-line copies of the body; other loops whose trip count is a
+//! multiple of a small factor are unrolled by that factor, with the exit test only kept in the
+//! last copy.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::entity::EntityList;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::{FxHashMap, FxHashSet};
+use crate::induction::{find_induction_vars, single_block_latch, trip_count, LoopLatch};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{Block, Function, Inst, InstBuilder, Value};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::timing;
+use alloc::vec::Vec;
+
+/// Maximum number of instructions in a loop after it has been unrolled.
+const MAX_UNROLLED_INSTS: u64 = 64;
+
+/// Maximum trip count of a fully unrolled loop.
+const MAX_FULL_UNROLL_TRIP_COUNT: u64 = 16;
+
+/// Partial unrolling factors to try, in order of preference.
+const PARTIAL_UNROLL_FACTORS: [u64; 2] = [4, 2];
+
+/// The shape of a single-block loop that can be unrolled.
+struct UnrollCandidate {
+    header: Block,
+    /// Instructions of the loop body, excluding the two terminating branches.
+    body: Vec<Inst>,
+    latch: LoopLatch,
+}
+
+/// Performs unrolling of the small single-block loops found by `loop_analysis`.
+///
+/// Returns `true` if any loop was unrolled, in which case the CFG, the dominator tree and the
+/// loop analysis need to be recomputed.
+pub fn do_loop_unrolling(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) -> bool {
+    let _tt = timing::loop_unrolling();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let mut plans = Vec::new();
+    for lp in loop_analysis.loops() {
+        if let Some(candidate) = unroll_candidate(func, cfg, loop_analysis, lp) {
+            let ivs = find_induction_vars(func, cfg, domtree, loop_analysis, lp);
+            if let Some(trip_count) =
+                trip_count(func, cfg, domtree, candidate.header, &candidate.latch, &ivs)
+            {
+                if let Some(plan) = unroll_plan(&candidate, trip_count) {
+                    plans.push((candidate, plan));
+                }
+            }
+        }
+    }
+
+    let changed = !plans.is_empty();
+    for (candidate, plan) in plans {
+        match plan {
+            UnrollPlan::Full(trip_count) => unroll(func, &candidate, trip_count, true),
+            UnrollPlan::Partial(factor) => unroll(func, &candidate, factor, false),
+        }
+    }
+    changed
+}
+
+enum UnrollPlan {
+    /// Replace the loop by the given number of copies of its body.
+    Full(u64),
+    /// Unroll the loop by the given factor.
+    Partial(u64),
+}
+
+fn unroll_plan(candidate: &UnrollCandidate, trip_count: u64) -> Option<UnrollPlan> {
+    let size = candidate.body.len().max(1) as u64;
+    if trip_count <= MAX_FULL_UNROLL_TRIP_COUNT && trip_count * size <= MAX_UNROLLED_INSTS {
+        return Some(UnrollPlan::Full(trip_count));
+    }
+    PARTIAL_UNROLL_FACTORS
+        .iter()
+        .find(|&&factor| trip_count % factor == 0 && factor * size <= MAX_UNROLLED_INSTS)
+        .map(|&factor| UnrollPlan::Partial(factor))
+}
+
+/// Check that `lp` is a single-block loop that we know how to unroll.
+fn unroll_candidate(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) -> Option<UnrollCandidate> {
+    let header = loop_analysis.loop_header(lp);
+    let latch = single_block_latch(func, cfg, loop_analysis, lp)?;
+
+    let mut body = Vec::new();
+    for inst in func.layout.block_insts(header) {
+        if inst == latch.cond_branch || inst == latch.jump {
+            continue;
+        }
+        let opcode = func.dfg[inst].opcode();
+        if opcode.is_branch() || opcode.is_terminator() {
+            return None;
+        }
+        body.push(inst);
+    }
+
+    // Values defined in the loop must not be used outside of it, except as arguments to the exit
+    // edge: after unrolling they would refer to the first iteration instead of the last one.
+    let mut defined: FxHashSet<Value> = FxHashSet();
+    defined.extend(func.dfg.block_params(header).iter().cloned());
+    for &inst in &body {
+        defined.extend(func.dfg.inst_results(inst).iter().cloned());
+    }
+    for block in func.layout.blocks() {
+        if block == header {
+            continue;
+        }
+        for inst in func.layout.block_insts(block) {
+            if func
+                .dfg
+                .inst_args(inst)
+                .iter()
+                .any(|&arg| defined.contains(&func.dfg.resolve_aliases(arg)))
+            {
+                return None;
+            }
+        }
+    }
+
+    Some(UnrollCandidate {
+        header,
+        body,
+        latch,
+    })
+}
+
+/// Append a copy of the body of `candidate` to a new block inserted after `after`.
+///
+/// Returns the new block and the mapping of the header's values to their copies.
+fn copy_body(
+    func: &mut Function,
+    candidate: &UnrollCandidate,
+    after: Block,
+) -> (Block, FxHashMap<Value, Value>) {
+    let mut map: FxHashMap<Value, Value> = FxHashMap();
+    let block = func.dfg.make_block();
+    func.layout.insert_block_after(block, after);
+    for i in 0..func.dfg.num_block_params(candidate.header) {
+        let param = func.dfg.block_params(candidate.header)[i];
+        let ty = func.dfg.value_type(param);
+        map.insert(param, func.dfg.append_block_param(block, ty));
+    }
+    for &inst in &candidate.body {
+        let copy = copy_inst(func, inst, &map);
+        func.layout.append_inst(copy, block);
+        let ctrl_typevar = func.dfg.ctrl_typevar(inst);
+        func.dfg.make_inst_results(copy, ctrl_typevar);
+        for (&old, &new) in func
+            .dfg
+            .inst_results(inst)
+            .iter()
+            .zip(func.dfg.inst_results(copy))
+        {
+            map.insert(old, new);
+        }
+    }
+    (block, map)
+}
+
+/// Create a copy of `inst` with its arguments renamed through `map`, without results.
+fn copy_inst(func: &mut Function, inst: Inst, map: &FxHashMap<Value, Value>) -> Inst {
+    let mut data = func.dfg[inst].clone();
+    // Don't share the value list with the original instruction.
+    if let Some(list) = data.take_value_list() {
+        let args = list.as_slice(&func.dfg.value_lists).to_vec();
+        data.put_value_list(EntityList::from_slice(&args, &mut func.dfg.value_lists));
+    }
+    let copy = func.dfg.make_inst(data);
+    for arg in func.dfg.inst_args_mut(copy) {
+        if let Some(&new) = map.get(arg) {
+            *arg = new;
+        }
+    }
+    func.srclocs[copy] = func.srclocs[inst];
+    copy
+}
+
+/// Jump from the end of `block` to `dest`, passing the back edge arguments renamed through
+/// `map`.
+fn jump_to(
+    func: &mut Function,
+    candidate: &UnrollCandidate,
+    block: Block,
+    dest: Block,
+    edge: Inst,
+    map: &FxHashMap<Value, Value>,
+) {
+    let args: Vec<Value> = match func.dfg.analyze_branch(edge) {
+        BranchInfo::SingleDest(_, args) => args
+            .iter()
+            .map(|arg| {
+                let arg = func.dfg.resolve_aliases(*arg);
+                *map.get(&arg).unwrap_or(&arg)
+            })
+            .collect(),
+        BranchInfo::Table(..) | BranchInfo::NotABranch => unreachable!(),
+    };
+    let mut pos = FuncCursor::new(func).at_bottom(block);
+    if block == candidate.header {
+        // Replace the original terminators of the header.
+        pos.func.layout.remove_inst(candidate.latch.jump);
+        pos.func.layout.remove_inst(candidate.latch.cond_branch);
+    }
+    pos.ins().jump(dest, &args);
+}
+
+/// Unroll the loop described by `candidate` into `copies` copies of its body.
+///
+/// When `full` is set, the last copy unconditionally exits the loop; otherwise it keeps the
+/// original exit test and branches back to the header.
+fn unroll(func: &mut Function, candidate: &UnrollCandidate, copies: u64, full: bool) {
+    let header = candidate.header;
+    let mut block = header;
+    let mut map = FxHashMap();
+    for _ in 1..copies {
+        let (next, next_map) = copy_body(func, candidate, block);
+        jump_to(
+            func,
+            candidate,
+            block,
+            next,
+            candidate.latch.back_edge,
+            &map,
+        );
+        block = next;
+        map = next_map;
+    }
+
+    if full {
+        let exit = func.dfg[candidate.latch.exit_edge]
+            .branch_destination()
+            .unwrap();
+        jump_to(
+            func,
+            candidate,
+            block,
+            exit,
+            candidate.latch.exit_edge,
+            &map,
+        );
+    } else if block != header {
+        // Keep the exit test in the last copy.
+        for &inst in &[candidate.latch.cond_branch, candidate.latch.jump] {
+            let copy = copy_inst(func, inst, &map);
+            func.layout.append_inst(copy, block);
+        }
+    }
+}
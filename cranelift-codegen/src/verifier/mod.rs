@@ -580,9 +580,9 @@ impl<'a> Verifier<'a> {
         }
 
         let num_fixed_results = inst_data.opcode().constraints().num_fixed_results();
-        // var_results is 0 if we aren't a call instruction
+        // var_results is 0 if we aren't a call instruction, or are a tail call
         let var_results = dfg
-            .call_signature(inst)
+            .call_results_signature(inst)
             .map_or(0, |sig| dfg.signatures[sig].returns.len());
         let total_results = num_fixed_results + var_results;
 
@@ -1199,6 +1199,7 @@ impl<'a> Verifier<'a> {
         let _ = self.typecheck_fixed_args(inst, ctrl_type, errors);
         let _ = self.typecheck_variable_args(inst, errors);
         let _ = self.typecheck_return(inst, errors);
+        let _ = self.typecheck_tail_call(inst, errors);
         let _ = self.typecheck_special(inst, ctrl_type, errors);
 
         // Misuses of copy_nop instructions are fatal
@@ -1500,6 +1501,65 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Check that the callee of a tail call is compatible with the calling function.
+    fn typecheck_tail_call(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let sig_ref = match self.func.dfg[inst] {
+            ir::InstructionData::Call {
+                opcode: Opcode::ReturnCall,
+                func_ref,
+                ..
+            } => self.func.dfg.ext_funcs[func_ref].signature,
+            ir::InstructionData::CallIndirect {
+                opcode: Opcode::ReturnCallIndirect,
+                sig_ref,
+                ..
+            } => sig_ref,
+            _ => return Ok(()),
+        };
+        let caller = &self.func.signature;
+        let callee = &self.func.dfg.signatures[sig_ref];
+
+        if callee.call_conv != caller.call_conv {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                format!(
+                    "tail call to a function with calling convention {}, must match {}",
+                    callee.call_conv, caller.call_conv
+                ),
+            ));
+        }
+
+        if caller.uses_struct_return_param() || callee.uses_struct_return_param() {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                "tail calls can't use struct return parameters",
+            ));
+        }
+
+        // The special return values added by the ABI legalization of the caller don't need to
+        // match.
+        fn normal_returns(sig: &ir::Signature) -> impl Iterator<Item = Type> + '_ {
+            sig.returns
+                .iter()
+                .filter(|r| r.purpose == ir::ArgumentPurpose::Normal)
+                .map(|r| r.value_type)
+        }
+        if !normal_returns(caller).eq(normal_returns(callee)) {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                "tail call return types must match function signature",
+            ));
+        }
+        Ok(())
+    }
+
     // Check special-purpose type constraints that can't be expressed in the normal opcode
     // constraints.
     fn typecheck_special(
@@ -1,13 +1,14 @@
 //! Converting Cranelift IR to text.
 //!
 //! The `write` module provides the `write_function` function which converts an IR `Function` to an
-//! equivalent textual form. This textual form can be read back by the `cranelift-reader` crate.
+//! equivalent textual form, and the `write_data_object` function which does the same for a
+//! `DataObject`. This textual form can be read back by the `cranelift-reader` crate.
 
 use crate::entity::SecondaryMap;
 use crate::ir::entities::AnyEntity;
 use crate::ir::{
-    Block, DataFlowGraph, DisplayFunctionAnnotations, Function, Inst, SigRef, Type, Value,
-    ValueDef, ValueLoc,
+    Block, DataFlowGraph, DataInit, DataObject, DisplayFunctionAnnotations, Function, Inst, SigRef,
+    Type, Value, ValueDef, ValueLoc,
 };
 use crate::isa::{RegInfo, TargetIsa};
 use crate::packed_option::ReservedValue;
@@ -192,6 +193,9 @@ pub fn decorate_function<FW: FuncWriter>(
     let regs = regs.as_ref();
 
     write!(w, "function ")?;
+    if let Some(linkage) = annotations.linkage {
+        write!(w, "{} ", linkage)?;
+    }
     write_spec(w, func, regs)?;
     writeln!(w, " {{")?;
     let aliases = alias_map(func);
@@ -214,6 +218,60 @@ fn write_spec(w: &mut dyn Write, func: &Function, regs: Option<&RegInfo>) -> fmt
     write!(w, "{}{}", func.name, func.signature.display(regs))
 }
 
+//----------------------------------------------------------------------
+//
+// Data objects.
+
+/// Write `data` to `w` as equivalent text.
+///
+///    data %foo import
+///    data %bar local writable align 8 = zeroinit 16 {
+///        func_addr 0, %f
+///        data_addr 8, %foo+4
+///    }
+pub fn write_data_object(w: &mut dyn Write, data: &DataObject) -> fmt::Result {
+    write!(w, "data {} {}", data.name, data.linkage)?;
+    if data.writable {
+        write!(w, " writable")?;
+    }
+    if let Some(align) = data.align {
+        write!(w, " align {}", align)?;
+    }
+    match data.init {
+        DataInit::Uninitialized => {}
+        DataInit::Zeros { size } => write!(w, " = zeroinit {}", size)?,
+        DataInit::Bytes { ref contents } => {
+            write!(w, " = [")?;
+            for (i, byte) in contents.iter().enumerate() {
+                if i == 0 {
+                    write!(w, "{}", byte)?;
+                } else {
+                    write!(w, " {}", byte)?;
+                }
+            }
+            write!(w, "]")?;
+        }
+    }
+    if data.function_relocs.is_empty() && data.data_relocs.is_empty() {
+        return writeln!(w);
+    }
+    writeln!(w, " {{")?;
+    for &(offset, ref name) in &data.function_relocs {
+        writeln!(w, "    func_addr {}, {}", offset, name)?;
+    }
+    for &(offset, ref name, addend) in &data.data_relocs {
+        write!(w, "    data_addr {}, {}", offset, name)?;
+        if addend > 0 {
+            write!(w, "+")?;
+        }
+        if addend != 0 {
+            write!(w, "{}", addend)?;
+        }
+        writeln!(w)?;
+    }
+    writeln!(w, "}}")
+}
+
 //----------------------------------------------------------------------
 //
 // Basic blocks
@@ -758,7 +816,10 @@ impl<'a> fmt::Display for DisplayValuesWithDelimiter<'a> {
 mod tests {
     use crate::cursor::{Cursor, CursorPosition, FuncCursor};
     use crate::ir::types;
-    use crate::ir::{ExternalName, Function, InstBuilder, StackSlotData, StackSlotKind};
+    use crate::ir::{
+        DataInit, DataObject, DisplayFunctionAnnotations, ExternalName, Function, InstBuilder,
+        Linkage, StackSlotData, StackSlotKind,
+    };
     use alloc::string::ToString;
 
     #[test]
@@ -840,4 +901,45 @@ mod tests {
             "function u0:0() fast {\nblock0(v3: i32):\n    v0 -> v3\n    v2 -> v0\n    v4 = iconst.i32 42\n    v5 = iadd v0, v0\n    v1 -> v5\n    v6 = iconst.i32 23\n    v7 = iadd v1, v1\n}\n"
         );
     }
+
+    #[test]
+    fn linkage() {
+        let mut f = Function::new();
+        f.name = ExternalName::testcase("foo");
+        let annotations = DisplayFunctionAnnotations {
+            linkage: Some(Linkage::Export),
+            ..DisplayFunctionAnnotations::default()
+        };
+        assert_eq!(
+            f.display_with(annotations).to_string(),
+            "function export %foo() fast {\n}\n"
+        );
+    }
+
+    #[test]
+    fn data_objects() {
+        let mut data = DataObject::new(ExternalName::testcase("foo"), Linkage::Import);
+        assert_eq!(data.to_string(), "data %foo import\n");
+
+        data.linkage = Linkage::Local;
+        data.writable = true;
+        data.align = Some(8);
+        data.init = DataInit::Zeros { size: 16 };
+        assert_eq!(
+            data.to_string(),
+            "data %foo local writable align 8 = zeroinit 16\n"
+        );
+
+        data.init = DataInit::Bytes {
+            contents: vec![1, 2, 255],
+        };
+        data.function_relocs.push((0, ExternalName::testcase("f")));
+        data.data_relocs.push((8, ExternalName::testcase("bar"), 4));
+        data.data_relocs
+            .push((16, ExternalName::testcase("bar"), -2));
+        assert_eq!(
+            data.to_string(),
+            "data %foo local writable align 8 = [1 2 255] {\n    func_addr 0, %f\n    data_addr 8, %bar+4\n    data_addr 16, %bar-2\n}\n"
+        );
+    }
 }
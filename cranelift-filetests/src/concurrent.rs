@@ -6,6 +6,7 @@
 use crate::runone;
 use crate::TestResult;
 use cranelift_codegen::dbg::LOG_FILENAME_PREFIX;
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::timing;
 use file_per_thread_logger;
 use log::error;
@@ -43,7 +44,7 @@ pub struct ConcurrentRunner {
 
 impl ConcurrentRunner {
     /// Create a new `ConcurrentRunner` with threads spun up.
-    pub fn new() -> Self {
+    pub fn new(backend_variant: BackendVariant) -> Self {
         let (request_tx, request_rx) = channel();
         let request_mutex = Arc::new(Mutex::new(request_rx));
         let (reply_tx, reply_rx) = channel();
@@ -51,7 +52,14 @@ impl ConcurrentRunner {
         heartbeat_thread(reply_tx.clone());
 
         let handles = (0..num_cpus::get())
-            .map(|num| worker_thread(num, request_mutex.clone(), reply_tx.clone()))
+            .map(|num| {
+                worker_thread(
+                    num,
+                    request_mutex.clone(),
+                    reply_tx.clone(),
+                    backend_variant,
+                )
+            })
             .collect();
 
         Self {
@@ -117,6 +125,7 @@ fn worker_thread(
     thread_num: usize,
     requests: Arc<Mutex<Receiver<Request>>>,
     replies: Sender<Reply>,
+    backend_variant: BackendVariant,
 ) -> thread::JoinHandle<timing::PassTimes> {
     thread::Builder::new()
         .name(format!("worker #{}", thread_num))
@@ -133,18 +142,19 @@ fn worker_thread(
                 // The receiver should always be present for this as long as we have jobs.
                 replies.send(Reply::Starting { jobid, thread_num }).unwrap();
 
-                let result = catch_unwind(|| runone::run(path.as_path(), None, None))
-                    .unwrap_or_else(|e| {
-                        // The test panicked, leaving us a `Box<Any>`.
-                        // Panics are usually strings.
-                        if let Some(msg) = e.downcast_ref::<String>() {
-                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                        } else if let Some(msg) = e.downcast_ref::<&'static str>() {
-                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                        } else {
-                            Err(format!("panicked in worker #{}", thread_num))
-                        }
-                    });
+                let result =
+                    catch_unwind(|| runone::run(path.as_path(), None, None, backend_variant))
+                        .unwrap_or_else(|e| {
+                            // The test panicked, leaving us a `Box<Any>`.
+                            // Panics are usually strings.
+                            if let Some(msg) = e.downcast_ref::<String>() {
+                                Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                            } else if let Some(msg) = e.downcast_ref::<&'static str>() {
+                                Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                            } else {
+                                Err(format!("panicked in worker #{}", thread_num))
+                            }
+                        });
 
                 if let Err(ref msg) = result {
                     error!("FAIL: {}", msg);
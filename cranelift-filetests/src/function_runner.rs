@@ -1,6 +1,6 @@
 use core::mem;
-use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
-use cranelift_codegen::ir::Function;
+use cranelift_codegen::binemit::{CodeOffset, NullStackmapSink, NullTrapSink, Reloc, RelocSink};
+use cranelift_codegen::ir::{ConstantOffset, ExternalName, Function, JumpTable, LibCall};
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{settings, Context};
 use cranelift_native::builder as host_isa_builder;
@@ -56,7 +56,7 @@ impl FunctionRunner {
         context.func = func;
 
         // compile and encode the result to machine code
-        let relocs = &mut NullRelocSink {};
+        let relocs = &mut LibCallRelocSink::default();
         let traps = &mut NullTrapSink {};
         let stackmaps = &mut NullStackmapSink {};
         let code_info = context
@@ -75,6 +75,8 @@ impl FunctionRunner {
             );
         };
 
+        relocs.apply(code_page.as_mut_ptr())?;
+
         let code_page = code_page.make_exec().map_err(|e| e.to_string())?;
         let callable_fn: fn() -> bool = unsafe { mem::transmute(code_page.as_ptr()) };
 
@@ -87,6 +89,114 @@ impl FunctionRunner {
     }
 }
 
+/// A `RelocSink` that resolves the absolute addresses of the library calls that legalization may
+/// introduce, so that functions using them can be run. Any other external relocation is an error.
+#[derive(Default)]
+struct LibCallRelocSink {
+    relocs: Vec<(CodeOffset, Reloc, ExternalName)>,
+}
+
+impl LibCallRelocSink {
+    /// Patch the recorded relocations into the code at `code`.
+    fn apply(&self, code: *mut u8) -> Result<(), String> {
+        for &(offset, reloc, ref name) in &self.relocs {
+            let addr = match (reloc, name) {
+                (Reloc::Abs8, ExternalName::LibCall(libcall)) => libcall_addr(*libcall),
+                _ => None,
+            }
+            .ok_or_else(|| format!("Unsupported relocation {} to {}", reloc, name))?;
+            unsafe {
+                let at = code.add(offset as usize) as *mut u64;
+                at.write_unaligned(addr as u64);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RelocSink for LibCallRelocSink {
+    fn reloc_block(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {}
+
+    fn reloc_external(&mut self, offset: CodeOffset, reloc: Reloc, name: &ExternalName, _: i64) {
+        self.relocs.push((offset, reloc, name.clone()));
+    }
+
+    fn reloc_constant(&mut self, _: CodeOffset, _: Reloc, _: ConstantOffset) {}
+
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {}
+}
+
+/// The two halves of an `i128`, returned in a pair of registers like a legalized `i128` result.
+#[repr(C)]
+#[allow(dead_code)] // The fields are only read by the compiled code.
+struct I128Pair {
+    low: u64,
+    high: u64,
+}
+
+impl From<u128> for I128Pair {
+    fn from(x: u128) -> Self {
+        Self {
+            low: x as u64,
+            high: (x >> 64) as u64,
+        }
+    }
+}
+
+/// Join the halves of an `i128` that was passed in a pair of registers.
+fn join(low: u64, high: u64) -> u128 {
+    u128::from(low) | u128::from(high) << 64
+}
+
+extern "C" fn udiv_i64(x: u64, y: u64) -> u64 {
+    x / y
+}
+
+extern "C" fn sdiv_i64(x: i64, y: i64) -> i64 {
+    x.wrapping_div(y)
+}
+
+extern "C" fn urem_i64(x: u64, y: u64) -> u64 {
+    x % y
+}
+
+extern "C" fn srem_i64(x: i64, y: i64) -> i64 {
+    x.wrapping_rem(y)
+}
+
+extern "C" fn udiv_i128(xl: u64, xh: u64, yl: u64, yh: u64) -> I128Pair {
+    I128Pair::from(join(xl, xh) / join(yl, yh))
+}
+
+extern "C" fn sdiv_i128(xl: u64, xh: u64, yl: u64, yh: u64) -> I128Pair {
+    let (x, y) = (join(xl, xh) as i128, join(yl, yh) as i128);
+    I128Pair::from(x.wrapping_div(y) as u128)
+}
+
+extern "C" fn urem_i128(xl: u64, xh: u64, yl: u64, yh: u64) -> I128Pair {
+    I128Pair::from(join(xl, xh) % join(yl, yh))
+}
+
+extern "C" fn srem_i128(xl: u64, xh: u64, yl: u64, yh: u64) -> I128Pair {
+    let (x, y) = (join(xl, xh) as i128, join(yl, yh) as i128);
+    I128Pair::from(x.wrapping_rem(y) as u128)
+}
+
+/// Get the address of the host implementation of `libcall`, if there is one.
+fn libcall_addr(libcall: LibCall) -> Option<usize> {
+    Some(match libcall {
+        LibCall::UdivI64 => udiv_i64 as usize,
+        LibCall::SdivI64 => sdiv_i64 as usize,
+        LibCall::UremI64 => urem_i64 as usize,
+        LibCall::SremI64 => srem_i64 as usize,
+        LibCall::UdivI128 => udiv_i128 as usize,
+        LibCall::SdivI128 => sdiv_i128 as usize,
+        LibCall::UremI128 => urem_i128 as usize,
+        LibCall::SremI128 => srem_i128 as usize,
+        _ => return None,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
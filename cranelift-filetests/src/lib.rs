@@ -25,6 +25,7 @@
 
 pub use crate::function_runner::FunctionRunner;
 use crate::runner::TestRunner;
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::timing;
 use cranelift_reader::TestCommand;
 use std::path::Path;
@@ -33,10 +34,12 @@ use std::time;
 mod concurrent;
 mod function_runner;
 mod match_directive;
+mod precise_output;
 mod runner;
 mod runone;
 mod subtest;
 
+mod test_bce;
 mod test_binemit;
 mod test_cat;
 mod test_compile;
@@ -45,6 +48,7 @@ mod test_domtree;
 mod test_fde;
 mod test_legalizer;
 mod test_licm;
+mod test_module;
 mod test_postopt;
 mod test_preopt;
 mod test_print_cfg;
@@ -55,6 +59,8 @@ mod test_safepoint;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
+mod test_strength_reduce;
+mod test_unroll;
 mod test_unwind;
 mod test_vcode;
 mod test_verifier;
@@ -71,8 +77,16 @@ type TestResult = Result<time::Duration, String>;
 /// Directories are scanned recursively for test cases ending in `.clif`. These test cases are
 /// executed on background threads.
 ///
-pub fn run(verbose: bool, report_times: bool, files: &[String]) -> TestResult {
-    let mut runner = TestRunner::new(verbose, report_times);
+/// The targets of the test cases are looked up with `backend_variant`, so the same tests can be
+/// run against each backend of a target that has several.
+///
+pub fn run(
+    verbose: bool,
+    report_times: bool,
+    backend_variant: BackendVariant,
+    files: &[String],
+) -> TestResult {
+    let mut runner = TestRunner::new(verbose, report_times, backend_variant);
 
     for path in files.iter().map(Path::new) {
         if path.is_file() {
@@ -98,7 +112,7 @@ pub fn run_passes(
     target: &str,
     file: &str,
 ) -> TestResult {
-    let mut runner = TestRunner::new(verbose, /* report_times */ false);
+    let mut runner = TestRunner::new(verbose, /* report_times */ false, BackendVariant::Any);
 
     let path = Path::new(file);
     if path == Path::new("-") || path.is_file() {
@@ -120,6 +134,7 @@ pub fn run_passes(
 /// a `.clif` test file.
 fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::SubTest>> {
     match parsed.command {
+        "bce" => test_bce::subtest(parsed),
         "binemit" => test_binemit::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
@@ -135,6 +150,8 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "run" => test_run::subtest(parsed),
         "shrink" => test_shrink::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
+        "strength-reduce" => test_strength_reduce::subtest(parsed),
+        "unroll" => test_unroll::subtest(parsed),
         "vcode" => test_vcode::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
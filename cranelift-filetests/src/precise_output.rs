@@ -0,0 +1,265 @@
+//! Comparing the whole output of a subtest against an expectation in the test file.
+//!
+//! Subtests given the `precise-output` option don't run filecheck. Instead, their complete output
+//! must match the comment lines directly following the closing brace of each function:
+//!
+//! <pre>
+//! function %f() {
+//!     ...
+//! }
+//! ; first line of output
+//! ; second line of output
+//! </pre>
+//!
+//! When the `CRANELIFT_TEST_BLESS` environment variable is set to `1`, mismatches are not
+//! reported. The expected output in the `.clif` file is rewritten in place instead.
+
+use crate::subtest::{Context, SubtestResult};
+use cranelift_reader::{IsaSpec, Location, TestCommand, TestOption};
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Does `parsed` have the `precise-output` flag? Other options are rejected.
+pub fn precise_output_flag(parsed: &TestCommand) -> SubtestResult<bool> {
+    let mut precise_output = false;
+    for option in &parsed.options {
+        match *option {
+            TestOption::Flag("precise-output") => precise_output = true,
+            _ => return Err(format!("Unknown option {} on {}", option, parsed)),
+        }
+    }
+    Ok(precise_output)
+}
+
+/// Check that the `precise-output` expectations of a file can be blessed: the expectation after
+/// each function can only hold one output, so at most one of the test `commands` may use
+/// `precise-output`, and only with a single target.
+pub fn check_precise_output_commands(
+    commands: &[&TestCommand],
+    isa_spec: &IsaSpec,
+) -> SubtestResult<()> {
+    let mut precise = commands.iter().filter(|command| {
+        command
+            .options
+            .contains(&TestOption::Flag("precise-output"))
+    });
+    let first = match precise.next() {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    if let Some(second) = precise.next() {
+        return Err(format!(
+            "only one precise-output test command is allowed per file, found `test {}` and \
+             `test {}`",
+            first.command, second.command
+        ));
+    }
+    if let IsaSpec::Some(ref isas) = *isa_spec {
+        if isas.len() > 1 {
+            return Err(format!(
+                "precise-output `test {}` needs a single target, found {}",
+                first.command,
+                isas.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Compare `actual` with the output expected after the function being tested.
+///
+/// If blessing is enabled, a mismatching expectation is replaced with `actual` instead.
+pub fn run_precise_output(actual: &str, context: &Context) -> SubtestResult<()> {
+    let actual = actual.lines().map(str::trim_end).collect::<Vec<_>>();
+    let location = context.details.location;
+    let expected = context.file_update.expected_output(location);
+    if actual == expected {
+        return Ok(());
+    }
+
+    if env::var("CRANELIFT_TEST_BLESS").map_or(false, |v| v == "1") {
+        return context.file_update.bless(location, &actual);
+    }
+
+    Err(format!(
+        "output of the function on line {} doesn't match the expectation.\n\
+         Expected:\n{}\nGot:\n{}\n\
+         Set CRANELIFT_TEST_BLESS=1 to update the expected output in the test file.",
+        location.line_number,
+        expected.join("\n"),
+        actual.join("\n"),
+    ))
+}
+
+/// The text of a test file, and the bookkeeping needed to rewrite expectations in it.
+pub struct FileUpdate<'a> {
+    path: PathBuf,
+    /// The file as it was parsed. Function locations refer to lines in this text.
+    source: &'a str,
+    /// Number of lines added to the file on disk by blessing so far, minus lines removed.
+    line_diff: Cell<isize>,
+}
+
+impl<'a> FileUpdate<'a> {
+    /// Create a `FileUpdate` for the file at `path` which was parsed from `source`.
+    pub fn new(path: &Path, source: &'a str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            source,
+            line_diff: Cell::new(0),
+        }
+    }
+
+    /// Get the expected output after the function starting at `location`, without the comment
+    /// markers.
+    pub fn expected_output(&self, location: Location) -> Vec<&'a str> {
+        let mut lines = self.source.lines().skip(location.line_number - 1);
+        lines.by_ref().find(|line| line.starts_with('}'));
+        lines
+            .take_while(|line| line.starts_with(';'))
+            .map(|line| {
+                let text = &line[1..];
+                if text.starts_with(' ') {
+                    &text[1..]
+                } else {
+                    text
+                }
+            })
+            .map(str::trim_end)
+            .collect()
+    }
+
+    /// Replace the expected output after the function starting at `location` with `actual`.
+    ///
+    /// Functions must be blessed in the order they appear in the file.
+    fn bless(&self, location: Location, actual: &[&str]) -> SubtestResult<()> {
+        let old = fs::read_to_string(&self.path).map_err(|e| e.to_string())?;
+        let start = location.line_number as isize - 1 + self.line_diff.get();
+        let mut lines = old.lines();
+        let mut new = String::new();
+
+        // Copy everything up to and including the function's closing brace.
+        for line in lines.by_ref().take(start as usize) {
+            new.push_str(line);
+            new.push('\n');
+        }
+        for line in lines.by_ref() {
+            new.push_str(line);
+            new.push('\n');
+            if line.starts_with('}') {
+                break;
+            }
+        }
+
+        // Replace the old expectation.
+        for line in actual {
+            new.push(';');
+            if !line.is_empty() {
+                new.push(' ');
+                new.push_str(line);
+            }
+            new.push('\n');
+        }
+        for line in lines.skip_while(|line| line.starts_with(';')) {
+            new.push_str(line);
+            new.push('\n');
+        }
+
+        let added = new.lines().count() as isize - old.lines().count() as isize;
+        self.line_diff.set(self.line_diff.get() + added);
+        fs::write(&self.path, new).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_precise_output_commands, FileUpdate};
+    use cranelift_codegen::settings;
+    use cranelift_reader::{IsaSpec, Location, TestCommand};
+    use std::fs;
+
+    const SOURCE: &str = "test vcode precise-output
+
+function %f() {
+block0:
+    return
+}
+; old line
+;
+; old last line
+
+function %g() {
+block0:
+    return
+}
+";
+
+    fn location(line_number: usize) -> Location {
+        Location { line_number }
+    }
+
+    #[test]
+    fn expected_output() {
+        let file = FileUpdate::new("unused.clif".as_ref(), SOURCE);
+        assert_eq!(
+            file.expected_output(location(3)),
+            ["old line", "", "old last line"]
+        );
+        assert!(file.expected_output(location(11)).is_empty());
+    }
+
+    #[test]
+    fn bless() {
+        let path = std::env::temp_dir().join(format!(
+            "cranelift-filetests-bless-{}.clif",
+            std::process::id()
+        ));
+        fs::write(&path, SOURCE).unwrap();
+
+        let file = FileUpdate::new(&path, SOURCE);
+        file.bless(location(3), &["new line"]).unwrap();
+        file.bless(location(11), &["first", "", "last"]).unwrap();
+        let blessed = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            blessed,
+            "test vcode precise-output
+
+function %f() {
+block0:
+    return
+}
+; new line
+
+function %g() {
+block0:
+    return
+}
+; first
+;
+; last
+"
+        );
+    }
+
+    #[test]
+    fn one_precise_output_command() {
+        let no_isa = IsaSpec::None(settings::Flags::new(settings::builder()));
+        let vcode = TestCommand::new("vcode arch=arm64 precise-output");
+        let compile = TestCommand::new("compile precise-output");
+        let cat = TestCommand::new("cat");
+
+        assert!(check_precise_output_commands(&[&vcode, &cat], &no_isa).is_ok());
+        assert_eq!(
+            check_precise_output_commands(&[&vcode, &cat, &compile], &no_isa),
+            Err(
+                "only one precise-output test command is allowed per file, found \
+                 `test vcode` and `test compile`"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -6,6 +6,7 @@
 use crate::concurrent::{ConcurrentRunner, Reply};
 use crate::runone;
 use crate::TestResult;
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::timing;
 use std::error::Error;
 use std::ffi::OsStr;
@@ -80,11 +81,14 @@ pub struct TestRunner {
     ticks_since_progress: usize,
 
     threads: Option<ConcurrentRunner>,
+
+    // Backend used for the targets of the tests.
+    backend_variant: BackendVariant,
 }
 
 impl TestRunner {
     /// Create a new blank TestRunner.
-    pub fn new(verbose: bool, report_times: bool) -> Self {
+    pub fn new(verbose: bool, report_times: bool, backend_variant: BackendVariant) -> Self {
         Self {
             verbose,
             report_times,
@@ -95,6 +99,7 @@ impl TestRunner {
             errors: 0,
             ticks_since_progress: 0,
             threads: None,
+            backend_variant,
         }
     }
 
@@ -119,7 +124,7 @@ impl TestRunner {
     /// Begin running tests concurrently.
     pub fn start_threads(&mut self) {
         assert!(self.threads.is_none());
-        self.threads = Some(ConcurrentRunner::new());
+        self.threads = Some(ConcurrentRunner::new(self.backend_variant));
     }
 
     /// Scan any directories pushed so far.
@@ -213,7 +218,8 @@ impl TestRunner {
             } else {
                 // Run test synchronously.
                 self.tests[jobid].state = State::Running;
-                let result = runone::run(self.tests[jobid].path(), None, None);
+                let result =
+                    runone::run(self.tests[jobid].path(), None, None, self.backend_variant);
                 self.finish_job(jobid, result);
             }
             self.new_tests = jobid + 1;
@@ -235,7 +241,12 @@ impl TestRunner {
             targ => Some(targ),
         };
 
-        result = runone::run(self.tests[0].path(), Some(passes), specified_target);
+        result = runone::run(
+            self.tests[0].path(),
+            Some(passes),
+            specified_target,
+            self.backend_variant,
+        );
         self.finish_job(0, result);
     }
 
@@ -1,9 +1,11 @@
 //! Run the tests in a single test file.
 
+use crate::precise_output::{check_precise_output_commands, FileUpdate};
 use crate::subtest::{Context, SubTest, SubtestResult};
+use crate::test_module;
 use crate::{new_subtest, TestResult};
 use cranelift_codegen::ir::Function;
-use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::isa::{BackendVariant, TargetIsa};
 use cranelift_codegen::print_errors::pretty_verifier_error;
 use cranelift_codegen::settings::Flags;
 use cranelift_codegen::timing;
@@ -24,10 +26,15 @@ fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
     Ok(buffer)
 }
 
-/// Load `path` and run the test in it.
+/// Load `path` and run the test in it, looking up its targets with the given `backend_variant`.
 ///
 /// If running this test causes a panic, it will propagate as normal.
-pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> TestResult {
+pub fn run(
+    path: &Path,
+    passes: Option<&[String]>,
+    target: Option<&str>,
+    backend_variant: BackendVariant,
+) -> TestResult {
     let _tt = timing::process_file();
     info!("---\nFile: {}", path.to_string_lossy());
     let started = time::Instant::now();
@@ -35,6 +42,7 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
     let options = ParseOptions {
         target,
         passes,
+        backend_variant,
         ..ParseOptions::default()
     };
 
@@ -52,17 +60,44 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
         }
     };
 
-    if testfile.functions.is_empty() {
+    if testfile.functions.is_empty() && testfile.data_objects.is_empty() {
         return Err("no functions found".to_string());
     }
 
-    // Parse the test commands.
-    let mut tests = testfile
+    // `test module` works on the whole file rather than on one function at a time, so it is split
+    // off from the other test commands.
+    let (module_commands, commands): (Vec<_>, Vec<_>) = testfile
         .commands
         .iter()
+        .partition(|command| command.command == "module");
+    for command in &module_commands {
+        test_module::subtest(command)?;
+    }
+
+    check_precise_output_commands(&commands, &testfile.isa_spec)?;
+
+    // Parse the test commands.
+    let mut tests = commands
+        .into_iter()
         .map(new_subtest)
         .collect::<SubtestResult<Vec<_>>>()?;
 
+    if !module_commands.is_empty() {
+        // The module test consumes its own copy of the file, so the ISAs can be handed over to
+        // the module's backend.
+        let options = ParseOptions {
+            target,
+            passes,
+            backend_variant,
+            ..ParseOptions::default()
+        };
+        let module_testfile = parse_test(&buffer, options).map_err(|e| e.to_string())?;
+        test_module::run(module_testfile)?;
+        if tests.is_empty() {
+            return Ok(started.elapsed());
+        }
+    }
+
     // Flags to use for those tests that don't need an ISA.
     // This is the cumulative effect of all the `set` commands in the file.
     let flags = match testfile.isa_spec {
@@ -84,10 +119,12 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
         Some(t) => t,
     };
 
+    let file_update = FileUpdate::new(path, &buffer);
     for (func, details) in testfile.functions {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
             details,
+            file_update: &file_update,
             verified: false,
             flags,
             isa: None,
@@ -1,5 +1,6 @@
 //! `SubTest` trait.
 
+use crate::precise_output::FileUpdate;
 use cranelift_codegen::ir::Function;
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::settings::{Flags, FlagsOrIsa};
@@ -17,6 +18,9 @@ pub struct Context<'a> {
     /// Additional details about the function from the parser.
     pub details: Details<'a>,
 
+    /// The test file, for checking and blessing `precise-output` expectations.
+    pub file_update: &'a FileUpdate<'a>,
+
     /// Was the function verified before running this test?
     pub verified: bool,
 
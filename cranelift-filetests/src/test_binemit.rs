@@ -2,8 +2,13 @@
 //!
 //! The `binemit` test command generates binary machine code for every instruction in the input
 //! functions and compares the results to the expected output.
+//!
+//! By default the expected output is given by `bin:` directives on the instructions. With the
+//! `precise-output` option, a listing of the whole function's machine code is compared with the
+//! expectation after the function instead.
 
 use crate::match_directive::match_directive;
+use crate::precise_output::{precise_output_flag, run_precise_output};
 use crate::subtest::{Context, SubTest, SubtestResult};
 use cranelift_codegen::binemit::{self, CodeInfo, CodeSink, RegDiversions};
 use cranelift_codegen::dbg::DisplayList;
@@ -19,15 +24,15 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Write;
 
-struct TestBinEmit;
+struct TestBinEmit {
+    precise_output: bool,
+}
 
 pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
     assert_eq!(parsed.command, "binemit");
-    if !parsed.options.is_empty() {
-        Err(format!("No options allowed on {}", parsed))
-    } else {
-        Ok(Box::new(TestBinEmit))
-    }
+    Ok(Box::new(TestBinEmit {
+        precise_output: precise_output_flag(parsed)?,
+    }))
 }
 
 /// Code sink that generates text.
@@ -212,14 +217,16 @@ impl SubTest for TestBinEmit {
                 }
             }
         }
-        if bins.is_empty() {
+        if bins.is_empty() && !self.precise_output {
             return Err("No 'bin:' directives found".to_string());
         }
 
-        // Now emit all instructions.
+        // Now emit all instructions, keeping a listing for `precise-output`.
         let mut sink = TextSink::new();
+        let mut listing = String::new();
         for block in func.layout.blocks() {
             divert.clear();
+            writeln!(listing, "{}:", block).unwrap();
             // Correct header offsets should have been computed by `relax_branches()`.
             assert_eq!(
                 sink.offset, func.offsets[block],
@@ -248,6 +255,14 @@ impl SubTest for TestBinEmit {
                         func.dfg.display_inst(inst, isa)
                     );
                 }
+                writeln!(
+                    listing,
+                    "  {:04x}: {:<24} {}",
+                    offset,
+                    sink.text.trim(),
+                    func.dfg.display_inst(inst, isa)
+                )
+                .unwrap();
 
                 // Check against bin: directives.
                 if let Some(want) = bins.remove(&inst) {
@@ -317,7 +332,11 @@ impl SubTest for TestBinEmit {
             ));
         }
 
-        Ok(())
+        if self.precise_output {
+            run_precise_output(&listing, context)
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -1,7 +1,7 @@
 //! The `cat` subtest.
 
 use crate::subtest::{self, Context, SubTest, SubtestResult};
-use cranelift_codegen::ir::Function;
+use cranelift_codegen::ir::{DisplayFunctionAnnotations, Function};
 use cranelift_reader::TestCommand;
 use std::borrow::Cow;
 
@@ -32,6 +32,11 @@ impl SubTest for TestCat {
     }
 
     fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
-        subtest::run_filecheck(&func.display(context.isa).to_string(), context)
+        let annotations = DisplayFunctionAnnotations {
+            isa: context.isa,
+            value_ranges: None,
+            linkage: context.details.linkage,
+        };
+        subtest::run_filecheck(&func.display_with(annotations).to_string(), context)
     }
 }
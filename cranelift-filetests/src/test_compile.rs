@@ -1,7 +1,10 @@
 //! Test command for testing the code generator pipeline
 //!
-//! The `compile` test command runs each function through the full code generator pipeline
+//! The `compile` test command runs each function through the full code generator pipeline.
+//! With the `precise-output` option, the final function is compared with the expectation after
+//! it instead of being run through filecheck.
 
+use crate::precise_output::{precise_output_flag, run_precise_output};
 use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
 use cranelift_codegen;
 use cranelift_codegen::binemit::{self, CodeInfo};
@@ -12,15 +15,15 @@ use cranelift_reader::TestCommand;
 use log::info;
 use std::borrow::Cow;
 
-struct TestCompile;
+struct TestCompile {
+    precise_output: bool,
+}
 
 pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
     assert_eq!(parsed.command, "compile");
-    if !parsed.options.is_empty() {
-        Err(format!("No options allowed on {}", parsed))
-    } else {
-        Ok(Box::new(TestCompile))
-    }
+    Ok(Box::new(TestCompile {
+        precise_output: precise_output_flag(parsed)?,
+    }))
 }
 
 impl SubTest for TestCompile {
@@ -66,9 +69,13 @@ impl SubTest for TestCompile {
             ));
         }
 
-        // Run final code through filecheck.
+        // Check the final code.
         let text = comp_ctx.func.display(Some(isa)).to_string();
-        run_filecheck(&text, context)
+        if self.precise_output {
+            run_precise_output(&text, context)
+        } else {
+            run_filecheck(&text, context)
+        }
     }
 }
 
@@ -0,0 +1,367 @@
+//! Test command for compiling a whole file as a module.
+//!
+//! The `module` test command declares all the functions and data objects of a file in a
+//! `cranelift_module::Module`, defines them with an object file backend and writes out the object.
+//! The data objects are then printed, followed by the compiled functions and the symbols and
+//! relocations of the emitted object, and the result is verified by filecheck using the directives
+//! in the preamble and in the functions.
+//!
+//! Functions without a linkage annotation are exported. Names that are referenced but not defined
+//! in the file are declared as imports.
+
+use crate::subtest::SubtestResult;
+use cranelift_codegen::ir::{
+    self, DataInit, DataObject, DisplayFunctionAnnotations, ExternalName, Linkage,
+};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::print_errors::pretty_error;
+use cranelift_codegen::Context;
+use cranelift_module::{
+    default_libcall_names, DataContext, DataId, FuncId, FuncOrDataId, Module, ModuleError,
+};
+use cranelift_object::{ObjectBackend, ObjectBuilder, ObjectTrapCollection};
+use cranelift_reader::{Details, IsaSpec, TestCommand, TestFile};
+use filecheck::{Checker, CheckerBuilder, NO_VARIABLES};
+use object::{Object, ObjectSection, RelocationTarget, SectionIndex, SymbolKind};
+use std::fmt::Write;
+
+/// Check the options given to a `test module` command.
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<()> {
+    assert_eq!(parsed.command, "module");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(())
+    }
+}
+
+/// Compile the whole of `testfile` once for each of its target ISAs.
+pub fn run(testfile: TestFile) -> SubtestResult<()> {
+    let checker = build_filechecker(&testfile)?;
+    let isas = match testfile.isa_spec {
+        IsaSpec::None(_) => return Err("test module requires an ISA".to_string()),
+        IsaSpec::Some(isas) => isas,
+    };
+
+    for isa in isas {
+        let name = format!("module({})", isa.name());
+        let text = compile_module(isa, &testfile.functions, &testfile.data_objects)
+            .map_err(|e| format!("{}:\n{}", name, e))?;
+        let matched = checker
+            .check(&text, NO_VARIABLES)
+            .map_err(|e| format!("{}:\nfilecheck: {}", name, e))?;
+        if !matched {
+            let (_, explain) = checker
+                .explain(&text, NO_VARIABLES)
+                .map_err(|e| format!("{}:\nexplain: {}", name, e))?;
+            return Err(format!(
+                "{}:\nfilecheck failed:\n{}{}",
+                name, checker, explain
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Build a filechecker using the directives in the file preamble and in all the functions.
+fn build_filechecker(testfile: &TestFile) -> SubtestResult<Checker> {
+    let mut builder = CheckerBuilder::new();
+    let function_comments = testfile
+        .functions
+        .iter()
+        .flat_map(|(_, details)| details.comments.iter());
+    for comment in testfile.preamble_comments.iter().chain(function_comments) {
+        builder
+            .directive(comment.text)
+            .map_err(|e| format!("filecheck: {}", e))?;
+    }
+    Ok(builder.finish())
+}
+
+/// Define `functions` and `data_objects` in a module for `isa` and return their text form.
+fn compile_module(
+    isa: Box<dyn TargetIsa>,
+    functions: &[(ir::Function, Details)],
+    data_objects: &[DataObject],
+) -> SubtestResult<String> {
+    let builder = ObjectBuilder::new(
+        isa,
+        "module".to_string(),
+        ObjectTrapCollection::Disabled,
+        default_libcall_names(),
+    );
+    let mut module = Module::<ObjectBackend>::new(builder);
+
+    // Declare everything up front so definitions can refer to each other in any order.
+    let mut func_ids = Vec::new();
+    for (func, details) in functions {
+        let linkage = details.linkage.unwrap_or(Linkage::Export);
+        let id = module
+            .declare_function(&symbol_name(&func.name)?, linkage, &func.signature)
+            .map_err(|e| e.to_string())?;
+        func_ids.push((id, linkage));
+    }
+    let mut data_ids = Vec::new();
+    for data in data_objects {
+        let id = module
+            .declare_data(
+                &symbol_name(&data.name)?,
+                data.linkage,
+                data.writable,
+                data.align,
+            )
+            .map_err(|e| e.to_string())?;
+        data_ids.push(id);
+    }
+
+    let mut text = String::new();
+    for (data, &id) in data_objects.iter().zip(&data_ids) {
+        if data.linkage.is_definable() {
+            define_data(&mut module, id, data)?;
+        }
+        write!(text, "{}", data).unwrap();
+    }
+
+    for ((func, _), &(id, linkage)) in functions.iter().zip(&func_ids) {
+        let mut ctx = Context::for_function(func.clone());
+        let renamed = resolve_names(&mut module, &mut ctx.func)?;
+        module.define_function(id, &mut ctx).map_err(|e| match e {
+            ModuleError::Compilation(e) => pretty_error(&ctx.func, Some(module.isa()), e),
+            e => e.to_string(),
+        })?;
+
+        // Print the compiled function with the names used in the file.
+        for (entity, name) in renamed {
+            match entity {
+                Renamed::Func(fnref) => ctx.func.dfg.ext_funcs[fnref].name = name,
+                Renamed::GlobalValue(gv) => {
+                    if let ir::GlobalValueData::Symbol {
+                        name: ref mut n, ..
+                    } = ctx.func.global_values[gv]
+                    {
+                        *n = name;
+                    }
+                }
+            }
+        }
+        let annotations = DisplayFunctionAnnotations {
+            isa: Some(module.isa()),
+            value_ranges: None,
+            linkage: Some(linkage),
+        };
+        write!(text, "{}", ctx.func.display_with(annotations)).unwrap();
+    }
+
+    let object = module.finish().emit()?;
+    describe_object(&object, &mut text)?;
+    Ok(text)
+}
+
+/// Print the symbols and relocations of the emitted `object`, sorted so that they don't depend on
+/// the order the backend happened to add them in:
+///
+/// ```text
+/// symbol message: local .rodata
+/// symbol puts: undefined
+/// reloc .data.rel.ro+8: Absolute 64 message+2
+/// ```
+fn describe_object(object: &[u8], text: &mut String) -> SubtestResult<()> {
+    let file = object::File::parse(object).map_err(|e| format!("emitted object: {}", e))?;
+    let section_name = |index| {
+        file.section_by_index(index)
+            .and_then(|section| section.name().map(str::to_string))
+            .unwrap_or_default()
+    };
+
+    let mut symbols = Vec::new();
+    for (_, symbol) in file.symbols() {
+        let name = match symbol.name() {
+            Some(name) if is_named_symbol(&symbol) && !name.is_empty() => name,
+            _ => continue,
+        };
+        let line = if symbol.is_undefined() {
+            format!("symbol {}: undefined", name)
+        } else {
+            let binding = if symbol.is_weak() {
+                "weak"
+            } else if symbol.is_global() {
+                "global"
+            } else {
+                "local"
+            };
+            let section = symbol.section_index().map(section_name).unwrap_or_default();
+            format!("symbol {}: {} {}", name, binding, section)
+        };
+        symbols.push(line);
+    }
+    symbols.sort();
+
+    let mut relocs = Vec::new();
+    for section in file.sections() {
+        let name = section.name().unwrap_or("").to_string();
+        for (offset, reloc) in section.relocations() {
+            let (target, addend) = match reloc.target() {
+                RelocationTarget::Symbol(index) => match file.symbol_by_index(index) {
+                    Some(ref symbol) if is_named_symbol(symbol) => {
+                        (symbol.name().unwrap_or("").to_string(), reloc.addend())
+                    }
+                    // The object writer may refer to a section instead of a symbol in it.
+                    Some(ref symbol) if symbol.section_index().is_some() => {
+                        let section = symbol.section_index().unwrap();
+                        symbol_at(&file, section, reloc.addend())
+                    }
+                    _ => return Err(format!("relocation against unknown {:?}", index)),
+                },
+                RelocationTarget::Section(section) => symbol_at(&file, section, reloc.addend()),
+            };
+            let mut line = format!("{:?} {} {}", reloc.kind(), reloc.size(), target);
+            if addend != 0 {
+                write!(line, "{:+}", addend).unwrap();
+            }
+            relocs.push((name.clone(), offset, line));
+        }
+    }
+    relocs.sort();
+
+    for line in symbols {
+        writeln!(text, "{}", line).unwrap();
+    }
+    for (section, offset, line) in relocs {
+        writeln!(text, "reloc {}+{}: {}", section, offset, line).unwrap();
+    }
+    Ok(())
+}
+
+/// Is `symbol` one of the functions or data objects of the module, rather than a file or section
+/// symbol?
+fn is_named_symbol(symbol: &object::Symbol) -> bool {
+    match symbol.kind() {
+        SymbolKind::Text | SymbolKind::Data | SymbolKind::Unknown => symbol.name().is_some(),
+        _ => false,
+    }
+}
+
+/// Find the symbol that `address` in the section `index` points into, and return its name with
+/// the offset from its start.
+fn symbol_at(file: &object::File, index: SectionIndex, address: i64) -> (String, i64) {
+    file.symbols()
+        .map(|(_, symbol)| symbol)
+        .filter(|symbol| is_named_symbol(symbol) && symbol.section_index() == Some(index))
+        .filter(|symbol| symbol.address() as i64 <= address)
+        .max_by_key(|symbol| symbol.address())
+        .map(|symbol| {
+            let name = symbol.name().unwrap_or("").to_string();
+            (name, address - symbol.address() as i64)
+        })
+        .unwrap_or_else(|| {
+            let section = file
+                .section_by_index(index)
+                .and_then(|section| section.name().map(str::to_string))
+                .unwrap_or_default();
+            (section, address)
+        })
+}
+
+/// An entity whose external name was rewritten by `resolve_names`.
+enum Renamed {
+    Func(ir::FuncRef),
+    GlobalValue(ir::GlobalValue),
+}
+
+/// Rewrite the external names used in `func` to the names `module` gives its declarations, and
+/// return the original names.
+fn resolve_names(
+    module: &mut Module<ObjectBackend>,
+    func: &mut ir::Function,
+) -> SubtestResult<Vec<(Renamed, ExternalName)>> {
+    let mut renamed = Vec::new();
+    for (fnref, ext_func) in func.dfg.ext_funcs.iter_mut() {
+        if let ExternalName::LibCall(_) = ext_func.name {
+            continue;
+        }
+        let signature = &func.dfg.signatures[ext_func.signature];
+        let id = func_id(module, &ext_func.name, signature)?;
+        let name = std::mem::replace(&mut ext_func.name, FuncOrDataId::Func(id).into());
+        renamed.push((Renamed::Func(fnref), name));
+    }
+    for (gv, gv_data) in func.global_values.iter_mut() {
+        if let ir::GlobalValueData::Symbol { ref mut name, .. } = *gv_data {
+            if let ExternalName::LibCall(_) = *name {
+                continue;
+            }
+            let symbol = symbol_name(name)?;
+            let id = match module.get_name(&symbol) {
+                Some(id) => id,
+                None => FuncOrDataId::Data(
+                    module
+                        .declare_data(&symbol, Linkage::Import, false, None)
+                        .map_err(|e| e.to_string())?,
+                ),
+            };
+            let original = std::mem::replace(name, id.into());
+            renamed.push((Renamed::GlobalValue(gv), original));
+        }
+    }
+    Ok(renamed)
+}
+
+/// Define `data` in `module`, including the addresses written into it.
+fn define_data(
+    module: &mut Module<ObjectBackend>,
+    id: DataId,
+    data: &DataObject,
+) -> SubtestResult<()> {
+    let mut data_ctx = DataContext::new();
+    match data.init {
+        DataInit::Uninitialized => {
+            return Err(format!("data object {} has no contents", data.name));
+        }
+        DataInit::Zeros { size } => data_ctx.define_zeroinit(size),
+        DataInit::Bytes { ref contents } => data_ctx.define(contents.clone().into_boxed_slice()),
+    }
+    for &(offset, ref name) in &data.function_relocs {
+        let signature = module.make_signature();
+        let func = func_id(module, name, &signature)?;
+        let fnref = module.declare_func_in_data(func, &mut data_ctx);
+        data_ctx.write_function_addr(offset, fnref);
+    }
+    for &(offset, ref name, addend) in &data.data_relocs {
+        let symbol = symbol_name(name)?;
+        let target = match module.get_name(&symbol) {
+            Some(FuncOrDataId::Data(id)) => id,
+            Some(FuncOrDataId::Func(_)) => {
+                return Err(format!("{} is a function, not a data object", name));
+            }
+            None => module
+                .declare_data(&symbol, Linkage::Import, false, None)
+                .map_err(|e| e.to_string())?,
+        };
+        let gv = module.declare_data_in_data(target, &mut data_ctx);
+        data_ctx.write_data_addr(offset, gv, addend);
+    }
+    module.define_data(id, &data_ctx).map_err(|e| e.to_string())
+}
+
+/// Look up the function called `name`, declaring it as an import if the file doesn't define it.
+fn func_id(
+    module: &mut Module<ObjectBackend>,
+    name: &ExternalName,
+    signature: &ir::Signature,
+) -> SubtestResult<FuncId> {
+    let symbol = symbol_name(name)?;
+    match module.get_name(&symbol) {
+        Some(FuncOrDataId::Func(id)) => Ok(id),
+        Some(FuncOrDataId::Data(_)) => Err(format!("{} is a data object, not a function", name)),
+        None => module
+            .declare_function(&symbol, Linkage::Import, signature)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Get the symbol name for a `%name` in the file.
+fn symbol_name(name: &ExternalName) -> SubtestResult<String> {
+    match *name {
+        ExternalName::TestCase { .. } => Ok(name.to_string()[1..].to_string()),
+        _ => Err(format!("test module needs %name symbols, not {}", name)),
+    }
+}
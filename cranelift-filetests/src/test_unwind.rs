@@ -72,7 +72,8 @@ impl SubTest for TestUnwind {
     }
 }
 
-fn print_unwind_info(text: &mut String, mem: &[u8]) {
+/// Decode the Windows x64 unwind information in `mem` and print it to `text`.
+pub(crate) fn print_unwind_info(text: &mut String, mem: &[u8]) {
     let info = UnwindInfo::from_slice(mem);
 
     // Assert correct alignment and padding of the unwind information
@@ -198,9 +199,9 @@ impl From<u8> for UnwindOperation {
             3 => Self::SetFramePointer,
             4 => Self::SaveNonvolatileRegister,
             5 => Self::SaveNonvolatileRegisterFar,
-            6 => Self::SaveXmm128,
-            7 => Self::SaveXmm128Far,
-            8 => Self::PushMachineFrame,
+            8 => Self::SaveXmm128,
+            9 => Self::SaveXmm128Far,
+            10 => Self::PushMachineFrame,
             _ => panic!("unsupported unwind operation"),
         }
     }
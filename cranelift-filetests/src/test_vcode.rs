@@ -1,6 +1,8 @@
+use crate::precise_output::run_precise_output;
 use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use crate::test_unwind::print_unwind_info;
 use cranelift_codegen::ir::Function;
-use cranelift_codegen::isa::lookup_mach_backend;
+use cranelift_codegen::isa::{lookup_mach_backend, LookupError};
 use cranelift_reader::{TestCommand, TestOption};
 use target_lexicon::Triple;
 
@@ -11,22 +13,30 @@ use std::string::String;
 
 struct TestVCode {
     arch: String,
+    precise_output: bool,
 }
 
 pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
     assert_eq!(parsed.command, "vcode");
 
     let mut arch = "arm64".to_string();
+    let mut precise_output = false;
     for option in &parsed.options {
         match option {
             TestOption::Value(k, v) if k == &"arch" => {
                 arch = v.to_string();
             }
+            TestOption::Flag("precise-output") => {
+                precise_output = true;
+            }
             _ => {}
         }
     }
 
-    Ok(Box::new(TestVCode { arch }))
+    Ok(Box::new(TestVCode {
+        arch,
+        precise_output,
+    }))
 }
 
 impl SubTest for TestVCode {
@@ -48,14 +58,34 @@ impl SubTest for TestVCode {
         let triple =
             Triple::from_str(&self.arch).map_err(|_| format!("Unknown arch: '{}'", self.arch))?;
 
-        let backend = lookup_mach_backend(triple)
-            .map_err(|_| format!("Could not look up backend for arch '{}'", self.arch))?;
+        let backend = match lookup_mach_backend(triple, context.flags.clone()) {
+            Ok(backend) => backend,
+            Err(LookupError::SupportDisabled) => {
+                info!(
+                    "skipping vcode test: support for '{}' is disabled",
+                    self.arch
+                );
+                return Ok(());
+            }
+            Err(_) => {
+                return Err(format!(
+                    "Could not look up backend for arch '{}'",
+                    self.arch
+                ))
+            }
+        };
 
-        let text = backend
+        let result = backend
             .compile_function(func, /* want_disasm = */ true)
-            .map_err(|e| format!("Error from backend compilation: {:?}", e))?
-            .disasm
-            .unwrap();
+            .map_err(|e| format!("Error from backend compilation: {:?}", e))?;
+        let mut text = result.disasm.unwrap();
+        if let Some(info) = result.unwind_info {
+            print_unwind_info(&mut text, &info.data);
+        }
+
+        if self.precise_output {
+            return run_precise_output(&text, context);
+        }
 
         info!("text input to filecheck is:\n{}\n", text);
 
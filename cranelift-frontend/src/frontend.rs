@@ -990,7 +990,7 @@ mod tests {
 
         let target = isa::lookup(triple)
             .ok()
-            .map(|b| b.finish(shared_flags))
+            .map(|b| b.as_builder().finish(shared_flags))
             .expect("This test requires arm support.");
 
         let mut sig = Signature::new(target.default_call_conv());
@@ -1051,7 +1051,7 @@ block0:
 
         let target = isa::lookup(triple)
             .ok()
-            .map(|b| b.finish(shared_flags))
+            .map(|b| b.as_builder().finish(shared_flags))
             .expect("This test requires arm support.");
 
         let mut sig = Signature::new(target.default_call_conv());
@@ -1108,7 +1108,7 @@ block0:
 
         let target = isa::lookup(triple)
             .ok()
-            .map(|b| b.finish(shared_flags))
+            .map(|b| b.as_builder().finish(shared_flags))
             .expect("This test requires arm support.");
 
         let mut sig = Signature::new(target.default_call_conv());
@@ -1168,7 +1168,7 @@ block0:
 
         let target = isa::lookup(triple)
             .ok()
-            .map(|b| b.finish(shared_flags))
+            .map(|b| b.as_builder().finish(shared_flags))
             .expect("This test requires arm support.");
 
         let mut sig = Signature::new(target.default_call_conv());
@@ -1220,7 +1220,7 @@ block0:
 
         let target = isa::lookup(triple)
             .ok()
-            .map(|b| b.finish(shared_flags))
+            .map(|b| b.as_builder().finish(shared_flags))
             .expect("This test requires arm support.");
 
         let mut sig = Signature::new(target.default_call_conv());
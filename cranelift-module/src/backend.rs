@@ -4,6 +4,7 @@ use crate::DataContext;
 use crate::DataId;
 use crate::FuncId;
 use crate::Linkage;
+use crate::ModuleError;
 use crate::ModuleNamespace;
 use crate::ModuleResult;
 use core::marker;
@@ -85,6 +86,21 @@ where
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction>;
 
+    /// Replace the definition of a function that was already defined.
+    ///
+    /// Backends that can redirect the calls to a function to a new body, such as JITs that
+    /// call functions through stubs, override this. By default redefinitions are rejected.
+    fn redefine_function(
+        &mut self,
+        _id: FuncId,
+        name: &str,
+        _ctx: &Context,
+        _namespace: &ModuleNamespace<Self>,
+        _code_size: u32,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        Err(ModuleError::DuplicateDefinition(name.to_owned()))
+    }
+
     /// Define a zero-initialized data object of the given size.
     ///
     /// Data objects must be declared before being defined.
@@ -132,6 +148,12 @@ where
     /// Return the finalized artifact from the backend, if relevant.
     fn get_finalized_function(&self, func: &Self::CompiledFunction) -> Self::FinalizedFunction;
 
+    /// Return the finalized artifact for a function which is declared but not defined yet, if
+    /// the backend compiles such functions lazily when they are first called.
+    fn get_lazy_function(&self, _id: FuncId) -> Option<Self::FinalizedFunction> {
+        None
+    }
+
     /// Perform all outstanding relocations on the given data object. This requires all
     /// `Local` and `Export` entities referenced to be defined.
     ///
@@ -174,5 +196,13 @@ pub fn default_libcall_names() -> Box<dyn Fn(ir::LibCall) -> String> {
         ir::LibCall::Memcpy => "memcpy".to_owned(),
         ir::LibCall::Memset => "memset".to_owned(),
         ir::LibCall::Memmove => "memmove".to_owned(),
+        ir::LibCall::UdivI64 => "__udivdi3".to_owned(),
+        ir::LibCall::SdivI64 => "__divdi3".to_owned(),
+        ir::LibCall::UremI64 => "__umoddi3".to_owned(),
+        ir::LibCall::SremI64 => "__moddi3".to_owned(),
+        ir::LibCall::UdivI128 => "__udivti3".to_owned(),
+        ir::LibCall::SdivI128 => "__divti3".to_owned(),
+        ir::LibCall::UremI128 => "__umodti3".to_owned(),
+        ir::LibCall::SremI128 => "__modti3".to_owned(),
     })
 }
@@ -39,9 +39,10 @@ mod module;
 pub use crate::backend::{default_libcall_names, Backend};
 pub use crate::data_context::{DataContext, DataDescription, Init};
 pub use crate::module::{
-    DataId, FuncId, FuncOrDataId, Linkage, Module, ModuleError, ModuleFunction, ModuleNamespace,
+    DataId, FuncId, FuncOrDataId, Module, ModuleError, ModuleFunction, ModuleNamespace,
     ModuleResult,
 };
+pub use cranelift_codegen::ir::Linkage;
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -10,6 +10,7 @@ use crate::data_context::DataContext;
 use crate::Backend;
 use cranelift_codegen::binemit::{self, CodeInfo};
 use cranelift_codegen::entity::{entity_impl, PrimaryMap};
+use cranelift_codegen::ir::Linkage;
 use cranelift_codegen::{ir, isa, CodegenError, Context};
 use log::info;
 use std::borrow::ToOwned;
@@ -47,53 +48,6 @@ impl From<DataId> for ir::ExternalName {
     }
 }
 
-/// Linkage refers to where an entity is defined and who can see it.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum Linkage {
-    /// Defined outside of a module.
-    Import,
-    /// Defined inside the module, but not visible outside it.
-    Local,
-    /// Defined inside the module, visible outside it, and may be preempted.
-    Preemptible,
-    /// Defined inside the module, and visible outside it.
-    Export,
-}
-
-impl Linkage {
-    fn merge(a: Self, b: Self) -> Self {
-        match a {
-            Self::Export => Self::Export,
-            Self::Preemptible => match b {
-                Self::Export => Self::Export,
-                _ => Self::Preemptible,
-            },
-            Self::Local => match b {
-                Self::Export => Self::Export,
-                Self::Preemptible => Self::Preemptible,
-                _ => Self::Local,
-            },
-            Self::Import => b,
-        }
-    }
-
-    /// Test whether this linkage can have a definition.
-    pub fn is_definable(self) -> bool {
-        match self {
-            Self::Import => false,
-            Self::Local | Self::Preemptible | Self::Export => true,
-        }
-    }
-
-    /// Test whether this linkage will have a definition that cannot be preempted.
-    pub fn is_final(self) -> bool {
-        match self {
-            Self::Import | Self::Preemptible => false,
-            Self::Local | Self::Export => true,
-        }
-    }
-}
-
 /// A declared name may refer to either a function or data declaration
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub enum FuncOrDataId {
@@ -139,6 +93,9 @@ pub enum ModuleError {
     /// Indicates an identifier was defined, but was declared as an import
     #[error("Invalid to define identifier declared as an import: {0}")]
     InvalidImportDefinition(String),
+    /// Indicates a function was redefined with a `TargetIsa` that doesn't match the module's
+    #[error("Incompatible TargetIsa: {0}")]
+    IncompatibleIsa(String),
     /// Wraps a `cranelift-codegen` error
     #[error("Compilation error: {0}")]
     Compilation(#[from] CodegenError),
@@ -573,6 +530,49 @@ where
         Ok(total_size)
     }
 
+    /// Define a function again, replacing its previous definition, for example to recompile a
+    /// frequently called function with a higher `opt_level`.
+    ///
+    /// The function is compiled with `isa` rather than the module's own `TargetIsa`. It must be
+    /// the same backend for the same target, and agree on the shared flags that affect calls and
+    /// relocations, such as `is_pic`; otherwise `ModuleError::IncompatibleIsa` is returned. Calls
+    /// to the function use the new definition once it is finalized. Functions that aren't defined
+    /// yet are defined as with `define_function`. Backends which can't redirect calls to a new
+    /// definition return `ModuleError::DuplicateDefinition`.
+    ///
+    /// Returns the size of the function's code and constant data.
+    pub fn redefine_function(
+        &mut self,
+        func: FuncId,
+        ctx: &mut Context,
+        isa: &dyn isa::TargetIsa,
+    ) -> ModuleResult<binemit::CodeOffset> {
+        info!("redefining function {}: {}", func, ctx.func.display(isa));
+        let info = &self.contents.functions[func];
+        if !info.decl.linkage.is_definable() {
+            return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+        }
+        check_redefinition_isa(self.backend.isa(), isa)?;
+        let CodeInfo { total_size, .. } = ctx.compile(isa)?;
+
+        let namespace = ModuleNamespace::<B> {
+            contents: &self.contents,
+        };
+        let compiled = if info.compiled.is_some() {
+            self.backend
+                .redefine_function(func, &info.decl.name, ctx, &namespace, total_size)?
+        } else {
+            self.backend
+                .define_function(func, &info.decl.name, ctx, &namespace, total_size)?
+        };
+
+        self.contents.functions[func].compiled = Some(compiled);
+        if !self.functions_to_finalize.contains(&func) {
+            self.functions_to_finalize.push(func);
+        }
+        Ok(total_size)
+    }
+
     /// Define a data object, producing the data contents from the given `DataContext`.
     pub fn define_data(&mut self, data: DataId, data_ctx: &DataContext) -> ModuleResult<()> {
         let compiled = {
@@ -682,17 +682,22 @@ where
     }
 
     /// Return the finalized artifact from the backend, if it provides one.
+    ///
+    /// For functions that aren't defined yet, this returns the artifact of backends that compile
+    /// functions lazily.
     pub fn get_finalized_function(&mut self, func: FuncId) -> B::FinalizedFunction {
         let info = &self.contents.functions[func];
         debug_assert!(
             !self.functions_to_finalize.iter().any(|x| *x == func),
             "function not yet finalized"
         );
-        self.backend.get_finalized_function(
-            info.compiled
-                .as_ref()
+        match info.compiled {
+            Some(ref compiled) => self.backend.get_finalized_function(compiled),
+            None => self
+                .backend
+                .get_lazy_function(func)
                 .expect("function must be compiled before it can be finalized"),
-        )
+        }
     }
 
     /// Return the finalized artifact from the backend, if it provides one.
@@ -723,3 +728,38 @@ where
         })
     }
 }
+
+/// Check that code compiled for `isa` can replace code compiled for the module's `TargetIsa`.
+///
+/// They must be the same backend for the same target, and agree on the shared flags that affect
+/// how functions are called and how they refer to other symbols.
+fn check_redefinition_isa(
+    module_isa: &dyn isa::TargetIsa,
+    isa: &dyn isa::TargetIsa,
+) -> ModuleResult<()> {
+    if isa.triple() != module_isa.triple() || isa.name() != module_isa.name() {
+        return Err(ModuleError::IncompatibleIsa(format!(
+            "{} for {} instead of {} for {}",
+            isa.name(),
+            isa.triple(),
+            module_isa.name(),
+            module_isa.triple()
+        )));
+    }
+    let (flags, module_flags) = (isa.flags(), module_isa.flags());
+    let mismatch = if flags.is_pic() != module_flags.is_pic() {
+        "is_pic"
+    } else if flags.use_colocated_libcalls() != module_flags.use_colocated_libcalls() {
+        "use_colocated_libcalls"
+    } else if flags.libcall_call_conv() != module_flags.libcall_call_conv() {
+        "libcall_call_conv"
+    } else if flags.enable_pinned_reg() != module_flags.enable_pinned_reg() {
+        "enable_pinned_reg"
+    } else {
+        return Ok(());
+    };
+    Err(ModuleError::IncompatibleIsa(format!(
+        "the {} setting differs",
+        mismatch
+    )))
+}
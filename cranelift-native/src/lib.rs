@@ -25,6 +25,9 @@
 )]
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
 use cranelift_codegen::isa;
 use target_lexicon::Triple;
 
@@ -48,6 +51,10 @@ pub fn builder() -> Result<isa::Builder, &'static str> {
         parse_x86_cpuid(&mut isa_builder)?;
     }
 
+    if cfg!(any(target_arch = "riscv32", target_arch = "riscv64")) {
+        parse_riscv_hwcap(&mut isa_builder)?;
+    }
+
     Ok(isa_builder)
 }
 
@@ -66,6 +73,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_ssse3() {
             isa_builder.enable("has_ssse3").unwrap();
         }
+        if info.has_fma() {
+            isa_builder.enable("has_fma").unwrap();
+        }
         if info.has_sse41() {
             isa_builder.enable("has_sse41").unwrap();
         }
@@ -83,6 +93,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_bmi1() {
             isa_builder.enable("has_bmi1").unwrap();
         }
+        if info.has_avx2() {
+            isa_builder.enable("has_avx2").unwrap();
+        }
         if info.has_bmi2() {
             isa_builder.enable("has_bmi2").unwrap();
         }
@@ -100,11 +113,134 @@ fn parse_x86_cpuid(_isa_builder: &mut isa::Builder) -> Result<(), &'static str>
     unreachable!();
 }
 
+/// The RISC-V single-letter extensions that map onto riscv ISA settings.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64", test))]
+const RISCV_EXTENSIONS: &[(u8, &str)] = &[
+    (b'm', "supports_m"),
+    (b'a', "supports_a"),
+    (b'f', "supports_f"),
+    (b'd', "supports_d"),
+];
+
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+fn parse_riscv_hwcap(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
+    use cranelift_codegen::settings::Configurable;
+    let mut hwcap = getauxval_hwcap();
+    if hwcap == 0 {
+        // Fall back to `/proc/cpuinfo` when the auxiliary vector isn't available.
+        hwcap = with_cpuinfo(riscv_cpuinfo_hwcap).unwrap_or(0);
+    }
+
+    for setting in riscv_hwcap_settings(hwcap) {
+        isa_builder.enable(setting).unwrap();
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+fn parse_riscv_hwcap(_isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
+    unreachable!();
+}
+
+/// The riscv settings to enable for the RISC-V `AT_HWCAP` bits in `hwcap`. Linux reports each
+/// single-letter extension as bit `letter - 'a'`.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64", test))]
+fn riscv_hwcap_settings(hwcap: u64) -> impl Iterator<Item = &'static str> {
+    RISCV_EXTENSIONS
+        .iter()
+        .filter(move |&&(letter, _)| hwcap & riscv_letter_mask(&[letter]) != 0)
+        .map(|&(_, setting)| setting)
+}
+
+/// Decode the single-letter extensions of the `isa` line of the RISC-V `/proc/cpuinfo`, e.g.
+/// `rv64imafdc`, into `AT_HWCAP` bits.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64", test))]
+fn riscv_cpuinfo_hwcap(cpuinfo: &str) -> u64 {
+    let isa = match cpuinfo_field(cpuinfo, "isa") {
+        Some(isa) => isa,
+        None => return 0,
+    };
+    let letters = if isa.starts_with("rv32") || isa.starts_with("rv64") {
+        &isa[4..]
+    } else {
+        return 0;
+    };
+
+    let mut hwcap = 0;
+    // Multi-letter extensions follow the single-letter ones, separated by underscores.
+    for letter in letters.bytes().take_while(|&b| b != b'_') {
+        match letter {
+            // `G` is shorthand for `IMAFD`.
+            b'g' => hwcap |= riscv_letter_mask(b"imafd"),
+            b'a'..=b'z' => hwcap |= riscv_letter_mask(&[letter]),
+            _ => break,
+        }
+    }
+    hwcap
+}
+
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64", test))]
+fn riscv_letter_mask(letters: &[u8]) -> u64 {
+    letters
+        .iter()
+        .fold(0, |mask, &letter| mask | (1 << (letter - b'a')))
+}
+
+/// Get the value of the first `field` line in the contents of `/proc/cpuinfo`.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64", test))]
+fn cpuinfo_field<'a>(cpuinfo: &'a str, field: &str) -> Option<&'a str> {
+    cpuinfo.lines().find_map(|line| {
+        let mut parts = line.splitn(2, ':');
+        if parts.next()?.trim() == field {
+            Some(parts.next()?.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// Call `f` with the contents of `/proc/cpuinfo`, or return `None` if it can't be read.
+#[cfg(all(
+    feature = "std",
+    target_os = "linux",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+fn with_cpuinfo<R>(f: impl FnOnce(&str) -> R) -> Option<R> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    Some(f(&cpuinfo))
+}
+
+#[cfg(all(
+    not(all(feature = "std", target_os = "linux")),
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+fn with_cpuinfo<R>(_f: impl FnOnce(&str) -> R) -> Option<R> {
+    None
+}
+
+/// Get the `AT_HWCAP` entry of the auxiliary vector, or 0 if it is unavailable.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+fn getauxval_hwcap() -> u64 {
+    unsafe { libc::getauxval(libc::AT_HWCAP) as u64 }
+}
+
+#[cfg(all(
+    not(target_os = "linux"),
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+fn getauxval_hwcap() -> u64 {
+    0
+}
+
 #[cfg(test)]
 mod tests {
-    use super::builder;
+    use super::{builder, riscv_cpuinfo_hwcap, riscv_hwcap_settings};
     use cranelift_codegen::isa::CallConv;
     use cranelift_codegen::settings;
+    use std::vec::Vec;
 
     #[test]
     fn test() {
@@ -127,6 +263,41 @@ mod tests {
             }
         }
     }
+
+    // Captured from a SiFive HiFive Unleashed (U54).
+    const U54_HWCAP: u64 = 0x112d;
+    const U54_CPUINFO: &str = "processor\t: 0
+hart\t\t: 1
+isa\t\t: rv64imafdc
+mmu\t\t: sv39
+";
+
+    #[test]
+    fn riscv_hwcap() {
+        let settings: Vec<_> = riscv_hwcap_settings(U54_HWCAP).collect();
+        assert_eq!(
+            settings,
+            ["supports_m", "supports_a", "supports_f", "supports_d"]
+        );
+        assert_eq!(riscv_hwcap_settings(0).count(), 0);
+    }
+
+    #[test]
+    fn riscv_cpuinfo() {
+        assert_eq!(riscv_cpuinfo_hwcap(U54_CPUINFO), U54_HWCAP);
+        assert_eq!(riscv_cpuinfo_hwcap("isa\t\t: rv64gc\n"), U54_HWCAP);
+        assert_eq!(
+            riscv_cpuinfo_hwcap("isa\t\t: rv32imac_zicsr_zifencei\n"),
+            riscv_cpuinfo_hwcap("isa\t\t: rv32imac\n")
+        );
+
+        let settings: Vec<_> =
+            riscv_hwcap_settings(riscv_cpuinfo_hwcap("isa\t\t: rv32imac\n")).collect();
+        assert_eq!(settings, ["supports_m", "supports_a"]);
+
+        assert_eq!(riscv_cpuinfo_hwcap("isa\t\t: unknown\n"), 0);
+        assert_eq!(riscv_cpuinfo_hwcap("processor\t: 0\n"), 0);
+    }
 }
 
 /// Version number of this crate.
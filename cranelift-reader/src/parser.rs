@@ -14,12 +14,12 @@ use cranelift_codegen::ir::instructions::{InstructionData, InstructionFormat, Va
 use cranelift_codegen::ir::types::INVALID;
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{
-    AbiParam, ArgumentExtension, ArgumentLoc, Block, ConstantData, ExtFuncData, ExternalName,
-    FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle, JumpTable,
-    JumpTableData, MemFlags, Opcode, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind,
-    Table, TableData, Type, Value, ValueLoc,
+    AbiParam, ArgumentExtension, ArgumentLoc, Block, ConstantData, DataInit, DataObject,
+    ExtFuncData, ExternalName, FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData,
+    HeapStyle, JumpTable, JumpTableData, Linkage, MemFlags, Opcode, SigRef, Signature, StackSlot,
+    StackSlotData, StackSlotKind, Table, TableData, Type, Value, ValueLoc,
 };
-use cranelift_codegen::isa::{self, CallConv, Encoding, RegUnit, TargetIsa};
+use cranelift_codegen::isa::{self, BackendVariant, CallConv, Encoding, RegUnit, TargetIsa};
 use cranelift_codegen::packed_option::ReservedValue;
 use cranelift_codegen::{settings, timing};
 use std::mem;
@@ -44,6 +44,8 @@ pub struct ParseOptions<'a> {
     pub target: Option<&'a str>,
     /// Default calling convention used when none is specified for a parsed function.
     pub default_calling_convention: CallConv,
+    /// Which backend to use for targets that have more than one.
+    pub backend_variant: BackendVariant,
 }
 
 impl Default for ParseOptions<'_> {
@@ -52,6 +54,7 @@ impl Default for ParseOptions<'_> {
             passes: None,
             target: None,
             default_calling_convention: CallConv::Fast,
+            backend_variant: BackendVariant::Any,
         }
     }
 }
@@ -61,7 +64,7 @@ impl Default for ParseOptions<'_> {
 /// The returned `TestFile` contains direct references to substrings of `text`.
 pub fn parse_test<'a>(text: &'a str, options: ParseOptions<'a>) -> ParseResult<TestFile<'a>> {
     let _tt = timing::parse_text();
-    let mut parser = Parser::new(text);
+    let mut parser = Parser::new(text).with_backend_variant(options.backend_variant);
 
     // Gather the preamble comments.
     parser.start_gathering_comments();
@@ -99,7 +102,7 @@ pub fn parse_test<'a>(text: &'a str, options: ParseOptions<'a>) -> ParseResult<T
     parser.claim_gathered_comments(AnyEntity::Function);
 
     let preamble_comments = parser.take_comments();
-    let functions = parser.parse_function_list(isa_spec.unique_isa())?;
+    let (functions, data_objects) = parser.parse_items(isa_spec.unique_isa())?;
 
     Ok(TestFile {
         commands,
@@ -107,6 +110,7 @@ pub fn parse_test<'a>(text: &'a str, options: ParseOptions<'a>) -> ParseResult<T
         features,
         preamble_comments,
         functions,
+        data_objects,
     })
 }
 
@@ -132,6 +136,9 @@ pub struct Parser<'a> {
 
     /// Default calling conventions; used when none is specified.
     default_calling_convention: CallConv,
+
+    /// Backend to look up for the targets of `target` commands.
+    backend_variant: BackendVariant,
 }
 
 /// Context for resolving references when parsing a single function.
@@ -357,6 +364,7 @@ impl<'a> Parser<'a> {
             gathered_comments: Vec::new(),
             comments: Vec::new(),
             default_calling_convention: CallConv::Fast,
+            backend_variant: BackendVariant::Any,
         }
     }
 
@@ -369,6 +377,15 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Modify the backend variant used for targets; returns a new parser with the changed
+    /// variant.
+    pub fn with_backend_variant(self, backend_variant: BackendVariant) -> Self {
+        Self {
+            backend_variant,
+            ..self
+        }
+    }
+
     // Consume the current lookahead token and return it.
     fn consume(&mut self) -> Token<'a> {
         self.lookahead.take().expect("No token to consume")
@@ -943,7 +960,7 @@ impl<'a> Parser<'a> {
                 Ok(triple) => triple,
                 Err(err) => return err!(loc, err),
             };
-            let isa_builder = match isa::lookup(triple) {
+            let isa_builder = match isa::lookup_variant(triple, self.backend_variant) {
                 Err(isa::LookupError::SupportDisabled) => {
                     return err!(loc, "support disabled target '{}'", targ);
                 }
@@ -1003,7 +1020,9 @@ impl<'a> Parser<'a> {
                         Ok(triple) => triple,
                         Err(err) => return err!(loc, err),
                     };
-                    let mut isa_builder = match isa::lookup(triple).map(|b| b.as_builder()) {
+                    let mut isa_builder = match isa::lookup_variant(triple, self.backend_variant)
+                        .map(|b| b.as_builder())
+                    {
                         Err(isa::LookupError::SupportDisabled) => {
                             continue;
                         }
@@ -1060,26 +1079,43 @@ impl<'a> Parser<'a> {
 
     /// Parse a list of function definitions.
     ///
-    /// This is the top-level parse function matching the whole contents of a file.
+    /// Data declarations are allowed between the functions, but they are discarded; use
+    /// `parse_items` to get them too.
     pub fn parse_function_list(
         &mut self,
         unique_isa: Option<&dyn TargetIsa>,
     ) -> ParseResult<Vec<(Function, Details<'a>)>> {
-        let mut list = Vec::new();
-        while self.token().is_some() {
-            list.push(self.parse_function(unique_isa)?);
+        self.parse_items(unique_isa)
+            .map(|(functions, _data_objects)| functions)
+    }
+
+    /// Parse a list of function definitions and data declarations.
+    ///
+    /// This is the top-level parse function matching the whole contents of a file.
+    pub fn parse_items(
+        &mut self,
+        unique_isa: Option<&dyn TargetIsa>,
+    ) -> ParseResult<(Vec<(Function, Details<'a>)>, Vec<DataObject>)> {
+        let mut functions = Vec::new();
+        let mut data_objects = Vec::new();
+        while let Some(token) = self.token() {
+            if token == Token::Identifier("data") {
+                data_objects.push(self.parse_data_object()?);
+            } else {
+                functions.push(self.parse_function(unique_isa)?);
+            }
         }
         if let Some(err) = self.lex_error {
             return match err {
                 LexError::InvalidChar => err!(self.loc, "invalid character"),
             };
         }
-        Ok(list)
+        Ok((functions, data_objects))
     }
 
     // Parse a whole function definition.
     //
-    // function ::= * "function" name signature "{" preamble function-body "}"
+    // function ::= * "function" [linkage] name signature "{" preamble function-body "}"
     //
     fn parse_function(
         &mut self,
@@ -1095,7 +1131,10 @@ impl<'a> Parser<'a> {
 
         let location = self.loc;
 
-        // function ::= "function" * name signature "{" preamble function-body "}"
+        // function ::= "function" * [linkage] name signature "{" preamble function-body "}"
+        let linkage = self.optional_linkage()?;
+
+        // function ::= "function" [linkage] * name signature "{" preamble function-body "}"
         let name = self.parse_external_name()?;
 
         // function ::= "function" name * signature "{" preamble function-body "}"
@@ -1123,6 +1162,7 @@ impl<'a> Parser<'a> {
 
         let details = Details {
             location,
+            linkage,
             comments: self.take_comments(),
             map: ctx.map,
         };
@@ -1130,6 +1170,104 @@ impl<'a> Parser<'a> {
         Ok((ctx.function, details))
     }
 
+    // Parse an optional linkage annotation.
+    //
+    // linkage ::= "import" | "local" | "preemptible" | "export"
+    //
+    fn optional_linkage(&mut self) -> ParseResult<Option<Linkage>> {
+        if let Some(Token::Identifier(text)) = self.token() {
+            let linkage = text
+                .parse()
+                .map_err(|_| self.error(&format!("unknown linkage '{}'", text)))?;
+            self.consume();
+            Ok(Some(linkage))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Parse a data object declaration.
+    //
+    // data ::= * "data" name linkage ["writable"] ["align" uimm8] ["=" data-init] [data-relocs]
+    // data-init ::= "zeroinit" uimm64(size) | "[" { uimm8 } "]"
+    // data-relocs ::= "{" { data-reloc } "}"
+    // data-reloc ::= "func_addr" uimm32(offset) "," name
+    //              | "data_addr" uimm32(offset) "," name [offset-imm64]
+    //
+    fn parse_data_object(&mut self) -> ParseResult<DataObject> {
+        self.match_identifier("data", "expected 'data'")?;
+
+        // data ::= "data" * name linkage ["writable"] ...
+        let name = self.parse_external_name()?;
+        let linkage = match self.optional_linkage()? {
+            Some(linkage) => linkage,
+            None => return err!(self.loc, "expected linkage for data object"),
+        };
+        let mut data = DataObject::new(name, linkage);
+        data.writable = self.optional(Token::Identifier("writable"));
+        if self.optional(Token::Identifier("align")) {
+            let align = self.match_uimm8("expected alignment in bytes")?;
+            if !align.is_power_of_two() {
+                return err!(self.loc, "alignment must be a power of two");
+            }
+            data.align = Some(align);
+        }
+
+        // data ::= "data" name linkage ["writable"] ["align" uimm8] * ["=" data-init] ...
+        if self.optional(Token::Equal) {
+            data.init = if self.optional(Token::Identifier("zeroinit")) {
+                let size: u64 = self.match_uimm64("expected data size in bytes")?.into();
+                DataInit::Zeros {
+                    size: size as usize,
+                }
+            } else {
+                self.match_token(
+                    Token::LBracket,
+                    "expected 'zeroinit' or '[' for data contents",
+                )?;
+                let mut contents = Vec::new();
+                while !self.optional(Token::RBracket) {
+                    contents.push(self.match_uimm8("expected a byte or ']'")?);
+                }
+                DataInit::Bytes { contents }
+            };
+        }
+        if data.linkage.is_definable() {
+            if data.init == DataInit::Uninitialized {
+                return err!(
+                    self.loc,
+                    "data object with {} linkage needs contents",
+                    data.linkage
+                );
+            }
+        } else if data.init != DataInit::Uninitialized {
+            return err!(self.loc, "imported data object cannot have contents");
+        }
+
+        // data ::= "data" name linkage ["writable"] ["align" uimm8] ["=" data-init] * [data-relocs]
+        if self.optional(Token::LBrace) {
+            if data.init == DataInit::Uninitialized {
+                return err!(self.loc, "relocations require data contents");
+            }
+            while !self.optional(Token::RBrace) {
+                let kind = self.match_any_identifier("expected 'func_addr' or 'data_addr'")?;
+                let offset: u32 = self.match_uimm32("expected offset in data object")?.into();
+                self.match_token(Token::Comma, "expected ',' after offset")?;
+                let target = self.parse_external_name()?;
+                match kind {
+                    "func_addr" => data.function_relocs.push((offset, target)),
+                    "data_addr" => {
+                        let addend: i64 = self.optional_offset_imm64()?.into();
+                        data.data_relocs.push((offset, target, addend));
+                    }
+                    _ => return err!(self.loc, "unknown relocation '{}'", kind),
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
     // Parse an external name.
     //
     // For example, in a function decl, the parser would be in this state:
@@ -3326,4 +3464,54 @@ mod tests {
             [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]
         )
     }
+
+    #[test]
+    fn data_objects() {
+        let (functions, data_objects) = Parser::new(
+            "data %msg local = [104 105 0]
+             function export %f() system_v {
+             block0:
+                 return
+             }
+             data %table preemptible writable align 8 = zeroinit 16 {
+                 func_addr 0, %f
+                 data_addr 8, %msg+1
+             }
+             data %ext import",
+        )
+        .parse_items(None)
+        .unwrap();
+
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].1.linkage, Some(Linkage::Export));
+
+        assert_eq!(data_objects.len(), 3);
+        assert_eq!(data_objects[0].linkage, Linkage::Local);
+        assert_eq!(
+            data_objects[0].init,
+            DataInit::Bytes {
+                contents: vec![104, 105, 0]
+            }
+        );
+        assert_eq!(
+            data_objects[1].to_string(),
+            "data %table preemptible writable align 8 = zeroinit 16 {\n    func_addr 0, %f\n    data_addr 8, %msg+1\n}\n"
+        );
+        assert_eq!(data_objects[2].init, DataInit::Uninitialized);
+
+        let ParseError { message, .. } = Parser::new("data %ext import = zeroinit 4")
+            .parse_items(None)
+            .unwrap_err();
+        assert_eq!(message, "imported data object cannot have contents");
+
+        let ParseError { message, .. } = Parser::new("data %x export writable")
+            .parse_items(None)
+            .unwrap_err();
+        assert_eq!(message, "data object with export linkage needs contents");
+
+        let ParseError { message, .. } = Parser::new("data %x local align 3 = zeroinit 4")
+            .parse_items(None)
+            .unwrap_err();
+        assert_eq!(message, "alignment must be a power of two");
+    }
 }
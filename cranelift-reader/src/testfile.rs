@@ -9,12 +9,12 @@ use crate::isaspec::IsaSpec;
 use crate::sourcemap::SourceMap;
 use crate::testcommand::TestCommand;
 use cranelift_codegen::ir::entities::AnyEntity;
-use cranelift_codegen::ir::Function;
+use cranelift_codegen::ir::{DataObject, Function, Linkage};
 
 /// A parsed test case.
 ///
 /// This is the result of parsing a `.clif` file which contains a number of test commands and ISA
-/// specs followed by the functions that should be tested.
+/// specs followed by the functions and data objects that should be tested.
 pub struct TestFile<'a> {
     /// `test foo ...` lines.
     pub commands: Vec<TestCommand<'a>>,
@@ -27,6 +27,8 @@ pub struct TestFile<'a> {
     pub preamble_comments: Vec<Comment<'a>>,
     /// Parsed functions and additional details about each function.
     pub functions: Vec<(Function, Details<'a>)>,
+    /// `data` declarations, in the order they appear in the file.
+    pub data_objects: Vec<DataObject>,
 }
 
 /// Additional details about a function parsed from a text string.
@@ -36,6 +38,8 @@ pub struct TestFile<'a> {
 pub struct Details<'a> {
     /// Location of the `function` keyword that begins this function.
     pub location: Location,
+    /// Linkage annotation following the `function` keyword, if any.
+    pub linkage: Option<Linkage>,
     /// Annotation comments that appeared inside or after the function.
     pub comments: Vec<Comment<'a>>,
     /// Mapping of entity numbers to source locations.
@@ -1,21 +1,33 @@
 //! Defines `SimpleJITBackend`.
 
+#[cfg(any(all(feature = "perf", target_os = "linux"), feature = "gdb-jit"))]
+use crate::debug::collect_srclocs;
+#[cfg(all(feature = "perf", target_os = "linux"))]
+use crate::debug::elf_machine;
+#[cfg(feature = "gdb-jit")]
+use crate::gdb_jit::{self, GdbJitFunction};
+#[cfg(all(feature = "perf", target_os = "linux"))]
+use crate::jitdump::JitDump;
+use crate::lazy::{LazyCompiler, LazyStub};
 use crate::memory::Memory;
+#[cfg(target_os = "linux")]
+use crate::perf::PerfMap;
 use cranelift_codegen::binemit::{
     Addend, CodeOffset, NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
 };
+#[cfg(feature = "gdb-jit")]
+use cranelift_codegen::binemit::{FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink};
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, ir, settings};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult,
+    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
+    ModuleNamespace, ModuleResult,
 };
 use cranelift_native;
 #[cfg(not(windows))]
 use libc;
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::io::Write;
 use std::ptr;
 use target_lexicon::PointerWidth;
 #[cfg(windows)]
@@ -30,6 +42,13 @@ pub struct SimpleJITBuilder {
     isa: Box<dyn TargetIsa>,
     symbols: HashMap<String, *const u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    lazy_callback: Option<Box<dyn Fn(FuncId)>>,
+    #[cfg(target_os = "linux")]
+    perf_map: bool,
+    #[cfg(all(feature = "perf", target_os = "linux"))]
+    jitdump: bool,
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit: bool,
 }
 
 impl SimpleJITBuilder {
@@ -70,6 +89,13 @@ impl SimpleJITBuilder {
             isa,
             symbols,
             libcall_names,
+            lazy_callback: None,
+            #[cfg(target_os = "linux")]
+            perf_map: std::env::var_os("PERF_BUILDID_DIR").is_some(),
+            #[cfg(all(feature = "perf", target_os = "linux"))]
+            jitdump: false,
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit: true,
         }
     }
 
@@ -108,6 +134,73 @@ impl SimpleJITBuilder {
         }
         self
     }
+
+    /// Compile local functions lazily, when they are first called.
+    ///
+    /// Local functions don't need to be defined before `finalize_definitions` in this mode.
+    /// Instead, each of them gets a stub, through which all calls to the function go. The first
+    /// call to a function that isn't defined yet invokes `callback` with its `FuncId`, which must
+    /// define the function and finalize it with `Module::finalize_definitions`. The stub then
+    /// continues in the new code. `Module::get_finalized_function` returns the stub of functions
+    /// that aren't defined yet.
+    ///
+    /// Since the callback runs while the JIT code that called the function is on the stack, it
+    /// can't borrow the `Module` from the caller. Embedders typically keep the `Module` in a
+    /// `RefCell` which the callback can reach, and don't hold a borrow of it while calling into
+    /// JIT code. Functions can also be recompiled later with `Module::redefine_function`, for
+    /// example to optimize frequently called functions with a higher `opt_level`.
+    ///
+    /// Lazy compilation is only available on x86-64 hosts using the System V calling convention;
+    /// elsewhere, functions must be defined before they are finalized.
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    pub fn lazy_compilation<F>(&mut self, callback: F) -> &Self
+    where
+        F: Fn(FuncId) + 'static,
+    {
+        self.lazy_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Write an entry to `/tmp/perf-<pid>.map` for every finalized function, which `perf report`
+    /// uses to name the JIT code in its profiles.
+    ///
+    /// This is enabled by default if the `PERF_BUILDID_DIR` environment variable is set, as it
+    /// is for programs run by `perf record`.
+    #[cfg(target_os = "linux")]
+    pub fn perf_map(&mut self, enable: bool) -> &Self {
+        self.perf_map = enable;
+        self
+    }
+
+    /// Write every finalized function to a `jit-<pid>.dump` file in the current directory, in
+    /// the perf jitdump format.
+    ///
+    /// After profiling the program with `perf record -k mono`, `perf inject --jit` turns the
+    /// jitdump file into ELF images with the code of each function, so `perf annotate` can show
+    /// the hot instructions. Functions with source locations also get line information, where
+    /// the line numbers are the `SourceLoc` values. Source locations are only known for ISAs
+    /// that record instruction encodings, so functions compiled by the new `MachInst`
+    /// backends have none.
+    ///
+    /// This requires the `perf` feature.
+    #[cfg(all(feature = "perf", target_os = "linux"))]
+    pub fn jitdump(&mut self, enable: bool) -> &Self {
+        self.jitdump = enable;
+        self
+    }
+
+    /// Register the functions finalized by each `Module::finalize_definitions` call with
+    /// debuggers, through the GDB JIT compilation interface. This is enabled by default.
+    ///
+    /// GDB and LLDB then show the names of the functions in backtraces, can set breakpoints on
+    /// them, and map their code to the `SourceLoc` values as line numbers. They can only unwind
+    /// through functions whose `Function::collect_frame_layout_info` was called before they
+    /// were defined, which makes the backend emit their `.eh_frame` unwind information.
+    #[cfg(feature = "gdb-jit")]
+    pub fn gdb_jit(&mut self, enable: bool) -> &Self {
+        self.gdb_jit = enable;
+        self
+    }
 }
 
 /// A `SimpleJITBackend` implements `Backend` and emits code and data into memory where it can be
@@ -119,6 +212,19 @@ pub struct SimpleJITBackend {
     symbols: HashMap<String, *const u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
     memory: SimpleJITMemoryHandle,
+    lazy_compiler: Option<*const LazyCompiler>,
+    lazy_stubs: HashMap<FuncId, LazyStub>,
+    /// Stub slots to point at newly finalized code once it is executable.
+    pending_stub_updates: Vec<(*mut *const u8, *const u8)>,
+    #[cfg(target_os = "linux")]
+    perf_map: Option<PerfMap>,
+    #[cfg(all(feature = "perf", target_os = "linux"))]
+    jitdump: Option<JitDump>,
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit: bool,
+    /// Functions to register with the debugger once they are executable.
+    #[cfg(feature = "gdb-jit")]
+    pending_gdb_functions: Vec<GdbJitFunction>,
 }
 
 /// A record of a relocation to perform.
@@ -140,6 +246,15 @@ pub struct SimpleJITCompiledFunction {
     code: *mut u8,
     size: usize,
     relocs: Vec<RelocRecord>,
+    /// Code offsets where source locations start, for the jitdump file and the debugger.
+    #[cfg(any(all(feature = "perf", target_os = "linux"), feature = "gdb-jit"))]
+    srclocs: Vec<(CodeOffset, ir::SourceLoc)>,
+    /// The `.eh_frame` CIE and FDE of the function, and the offsets of its start address in
+    /// them.
+    #[cfg(feature = "gdb-jit")]
+    eh_frame: Vec<u8>,
+    #[cfg(feature = "gdb-jit")]
+    eh_frame_relocs: Vec<usize>,
 }
 
 pub struct SimpleJITCompiledData {
@@ -171,6 +286,9 @@ impl SimpleJITBackend {
         match *name {
             ir::ExternalName::User { .. } => {
                 if namespace.is_function(name) {
+                    if let Some(stub) = self.lazy_stubs.get(&namespace.get_function_id(name)) {
+                        return stub.code;
+                    }
                     let (def, name_str, _signature) = namespace.get_function_definition(&name);
                     match def {
                         Some(compiled) => compiled.code,
@@ -191,6 +309,46 @@ impl SimpleJITBackend {
             _ => panic!("invalid ExternalName {}", name),
         }
     }
+
+    /// Add code that isn't a function of the module, such as a lazy stub, to the perf map and
+    /// the jitdump file.
+    fn add_profiling_entry(&mut self, name: &str, code: *const u8, size: usize) {
+        #[cfg(target_os = "linux")]
+        {
+            // Profiling output is best-effort, so write errors are ignored.
+            if let Some(ref mut perf_map) = self.perf_map {
+                let _ = perf_map.add_function(name, code, size);
+            }
+            #[cfg(feature = "perf")]
+            {
+                if let Some(ref mut jitdump) = self.jitdump {
+                    let _ = jitdump.add_function(name, code, size, &[]);
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (name, code, size);
+        }
+    }
+
+    /// Whether the source locations of functions are needed.
+    #[cfg(any(all(feature = "perf", target_os = "linux"), feature = "gdb-jit"))]
+    fn collects_srclocs(&self) -> bool {
+        #[cfg(all(feature = "perf", target_os = "linux"))]
+        {
+            if self.jitdump.is_some() {
+                return true;
+            }
+        }
+        #[cfg(feature = "gdb-jit")]
+        {
+            if self.gdb_jit {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 impl<'simple_jit_backend> Backend for SimpleJITBackend {
@@ -219,6 +377,15 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     /// Create a new `SimpleJITBackend`.
     fn new(builder: SimpleJITBuilder) -> Self {
+        #[cfg(all(feature = "perf", target_os = "linux"))]
+        let jitdump = if builder.jitdump {
+            Some(
+                JitDump::new(elf_machine(&*builder.isa))
+                    .expect("unable to create the jitdump file"),
+            )
+        } else {
+            None
+        };
         let memory = SimpleJITMemoryHandle {
             code: Memory::new(),
             readonly: Memory::new(),
@@ -230,6 +397,21 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             symbols: builder.symbols,
             libcall_names: builder.libcall_names,
             memory,
+            lazy_compiler: builder.lazy_callback.map(LazyCompiler::leak),
+            lazy_stubs: HashMap::new(),
+            pending_stub_updates: Vec::new(),
+            #[cfg(target_os = "linux")]
+            perf_map: if builder.perf_map {
+                Some(PerfMap::new().expect("unable to create the perf map"))
+            } else {
+                None
+            },
+            #[cfg(all(feature = "perf", target_os = "linux"))]
+            jitdump,
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit: builder.gdb_jit,
+            #[cfg(feature = "gdb-jit")]
+            pending_gdb_functions: Vec::new(),
         }
     }
 
@@ -237,8 +419,19 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         &*self.isa
     }
 
-    fn declare_function(&mut self, _id: FuncId, _name: &str, _linkage: Linkage) {
-        // Nothing to do.
+    fn declare_function(&mut self, id: FuncId, name: &str, linkage: Linkage) {
+        if let Some(compiler) = self.lazy_compiler {
+            if linkage.is_definable() && !self.lazy_stubs.contains_key(&id) {
+                let stub = LazyStub::new(
+                    compiler,
+                    id,
+                    &mut self.memory.code,
+                    &mut self.memory.writable,
+                );
+                self.add_profiling_entry(&format!("{} (lazy stub)", name), stub.code, stub.size);
+                self.lazy_stubs.insert(id, stub);
+            }
+        }
     }
 
     fn declare_data(
@@ -255,7 +448,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn define_function(
         &mut self,
         _id: FuncId,
-        name: &str,
+        _name: &str,
         ctx: &cranelift_codegen::Context,
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
@@ -267,16 +460,6 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
             .expect("TODO: handle OOM etc.");
 
-        if cfg!(target_os = "linux") && ::std::env::var_os("PERF_BUILDID_DIR").is_some() {
-            let mut map_file = ::std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(format!("/tmp/perf-{}.map", ::std::process::id()))
-                .unwrap();
-
-            let _ = writeln!(map_file, "{:x} {:x} {}", ptr as usize, code_size, name);
-        }
-
         let mut reloc_sink = SimpleJITRelocSink::new();
         // Ignore traps for now. For now, frontends should just avoid generating code
         // that traps.
@@ -292,13 +475,50 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             )
         };
 
+        #[cfg(feature = "gdb-jit")]
+        let mut unwind_sink = SimpleJITFrameUnwindSink::new();
+        #[cfg(feature = "gdb-jit")]
+        {
+            if self.gdb_jit && !ctx.func.signature.call_conv.extends_windows_fastcall() {
+                ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+                // The terminator is added after the entries of all functions instead.
+                let len = unwind_sink.bytes.len().saturating_sub(4);
+                unwind_sink.bytes.truncate(len);
+            }
+        }
+
         Ok(Self::CompiledFunction {
             code: ptr,
             size,
             relocs: reloc_sink.relocs,
+            #[cfg(any(all(feature = "perf", target_os = "linux"), feature = "gdb-jit"))]
+            srclocs: if self.collects_srclocs() {
+                collect_srclocs(ctx, &*self.isa)
+            } else {
+                Vec::new()
+            },
+            #[cfg(feature = "gdb-jit")]
+            eh_frame: unwind_sink.bytes,
+            #[cfg(feature = "gdb-jit")]
+            eh_frame_relocs: unwind_sink.relocs,
         })
     }
 
+    /// Functions can only be redefined in lazy mode, where all calls go through their stubs.
+    fn redefine_function(
+        &mut self,
+        id: FuncId,
+        name: &str,
+        ctx: &cranelift_codegen::Context,
+        namespace: &ModuleNamespace<Self>,
+        code_size: u32,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        if !self.lazy_stubs.contains_key(&id) {
+            return Err(ModuleError::DuplicateDefinition(name.to_owned()));
+        }
+        self.define_function(id, name, ctx, namespace, code_size)
+    }
+
     fn define_data(
         &mut self,
         _id: DataId,
@@ -393,7 +613,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     fn finalize_function(
         &mut self,
-        _id: FuncId,
+        id: FuncId,
         func: &Self::CompiledFunction,
         namespace: &ModuleNamespace<Self>,
     ) -> Self::FinalizedFunction {
@@ -438,6 +658,38 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                 _ => unimplemented!(),
             }
         }
+        if let Some(stub) = self.lazy_stubs.get(&id) {
+            self.pending_stub_updates.push((stub.slot, func.code));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let name = &namespace.get_function_decl(&id.into()).name;
+            // Profiling output is best-effort, so write errors are ignored.
+            if let Some(ref mut perf_map) = self.perf_map {
+                let _ = perf_map.add_function(name, func.code, func.size);
+            }
+            #[cfg(feature = "perf")]
+            {
+                if let Some(ref mut jitdump) = self.jitdump {
+                    let _ = jitdump.add_function(name, func.code, func.size, &func.srclocs);
+                }
+            }
+        }
+        #[cfg(feature = "gdb-jit")]
+        {
+            if self.gdb_jit {
+                self.pending_gdb_functions.push(GdbJitFunction {
+                    name: namespace.get_function_decl(&id.into()).name.clone(),
+                    code: func.code,
+                    size: func.size,
+                    srclocs: func.srclocs.clone(),
+                    eh_frame: func.eh_frame.clone(),
+                    eh_frame_relocs: func.eh_frame_relocs.clone(),
+                });
+            }
+        }
+
         func.code
     }
 
@@ -445,6 +697,10 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         func.code
     }
 
+    fn get_lazy_function(&self, id: FuncId) -> Option<Self::FinalizedFunction> {
+        self.lazy_stubs.get(&id).map(|stub| stub.code)
+    }
+
     fn finalize_data(
         &mut self,
         _id: DataId,
@@ -498,6 +754,20 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         // Now that we're done patching, prepare the memory for execution!
         self.memory.readonly.set_readonly();
         self.memory.code.set_readable_and_executable();
+
+        // Redirect the stubs of lazily compiled functions to their new code.
+        for (slot, code) in self.pending_stub_updates.drain(..) {
+            unsafe { ptr::write_volatile(slot, code) };
+        }
+
+        #[cfg(feature = "gdb-jit")]
+        {
+            gdb_jit::register_functions(
+                &self.pending_gdb_functions,
+                self.isa.triple().architecture,
+            );
+            self.pending_gdb_functions.clear();
+        }
     }
 
     /// SimpleJIT emits code and data into memory as it processes them. This
@@ -626,6 +896,44 @@ impl RelocSink for SimpleJITRelocSink {
     }
 }
 
+/// Collects the `.eh_frame` entries of a function.
+#[cfg(feature = "gdb-jit")]
+struct SimpleJITFrameUnwindSink {
+    bytes: Vec<u8>,
+    /// Offsets of the function's start address in `bytes`.
+    relocs: Vec<usize>,
+}
+
+#[cfg(feature = "gdb-jit")]
+impl SimpleJITFrameUnwindSink {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            relocs: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "gdb-jit")]
+impl FrameUnwindSink for SimpleJITFrameUnwindSink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.bytes.len()
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn reloc(&mut self, reloc: Reloc, offset: FrameUnwindOffset) {
+        match reloc {
+            Reloc::Abs8 => self.relocs.push(offset),
+            _ => panic!("unexpected unwind relocation {}", reloc),
+        }
+    }
+
+    fn set_entry_offset(&mut self, _offset: FrameUnwindOffset) {}
+}
+
 struct SimpleJITStackmapSink {
     pub stackmaps: Vec<StackmapRecord>,
 }
@@ -0,0 +1,43 @@
+//! Information about compiled functions for debuggers and profilers.
+
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_codegen::ir;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::Context;
+#[cfg(all(feature = "perf", target_os = "linux"))]
+use target_lexicon::Architecture;
+
+/// Collect the source locations of a function compiled in `ctx`, with the code offsets where
+/// they start.
+///
+/// Source locations are only available for ISAs that record instruction encodings in the
+/// function.
+pub(crate) fn collect_srclocs(
+    ctx: &Context,
+    isa: &dyn TargetIsa,
+) -> Vec<(CodeOffset, ir::SourceLoc)> {
+    let mut srclocs = Vec::new();
+    if ctx.mach_compile_result.is_some() {
+        return srclocs;
+    }
+    let encinfo = isa.encoding_info();
+    for block in ctx.func.layout.blocks() {
+        for (offset, inst, _size) in ctx.func.inst_offsets(block, &encinfo) {
+            let srcloc = ctx.func.srclocs[inst];
+            if !srcloc.is_default() && srclocs.last().map(|&(_, last)| last) != Some(srcloc) {
+                srclocs.push((offset, srcloc));
+            }
+        }
+    }
+    srclocs
+}
+
+/// The `e_machine` value of ELF files for the target.
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub(crate) fn elf_machine(isa: &dyn TargetIsa) -> u16 {
+    match isa.triple().architecture {
+        Architecture::X86_64 => 62,
+        Architecture::Aarch64 { .. } => 183,
+        _ => 0,
+    }
+}
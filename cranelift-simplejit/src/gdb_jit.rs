@@ -0,0 +1,328 @@
+//! Registration of JIT code with debuggers through the GDB JIT compilation interface.
+//!
+//! Debuggers that implement the interface, such as GDB and LLDB, put a breakpoint on
+//! `__jit_debug_register_code`. Whenever it is called, they read the in-memory object file
+//! referenced by `__jit_debug_descriptor`, and load its symbols and debug information as if
+//! it were a shared library. Each finalized function gets an object file with a symbol for
+//! it, its unwind information in `.eh_frame`, and its source locations in a DWARF line table.
+//!
+//! The interface is documented in the "JIT Compilation Interface" chapter of the GDB manual.
+
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_codegen::ir;
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, LineProgram, LineString, Sections,
+};
+use gimli::{Encoding, Format, LineEncoding, LittleEndian};
+use object::write::{Object, StandardSection, Symbol, SymbolSection};
+use object::{SectionKind, SymbolFlags, SymbolKind, SymbolScope};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{ptr, slice, thread};
+use target_lexicon::{Architecture, BinaryFormat};
+
+/// A function to describe in the object file.
+pub(crate) struct GdbJitFunction {
+    pub name: String,
+    pub code: *const u8,
+    pub size: usize,
+    /// Code offsets where source locations start.
+    pub srclocs: Vec<(CodeOffset, ir::SourceLoc)>,
+    /// The CIE and FDE of the function, without a terminator.
+    pub eh_frame: Vec<u8>,
+    /// Offsets in `eh_frame` of the function's start address.
+    pub eh_frame_relocs: Vec<usize>,
+}
+
+/// Build an object file for each of `functions` and register them with the debugger.
+///
+/// The object files and their registrations are leaked, since the code they describe is never
+/// freed either, unless `free_memory` is used.
+pub(crate) fn register_functions(functions: &[GdbJitFunction], architecture: Architecture) {
+    for function in functions {
+        register_object(build_object(function, architecture));
+    }
+}
+
+fn register_object(image: Vec<u8>) {
+    let image = Box::leak(image.into_boxed_slice());
+    let entry = Box::into_raw(Box::new(JITCodeEntry {
+        next_entry: ptr::null_mut(),
+        prev_entry: ptr::null_mut(),
+        symfile_addr: image.as_ptr(),
+        symfile_size: image.len() as u64,
+    }));
+
+    let _guard = RegistrationGuard::lock();
+    unsafe {
+        let descriptor = &mut __jit_debug_descriptor;
+        (*entry).next_entry = descriptor.first_entry;
+        if !descriptor.first_entry.is_null() {
+            (*descriptor.first_entry).prev_entry = entry;
+        }
+        descriptor.first_entry = entry;
+        descriptor.relevant_entry = entry;
+        descriptor.action_flag = JIT_REGISTER_FN;
+        __jit_debug_register_code();
+        descriptor.action_flag = JIT_NOACTION;
+        descriptor.relevant_entry = ptr::null_mut();
+    }
+}
+
+/// Call `f` with the object files registered with debuggers through the GDB JIT compilation
+/// interface, newest first.
+///
+/// SimpleJIT backends wait for `f` to return before registering more object files.
+pub fn with_gdb_jit_objects<F, R>(f: F) -> R
+where
+    F: FnOnce(&[&[u8]]) -> R,
+{
+    let _guard = RegistrationGuard::lock();
+    let mut objects = Vec::new();
+    unsafe {
+        let mut entry = __jit_debug_descriptor.first_entry;
+        while !entry.is_null() {
+            objects.push(slice::from_raw_parts(
+                (*entry).symfile_addr,
+                (*entry).symfile_size as usize,
+            ));
+            entry = (*entry).next_entry;
+        }
+    }
+    f(&objects)
+}
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+
+/// A registered object file. Some fields are only read by the debugger.
+#[repr(C)]
+#[allow(dead_code)]
+pub struct JITCodeEntry {
+    next_entry: *mut JITCodeEntry,
+    prev_entry: *mut JITCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+/// The layout of `__jit_debug_descriptor` that debuggers expect.
+#[repr(C)]
+#[allow(dead_code)]
+pub struct JITDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JITCodeEntry,
+    first_entry: *mut JITCodeEntry,
+}
+
+/// The list of registered object files, which the debugger reads.
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static mut __jit_debug_descriptor: JITDescriptor = JITDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+/// The function the debugger puts a breakpoint on, to be notified of changes to
+/// `__jit_debug_descriptor`.
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn __jit_debug_register_code() {
+    // Keep the function from being optimized away or merged with another empty function.
+    let x = 0;
+    unsafe { ptr::read_volatile(&x) };
+}
+
+/// Serializes updates of `__jit_debug_descriptor` by different backends.
+static REGISTRATION_LOCK: AtomicBool = AtomicBool::new(false);
+
+struct RegistrationGuard;
+
+impl RegistrationGuard {
+    fn lock() -> Self {
+        while REGISTRATION_LOCK
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            thread::yield_now();
+        }
+        Self
+    }
+}
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        REGISTRATION_LOCK.store(false, Ordering::Release);
+    }
+}
+
+/// Build a relocatable ELF object file describing `function`.
+///
+/// The `.text` section contains a copy of the code and has its address, so the debugger places
+/// the symbol at the actual address of the function.
+fn build_object(function: &GdbJitFunction, architecture: Architecture) -> Vec<u8> {
+    let mut object = Object::new(BinaryFormat::Elf, architecture);
+    let text = object.section_id(StandardSection::Text);
+    let code = unsafe { slice::from_raw_parts(function.code, function.size) };
+    object.append_section_data(text, code, 1);
+    object.add_symbol(Symbol {
+        name: function.name.as_bytes().to_vec(),
+        value: 0,
+        size: function.size as u64,
+        kind: SymbolKind::Text,
+        scope: SymbolScope::Dynamic,
+        weak: false,
+        section: SymbolSection::Section(text),
+        flags: SymbolFlags::None,
+    });
+
+    if !function.eh_frame.is_empty() {
+        let mut eh_frame = function.eh_frame.clone();
+        for &at in &function.eh_frame_relocs {
+            eh_frame[at..at + 8].copy_from_slice(&(function.code as u64).to_le_bytes());
+        }
+        // The terminator of the `.eh_frame` entries.
+        eh_frame.extend_from_slice(&[0, 0, 0, 0]);
+        let section =
+            object.add_section(Vec::new(), b".eh_frame".to_vec(), SectionKind::ReadOnlyData);
+        object.append_section_data(section, &eh_frame, 8);
+    }
+
+    if !function.srclocs.is_empty() {
+        let sections = debug_sections(function).expect("unable to write the line table");
+        sections
+            .for_each(|id, data| {
+                if !data.slice().is_empty() {
+                    let section = object.add_section(
+                        Vec::new(),
+                        id.name().as_bytes().to_vec(),
+                        SectionKind::Debug,
+                    );
+                    object.append_section_data(section, data.slice(), 1);
+                }
+                Ok::<(), ()>(())
+            })
+            .unwrap();
+    }
+
+    let mut image = object.write().expect("unable to write the object file");
+    set_text_address(&mut image, function.code as u64);
+    image
+}
+
+/// Set the address of the `.text` section in the ELF64 `image`.
+///
+/// Like other relocatable object files, those written by `object::write` have sections at
+/// address 0. Debuggers don't relocate the object files registered with them, so the address
+/// of the code is written into the section header instead.
+fn set_text_address(image: &mut [u8], address: u64) {
+    const E_SHOFF: usize = 0x28;
+    const E_SHENTSIZE: usize = 0x3a;
+    const SH_ADDR: usize = 0x10;
+    // `.text` was the first section added, and follows the null section.
+    const TEXT_INDEX: usize = 1;
+
+    let shoff = u64::from_le_bytes(image[E_SHOFF..E_SHOFF + 8].try_into().unwrap()) as usize;
+    let shentsize =
+        u16::from_le_bytes(image[E_SHENTSIZE..E_SHENTSIZE + 2].try_into().unwrap()) as usize;
+    let at = shoff + TEXT_INDEX * shentsize + SH_ADDR;
+    image[at..at + 8].copy_from_slice(&address.to_le_bytes());
+}
+
+/// Write the DWARF compile unit of `function`, with a line table mapping its code to its
+/// source locations.
+///
+/// Since a `SourceLoc` doesn't identify a file, the line numbers are the `SourceLoc` bits, and
+/// the file is named after the function.
+fn debug_sections(
+    function: &GdbJitFunction,
+) -> gimli::write::Result<Sections<EndianVec<LittleEndian>>> {
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let code = function.code as u64;
+    let name = LineString::String(function.name.as_bytes().to_vec());
+
+    let mut dwarf = DwarfUnit::new(encoding);
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(Vec::new()),
+        name.clone(),
+        None,
+    );
+    let directory = program.default_directory();
+    let file = program.add_file(name, directory, None);
+    program.begin_sequence(Some(Address::Constant(code)));
+    for &(offset, srcloc) in &function.srclocs {
+        let row = program.row();
+        row.address_offset = u64::from(offset);
+        row.file = file;
+        row.line = u64::from(srcloc.bits());
+        program.generate_row();
+    }
+    program.end_sequence(function.size as u64);
+    dwarf.unit.line_program = program;
+
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(function.name.as_bytes().to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(code)),
+    );
+    entry.set(
+        gimli::DW_AT_high_pc,
+        AttributeValue::Udata(function.size as u64),
+    );
+
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections)?;
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::{Object as _, ObjectSection};
+
+    #[test]
+    fn object_layout() {
+        let code = [0xc3u8; 16];
+        let function = GdbJitFunction {
+            name: "f".to_string(),
+            code: code.as_ptr(),
+            size: code.len(),
+            srclocs: vec![(0, ir::SourceLoc::new(3)), (4, ir::SourceLoc::new(7))],
+            eh_frame: Vec::new(),
+            eh_frame_relocs: Vec::new(),
+        };
+        let image = build_object(&function, Architecture::X86_64);
+        let file = object::File::parse(&image).unwrap();
+
+        // The `.text` section is at the code, and `f` is at its start.
+        let text = file.section_by_name(".text").unwrap();
+        assert_eq!(text.address(), code.as_ptr() as u64);
+        assert_eq!(&*text.data(), &code[..]);
+        let (_, f) = file
+            .symbols()
+            .find(|(_, symbol)| symbol.name() == Some("f"))
+            .unwrap();
+        let section = f
+            .section_index()
+            .and_then(|index| file.section_by_index(index));
+        assert_eq!(section.and_then(|section| section.name()), Some(".text"));
+        assert_eq!((f.address(), f.size()), (0, 16));
+
+        assert!(file.section_by_name(".eh_frame").is_none());
+        assert!(file.section_by_name(".debug_info").is_some());
+        assert!(file.section_by_name(".debug_line").is_some());
+    }
+}
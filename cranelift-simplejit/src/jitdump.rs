@@ -0,0 +1,151 @@
+//! A jitdump file that lets the Linux `perf` tool annotate JIT code.
+//!
+//! Unlike a perf map, a jitdump file also contains the code bytes and line information of
+//! each function, from which `perf inject --jit` creates ELF images that can be annotated.
+
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_codegen::ir;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::{process, ptr, slice};
+
+const JITDUMP_MAGIC: u32 = 0x4a69_5444;
+const JITDUMP_VERSION: u32 = 1;
+const JITDUMP_HEADER_SIZE: u32 = 40;
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_DEBUG_INFO: u32 = 2;
+
+/// Writer of a `jit-<pid>.dump` file in the current directory, in the jitdump format described
+/// in `tools/perf/Documentation/jitdump-specification.txt` of the Linux sources.
+pub(crate) struct JitDump {
+    file: File,
+    /// The executable mapping of the file, which is how `perf record` finds the file.
+    map: *mut libc::c_void,
+    map_len: usize,
+    /// The number of functions written so far.
+    code_index: u64,
+}
+
+impl JitDump {
+    /// Create the file for code of the ELF machine type `e_machine`.
+    pub fn new(e_machine: u16) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(format!("jit-{}.dump", process::id()))?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&JITDUMP_MAGIC.to_ne_bytes());
+        header.extend_from_slice(&JITDUMP_VERSION.to_ne_bytes());
+        header.extend_from_slice(&JITDUMP_HEADER_SIZE.to_ne_bytes());
+        header.extend_from_slice(&u32::from(e_machine).to_ne_bytes());
+        header.extend_from_slice(&0u32.to_ne_bytes()); // pad1
+        header.extend_from_slice(&process::id().to_ne_bytes());
+        header.extend_from_slice(&timestamp().to_ne_bytes());
+        header.extend_from_slice(&0u64.to_ne_bytes()); // flags
+        debug_assert_eq!(header.len(), JITDUMP_HEADER_SIZE as usize);
+        file.write_all(&header)?;
+
+        let map_len = region::page::size();
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            file,
+            map,
+            map_len,
+            code_index: 0,
+        })
+    }
+
+    /// Add the function `name` with its code, and the source locations of the code if there
+    /// are any.
+    ///
+    /// Since a `SourceLoc` doesn't identify a file, the line numbers are the `SourceLoc` bits,
+    /// and the file name is the name of the function.
+    pub fn add_function(
+        &mut self,
+        name: &str,
+        code: *const u8,
+        size: usize,
+        srclocs: &[(CodeOffset, ir::SourceLoc)],
+    ) -> io::Result<()> {
+        let timestamp = timestamp();
+        let code_addr = code as u64;
+
+        if !srclocs.is_empty() {
+            let mut record = Vec::new();
+            record.extend_from_slice(&code_addr.to_ne_bytes());
+            record.extend_from_slice(&(srclocs.len() as u64).to_ne_bytes());
+            for &(offset, srcloc) in srclocs {
+                record.extend_from_slice(&(code_addr + u64::from(offset)).to_ne_bytes());
+                record.extend_from_slice(&srcloc.bits().to_ne_bytes()); // line
+                record.extend_from_slice(&0u32.to_ne_bytes()); // discriminator
+                push_str(&mut record, name);
+            }
+            self.write_record(JIT_CODE_DEBUG_INFO, timestamp, &record)?;
+        }
+
+        let mut record = Vec::new();
+        record.extend_from_slice(&process::id().to_ne_bytes());
+        record.extend_from_slice(&thread_id().to_ne_bytes());
+        record.extend_from_slice(&code_addr.to_ne_bytes()); // vma
+        record.extend_from_slice(&code_addr.to_ne_bytes());
+        record.extend_from_slice(&(size as u64).to_ne_bytes());
+        record.extend_from_slice(&self.code_index.to_ne_bytes());
+        push_str(&mut record, name);
+        record.extend_from_slice(unsafe { slice::from_raw_parts(code, size) });
+        self.code_index += 1;
+        self.write_record(JIT_CODE_LOAD, timestamp, &record)
+    }
+
+    fn write_record(&mut self, id: u32, timestamp: u64, body: &[u8]) -> io::Result<()> {
+        let total_size = (16 + body.len()) as u32;
+        let mut header = [0; 16];
+        header[0..4].copy_from_slice(&id.to_ne_bytes());
+        header[4..8].copy_from_slice(&total_size.to_ne_bytes());
+        header[8..16].copy_from_slice(&timestamp.to_ne_bytes());
+        self.file.write_all(&header)?;
+        self.file.write_all(body)
+    }
+}
+
+impl Drop for JitDump {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map, self.map_len) };
+    }
+}
+
+/// Append a null-terminated string.
+fn push_str(record: &mut Vec<u8>, s: &str) {
+    record.extend_from_slice(s.as_bytes());
+    record.push(0);
+}
+
+/// Timestamps of the monotonic clock, which `perf record -k mono` uses as well.
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+fn thread_id() -> u32 {
+    unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+}
@@ -0,0 +1,157 @@
+//! Stubs for functions which are compiled when they are first called.
+//!
+//! In lazy mode, every local function gets a stub when it is declared, and all calls to the
+//! function go through the stub. The stub jumps to the address stored in the function's slot,
+//! which initially is a resolver entry that saves the argument registers, calls the user's
+//! callback to define and finalize the function, and then jumps to the new code. Finalizing a
+//! function stores its code address into the slot, which also redirects the calls when a
+//! function is redefined.
+//!
+//! Stubs are only implemented for x86-64 with the System V calling convention.
+
+use crate::memory::Memory;
+use cranelift_module::FuncId;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::ptr;
+
+const SLOT_SIZE: usize = 8;
+const STUB_ALIGNMENT: u8 = 0x10;
+
+/// The user callback that defines lazily compiled functions.
+pub(crate) struct LazyCompiler {
+    callback: Box<dyn Fn(FuncId)>,
+}
+
+impl LazyCompiler {
+    /// Create a `LazyCompiler` for `callback`. It is leaked, since stubs referring to it may
+    /// outlive the backend just like the JIT memory itself.
+    pub fn leak(callback: Box<dyn Fn(FuncId)>) -> *const Self {
+        Box::into_raw(Box::new(Self { callback }))
+    }
+}
+
+/// The stub of a lazily compiled function.
+pub(crate) struct LazyStub {
+    /// The entry point that calls to the function use.
+    pub code: *const u8,
+    /// The size of the stub and its resolver entry.
+    pub size: usize,
+    /// The address the stub jumps to.
+    pub slot: *mut *const u8,
+}
+
+impl LazyStub {
+    /// Emit the stub and resolver entry for the function `id`.
+    pub fn new(
+        compiler: *const LazyCompiler,
+        id: FuncId,
+        code_memory: &mut Memory,
+        slot_memory: &mut Memory,
+    ) -> Self {
+        let slot = slot_memory
+            .allocate(SLOT_SIZE, SLOT_SIZE as u8)
+            .expect("TODO: handle OOM etc.") as *mut *const u8;
+
+        let mut code = Vec::new();
+        // The stub: `movabs r11, slot; jmp [r11]`.
+        code.extend_from_slice(&[0x49, 0xbb]);
+        code.extend_from_slice(&(slot as u64).to_le_bytes());
+        code.extend_from_slice(&[0x41, 0xff, 0x23]);
+        let entry_offset = code.len();
+        emit_resolver_entry(&mut code, compiler, id, slot);
+
+        let ptr = code_memory
+            .allocate(code.len(), STUB_ALIGNMENT)
+            .expect("TODO: handle OOM etc.");
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), ptr, code.len());
+            ptr::write(slot, ptr.add(entry_offset));
+        }
+        Self {
+            code: ptr,
+            size: code.len(),
+            slot,
+        }
+    }
+}
+
+/// Emit the code that calls `resolve_lazy_function` and continues in the function it returns.
+fn emit_resolver_entry(
+    code: &mut Vec<u8>,
+    compiler: *const LazyCompiler,
+    id: FuncId,
+    slot: *mut *const u8,
+) {
+    let entry = code.len();
+    // Save the integer argument registers: `push rdi; push rsi; push rdx; push rcx; push r8;
+    // push r9`. The stack is 16-byte aligned again after reserving space for the vector
+    // argument registers.
+    code.extend_from_slice(&[0x57, 0x56, 0x52, 0x51, 0x41, 0x50, 0x41, 0x51]);
+    // `sub rsp, 136`
+    code.extend_from_slice(&[0x48, 0x81, 0xec, 0x88, 0x00, 0x00, 0x00]);
+    for xmm in 0..8 {
+        // `movdqu [rsp + 16 * xmm], xmm`
+        code.extend_from_slice(&[0xf3, 0x0f, 0x7f, 0x44 | xmm << 3, 0x24, 16 * xmm]);
+    }
+
+    // Call `resolve_lazy_function(compiler, id, slot, entry)`, where `entry` is the address of
+    // this code, which the slot contains until the function is defined.
+    let resolver: extern "C" fn(_, _, _, _) -> _ = resolve_lazy_function;
+    // `movabs rdi, compiler`
+    code.extend_from_slice(&[0x48, 0xbf]);
+    code.extend_from_slice(&(compiler as u64).to_le_bytes());
+    // `mov esi, id`
+    code.push(0xbe);
+    code.extend_from_slice(&id.as_u32().to_le_bytes());
+    // `movabs rdx, slot`
+    code.extend_from_slice(&[0x48, 0xba]);
+    code.extend_from_slice(&(slot as u64).to_le_bytes());
+    // `lea rcx, [rip - entry]`
+    code.extend_from_slice(&[0x48, 0x8d, 0x0d]);
+    let rip = (code.len() + 4) as i32;
+    code.extend_from_slice(&(entry as i32 - rip).to_le_bytes());
+    // `movabs rax, resolver; call rax`
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&(resolver as usize as u64).to_le_bytes());
+    code.extend_from_slice(&[0xff, 0xd0]);
+
+    // Restore the argument registers, and jump to the function address returned in `rax`.
+    for xmm in 0..8 {
+        // `movdqu xmm, [rsp + 16 * xmm]`
+        code.extend_from_slice(&[0xf3, 0x0f, 0x6f, 0x44 | xmm << 3, 0x24, 16 * xmm]);
+    }
+    // `add rsp, 136`
+    code.extend_from_slice(&[0x48, 0x81, 0xc4, 0x88, 0x00, 0x00, 0x00]);
+    // `pop r9; pop r8; pop rcx; pop rdx; pop rsi; pop rdi`
+    code.extend_from_slice(&[0x41, 0x59, 0x41, 0x58, 0x59, 0x5a, 0x5e, 0x5f]);
+    // `jmp rax`
+    code.extend_from_slice(&[0xff, 0xe0]);
+}
+
+/// Called by the resolver entry of a stub to compile the function `id`.
+///
+/// Returns the code address of the function, as stored in `slot` by the callback.
+extern "C" fn resolve_lazy_function(
+    compiler: *const LazyCompiler,
+    id: u32,
+    slot: *const *const u8,
+    entry: *const u8,
+) -> *const u8 {
+    let compiler = unsafe { &*compiler };
+    let id = FuncId::from_u32(id);
+    // Unwinding through JIT code isn't possible, so a panicking callback ends the process.
+    if panic::catch_unwind(AssertUnwindSafe(|| (compiler.callback)(id))).is_err() {
+        eprintln!("lazy compilation of {} panicked", id);
+        process::abort();
+    }
+    let code = unsafe { ptr::read_volatile(slot) };
+    if code == entry {
+        eprintln!(
+            "lazy compilation callback didn't define and finalize {}",
+            id
+        );
+        process::abort();
+    }
+    code
+}
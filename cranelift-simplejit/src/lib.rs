@@ -24,9 +24,20 @@
 )]
 
 mod backend;
+#[cfg(any(all(feature = "perf", target_os = "linux"), feature = "gdb-jit"))]
+mod debug;
+#[cfg(feature = "gdb-jit")]
+mod gdb_jit;
+#[cfg(all(feature = "perf", target_os = "linux"))]
+mod jitdump;
+mod lazy;
 mod memory;
+#[cfg(target_os = "linux")]
+mod perf;
 
 pub use crate::backend::{SimpleJITBackend, SimpleJITBuilder};
+#[cfg(feature = "gdb-jit")]
+pub use crate::gdb_jit::with_gdb_jit_objects;
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
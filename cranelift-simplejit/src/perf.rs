@@ -0,0 +1,28 @@
+//! A perf map that lets the Linux `perf` tool name the JIT code in its profiles.
+//!
+//! A perf map is a text file listing the address, size and name of each function, which
+//! `perf report` reads directly.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::process;
+
+/// Writer of `/tmp/perf-<pid>.map`.
+pub(crate) struct PerfMap {
+    file: File,
+}
+
+impl PerfMap {
+    pub fn new() -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(format!("/tmp/perf-{}.map", process::id()))?;
+        Ok(Self { file })
+    }
+
+    /// Add an entry for the function `name`.
+    pub fn add_function(&mut self, name: &str, code: *const u8, size: usize) -> io::Result<()> {
+        writeln!(self.file, "{:x} {:x} {}", code as usize, size, name)
+    }
+}
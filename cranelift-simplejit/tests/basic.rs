@@ -75,6 +75,40 @@ fn panic_on_define_after_finalize() {
     define_simple_function(&mut module);
 }
 
+#[test]
+fn redefine_checks() {
+    use cranelift_codegen::settings::{self, Configurable};
+
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+    let func_id = define_simple_function(&mut module);
+    let sig = Signature {
+        params: vec![],
+        returns: vec![],
+        call_conv: CallConv::SystemV,
+    };
+    let import = module
+        .declare_function("imported", Linkage::Import, &sig)
+        .unwrap();
+
+    let mut flag_builder = settings::builder();
+    flag_builder.set("is_pic", "true").unwrap();
+    let pic_isa = cranelift_native::builder()
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+
+    // Imports are rejected before compiling, so an empty function doesn't matter.
+    let mut ctx = Context::new();
+    match module.redefine_function(import, &mut ctx, &*pic_isa) {
+        Err(ModuleError::InvalidImportDefinition(name)) => assert_eq!(name, "imported"),
+        result => panic!("unexpected result {:?}", result),
+    }
+    match module.redefine_function(func_id, &mut ctx, &*pic_isa) {
+        Err(ModuleError::IncompatibleIsa(_)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
 #[test]
 fn switch_error() {
     use cranelift_codegen::settings;
@@ -195,3 +229,219 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+mod lazy {
+    use super::*;
+    use cranelift_codegen::isa::TargetIsa;
+    use cranelift_codegen::settings::{self, Configurable};
+    use std::cell::{Cell, RefCell};
+    use std::mem;
+
+    thread_local! {
+        static MODULE: RefCell<Option<Module<SimpleJITBackend>>> = RefCell::new(None);
+        static COMPILATIONS: Cell<u32> = Cell::new(0);
+    }
+
+    /// Define `func_id` as a function returning `value`, compiled with `isa` if it is present.
+    fn define_constant_function(
+        module: &mut Module<SimpleJITBackend>,
+        func_id: FuncId,
+        value: i64,
+        isa: Option<&dyn TargetIsa>,
+    ) {
+        let mut sig = module.make_signature();
+        sig.returns.push(AbiParam::new(types::I32));
+
+        let mut ctx = module.make_context();
+        ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        {
+            let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+            let block = bcx.create_block();
+            bcx.switch_to_block(block);
+            let result = bcx.ins().iconst(types::I32, value);
+            bcx.ins().return_(&[result]);
+            bcx.seal_all_blocks();
+            bcx.finalize();
+        }
+
+        match isa {
+            Some(isa) => module.redefine_function(func_id, &mut ctx, isa).unwrap(),
+            None => module.define_function(func_id, &mut ctx).unwrap(),
+        };
+    }
+
+    #[test]
+    fn lazy_compilation() {
+        let mut builder = SimpleJITBuilder::new(default_libcall_names());
+        builder.lazy_compilation(|func_id| {
+            COMPILATIONS.with(|count| count.set(count.get() + 1));
+            MODULE.with(|module| {
+                let mut module = module.borrow_mut();
+                let module = module.as_mut().unwrap();
+                define_constant_function(module, func_id, 42, None);
+                module.finalize_definitions();
+            });
+        });
+        let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+        let mut sig = module.make_signature();
+        sig.returns.push(AbiParam::new(types::I32));
+        let callee = module
+            .declare_function("callee", Linkage::Local, &sig)
+            .unwrap();
+        let caller = module
+            .declare_function("caller", Linkage::Local, &sig)
+            .unwrap();
+
+        let mut ctx = module.make_context();
+        ctx.func = Function::with_name_signature(ExternalName::user(0, caller.as_u32()), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        {
+            let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+            let block = bcx.create_block();
+            bcx.switch_to_block(block);
+            let local_callee = module.declare_func_in_func(callee, &mut bcx.func);
+            let call = bcx.ins().call(local_callee, &[]);
+            let result = bcx.inst_results(call)[0];
+            bcx.ins().return_(&[result]);
+            bcx.seal_all_blocks();
+            bcx.finalize();
+        }
+        module.define_function(caller, &mut ctx).unwrap();
+
+        // `callee` isn't defined, but calls to it go through its stub.
+        module.finalize_definitions();
+        let caller: extern "C" fn() -> i32 =
+            unsafe { mem::transmute(module.get_finalized_function(caller)) };
+        let callee_stub: extern "C" fn() -> i32 =
+            unsafe { mem::transmute(module.get_finalized_function(callee)) };
+        MODULE.with(|cell| *cell.borrow_mut() = Some(module));
+
+        assert_eq!(COMPILATIONS.with(Cell::get), 0);
+        assert_eq!(caller(), 42);
+        assert_eq!(caller(), 42);
+        assert_eq!(callee_stub(), 42);
+        assert_eq!(COMPILATIONS.with(Cell::get), 1);
+
+        // Recompile the callee with optimizations, which redirects the existing calls.
+        MODULE.with(|cell| {
+            let mut flag_builder = settings::builder();
+            flag_builder.set("opt_level", "speed").unwrap();
+            let isa = cranelift_native::builder()
+                .unwrap()
+                .finish(settings::Flags::new(flag_builder));
+
+            let mut module = cell.borrow_mut();
+            let module = module.as_mut().unwrap();
+            define_constant_function(module, callee, 43, Some(&*isa));
+            module.finalize_definitions();
+        });
+        assert_eq!(caller(), 43);
+        assert_eq!(callee_stub(), 43);
+        assert_eq!(COMPILATIONS.with(Cell::get), 1);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn lazy_stub_in_perf_map() {
+        let mut builder = SimpleJITBuilder::new(default_libcall_names());
+        builder.lazy_compilation(|_| unreachable!());
+        builder.perf_map(true);
+        let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+        let sig = module.make_signature();
+        let func_id = module
+            .declare_function("perf_map_stub", Linkage::Local, &sig)
+            .unwrap();
+        module.finalize_definitions();
+        let stub = module.get_finalized_function(func_id);
+
+        let map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id())).unwrap();
+        let entry = format!("{:x} ", stub as usize);
+        assert!(map
+            .lines()
+            .any(|line| line.starts_with(&entry) && line.ends_with(" perf_map_stub (lazy stub)")));
+    }
+}
+
+#[test]
+#[cfg(all(feature = "perf", target_os = "linux"))]
+fn jitdump() {
+    use std::convert::TryInto;
+
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    builder.perf_map(false);
+    builder.jitdump(true);
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+    let func_id = define_simple_function(&mut module);
+    module.finalize_definitions();
+    let code = module.get_finalized_function(func_id);
+
+    let path = format!("jit-{}.dump", std::process::id());
+    let dump = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let u32_at = |offset: usize| u32::from_ne_bytes(dump[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_ne_bytes(dump[offset..offset + 8].try_into().unwrap());
+
+    // The header is followed by the code load record of `abc`.
+    assert_eq!(u32_at(0), 0x4a69_5444);
+    assert_eq!(u32_at(40), 0);
+    assert_eq!(u32_at(44) as usize, dump.len() - 40);
+    assert_eq!(u64_at(64), code as u64);
+    assert_eq!(&dump[96..100], b"abc\0");
+}
+
+#[test]
+#[cfg(feature = "gdb-jit")]
+fn gdb_jit() {
+    use object::{Object, ObjectSection};
+
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+    let sig = Signature {
+        params: vec![],
+        returns: vec![],
+        call_conv: CallConv::SystemV,
+    };
+    let func_id = module
+        .declare_function("gdb_jit_function", Linkage::Local, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+    ctx.func.collect_frame_layout_info();
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.switch_to_block(block);
+        bcx.set_srcloc(SourceLoc::new(42));
+        bcx.ins().return_(&[]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    let code = module.get_finalized_function(func_id);
+    let function: extern "C" fn() = unsafe { std::mem::transmute(code) };
+    function();
+
+    // Other tests may register object files as well, so look for the one of this function.
+    let found = with_gdb_jit_objects(|objects| {
+        objects.iter().any(|image| {
+            let file = object::File::parse(image).unwrap();
+            let text = match file.section_by_name(".text") {
+                Some(text) => text,
+                None => return false,
+            };
+            text.address() == code as u64
+                && file
+                    .symbols()
+                    .any(|(_, symbol)| symbol.name() == Some("gdb_jit_function"))
+        })
+    });
+    assert!(found);
+}
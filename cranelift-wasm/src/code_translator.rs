@@ -546,8 +546,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          * special functions.
          ************************************************************************************/
         Operator::MemoryGrow { reserved } => {
-            // The WebAssembly MVP only supports one linear memory, but we expect the reserved
-            // argument to be a memory index.
+            // The WebAssembly MVP only supports one linear memory, but with the multi-memory
+            // proposal the reserved argument is a memory index.
             let heap_index = MemoryIndex::from_u32(*reserved);
             let heap = state.get_heap(builder.func, *reserved, environ)?;
             let val = state.pop1();
@@ -1675,7 +1675,8 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
 ) -> WasmResult<()> {
     let addr32 = state.pop1();
-    // We don't yet support multiple linear memories.
+    // The version of wasmparser we use doesn't decode the memory index of a `memarg`, so loads
+    // always access the first linear memory.
     let heap = state.get_heap(builder.func, 0, environ)?;
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
     // Note that we don't set `is_aligned` here, even if the load instruction's
@@ -1700,7 +1701,7 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     let (addr32, val) = state.pop2();
     let val_ty = builder.func.dfg.value_type(val);
 
-    // We don't yet support multiple linear memories.
+    // See the comment in `translate_load` about the memory index.
     let heap = state.get_heap(builder.func, 0, environ)?;
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
     // See the comments in `translate_load` about the flags.
@@ -0,0 +1,444 @@
+//! Reader for the DWARF `.debug_line` custom section of a WebAssembly module.
+//!
+//! Compilers targeting WebAssembly describe the source lines of the code in a `.debug_line`
+//! custom section, where addresses are offsets from the start of the Code section payload. The
+//! `LineTable` built from it maps those offsets back to source files and lines.
+//!
+//! Only the version 2 to 4 line number program formats are supported.
+
+use crate::environ::{WasmError, WasmResult};
+use core::cmp::Ordering;
+use std::string::String;
+use std::vec::Vec;
+
+/// A source position found in a `LineTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLine<'a> {
+    /// The path of the source file, including its include directory if there is one.
+    pub file: &'a str,
+    /// The 1-based line number, or 0 if the code doesn't correspond to any line.
+    pub line: u64,
+    /// The 1-based column number, or 0 for the whole line.
+    pub column: u64,
+}
+
+/// A row of the line number matrix.
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    address: u64,
+    file: usize,
+    line: u64,
+    column: u64,
+    end_sequence: bool,
+}
+
+/// The line number information of a module, decoded from its `.debug_line` section.
+#[derive(Debug, Default)]
+pub struct LineTable {
+    /// All rows of all sequences, sorted by address.
+    rows: Vec<Row>,
+    /// File names referenced by the rows.
+    files: Vec<String>,
+}
+
+impl LineTable {
+    /// Decode the line number programs in the contents of a `.debug_line` section.
+    pub fn parse(data: &[u8]) -> WasmResult<Self> {
+        let mut table = Self::default();
+        let mut reader = Reader { data, pos: 0 };
+        while !reader.eof() {
+            table.parse_unit(&mut reader)?;
+        }
+        // Sort the sequences by address. The end of a sequence sorts before a row starting
+        // another sequence at the same address.
+        table
+            .rows
+            .sort_by_key(|row| (row.address, !row.end_sequence));
+        Ok(table)
+    }
+
+    /// Find the source line of the code at `address`, an offset from the start of the Code
+    /// section payload.
+    pub fn lookup(&self, address: u64) -> Option<SourceLine> {
+        // Find the last row at or before `address`.
+        let next = self
+            .rows
+            .binary_search_by(|row| {
+                if row.address <= address {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_err();
+        let index = next.checked_sub(1)?;
+        let row = &self.rows[index];
+        if row.end_sequence {
+            return None;
+        }
+        Some(SourceLine {
+            file: &self.files[row.file],
+            line: row.line,
+            column: row.column,
+        })
+    }
+
+    /// Parse one line number program, starting with its header.
+    fn parse_unit(&mut self, reader: &mut Reader) -> WasmResult<()> {
+        let (unit_length, offset_size) = match reader.u32()? {
+            0xffff_ffff => (reader.u64()?, 8),
+            length => (u64::from(length), 4),
+        };
+        let unit_end = reader.offset_from_here(unit_length)?;
+        let version = reader.u16()?;
+        if version < 2 || version > 4 {
+            return Err(reader.error("unsupported .debug_line version"));
+        }
+        let header_length = if offset_size == 8 {
+            reader.u64()?
+        } else {
+            u64::from(reader.u32()?)
+        };
+        let program_start = reader.offset_from_here(header_length)?;
+        let minimum_instruction_length = u64::from(reader.u8()?);
+        if version >= 4 {
+            // maximum_operations_per_instruction, which is only used for VLIW targets.
+            reader.u8()?;
+        }
+        reader.u8()?; // default_is_stmt
+        let line_base = i64::from(reader.u8()? as i8);
+        let line_range = u64::from(reader.u8()?);
+        let opcode_base = reader.u8()?;
+        if line_range == 0 || opcode_base == 0 {
+            return Err(reader.error("invalid .debug_line header"));
+        }
+        let mut standard_opcode_lengths = Vec::new();
+        for _ in 1..opcode_base {
+            standard_opcode_lengths.push(reader.u8()?);
+        }
+
+        let mut directories = Vec::new();
+        loop {
+            let directory = reader.string()?;
+            if directory.is_empty() {
+                break;
+            }
+            directories.push(directory);
+        }
+        // File numbers are 1-based, so map them through the indices of this unit's files.
+        let mut files = Vec::new();
+        loop {
+            let name = reader.string()?;
+            if name.is_empty() {
+                break;
+            }
+            files.push(self.add_file(&directories, &name, reader)?);
+        }
+
+        reader.pos = program_start;
+        let mut state = LineState::new();
+        while reader.pos < unit_end {
+            let opcode = reader.u8()?;
+            if opcode >= opcode_base {
+                // A special opcode advances both the address and the line, and appends a row.
+                let adjusted = u64::from(opcode - opcode_base);
+                state.advance(adjusted / line_range * minimum_instruction_length);
+                state.advance_line(line_base + (adjusted % line_range) as i64);
+                self.push_row(&state, &files, false)?;
+                continue;
+            }
+            match opcode {
+                0 => {
+                    let length = reader.uleb()?;
+                    if length == 0 {
+                        return Err(reader.error("empty extended opcode in .debug_line"));
+                    }
+                    let end = reader.offset_from_here(length)?;
+                    match reader.u8()? {
+                        // DW_LNE_end_sequence
+                        1 => {
+                            self.push_row(&state, &files, true)?;
+                            state = LineState::new();
+                        }
+                        // DW_LNE_set_address
+                        2 => {
+                            state.address = match end.checked_sub(reader.pos) {
+                                Some(4) => u64::from(reader.u32()?),
+                                Some(8) => reader.u64()?,
+                                _ => return Err(reader.error("invalid DW_LNE_set_address")),
+                            };
+                        }
+                        // DW_LNE_define_file
+                        3 => {
+                            let name = reader.string()?;
+                            files.push(self.add_file(&directories, &name, reader)?);
+                        }
+                        // DW_LNE_set_discriminator and vendor extensions.
+                        _ => {}
+                    }
+                    reader.pos = end;
+                }
+                // DW_LNS_copy
+                1 => self.push_row(&state, &files, false)?,
+                // DW_LNS_advance_pc
+                2 => state.advance(reader.uleb()?.wrapping_mul(minimum_instruction_length)),
+                // DW_LNS_advance_line
+                3 => state.advance_line(reader.sleb()?),
+                // DW_LNS_set_file
+                4 => state.file = reader.uleb()?,
+                // DW_LNS_set_column
+                5 => state.column = reader.uleb()?,
+                // DW_LNS_const_add_pc
+                8 => {
+                    let adjusted = u64::from(255 - opcode_base);
+                    state.advance(adjusted / line_range * minimum_instruction_length);
+                }
+                // DW_LNS_fixed_advance_pc
+                9 => state.advance(u64::from(reader.u16()?)),
+                // Opcodes that only change flags we don't track, and unknown standard opcodes.
+                _ => {
+                    for _ in 0..standard_opcode_lengths[usize::from(opcode - 1)] {
+                        reader.uleb()?;
+                    }
+                }
+            }
+        }
+        reader.pos = unit_end;
+        Ok(())
+    }
+
+    /// Read the rest of a file entry with the given `name`, and return its index in `files`.
+    fn add_file(
+        &mut self,
+        directories: &[String],
+        name: &str,
+        reader: &mut Reader,
+    ) -> WasmResult<usize> {
+        let directory = reader.uleb()?;
+        reader.uleb()?; // Modification time.
+        reader.uleb()?; // File size.
+        let path = match directory {
+            // Directory 0 is the compilation directory, which isn't recorded here.
+            0 => String::from(name),
+            n => match directories.get(n as usize - 1) {
+                Some(directory) => format!("{}/{}", directory, name),
+                None => return Err(reader.error("invalid directory index in .debug_line")),
+            },
+        };
+        self.files.push(path);
+        Ok(self.files.len() - 1)
+    }
+
+    fn push_row(
+        &mut self,
+        state: &LineState,
+        files: &[usize],
+        end_sequence: bool,
+    ) -> WasmResult<()> {
+        let file = match files.get((state.file as usize).wrapping_sub(1)) {
+            Some(&file) => file,
+            None => {
+                return Err(WasmError::InvalidWebAssembly {
+                    message: "invalid file index in .debug_line",
+                    offset: 0,
+                })
+            }
+        };
+        self.rows.push(Row {
+            address: state.address,
+            file,
+            line: state.line,
+            column: state.column,
+            end_sequence,
+        });
+        Ok(())
+    }
+}
+
+/// The registers of the line number state machine that we track.
+struct LineState {
+    address: u64,
+    file: u64,
+    line: u64,
+    column: u64,
+}
+
+impl LineState {
+    fn new() -> Self {
+        Self {
+            address: 0,
+            file: 1,
+            line: 1,
+            column: 0,
+        }
+    }
+
+    fn advance(&mut self, operation_advance: u64) {
+        self.address = self.address.wrapping_add(operation_advance);
+    }
+
+    fn advance_line(&mut self, line_advance: i64) {
+        self.line = (self.line as i64).wrapping_add(line_advance) as u64;
+    }
+}
+
+/// A little-endian reader for DWARF data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn eof(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn error(&self, message: &'static str) -> WasmError {
+        WasmError::InvalidWebAssembly {
+            message,
+            offset: self.pos,
+        }
+    }
+
+    /// Get the position `length` bytes after the current one, checking that it's in bounds.
+    fn offset_from_here(&self, length: u64) -> WasmResult<usize> {
+        match (self.pos as u64).checked_add(length) {
+            Some(end) if end <= self.data.len() as u64 => Ok(end as usize),
+            _ => Err(self.error("unexpected end of .debug_line")),
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> WasmResult<&'a [u8]> {
+        let end = self.offset_from_here(len as u64)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> WasmResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> WasmResult<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from(b[0]) | u16::from(b[1]) << 8)
+    }
+
+    fn u32(&mut self) -> WasmResult<u32> {
+        let b = self.bytes(4)?;
+        Ok(b.iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | u32::from(byte)))
+    }
+
+    fn u64(&mut self) -> WasmResult<u64> {
+        let b = self.bytes(8)?;
+        Ok(b.iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | u64::from(byte)))
+    }
+
+    fn uleb(&mut self) -> WasmResult<u64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> WasmResult<i64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Read a null-terminated string.
+    fn string(&mut self) -> WasmResult<String> {
+        let len = match self.data[self.pos..].iter().position(|&b| b == 0) {
+            Some(len) => len,
+            None => return Err(self.error("unterminated string in .debug_line")),
+        };
+        let bytes = self.bytes(len)?;
+        self.pos += 1;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineTable, SourceLine};
+
+    #[test]
+    fn line_program() {
+        #[rustfmt::skip]
+        const DEBUG_LINE: [u8; 59] = [
+            55, 0, 0, 0, // unit_length
+            4, 0, // version
+            31, 0, 0, 0, // header_length
+            1, // minimum_instruction_length
+            1, // maximum_operations_per_instruction
+            1, // default_is_stmt
+            0xfb, // line_base = -5
+            14, // line_range
+            13, // opcode_base
+            0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, // standard_opcode_lengths
+            b's', b'r', b'c', 0, 0, // include_directories = ["src"]
+            b'a', b'.', b'c', 0, 1, 0, 0, 0, // file_names = ["src/a.c"]
+            0, 5, 2, 0x10, 0, 0, 0, // DW_LNE_set_address 0x10
+            3, 2, // DW_LNS_advance_line 3
+            1, // DW_LNS_copy
+            5, 7, // DW_LNS_set_column 7
+            0x4b, // special opcode: address += 4, line += 1
+            2, 6, // DW_LNS_advance_pc 6
+            0, 1, 1, // DW_LNE_end_sequence
+        ];
+
+        let table = LineTable::parse(&DEBUG_LINE).unwrap();
+        assert_eq!(table.lookup(0xf), None);
+        assert_eq!(
+            table.lookup(0x10),
+            Some(SourceLine {
+                file: "src/a.c",
+                line: 3,
+                column: 0,
+            })
+        );
+        assert_eq!(
+            table.lookup(0x17),
+            Some(SourceLine {
+                file: "src/a.c",
+                line: 4,
+                column: 7,
+            })
+        );
+        assert_eq!(table.lookup(0x1a), None);
+    }
+
+    #[test]
+    fn overlong_unit() {
+        // A 64-bit unit_length that would overflow when added to the current position.
+        const DEBUG_LINE: [u8; 14] = [
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 4, 0,
+        ];
+        assert!(LineTable::parse(&DEBUG_LINE).is_err());
+    }
+}
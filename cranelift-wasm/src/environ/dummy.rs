@@ -8,12 +8,14 @@
 use crate::environ::{
     FuncEnvironment, GlobalVariable, ModuleEnvironment, ReturnMode, TargetEnvironment, WasmResult,
 };
-use crate::func_translator::FuncTranslator;
+use crate::fuel::FuelConfig;
+use crate::func_translator::{FuncTranslator, FunctionBodyJob};
 use crate::state::ModuleTranslationState;
 use crate::translation_utils::{
-    DefinedFuncIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, PassiveDataIndex,
-    PassiveElemIndex, SignatureIndex, Table, TableIndex,
+    ConstExpr, DefinedFuncIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex,
+    PassiveDataIndex, PassiveElemIndex, SignatureIndex, Table, TableIndex,
 };
+use crate::HashMap;
 use core::convert::TryFrom;
 use cranelift_codegen::cursor::FuncCursor;
 use cranelift_codegen::ir::immediates::{Offset32, Uimm64};
@@ -21,6 +23,8 @@ use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{self, InstBuilder};
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_entity::{EntityRef, PrimaryMap, SecondaryMap};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::boxed::Box;
 use std::string::String;
 use std::vec::Vec;
@@ -130,6 +134,12 @@ pub struct DummyEnvironment {
 
     /// Function names.
     function_names: SecondaryMap<FuncIndex, String>,
+
+    /// Local variable names of each function, by local index.
+    local_names: SecondaryMap<FuncIndex, HashMap<u32, String>>,
+
+    /// Fuel metering configuration for the translated functions.
+    fuel_config: Option<FuelConfig>,
 }
 
 impl DummyEnvironment {
@@ -142,13 +152,22 @@ impl DummyEnvironment {
             return_mode,
             debug_info,
             function_names: SecondaryMap::new(),
+            local_names: SecondaryMap::new(),
+            fuel_config: None,
         }
     }
 
+    /// Instrument the functions translated from now on with fuel metering.
+    pub fn enable_fuel_metering(&mut self, config: FuelConfig) {
+        self.fuel_config = Some(config);
+    }
+
     /// Return a `DummyFuncEnvironment` for translating functions within this
     /// `DummyEnvironment`.
     pub fn func_env(&self) -> DummyFuncEnvironment {
-        DummyFuncEnvironment::new(&self.info, self.return_mode)
+        let mut func_env = DummyFuncEnvironment::new(&self.info, self.return_mode);
+        func_env.fuel_config = self.fuel_config;
+        func_env
     }
 
     fn get_func_type(&self, func_index: FuncIndex) -> SignatureIndex {
@@ -165,6 +184,56 @@ impl DummyEnvironment {
     pub fn get_func_name(&self, func_index: FuncIndex) -> Option<&str> {
         self.function_names.get(func_index).map(String::as_ref)
     }
+
+    /// Return the name of a local variable of a function, if the name section has one.
+    pub fn get_local_name(&self, func_index: FuncIndex, local_index: u32) -> Option<&str> {
+        self.local_names
+            .get(func_index)?
+            .get(&local_index)
+            .map(String::as_ref)
+    }
+
+    /// Translate the function bodies produced by `translate_module_deferred`.
+    ///
+    /// With the `parallel` feature the bodies are translated on the rayon thread pool, otherwise
+    /// one after the other. Either way the results are the same as with `translate_module`.
+    pub fn define_function_bodies(
+        &mut self,
+        module_translation_state: &ModuleTranslationState,
+        jobs: Vec<FunctionBodyJob>,
+    ) -> WasmResult<()> {
+        let sizes: Vec<usize> = jobs.iter().map(|job| job.body_bytes.len()).collect();
+        let this = &*self;
+        let translate = |mut job: FunctionBodyJob| -> WasmResult<ir::Function> {
+            let mut func_environ = this.func_env();
+            let mut func = this.empty_function(job.index);
+            job.translate(module_translation_state, &mut func, &mut func_environ)?;
+            Ok(func)
+        };
+
+        #[cfg(feature = "parallel")]
+        let funcs: WasmResult<Vec<ir::Function>> = jobs.into_par_iter().map(translate).collect();
+        #[cfg(not(feature = "parallel"))]
+        let funcs: WasmResult<Vec<ir::Function>> = jobs.into_iter().map(translate).collect();
+
+        for func in funcs? {
+            self.info.function_bodies.push(func);
+        }
+        self.func_bytecode_sizes.extend(sizes);
+        Ok(())
+    }
+
+    /// Create an empty function with the name and signature of the defined function `index`.
+    fn empty_function(&self, index: DefinedFuncIndex) -> ir::Function {
+        let func_index = FuncIndex::new(self.get_num_func_imports() + index.index());
+        let name = get_func_name(func_index);
+        let sig = self.func_env().vmctx_sig(self.get_func_type(func_index));
+        let mut func = ir::Function::with_name_signature(name, sig);
+        if self.debug_info {
+            func.collect_debug_info();
+        }
+        func
+    }
 }
 
 /// The `FuncEnvironment` implementation for use by the `DummyEnvironment`.
@@ -172,6 +241,8 @@ pub struct DummyFuncEnvironment<'dummy_environment> {
     pub mod_info: &'dummy_environment DummyModuleInfo,
 
     return_mode: ReturnMode,
+
+    fuel_config: Option<FuelConfig>,
 }
 
 impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
@@ -179,6 +250,7 @@ impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
         Self {
             mod_info,
             return_mode,
+            fuel_config: None,
         }
     }
 
@@ -220,16 +292,17 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         })
     }
 
-    fn make_heap(&mut self, func: &mut ir::Function, _index: MemoryIndex) -> WasmResult<ir::Heap> {
-        // Create a static heap whose base address is stored at `vmctx+0`.
+    fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> WasmResult<ir::Heap> {
+        // Create a static heap whose base address is stored at `vmctx+0` for the first memory,
+        // followed by the base addresses of the other memories.
         let addr = func.create_global_value(ir::GlobalValueData::VMContext);
+        let offset = i32::try_from(index.index() * usize::from(self.pointer_bytes())).unwrap();
         let gv = func.create_global_value(ir::GlobalValueData::Load {
             base: addr,
-            offset: Offset32::new(0),
+            offset: Offset32::new(offset),
             global_type: self.pointer_type(),
             readonly: true,
         });
-
         Ok(func.create_heap(ir::HeapData {
             base: gv,
             min_size: 0.into(),
@@ -357,23 +430,44 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         Ok(pos.ins().Call(ir::Opcode::Call, INVALID, callee, args).0)
     }
 
+    fn fuel_config(&self) -> Option<FuelConfig> {
+        self.fuel_config
+    }
+
+    fn make_out_of_fuel_func(&mut self, func: &mut ir::Function) -> WasmResult<ir::FuncRef> {
+        // A real implementation would refuel or trap in this function.
+        let mut sig = ir::Signature::new(self.target_config().default_call_conv);
+        sig.params.push(ir::AbiParam::special(
+            self.pointer_type(),
+            ir::ArgumentPurpose::VMContext,
+        ));
+        let signature = func.import_signature(sig);
+        Ok(func.import_function(ir::ExtFuncData {
+            name: ir::ExternalName::user(1, 0),
+            signature,
+            colocated: false,
+        }))
+    }
+
     fn translate_memory_grow(
         &mut self,
         mut pos: FuncCursor,
         _index: MemoryIndex,
-        _heap: ir::Heap,
+        heap: ir::Heap,
         _val: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Ok(pos.ins().iconst(I32, -1))
+        let index_type = pos.func.heaps[heap].index_type;
+        Ok(pos.ins().iconst(index_type, -1))
     }
 
     fn translate_memory_size(
         &mut self,
         mut pos: FuncCursor,
         _index: MemoryIndex,
-        _heap: ir::Heap,
+        heap: ir::Heap,
     ) -> WasmResult<ir::Value> {
-        Ok(pos.ins().iconst(I32, -1))
+        let index_type = pos.func.heaps[heap].index_type;
+        Ok(pos.ins().iconst(index_type, -1))
     }
 
     fn translate_memory_copy(
@@ -597,8 +691,7 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
     fn declare_table_elements(
         &mut self,
         _table_index: TableIndex,
-        _base: Option<GlobalIndex>,
-        _offset: usize,
+        _offset: ConstExpr,
         _elements: Box<[FuncIndex]>,
     ) -> WasmResult<()> {
         // We do nothing
@@ -642,8 +735,7 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
     fn declare_data_initialization(
         &mut self,
         _memory_index: MemoryIndex,
-        _base: Option<GlobalIndex>,
-        _offset: usize,
+        _offset: ConstExpr,
         _data: &'data [u8],
     ) -> WasmResult<()> {
         // We do nothing
@@ -704,14 +796,9 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
     ) -> WasmResult<()> {
         let func = {
             let mut func_environ = DummyFuncEnvironment::new(&self.info, self.return_mode);
-            let func_index =
-                FuncIndex::new(self.get_num_func_imports() + self.info.function_bodies.len());
-            let name = get_func_name(func_index);
-            let sig = func_environ.vmctx_sig(self.get_func_type(func_index));
-            let mut func = ir::Function::with_name_signature(name, sig);
-            if self.debug_info {
-                func.collect_debug_info();
-            }
+            func_environ.fuel_config = self.fuel_config;
+            let index = DefinedFuncIndex::new(self.info.function_bodies.len());
+            let mut func = self.empty_function(index);
             self.trans.translate(
                 module_translation_state,
                 body_bytes,
@@ -730,4 +817,18 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
         self.function_names[func_index] = String::from(name);
         Ok(())
     }
+
+    fn declare_local_name(
+        &mut self,
+        func_index: FuncIndex,
+        local_index: u32,
+        name: &'data str,
+    ) -> WasmResult<()> {
+        self.local_names[func_index].insert(local_index, String::from(name));
+        Ok(())
+    }
+
+    fn wants_line_table(&self) -> bool {
+        self.debug_info
+    }
 }
@@ -6,10 +6,11 @@
 //!
 //! [Wasmtime]: https://github.com/bytecodealliance/wasmtime
 
+use crate::fuel::FuelConfig;
 use crate::state::{FuncTranslationState, ModuleTranslationState};
 use crate::translation_utils::{
-    FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, PassiveDataIndex, PassiveElemIndex,
-    SignatureIndex, Table, TableIndex,
+    ConstExpr, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, PassiveDataIndex,
+    PassiveElemIndex, SignatureIndex, Table, TableIndex,
 };
 use core::convert::From;
 use cranelift_codegen::cursor::FuncCursor;
@@ -436,6 +437,23 @@ pub trait FuncEnvironment: TargetEnvironment {
         Ok(())
     }
 
+    /// Returns the configuration of the fuel metering instrumentation, or `None` to translate
+    /// functions without it.
+    ///
+    /// See the `fuel` module documentation for the code emitted when this is enabled.
+    fn fuel_config(&self) -> Option<FuelConfig> {
+        None
+    }
+
+    /// Set up the function to call when the fuel counter is found to be negative, or when the
+    /// embedder has requested an interrupt by making it negative.
+    ///
+    /// The function is called with the VM context pointer as its only argument and doesn't
+    /// return any values. This is only called when `fuel_config` returns a configuration.
+    fn make_out_of_fuel_func(&mut self, _func: &mut ir::Function) -> WasmResult<ir::FuncRef> {
+        Err(wasm_unsupported!("fuel metering"))
+    }
+
     /// Optional callback for the `FunctionEnvironment` performing this translation to maintain
     /// internal state or prepare custom state for the operator to translate
     fn before_translate_operator(
@@ -592,12 +610,12 @@ pub trait ModuleEnvironment<'data>: TargetEnvironment {
         Ok(())
     }
 
-    /// Fills a declared table with references to functions in the module.
+    /// Fills a declared table with references to functions in the module, starting at the
+    /// index computed by `offset`.
     fn declare_table_elements(
         &mut self,
         table_index: TableIndex,
-        base: Option<GlobalIndex>,
-        offset: usize,
+        offset: ConstExpr,
         elements: Box<[FuncIndex]>,
     ) -> WasmResult<()>;
 
@@ -641,12 +659,12 @@ pub trait ModuleEnvironment<'data>: TargetEnvironment {
         Ok(())
     }
 
-    /// Fills a declared memory with bytes at module instantiation.
+    /// Fills a declared memory with bytes at module instantiation, starting at the address
+    /// computed by `offset`.
     fn declare_data_initialization(
         &mut self,
         memory_index: MemoryIndex,
-        base: Option<GlobalIndex>,
-        offset: usize,
+        offset: ConstExpr,
         data: &'data [u8],
     ) -> WasmResult<()>;
 
@@ -658,6 +676,36 @@ pub trait ModuleEnvironment<'data>: TargetEnvironment {
         Ok(())
     }
 
+    /// Declares the name of a local variable of a function to the environment.
+    ///
+    /// `local_index` counts the function's parameters first, followed by its declared locals.
+    /// It is also the index of the `ValueLabel` that the function translator attaches to the
+    /// values of the local, so names can be attached to the value label ranges of a compiled
+    /// function. By default this does nothing.
+    fn declare_local_name(
+        &mut self,
+        _func_index: FuncIndex,
+        _local_index: u32,
+        _name: &'data str,
+    ) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Declares the name of the module to the environment.
+    ///
+    /// By default this does nothing.
+    fn declare_module_name(&mut self, _name: &'data str) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Should the `.debug_line` custom section be decoded into the line table of the
+    /// `ModuleTranslationState`?
+    ///
+    /// By default this returns `false`, which avoids the cost of decoding it.
+    fn wants_line_table(&self) -> bool {
+        false
+    }
+
     /// Indicates that a custom section has been found in the wasm file
     fn custom_section(&mut self, _name: &'data str, _data: &'data [u8]) -> WasmResult<()> {
         Ok(())
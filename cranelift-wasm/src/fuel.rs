@@ -0,0 +1,181 @@
+//! Fuel metering for translated WebAssembly functions.
+//!
+//! When a `FuncEnvironment` returns a `FuelConfig`, the translator keeps a signed 64-bit counter
+//! of remaining fuel at a fixed offset from the VM context. The cost of the operators in each
+//! basic block is subtracted from the counter before control leaves the block, and the counter
+//! is checked at function entry and at every loop header. When it is negative, the function
+//! provided by `FuncEnvironment::make_out_of_fuel_func` is called with the VM context.
+//!
+//! That function may add more fuel and return, or trap. Since the counter lives in memory, an
+//! embedder can also interrupt a running instance from another thread by storing a negative
+//! value into it, in which case the out-of-fuel function is called at the next check.
+
+use crate::environ::{FuncEnvironment, WasmResult};
+use crate::state::FuncTranslationState;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{self, InstBuilder};
+use cranelift_frontend::FunctionBuilder;
+use wasmparser::Operator;
+
+/// Configuration of the fuel metering instrumentation.
+#[derive(Clone, Copy)]
+pub struct FuelConfig {
+    /// Offset of the `i64` fuel counter from the VM context pointer.
+    pub vmctx_offset: i32,
+
+    /// The amount of fuel consumed by each operator.
+    pub operator_cost: fn(&Operator) -> u32,
+}
+
+impl FuelConfig {
+    /// Create a configuration for a fuel counter at `vmctx_offset`, with the operator costs of
+    /// `default_operator_cost`.
+    pub fn new(vmctx_offset: i32) -> Self {
+        Self {
+            vmctx_offset,
+            operator_cost: default_operator_cost,
+        }
+    }
+}
+
+/// The default cost table: operators that don't do any work at run time are free and all other
+/// operators cost one unit of fuel.
+pub fn default_operator_cost(op: &Operator) -> u32 {
+    match op {
+        Operator::Nop
+        | Operator::Drop
+        | Operator::Block { .. }
+        | Operator::Loop { .. }
+        | Operator::Else
+        | Operator::End => 0,
+        _ => 1,
+    }
+}
+
+/// Does `op` end a basic block, or otherwise need the fuel counter to be up to date?
+fn is_accounting_boundary(op: &Operator) -> bool {
+    match op {
+        Operator::Unreachable
+        | Operator::Loop { .. }
+        | Operator::If { .. }
+        | Operator::Else
+        | Operator::End
+        | Operator::Br { .. }
+        | Operator::BrIf { .. }
+        | Operator::BrTable { .. }
+        | Operator::Return
+        | Operator::Call { .. }
+        | Operator::CallIndirect { .. } => true,
+        _ => false,
+    }
+}
+
+/// The fuel metering state of a single function translation.
+pub(crate) struct FuelMeter {
+    config: FuelConfig,
+    pointer_type: ir::Type,
+    vmctx: ir::GlobalValue,
+    out_of_fuel: ir::FuncRef,
+
+    /// Fuel consumed by the operators translated since the counter was last updated.
+    pending: u64,
+}
+
+impl FuelMeter {
+    /// Set up fuel metering for `func`, which must have a VM context parameter.
+    pub fn new<FE: FuncEnvironment + ?Sized>(
+        config: FuelConfig,
+        func: &mut ir::Function,
+        environ: &mut FE,
+    ) -> WasmResult<Self> {
+        let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
+        let out_of_fuel = environ.make_out_of_fuel_func(func)?;
+        Ok(Self {
+            config,
+            pointer_type: environ.pointer_type(),
+            vmctx,
+            out_of_fuel,
+            pending: 0,
+        })
+    }
+
+    /// Account for `op`, which is about to be translated.
+    pub fn before_operator(
+        &mut self,
+        op: &Operator,
+        builder: &mut FunctionBuilder,
+        state: &FuncTranslationState,
+    ) {
+        // Unreachable operators don't emit any code, so there is nothing to pay for.
+        if !state.reachable() {
+            return;
+        }
+        self.pending += u64::from((self.config.operator_cost)(op));
+        if is_accounting_boundary(op) {
+            self.flush(builder);
+        }
+    }
+
+    /// Check the fuel counter at the header of a loop that was just entered by `op`.
+    pub fn after_operator(
+        &mut self,
+        op: &Operator,
+        builder: &mut FunctionBuilder,
+        state: &FuncTranslationState,
+    ) {
+        if let Operator::Loop { .. } = op {
+            if state.reachable() {
+                self.check(builder);
+            }
+        }
+    }
+
+    /// Subtract the pending fuel from the counter.
+    fn flush(&mut self, builder: &mut FunctionBuilder) {
+        if self.pending == 0 {
+            return;
+        }
+        let cost = self.pending as i64;
+        let (addr, fuel) = self.load_fuel(builder);
+        let fuel = builder.ins().iadd_imm(fuel, -cost);
+        builder.ins().store(
+            ir::MemFlags::trusted(),
+            fuel,
+            addr,
+            self.config.vmctx_offset,
+        );
+        self.pending = 0;
+    }
+
+    /// Call the out-of-fuel function if the counter is negative, and continue translating in a
+    /// new block afterwards.
+    pub fn check(&mut self, builder: &mut FunctionBuilder) {
+        debug_assert_eq!(self.pending, 0, "Fuel must be accounted for before a check");
+        let (addr, fuel) = self.load_fuel(builder);
+        let exhausted = builder.ins().icmp_imm(IntCC::SignedLessThan, fuel, 0);
+        let call_block = builder.create_block();
+        let continue_block = builder.create_block();
+        builder.ins().brnz(exhausted, call_block, &[]);
+        builder.ins().jump(continue_block, &[]);
+
+        builder.switch_to_block(call_block);
+        builder.seal_block(call_block);
+        builder.ins().call(self.out_of_fuel, &[addr]);
+        builder.ins().jump(continue_block, &[]);
+
+        builder.switch_to_block(continue_block);
+        builder.seal_block(continue_block);
+    }
+
+    /// Load the fuel counter, returning the VM context pointer and the counter value.
+    fn load_fuel(&self, builder: &mut FunctionBuilder) -> (ir::Value, ir::Value) {
+        let addr = builder.ins().global_value(self.pointer_type, self.vmctx);
+        let fuel = builder.ins().load(
+            ir::types::I64,
+            ir::MemFlags::trusted(),
+            addr,
+            self.config.vmctx_offset,
+        );
+        (addr, fuel)
+    }
+}
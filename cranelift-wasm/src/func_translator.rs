@@ -6,8 +6,9 @@
 
 use crate::code_translator::{bitcast_arguments, translate_operator, wasm_param_types};
 use crate::environ::{FuncEnvironment, ReturnMode, WasmResult};
+use crate::fuel::FuelMeter;
 use crate::state::{FuncTranslationState, ModuleTranslationState};
-use crate::translation_utils::get_vmctx_value_label;
+use crate::translation_utils::{get_vmctx_value_label, DefinedFuncIndex};
 use crate::wasm_unsupported;
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::{self, Block, InstBuilder, ValueLabel};
@@ -108,11 +109,22 @@ impl FuncTranslator {
         self.state.initialize(&builder.func.signature, exit_block);
 
         parse_local_decls(&mut reader, &mut builder, num_params, environ)?;
+
+        let mut fuel = match environ.fuel_config() {
+            Some(config) => {
+                let mut meter = FuelMeter::new(config, builder.func, environ)?;
+                meter.check(&mut builder);
+                Some(meter)
+            }
+            None => None,
+        };
+
         parse_function_body(
             module_translation_state,
             reader,
             &mut builder,
             &mut self.state,
+            fuel.as_mut(),
             environ,
         )?;
 
@@ -121,6 +133,59 @@ impl FuncTranslator {
     }
 }
 
+/// A function body whose translation has been deferred by `translate_module_deferred`.
+///
+/// Each job owns its own `FuncTranslator`, so jobs share no mutable state and can be sent to
+/// other threads and translated concurrently, with only the `ModuleTranslationState` and the
+/// embedder's module information shared between them.
+pub struct FunctionBodyJob<'data> {
+    /// The index of the defined function this body belongs to.
+    pub index: DefinedFuncIndex,
+
+    /// The binary WebAssembly function code, as accepted by `FuncTranslator::translate`.
+    pub body_bytes: &'data [u8],
+
+    /// The offset of `body_bytes` in the module, used for source locations.
+    pub body_offset: usize,
+
+    translator: FuncTranslator,
+}
+
+impl<'data> FunctionBodyJob<'data> {
+    /// Create a new job for translating the function body `body_bytes`.
+    pub fn new(index: DefinedFuncIndex, body_bytes: &'data [u8], body_offset: usize) -> Self {
+        Self {
+            index,
+            body_bytes,
+            body_offset,
+            translator: FuncTranslator::new(),
+        }
+    }
+
+    /// Translate the function body into `func`.
+    ///
+    /// See `FuncTranslator::translate` for the requirements on `func`.
+    pub fn translate<FE: FuncEnvironment + ?Sized>(
+        &mut self,
+        module_translation_state: &ModuleTranslationState,
+        func: &mut ir::Function,
+        environ: &mut FE,
+    ) -> WasmResult<()> {
+        self.translator.translate(
+            module_translation_state,
+            self.body_bytes,
+            self.body_offset,
+            func,
+            environ,
+        )
+    }
+
+    /// Consume the job and return its translator, so it can be reused for other functions.
+    pub fn into_translator(self) -> FuncTranslator {
+        self.translator
+    }
+}
+
 /// Declare local variables for the signature parameters that correspond to WebAssembly locals.
 ///
 /// Return the number of local variables declared.
@@ -143,6 +208,7 @@ fn declare_wasm_parameters<FE: FuncEnvironment + ?Sized>(
 
             let param_value = builder.block_params(entry_block)[i];
             builder.def_var(local, param_value);
+            builder.set_val_label(param_value, ValueLabel::new(local.index()));
         }
         if param_type.purpose == ir::ArgumentPurpose::VMContext {
             let param_value = builder.block_params(entry_block)[i];
@@ -222,6 +288,7 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     mut reader: BinaryReader,
     builder: &mut FunctionBuilder,
     state: &mut FuncTranslationState,
+    mut fuel: Option<&mut FuelMeter>,
     environ: &mut FE,
 ) -> WasmResult<()> {
     // The control stack is initialized with a single block representing the whole function.
@@ -231,9 +298,15 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     while !state.control_stack.is_empty() {
         builder.set_srcloc(cur_srcloc(&reader));
         let op = reader.read_operator()?;
+        if let Some(ref mut fuel) = fuel {
+            fuel.before_operator(&op, builder, state);
+        }
         environ.before_translate_operator(&op, builder, state)?;
         translate_operator(module_translation_state, &op, builder, state, environ)?;
         environ.after_translate_operator(&op, builder, state)?;
+        if let Some(ref mut fuel) = fuel {
+            fuel.after_operator(&op, builder, state);
+        }
     }
 
     // The final `End` operator left us in the exit block where we need to manually add a return
@@ -276,11 +349,14 @@ fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
 #[cfg(test)]
 mod tests {
     use super::{FuncTranslator, ReturnMode};
-    use crate::environ::DummyEnvironment;
-    use crate::ModuleTranslationState;
-    use cranelift_codegen::ir::types::I32;
+    use crate::environ::{DummyEnvironment, ModuleEnvironment};
+    use crate::fuel::FuelConfig;
+    use crate::{Memory, ModuleTranslationState};
+    use cranelift_codegen::ir::immediates::Offset32;
+    use cranelift_codegen::ir::types::{I32, I64};
     use cranelift_codegen::{ir, isa, settings, Context};
     use log::debug;
+    use std::vec::Vec;
     use target_lexicon::PointerWidth;
 
     #[test]
@@ -430,4 +506,135 @@ mod tests {
         debug!("{}", ctx.func.display(None));
         ctx.verify(&flags).unwrap();
     }
+
+    #[test]
+    fn fuel_metering() {
+        // The infinite loop from above, with fuel metering.
+        const BODY: [u8; 16] = [
+            0x01, // 1 local decl.
+            0x01, 0x7f, // 1 i32 local.
+            0x03, 0x7f, // loop i32
+            0x20, 0x00, // get_local 0
+            0x41, 0x01, // i32.const 0
+            0x6a, // i32.add
+            0x21, 0x00, // set_local 0
+            0x0c, 0x00, // br 0
+            0x0b, // end
+            0x0b, // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let flags = settings::Flags::new(settings::builder());
+        let mut runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        runtime.enable_fuel_metering(FuelConfig::new(16));
+
+        let module_translation_state = ModuleTranslationState::new();
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::ExternalName::testcase("fuel_metering");
+        ctx.func
+            .signature
+            .params
+            .push(ir::AbiParam::special(I64, ir::ArgumentPurpose::VMContext));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        trans
+            .translate(
+                &module_translation_state,
+                &BODY,
+                0,
+                &mut ctx.func,
+                &mut runtime.func_env(),
+            )
+            .unwrap();
+        debug!("{}", ctx.func.display(None));
+        ctx.verify(&flags).unwrap();
+
+        // The counter is checked at function entry and at the loop header, and the cost of the
+        // loop body is subtracted once per iteration.
+        let opcodes: Vec<_> = ctx
+            .func
+            .layout
+            .blocks()
+            .flat_map(|block| ctx.func.layout.block_insts(block))
+            .map(|inst| ctx.func.dfg[inst].opcode())
+            .collect();
+        let count = |opcode: ir::Opcode| opcodes.iter().filter(|&&op| op == opcode).count();
+        assert_eq!(count(ir::Opcode::Call), 2);
+        assert_eq!(count(ir::Opcode::Store), 1);
+        assert_eq!(count(ir::Opcode::IaddImm), 1);
+    }
+
+    #[test]
+    fn second_memory_grow() {
+        // Growing the second of two memories.
+        //
+        // (func $second_memory_grow (param i32) (result i32)
+        //     (memory.grow 1 (get_local 0))
+        // )
+        const BODY: [u8; 6] = [
+            0x00, // local decl count
+            0x20, 0x00, // get_local 0
+            0x40, 0x01, // memory.grow 1
+            0x0b, // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let flags = settings::Flags::new(settings::builder());
+        let mut runtime = DummyEnvironment::new(
+            isa::TargetFrontendConfig {
+                default_call_conv: isa::CallConv::Fast,
+                pointer_width: PointerWidth::U64,
+            },
+            ReturnMode::NormalReturns,
+            false,
+        );
+        for _ in 0..2 {
+            runtime
+                .declare_memory(Memory {
+                    minimum: 1,
+                    maximum: None,
+                    shared: false,
+                })
+                .unwrap();
+        }
+
+        let module_translation_state = ModuleTranslationState::new();
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::ExternalName::testcase("second_memory_grow");
+        ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        ctx.func
+            .signature
+            .params
+            .push(ir::AbiParam::special(I64, ir::ArgumentPurpose::VMContext));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        trans
+            .translate(
+                &module_translation_state,
+                &BODY,
+                0,
+                &mut ctx.func,
+                &mut runtime.func_env(),
+            )
+            .unwrap();
+        debug!("{}", ctx.func.display(None));
+        ctx.verify(&flags).unwrap();
+
+        // The only heap is the second memory's, whose base address follows the first one's.
+        assert_eq!(ctx.func.heaps.len(), 1);
+        let heap = ctx.func.heaps.keys().next().unwrap();
+        match ctx.func.global_values[ctx.func.heaps[heap].base] {
+            ir::GlobalValueData::Load { offset, .. } => assert_eq!(offset, Offset32::new(8)),
+            ref gv => panic!("unexpected heap base {}", gv),
+        }
+    }
 }
@@ -50,25 +50,32 @@ use std::collections::{
 };
 
 mod code_translator;
+mod debug_line;
 mod environ;
+mod fuel;
 mod func_translator;
 mod module_translator;
 mod sections_translator;
 mod state;
 mod translation_utils;
 
+pub use crate::debug_line::{LineTable, SourceLine};
 pub use crate::environ::{
     DummyEnvironment, FuncEnvironment, GlobalVariable, ModuleEnvironment, ReturnMode,
     TargetEnvironment, WasmError, WasmResult,
 };
-pub use crate::func_translator::FuncTranslator;
-pub use crate::module_translator::translate_module;
+pub use crate::fuel::{default_operator_cost, FuelConfig};
+pub use crate::func_translator::{FuncTranslator, FunctionBodyJob};
+pub use crate::module_translator::{
+    translate_module, translate_module_deferred, ModuleTranslation,
+};
 pub use crate::state::func_state::FuncTranslationState;
 pub use crate::state::module_state::ModuleTranslationState;
 pub use crate::translation_utils::{
-    get_vmctx_value_label, DefinedFuncIndex, DefinedGlobalIndex, DefinedMemoryIndex,
-    DefinedTableIndex, FuncIndex, Global, GlobalIndex, GlobalInit, Memory, MemoryIndex,
-    PassiveDataIndex, PassiveElemIndex, SignatureIndex, Table, TableElementType, TableIndex,
+    get_vmctx_value_label, ConstBinaryOp, ConstExpr, DefinedFuncIndex, DefinedGlobalIndex,
+    DefinedMemoryIndex, DefinedTableIndex, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
+    MemoryIndex, PassiveDataIndex, PassiveElemIndex, SignatureIndex, Table, TableElementType,
+    TableIndex,
 };
 
 /// Version number of this crate.
@@ -1,13 +1,18 @@
 //! Translation skeleton that traverses the whole WebAssembly module and call helper functions
 //! to deal with each part of it.
+use crate::debug_line::LineTable;
 use crate::environ::{ModuleEnvironment, WasmResult};
+use crate::func_translator::FunctionBodyJob;
 use crate::sections_translator::{
-    parse_code_section, parse_data_section, parse_element_section, parse_export_section,
-    parse_function_section, parse_global_section, parse_import_section, parse_memory_section,
-    parse_name_section, parse_start_section, parse_table_section, parse_type_section,
+    parse_code_section, parse_code_section_deferred, parse_data_section, parse_element_section,
+    parse_export_section, parse_function_section, parse_global_section, parse_import_section,
+    parse_memory_section, parse_name_section, parse_start_section, parse_table_section,
+    parse_type_section,
 };
 use crate::state::ModuleTranslationState;
 use cranelift_codegen::timing;
+use log::warn;
+use std::vec::Vec;
 use wasmparser::{CustomSectionContent, ModuleReader, SectionContent};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a list of valid Cranelift IR
@@ -15,6 +20,43 @@ use wasmparser::{CustomSectionContent, ModuleReader, SectionContent};
 pub fn translate_module<'data>(
     data: &'data [u8],
     environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<ModuleTranslationState> {
+    translate_module_sections(data, environ, None)
+}
+
+/// The result of `translate_module_deferred`: the module-level translation state, and the
+/// function bodies which still have to be translated.
+pub struct ModuleTranslation<'data> {
+    /// Information decoded from the module that is needed to translate the function bodies.
+    pub state: ModuleTranslationState,
+
+    /// The function bodies of the code section, in order of their `DefinedFuncIndex`.
+    pub function_bodies: Vec<FunctionBodyJob<'data>>,
+}
+
+/// Translate all sections of a Wasm binary except for the function bodies, which are returned
+/// as independent jobs instead of being passed to `ModuleEnvironment::define_function_body`.
+///
+/// Every `FunctionBodyJob` is `Send` and owns its translator state, so embedders can translate
+/// and compile the bodies concurrently once this function returns.
+pub fn translate_module_deferred<'data>(
+    data: &'data [u8],
+    environ: &mut dyn ModuleEnvironment<'data>,
+) -> WasmResult<ModuleTranslation<'data>> {
+    let mut function_bodies = Vec::new();
+    let state = translate_module_sections(data, environ, Some(&mut function_bodies))?;
+    Ok(ModuleTranslation {
+        state,
+        function_bodies,
+    })
+}
+
+/// Walk the sections of a Wasm binary. Function bodies are collected into `deferred_bodies` if
+/// it is present, and otherwise defined in `environ` as they are encountered.
+fn translate_module_sections<'data>(
+    data: &'data [u8],
+    environ: &mut dyn ModuleEnvironment<'data>,
+    mut deferred_bodies: Option<&mut Vec<FunctionBodyJob<'data>>>,
 ) -> WasmResult<ModuleTranslationState> {
     let _tt = timing::wasm_translate_module();
     let mut reader = ModuleReader::new(data)?;
@@ -60,7 +102,11 @@ pub fn translate_module<'data>(
             }
 
             SectionContent::Code(code) => {
-                parse_code_section(code, &module_translation_state, environ)?;
+                module_translation_state.code_section_offset = section.range().start;
+                match deferred_bodies {
+                    Some(ref mut jobs) => parse_code_section_deferred(code, jobs)?,
+                    None => parse_code_section(code, &module_translation_state, environ)?,
+                }
             }
 
             SectionContent::Data(data) => {
@@ -77,12 +123,19 @@ pub fn translate_module<'data>(
                 content,
             } => match content {
                 Some(CustomSectionContent::Name(names)) => {
-                    parse_name_section(names, environ)?;
+                    parse_name_section(names, &mut module_translation_state, environ)?;
                 }
                 _ => {
                     let mut reader = binary.clone();
                     let len = reader.bytes_remaining();
                     let payload = reader.read_bytes(len)?;
+                    if name == ".debug_line" && environ.wants_line_table() {
+                        // Debug information is optional, so a malformed section isn't fatal.
+                        match LineTable::parse(payload) {
+                            Ok(table) => module_translation_state.line_table = Some(table),
+                            Err(e) => warn!("Ignoring invalid .debug_line section: {}", e),
+                        }
+                    }
                     environ.custom_section(name, payload)?;
                 }
             },
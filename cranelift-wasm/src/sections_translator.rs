@@ -8,11 +8,12 @@
 //! is handled, according to the semantics of WebAssembly, to only specific expressions that are
 //! interpreted on the fly.
 use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
+use crate::func_translator::FunctionBodyJob;
 use crate::state::ModuleTranslationState;
 use crate::translation_utils::{
-    tabletype_to_type, type_to_type, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
-    MemoryIndex, PassiveDataIndex, PassiveElemIndex, SignatureIndex, Table, TableElementType,
-    TableIndex,
+    tabletype_to_type, type_to_type, ConstBinaryOp, ConstExpr, DefinedFuncIndex, FuncIndex, Global,
+    GlobalIndex, GlobalInit, Memory, MemoryIndex, PassiveDataIndex, PassiveElemIndex,
+    SignatureIndex, Table, TableElementType, TableIndex,
 };
 use crate::{wasm_unsupported, HashMap};
 use core::convert::TryFrom;
@@ -21,13 +22,14 @@ use cranelift_codegen::ir::{self, AbiParam, Signature};
 use cranelift_entity::packed_option::ReservedValue;
 use cranelift_entity::EntityRef;
 use std::boxed::Box;
+use std::string::String;
 use std::vec::Vec;
 use wasmparser::{
     self, CodeSectionReader, Data, DataKind, DataSectionReader, Element, ElementItem, ElementItems,
     ElementKind, ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType,
     FunctionSectionReader, GlobalSectionReader, GlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType, NameSectionReader, Naming, NamingReader,
-    Operator, TableSectionReader, Type, TypeSectionReader,
+    ImportSectionReader, InitExpr, MemorySectionReader, MemoryType, NameSectionReader, Naming,
+    NamingReader, Operator, TableSectionReader, Type, TypeSectionReader,
 };
 
 /// Parses the Type section of the wasm module.
@@ -218,8 +220,6 @@ pub fn parse_global_section(
         } = entry?;
         let mut init_expr_reader = init_expr.get_binary_reader();
         let initializer = match init_expr_reader.read_operator()? {
-            Operator::I32Const { value } => GlobalInit::I32Const(value),
-            Operator::I64Const { value } => GlobalInit::I64Const(value),
             Operator::F32Const { value } => GlobalInit::F32Const(value.bits()),
             Operator::F64Const { value } => GlobalInit::F64Const(value.bits()),
             Operator::V128Const { value } => {
@@ -229,15 +229,7 @@ pub fn parse_global_section(
             Operator::RefFunc { function_index } => {
                 GlobalInit::RefFunc(FuncIndex::from_u32(function_index))
             }
-            Operator::GlobalGet { global_index } => {
-                GlobalInit::GetGlobal(GlobalIndex::from_u32(global_index))
-            }
-            ref s => {
-                return Err(wasm_unsupported!(
-                    "unsupported init expr in global section: {:?}",
-                    s
-                ));
-            }
+            _ => parse_const_expr(&init_expr, "global")?.into(),
         };
         let global = Global {
             ty: type_to_type(content_type, environ).unwrap(),
@@ -250,6 +242,62 @@ pub fn parse_global_section(
     Ok(())
 }
 
+/// Parses an integer constant expression of the extended-const proposal, made of constants,
+/// `global.get` and the `add`, `sub` and `mul` operators, for a segment or global in `section`.
+fn parse_const_expr(init_expr: &InitExpr, section: &str) -> WasmResult<ConstExpr> {
+    let mut reader = init_expr.get_binary_reader();
+    let mut stack = Vec::new();
+    while !reader.eof() {
+        let position = reader.original_position();
+        let op = match reader.read_operator()? {
+            Operator::I32Const { value } => {
+                stack.push(ConstExpr::I32Const(value));
+                continue;
+            }
+            Operator::I64Const { value } => {
+                stack.push(ConstExpr::I64Const(value));
+                continue;
+            }
+            Operator::GlobalGet { global_index } => {
+                stack.push(ConstExpr::GetGlobal(GlobalIndex::from_u32(global_index)));
+                continue;
+            }
+            Operator::End => break,
+            Operator::I32Add => ConstBinaryOp::I32Add,
+            Operator::I32Sub => ConstBinaryOp::I32Sub,
+            Operator::I32Mul => ConstBinaryOp::I32Mul,
+            Operator::I64Add => ConstBinaryOp::I64Add,
+            Operator::I64Sub => ConstBinaryOp::I64Sub,
+            Operator::I64Mul => ConstBinaryOp::I64Mul,
+            ref s => {
+                return Err(wasm_unsupported!(
+                    "unsupported init expr in {} section: {:?}",
+                    section,
+                    s
+                ));
+            }
+        };
+        let rhs = stack.pop();
+        let lhs = stack.pop();
+        match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => stack.push(ConstExpr::binary(op, lhs, rhs)),
+            _ => {
+                return Err(WasmError::InvalidWebAssembly {
+                    message: "operand stack underflow in init expr",
+                    offset: position,
+                })
+            }
+        }
+    }
+    match (stack.pop(), stack.is_empty()) {
+        (Some(expr), true) => Ok(expr),
+        _ => Err(WasmError::InvalidWebAssembly {
+            message: "init expr must produce exactly one value",
+            offset: reader.original_position(),
+        }),
+    }
+}
+
 /// Parses the Export section of the wasm module.
 pub fn parse_export_section<'data>(
     exports: ExportSectionReader<'data>,
@@ -324,22 +372,9 @@ pub fn parse_element_section<'data>(
                 table_index,
                 init_expr,
             } => {
-                let mut init_expr_reader = init_expr.get_binary_reader();
-                let (base, offset) = match init_expr_reader.read_operator()? {
-                    Operator::I32Const { value } => (None, value as u32 as usize),
-                    Operator::GlobalGet { global_index } => {
-                        (Some(GlobalIndex::from_u32(global_index)), 0)
-                    }
-                    ref s => {
-                        return Err(wasm_unsupported!(
-                            "unsupported init expr in element section: {:?}",
-                            s
-                        ));
-                    }
-                };
+                let offset = parse_const_expr(&init_expr, "element")?;
                 environ.declare_table_elements(
                     TableIndex::from_u32(table_index),
-                    base,
                     offset,
                     segments,
                 )?
@@ -369,6 +404,24 @@ pub fn parse_code_section<'data>(
     Ok(())
 }
 
+/// Parses the Code section of the wasm module into jobs for translating the function bodies
+/// later.
+pub fn parse_code_section_deferred<'data>(
+    code: CodeSectionReader<'data>,
+    jobs: &mut Vec<FunctionBodyJob<'data>>,
+) -> WasmResult<()> {
+    jobs.reserve(code.get_count() as usize);
+    for body in code {
+        let mut reader = body?.get_binary_reader();
+        let size = reader.bytes_remaining();
+        let offset = reader.original_position();
+        let index = DefinedFuncIndex::new(jobs.len());
+        let body_bytes = reader.read_bytes(size)?;
+        jobs.push(FunctionBodyJob::new(index, body_bytes, offset));
+    }
+    Ok(())
+}
+
 /// Parses the Data section of the wasm module.
 pub fn parse_data_section<'data>(
     data: DataSectionReader<'data>,
@@ -383,22 +436,9 @@ pub fn parse_data_section<'data>(
                 memory_index,
                 init_expr,
             } => {
-                let mut init_expr_reader = init_expr.get_binary_reader();
-                let (base, offset) = match init_expr_reader.read_operator()? {
-                    Operator::I32Const { value } => (None, value as u32 as usize),
-                    Operator::GlobalGet { global_index } => {
-                        (Some(GlobalIndex::from_u32(global_index)), 0)
-                    }
-                    ref s => {
-                        return Err(wasm_unsupported!(
-                            "unsupported init expr in data section: {:?}",
-                            s
-                        ))
-                    }
-                };
+                let offset = parse_const_expr(&init_expr, "data")?;
                 environ.declare_data_initialization(
                     MemoryIndex::from_u32(memory_index),
-                    base,
                     offset,
                     data,
                 )?;
@@ -414,34 +454,62 @@ pub fn parse_data_section<'data>(
 }
 
 /// Parses the Name section of the wasm module.
+///
+/// Malformed subsections are ignored, since the name section is only informative.
 pub fn parse_name_section<'data>(
     mut names: NameSectionReader<'data>,
+    module_translation_state: &mut ModuleTranslationState,
     environ: &mut dyn ModuleEnvironment<'data>,
 ) -> WasmResult<()> {
     while let Ok(subsection) = names.read() {
         match subsection {
+            wasmparser::Name::Module(module_name) => {
+                if let Ok(name) = module_name.get_name() {
+                    environ.declare_module_name(name)?;
+                }
+            }
             wasmparser::Name::Function(function_subsection) => {
-                if let Some(function_names) = function_subsection
-                    .get_map()
-                    .ok()
-                    .and_then(parse_function_name_subsection)
+                if let Some(function_names) =
+                    function_subsection.get_map().ok().and_then(parse_name_map)
                 {
                     for (index, name) in function_names {
-                        environ.declare_func_name(index, name)?;
+                        environ.declare_func_name(FuncIndex::from_u32(index), name)?;
+                    }
+                }
+            }
+            wasmparser::Name::Local(local_subsection) => {
+                let mut reader = match local_subsection.get_function_local_reader() {
+                    Ok(reader) => reader,
+                    Err(_) => continue,
+                };
+                for _ in 0..reader.get_count() {
+                    let function_locals = match reader.read() {
+                        Ok(function_locals) => function_locals,
+                        Err(_) => break,
+                    };
+                    if function_locals.func_index == std::u32::MAX {
+                        continue;
+                    }
+                    let func_index = FuncIndex::from_u32(function_locals.func_index);
+                    if let Some(local_names) =
+                        function_locals.get_map().ok().and_then(parse_name_map)
+                    {
+                        for (index, name) in local_names {
+                            module_translation_state.local_names[func_index]
+                                .insert(index, String::from(name));
+                            environ.declare_local_name(func_index, index, name)?;
+                        }
                     }
                 }
-                return Ok(());
             }
-            wasmparser::Name::Local(_) | wasmparser::Name::Module(_) => {}
         };
     }
     Ok(())
 }
 
-fn parse_function_name_subsection(
-    mut naming_reader: NamingReader<'_>,
-) -> Option<HashMap<FuncIndex, &str>> {
-    let mut function_names = HashMap::new();
+/// Read a name map of the name section, or return `None` if it is malformed.
+fn parse_name_map(mut naming_reader: NamingReader<'_>) -> Option<HashMap<u32, &str>> {
+    let mut names = HashMap::new();
     for _ in 0..naming_reader.get_count() {
         let Naming { index, name } = naming_reader.read().ok()?;
         if index == std::u32::MAX {
@@ -449,15 +517,12 @@ fn parse_function_name_subsection(
             return None;
         }
 
-        if function_names
-            .insert(FuncIndex::from_u32(index), name)
-            .is_some()
-        {
-            // If the function index has been previously seen, then we
+        if names.insert(index, name).is_some() {
+            // If the index has been previously seen, then we
             // break out of the loop and early return `None`, because these
             // should be unique.
             return None;
         }
     }
-    Some(function_names)
+    Some(names)
 }
@@ -1,6 +1,10 @@
-use crate::translation_utils::SignatureIndex;
-use cranelift_entity::PrimaryMap;
+use crate::debug_line::{LineTable, SourceLine};
+use crate::translation_utils::{FuncIndex, SignatureIndex};
+use crate::HashMap;
+use cranelift_codegen::ir;
+use cranelift_entity::{PrimaryMap, SecondaryMap};
 use std::boxed::Box;
+use std::string::String;
 
 /// Map of signatures to a function's parameter and return types.
 pub(crate) type WasmTypes =
@@ -19,6 +23,15 @@ pub struct ModuleTranslationState {
     /// This is used for translating multi-value Wasm blocks inside functions,
     /// which are encoded to refer to their type signature via index.
     pub(crate) wasm_types: WasmTypes,
+
+    /// Offset of the Code section payload in the module, which DWARF addresses are relative to.
+    pub(crate) code_section_offset: usize,
+
+    /// The decoded `.debug_line` section, if the environment asked for it.
+    pub(crate) line_table: Option<LineTable>,
+
+    /// Local variable names of each function from the name section, by local index.
+    pub(crate) local_names: SecondaryMap<FuncIndex, HashMap<u32, String>>,
 }
 
 impl ModuleTranslationState {
@@ -26,6 +39,38 @@ impl ModuleTranslationState {
     pub fn new() -> Self {
         Self {
             wasm_types: PrimaryMap::new(),
+            code_section_offset: 0,
+            line_table: None,
+            local_names: SecondaryMap::new(),
+        }
+    }
+
+    /// The line table of the module's `.debug_line` section.
+    ///
+    /// This is only decoded if `ModuleEnvironment::wants_line_table` returns `true`, and the
+    /// module has a valid `.debug_line` section.
+    pub fn line_table(&self) -> Option<&LineTable> {
+        self.line_table.as_ref()
+    }
+
+    /// Find the source line of the wasm code at `loc`, a source location set by the function
+    /// translator.
+    pub fn source_line(&self, loc: ir::SourceLoc) -> Option<SourceLine> {
+        if loc.is_default() {
+            return None;
         }
+        let address = (loc.bits() as usize).checked_sub(self.code_section_offset)?;
+        self.line_table.as_ref()?.lookup(address as u64)
+    }
+
+    /// Find the name of the wasm local that `label` stands for in the function `func_index`.
+    ///
+    /// The function translator labels the values of local `n` with `ValueLabel::from_u32(n)`, so
+    /// this is the name the name section gives to that local. The vmctx label has no name.
+    pub fn value_label_name(&self, func_index: FuncIndex, label: ir::ValueLabel) -> Option<&str> {
+        self.local_names
+            .get(func_index)?
+            .get(&label.as_u32())
+            .map(String::as_str)
     }
 }
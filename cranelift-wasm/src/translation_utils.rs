@@ -9,6 +9,7 @@ use cranelift_codegen::ir::immediates::V128Imm;
 use cranelift_frontend::FunctionBuilder;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
+use std::boxed::Box;
 use wasmparser;
 
 /// Index type of a function (imported or defined) inside the WebAssembly module.
@@ -68,7 +69,7 @@ pub struct PassiveElemIndex(u32);
 entity_impl!(PassiveElemIndex);
 
 /// WebAssembly global.
-#[derive(Debug, Clone, Copy, Hash)]
+#[derive(Debug, Clone, Hash)]
 pub struct Global {
     /// The type of the value stored in the global.
     pub ty: ir::Type,
@@ -79,7 +80,7 @@ pub struct Global {
 }
 
 /// Globals are initialized via the `const` operators or by referring to another import.
-#[derive(Debug, Clone, Copy, Hash)]
+#[derive(Debug, Clone, Hash)]
 pub enum GlobalInit {
     /// An `i32.const`.
     I32Const(i32),
@@ -97,10 +98,123 @@ pub enum GlobalInit {
     RefNullConst,
     /// A `ref.func <index>`.
     RefFunc(FuncIndex),
+    /// An integer expression over constants and other globals which couldn't be folded into
+    /// one of the variants above.
+    Expr(ConstExpr),
     ///< The global is imported from, and thus initialized by, a different module.
     Import,
 }
 
+impl From<ConstExpr> for GlobalInit {
+    fn from(expr: ConstExpr) -> Self {
+        match expr {
+            ConstExpr::I32Const(value) => GlobalInit::I32Const(value),
+            ConstExpr::I64Const(value) => GlobalInit::I64Const(value),
+            ConstExpr::GetGlobal(index) => GlobalInit::GetGlobal(index),
+            expr => GlobalInit::Expr(expr),
+        }
+    }
+}
+
+/// An integer constant expression, as used to initialize globals and to compute the offsets of
+/// element and data segments.
+///
+/// Besides constants and `global.get`, the extended-const proposal allows the `add`, `sub` and
+/// `mul` operators in these expressions. Sub-expressions that don't refer to a global are
+/// folded into constants when the expression is built.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstExpr {
+    /// An `i32.const`.
+    I32Const(i32),
+    /// An `i64.const`.
+    I64Const(i64),
+    /// A `global.get` of an imported global.
+    GetGlobal(GlobalIndex),
+    /// A binary operator applied to two sub-expressions.
+    Binary(ConstBinaryOp, Box<ConstExpr>, Box<ConstExpr>),
+}
+
+/// The binary operators allowed in a `ConstExpr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstBinaryOp {
+    /// `i32.add`
+    I32Add,
+    /// `i32.sub`
+    I32Sub,
+    /// `i32.mul`
+    I32Mul,
+    /// `i64.add`
+    I64Add,
+    /// `i64.sub`
+    I64Sub,
+    /// `i64.mul`
+    I64Mul,
+}
+
+impl ConstBinaryOp {
+    /// Apply the operator to two values, wrapping around like the wasm instruction does.
+    ///
+    /// `i32` values are represented as sign-extended `i64` values.
+    pub fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        let (lhs32, rhs32) = (lhs as i32, rhs as i32);
+        match self {
+            ConstBinaryOp::I32Add => i64::from(lhs32.wrapping_add(rhs32)),
+            ConstBinaryOp::I32Sub => i64::from(lhs32.wrapping_sub(rhs32)),
+            ConstBinaryOp::I32Mul => i64::from(lhs32.wrapping_mul(rhs32)),
+            ConstBinaryOp::I64Add => lhs.wrapping_add(rhs),
+            ConstBinaryOp::I64Sub => lhs.wrapping_sub(rhs),
+            ConstBinaryOp::I64Mul => lhs.wrapping_mul(rhs),
+        }
+    }
+
+    /// Does the operator produce an `i64` value?
+    fn is_64bit(self) -> bool {
+        match self {
+            ConstBinaryOp::I64Add | ConstBinaryOp::I64Sub | ConstBinaryOp::I64Mul => true,
+            _ => false,
+        }
+    }
+}
+
+impl ConstExpr {
+    /// Build `lhs op rhs`, folding it into a constant if neither side refers to a global.
+    pub fn binary(op: ConstBinaryOp, lhs: Self, rhs: Self) -> Self {
+        match (lhs.as_const(), rhs.as_const()) {
+            (Some(lhs), Some(rhs)) => {
+                let value = op.apply(lhs, rhs);
+                if op.is_64bit() {
+                    ConstExpr::I64Const(value)
+                } else {
+                    ConstExpr::I32Const(value as i32)
+                }
+            }
+            _ => ConstExpr::Binary(op, Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    /// Get the value of the expression if it is a constant, with `i32` values sign-extended.
+    pub fn as_const(&self) -> Option<i64> {
+        match *self {
+            ConstExpr::I32Const(value) => Some(i64::from(value)),
+            ConstExpr::I64Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Evaluate the expression, calling `global` for the value of each global it refers to.
+    ///
+    /// The result of an `i32` expression is sign-extended to `i64`; truncate it with `as u32`
+    /// to get an unsigned segment offset.
+    pub fn eval<F: Fn(GlobalIndex) -> i64>(&self, global: &F) -> i64 {
+        match *self {
+            ConstExpr::I32Const(value) => i64::from(value),
+            ConstExpr::I64Const(value) => value,
+            ConstExpr::GetGlobal(index) => global(index),
+            ConstExpr::Binary(op, ref lhs, ref rhs) => op.apply(lhs.eval(global), rhs.eval(global)),
+        }
+    }
+}
+
 /// WebAssembly table.
 #[derive(Debug, Clone, Copy, Hash)]
 pub struct Table {
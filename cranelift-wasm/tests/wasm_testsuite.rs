@@ -1,8 +1,14 @@
+use cranelift_codegen::ir::ValueLabelAssignments;
 use cranelift_codegen::isa;
 use cranelift_codegen::print_errors::pretty_verifier_error;
 use cranelift_codegen::settings::{self, Flags};
 use cranelift_codegen::verifier;
-use cranelift_wasm::{translate_module, DummyEnvironment, FuncIndex, ReturnMode};
+use cranelift_wasm::{
+    get_vmctx_value_label, translate_module, translate_module_deferred, ConstBinaryOp, ConstExpr,
+    DefinedFuncIndex, DummyEnvironment, FuncIndex, FunctionBodyJob, GlobalIndex, GlobalInit,
+    ReturnMode,
+};
+use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -36,6 +42,54 @@ fn testsuite() {
     }
 }
 
+#[test]
+fn deferred_function_bodies() {
+    let mut paths: Vec<_> = fs::read_dir("../wasmtests")
+        .unwrap()
+        .map(|r| r.unwrap().path())
+        .filter(|p| match p.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) => !stem.starts_with('.'),
+            None => false,
+        })
+        .collect();
+    paths.sort();
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().as_builder().finish(flags);
+    for path in paths {
+        println!("=== {} ===", path.display());
+        let data = read_module(&path);
+        let return_mode = ReturnMode::NormalReturns;
+
+        let mut inline_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+        translate_module(&data, &mut inline_environ).unwrap();
+
+        let mut deferred_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+        let translation = translate_module_deferred(&data, &mut deferred_environ).unwrap();
+        assert!(deferred_environ.info.function_bodies.is_empty());
+        deferred_environ
+            .define_function_bodies(&translation.state, translation.function_bodies)
+            .unwrap();
+
+        let inline_funcs = &inline_environ.info.function_bodies;
+        let deferred_funcs = &deferred_environ.info.function_bodies;
+        assert_eq!(inline_funcs.len(), deferred_funcs.len());
+        for (inline, deferred) in inline_funcs.values().zip(deferred_funcs.values()) {
+            assert_eq!(inline.to_string(), deferred.to_string());
+        }
+        assert_eq!(
+            inline_environ.func_bytecode_sizes,
+            deferred_environ.func_bytecode_sizes
+        );
+    }
+}
+
+#[test]
+fn function_body_jobs_are_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<FunctionBodyJob>();
+}
+
 #[test]
 fn use_fallthrough_return() {
     let flags = Flags::new(settings::builder());
@@ -49,7 +103,7 @@ fn use_name_section() {
     let data = wat::parse_str(
         r#"
         (module $module_name
-            (func $func_name (local $loc_name i32)
+            (func $func_name (param $param_name i32) (local $loc_name i32)
             )
         )"#,
     )
@@ -57,7 +111,10 @@ fn use_name_section() {
 
     let flags = Flags::new(settings::builder());
     let triple = triple!("riscv64");
-    let isa = isa::lookup(triple).unwrap().finish(flags.clone());
+    let isa = isa::lookup(triple)
+        .unwrap()
+        .as_builder()
+        .finish(flags.clone());
     let return_mode = ReturnMode::NormalReturns;
     let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
 
@@ -67,6 +124,128 @@ fn use_name_section() {
         dummy_environ.get_func_name(FuncIndex::from_u32(0)).unwrap(),
         "func_name"
     );
+    assert_eq!(
+        dummy_environ.get_local_name(FuncIndex::from_u32(0), 0),
+        Some("param_name")
+    );
+    assert_eq!(
+        dummy_environ.get_local_name(FuncIndex::from_u32(0), 1),
+        Some("loc_name")
+    );
+}
+
+#[test]
+fn value_label_names() {
+    let data = wat::parse_str(
+        r#"
+        (module
+            (func (param $param_name i32) (result i32) (local $loc_name i32)
+                local.get $param_name
+                local.set $loc_name
+                local.get $loc_name
+            )
+        )"#,
+    )
+    .unwrap();
+
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().as_builder().finish(flags);
+    let return_mode = ReturnMode::NormalReturns;
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, true);
+
+    let state = translate_module(data.as_ref(), &mut dummy_environ).unwrap();
+
+    let func_index = FuncIndex::from_u32(0);
+    let func = &dummy_environ.info.function_bodies[DefinedFuncIndex::from_u32(0)];
+    let mut names = HashSet::new();
+    for assignments in func.dfg.values_labels.as_ref().unwrap().values() {
+        if let ValueLabelAssignments::Starts(starts) = assignments {
+            for start in starts {
+                if start.label != get_vmctx_value_label() {
+                    names.insert(state.value_label_name(func_index, start.label).unwrap());
+                }
+            }
+        }
+    }
+    let expected: HashSet<_> = ["param_name", "loc_name"].iter().cloned().collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn debug_line_section() {
+    let mut data = wat::parse_str("(module (func))").unwrap();
+    #[rustfmt::skip]
+    let debug_line = [
+        37, 0, 0, 0, // unit_length
+        2, 0, // version
+        23, 0, 0, 0, // header_length
+        1, 1, 0xfb, 14, 10, // instruction length, is_stmt, line_base, line_range, opcode_base
+        0, 1, 1, 1, 1, 0, 0, 0, 1, // standard_opcode_lengths
+        0, // include_directories
+        b'm', b'.', b'c', 0, 0, 0, 0, 0, // file_names
+        3, 41, // DW_LNS_advance_line 41
+        1, // DW_LNS_copy
+        2, 16, // DW_LNS_advance_pc 16
+        0, 1, 1, // DW_LNE_end_sequence
+    ];
+    let name = b".debug_line";
+    data.push(0);
+    data.push((1 + name.len() + debug_line.len()) as u8);
+    data.push(name.len() as u8);
+    data.extend_from_slice(name);
+    data.extend_from_slice(&debug_line);
+
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().as_builder().finish(flags);
+    let return_mode = ReturnMode::NormalReturns;
+
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+    let state = translate_module(&data, &mut dummy_environ).unwrap();
+    assert!(state.line_table().is_none());
+
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, true);
+    let state = translate_module(&data, &mut dummy_environ).unwrap();
+    let line = state.line_table().unwrap().lookup(1).unwrap();
+    assert_eq!((line.file, line.line), ("m.c", 42));
+    assert!(state.line_table().unwrap().lookup(16).is_none());
+}
+
+#[test]
+fn extended_const_initializers() {
+    let data = read_module(Path::new("../wasmtests/extended-const.wat"));
+
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().as_builder().finish(flags);
+    let return_mode = ReturnMode::NormalReturns;
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+
+    translate_module(&data, &mut dummy_environ).unwrap();
+
+    // Expressions over imported globals are kept as a tree.
+    let globals = &dummy_environ.info.globals;
+    match globals[GlobalIndex::from_u32(2)].entity.initializer {
+        GlobalInit::Expr(ref expr) => {
+            assert_eq!(
+                *expr,
+                ConstExpr::Binary(
+                    ConstBinaryOp::I32Add,
+                    Box::new(ConstExpr::GetGlobal(GlobalIndex::from_u32(0))),
+                    Box::new(ConstExpr::I32Const(1024)),
+                )
+            );
+            assert_eq!(expr.eval(&|_| 4096), 5120);
+        }
+        ref init => panic!("unexpected initializer {:?}", init),
+    }
+
+    // Constant expressions are folded.
+    match globals[GlobalIndex::from_u32(3)].entity.initializer {
+        GlobalInit::I64Const(6) => {}
+        ref init => panic!("unexpected initializer {:?}", init),
+    }
 }
 
 fn read_file(path: &Path) -> io::Result<Vec<u8>> {
@@ -91,7 +270,10 @@ fn read_module(path: &Path) -> Vec<u8> {
 
 fn handle_module(data: Vec<u8>, flags: &Flags, return_mode: ReturnMode) {
     let triple = triple!("riscv64");
-    let isa = isa::lookup(triple).unwrap().finish(flags.clone());
+    let isa = isa::lookup(triple)
+        .unwrap()
+        .as_builder()
+        .finish(flags.clone());
     let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
 
     translate_module(&data, &mut dummy_environ).unwrap();
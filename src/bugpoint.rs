@@ -9,7 +9,7 @@ use cranelift_codegen::ir::{
     self, Block, FuncRef, Function, GlobalValueData, Inst, InstBuilder, InstructionData,
     StackSlots, TrapCode,
 };
-use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::isa::{BackendVariant, TargetIsa};
 use cranelift_codegen::Context;
 use cranelift_entity::PrimaryMap;
 use cranelift_reader::{parse_test, ParseOptions};
@@ -24,7 +24,7 @@ pub fn run(
     flag_isa: &str,
     verbose: bool,
 ) -> Result<(), String> {
-    let parsed = parse_sets_and_triple(flag_set, flag_isa)?;
+    let parsed = parse_sets_and_triple(flag_set, flag_isa, BackendVariant::Any)?;
     let fisa = parsed.as_fisa();
 
     let path = Path::new(&filename).to_path_buf();
@@ -15,6 +15,7 @@
 
 use clap::{App, Arg, SubCommand};
 use cranelift_codegen::dbg::LOG_FILENAME_PREFIX;
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::VERSION;
 use std::io::{self, Write};
 use std::option::Option;
@@ -95,6 +96,15 @@ fn add_target_flag<'a>() -> clap::Arg<'a, 'a> {
         .help("Specify the Cranelift target")
 }
 
+fn add_backend_flag<'a>() -> clap::Arg<'a, 'a> {
+    Arg::with_name("backend")
+        .takes_value(true)
+        .long("backend")
+        .possible_values(&["any", "legacy", "machinst"])
+        .default_value("any")
+        .help("Choose the backend for targets that have several")
+}
+
 fn add_print_flag<'a>() -> clap::Arg<'a, 'a> {
     Arg::with_name("print")
         .short("p")
@@ -131,6 +141,12 @@ fn get_vec(argument_vec: Option<clap::Values>) -> Vec<String> {
     ret_vec
 }
 
+/// Returns the backend variant selected with `--backend`.
+fn get_backend_variant(matches: &clap::ArgMatches) -> BackendVariant {
+    // Can be unwrapped because the value has a default and is one of the possible values.
+    matches.value_of("backend").unwrap().parse().unwrap()
+}
+
 fn add_wasm_or_compile<'a>(cmd: &str) -> clap::App<'a, 'a> {
     let about_str = match cmd {
         "wasm" => "Compiles Wasm binary/text into Cranelift IR and then into target language",
@@ -147,6 +163,7 @@ fn add_wasm_or_compile<'a>(cmd: &str) -> clap::App<'a, 'a> {
         .arg(add_disasm_flag())
         .arg(add_set_flag())
         .arg(add_target_flag())
+        .arg(add_backend_flag())
         .arg(add_input_file_arg())
         .arg(add_debug_flag())
         .arg(add_just_decode_flag())
@@ -169,6 +186,7 @@ fn main() {
                 .about("Run Cranelift tests")
                 .arg(add_verbose_flag())
                 .arg(add_time_flag())
+                .arg(add_backend_flag())
                 .arg(add_input_file_arg())
                 .arg(add_debug_flag()),
         )
@@ -227,6 +245,7 @@ fn main() {
             cranelift_filetests::run(
                 rest_cmd.is_present("verbose"),
                 rest_cmd.is_present("time-passes"),
+                get_backend_variant(rest_cmd),
                 &get_vec(rest_cmd.values_of("file")),
             )
             .map(|_time| ())
@@ -276,6 +295,7 @@ fn main() {
                 rest_cmd.is_present("time-passes"),
                 &get_vec(rest_cmd.values_of("set")),
                 target_val,
+                get_backend_variant(rest_cmd),
             )
         }
         ("wasm", Some(rest_cmd)) => {
@@ -300,6 +320,7 @@ fn main() {
                     rest_cmd.is_present("print-size"),
                     rest_cmd.is_present("time-passes"),
                     rest_cmd.is_present("value-ranges"),
+                    get_backend_variant(rest_cmd),
                 )
             };
 
@@ -3,6 +3,7 @@
 use crate::disasm::{print_all, PrintRelocs, PrintStackmaps, PrintTraps};
 use crate::utils::{parse_sets_and_triple, read_to_string};
 use cranelift_codegen::binemit::{MemoryCodeSink, NullRelocSink, NullStackmapSink, NullTrapSink};
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::settings::FlagsOrIsa;
 use cranelift_codegen::timing;
@@ -18,8 +19,9 @@ pub fn run(
     flag_report_times: bool,
     flag_set: &[String],
     flag_isa: &str,
+    backend_variant: BackendVariant,
 ) -> Result<(), String> {
-    let parsed = parse_sets_and_triple(flag_set, flag_isa)?;
+    let parsed = parse_sets_and_triple(flag_set, flag_isa, backend_variant)?;
 
     for filename in files {
         let path = Path::new(&filename);
@@ -31,6 +33,7 @@ pub fn run(
             &path.to_path_buf(),
             &name,
             parsed.as_fisa(),
+            backend_variant,
         )?;
     }
     Ok(())
@@ -43,10 +46,14 @@ fn handle_module(
     path: &PathBuf,
     name: &str,
     fisa: FlagsOrIsa,
+    backend_variant: BackendVariant,
 ) -> Result<(), String> {
     let buffer = read_to_string(&path).map_err(|e| format!("{}: {}", name, e))?;
-    let test_file =
-        parse_test(&buffer, ParseOptions::default()).map_err(|e| format!("{}: {}", name, e))?;
+    let options = ParseOptions {
+        backend_variant,
+        ..ParseOptions::default()
+    };
+    let test_file = parse_test(&buffer, options).map_err(|e| format!("{}: {}", name, e))?;
 
     // If we have an isa from the command-line, use that. Otherwise if the
     // file contains a unique isa, use that.
@@ -65,6 +72,7 @@ fn handle_module(
         if let Some(isa) = isa {
             let mut context = Context::new();
             context.func = func;
+            context.want_disasm = flag_disasm;
             let mut mem = vec![];
 
             // Compile and encode the result to machine code.
@@ -79,6 +87,7 @@ fn handle_module(
             if flag_disasm {
                 print_all(
                     isa,
+                    &context,
                     &mem,
                     code_info.code_size,
                     code_info.jumptables_size + code_info.rodata_size,
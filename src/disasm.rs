@@ -1,6 +1,6 @@
 use cfg_if::cfg_if;
 use cranelift_codegen::isa::TargetIsa;
-use cranelift_codegen::{binemit, ir};
+use cranelift_codegen::{binemit, ir, Context};
 use std::fmt::Write;
 
 pub struct PrintRelocs {
@@ -199,7 +199,7 @@ cfg_if! {
         }
     } else {
         pub fn print_disassembly(_: &dyn TargetIsa, _: &[u8]) -> Result<(), String> {
-            println!("\nNo disassembly available.");
+            // The listing printed by `print_instructions` is all we have without capstone.
             Ok(())
         }
     }
@@ -207,6 +207,7 @@ cfg_if! {
 
 pub fn print_all(
     isa: &dyn TargetIsa,
+    context: &Context,
     mem: &[u8],
     code_size: u32,
     rodata_size: u32,
@@ -215,6 +216,7 @@ pub fn print_all(
     stackmaps: &PrintStackmaps,
 ) -> Result<(), String> {
     print_bytes(&mem);
+    print_instructions(isa, context, &mem[0..code_size as usize]);
     print_disassembly(isa, &mem[0..code_size as usize])?;
     print_readonly_data(&mem[code_size as usize..(code_size + rodata_size) as usize]);
     println!("\n{}\n{}\n{}", &relocs.text, &traps.text, &stackmaps.text);
@@ -235,6 +237,44 @@ pub fn print_bytes(mem: &[u8]) {
     println!();
 }
 
+/// Print the instructions of the function compiled in `context`, with their offsets.
+///
+/// MachInst backends provide a listing of their final instructions when the context asks for a
+/// disassembly. For the other backends, each CLIF instruction is shown next to its machine code.
+pub fn print_instructions(isa: &dyn TargetIsa, context: &Context, mem: &[u8]) {
+    if let Some(ref result) = context.mach_compile_result {
+        if let Some(ref disasm) = result.disasm {
+            println!("\nInstructions:\n{}", disasm.trim_end());
+        }
+        return;
+    }
+
+    println!("\nInstructions:");
+    let func = &context.func;
+    let encinfo = isa.encoding_info();
+    for block in func.layout.blocks() {
+        println!("{}:", block);
+        for (offset, inst, size) in func.inst_offsets(block, &encinfo) {
+            let bytes = mem[offset as usize..(offset + size) as usize]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut line = format!(
+                "  {:04x}: {:<24} {}",
+                offset,
+                bytes,
+                func.dfg.display_inst(inst, isa)
+            );
+            let srcloc = func.srclocs[inst];
+            if !srcloc.is_default() {
+                write!(&mut line, "  ; {}", srcloc).unwrap();
+            }
+            println!("{}", line);
+        }
+    }
+}
+
 pub fn print_readonly_data(mem: &[u8]) {
     if mem.is_empty() {
         return;
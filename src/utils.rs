@@ -1,8 +1,8 @@
 //! Utility functions.
 
 use cranelift_codegen::isa;
-use cranelift_codegen::isa::IsaBackend;
 use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::isa::{BackendVariant, IsaBackend};
 use cranelift_codegen::machinst::MachBackend;
 use cranelift_codegen::settings::{self, FlagsOrIsa};
 use cranelift_reader::{parse_options, Location};
@@ -44,10 +44,11 @@ impl OwnedFlagsOrIsa {
     }
 }
 
-/// Parse "set" and "triple" commands.
+/// Parse "set" and "triple" commands, looking up the triple's backend with `backend_variant`.
 pub fn parse_sets_and_triple(
     flag_set: &[String],
     flag_triple: &str,
+    backend_variant: BackendVariant,
 ) -> Result<OwnedFlagsOrIsa, String> {
     let mut flag_builder = settings::builder();
     parse_options(
@@ -64,15 +65,16 @@ pub fn parse_sets_and_triple(
             Ok(triple) => triple,
             Err(parse_error) => return Err(parse_error.to_string()),
         };
-        let builder_or_backend = isa::lookup(triple).map_err(|err| match err {
-            isa::LookupError::SupportDisabled => {
-                format!("support for triple '{}' is disabled", triple_name)
-            }
-            isa::LookupError::Unsupported => format!(
-                "support for triple '{}' is not implemented yet",
-                triple_name
-            ),
-        })?;
+        let builder_or_backend =
+            isa::lookup_variant(triple, backend_variant).map_err(|err| match err {
+                isa::LookupError::SupportDisabled => {
+                    format!("support for triple '{}' is disabled", triple_name)
+                }
+                isa::LookupError::Unsupported => format!(
+                    "support for triple '{}' with the {} backend is not implemented yet",
+                    triple_name, backend_variant
+                ),
+            })?;
 
         match builder_or_backend {
             IsaBackend::Builder(mut isa_builder) => {
@@ -10,6 +10,7 @@
 use crate::disasm::{print_all, PrintRelocs, PrintStackmaps, PrintTraps};
 use crate::utils::parse_sets_and_triple;
 use cranelift_codegen::ir::DisplayFunctionAnnotations;
+use cranelift_codegen::isa::BackendVariant;
 use cranelift_codegen::print_errors::{pretty_error, pretty_verifier_error};
 use cranelift_codegen::settings::FlagsOrIsa;
 use cranelift_codegen::timing;
@@ -49,8 +50,9 @@ pub fn run(
     flag_print_size: bool,
     flag_report_times: bool,
     flag_calc_value_ranges: bool,
+    backend_variant: BackendVariant,
 ) -> Result<(), String> {
-    let parsed = parse_sets_and_triple(flag_set, flag_triple)?;
+    let parsed = parse_sets_and_triple(flag_set, flag_triple, backend_variant)?;
 
     for filename in files {
         let path = Path::new(&filename);
@@ -169,6 +171,7 @@ fn handle_module(
     let num_func_imports = dummy_environ.get_num_func_imports();
     let mut total_module_code_size = 0;
     let mut context = Context::new();
+    context.want_disasm = flag_print_disasm;
     for (def_index, func) in dummy_environ.info.function_bodies.iter() {
         context.func = func.clone();
 
@@ -234,6 +237,7 @@ fn handle_module(
                 context.func.display_with(DisplayFunctionAnnotations {
                     isa: fisa.isa,
                     value_ranges: value_ranges.as_ref(),
+                    linkage: None,
                 })
             );
             vprintln!(flag_verbose, "");
@@ -242,6 +246,7 @@ fn handle_module(
         if let Some((code_size, rodata_size)) = saved_sizes {
             print_all(
                 isa,
+                &context,
                 &mem,
                 code_size,
                 rodata_size,
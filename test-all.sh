@@ -47,6 +47,10 @@ cargo build
 banner "Rust unit tests"
 RUST_BACKTRACE=1 cargo test --all
 
+# The parallel wasm translator is off by default.
+banner "Rust unit tests with the parallel wasm translator"
+RUST_BACKTRACE=1 cargo test -p cranelift-wasm --features cranelift-wasm/parallel
+
 has_toolchain() {
     rustup toolchain list | grep -q $1
 }
@@ -1,6 +1,13 @@
+use cranelift_codegen::isa::BackendVariant;
+
 #[test]
 fn filetests() {
     // Run all the filetests in the following directories.
-    cranelift_filetests::run(false, false, &["filetests".into(), "docs".into()])
-        .expect("test harness");
+    cranelift_filetests::run(
+        false,
+        false,
+        BackendVariant::Any,
+        &["filetests".into(), "docs".into()],
+    )
+    .expect("test harness");
 }
@@ -0,0 +1,17 @@
+(module
+  (import "env" "memory_base" (global $memory_base i32))
+  (import "env" "table_base" (global $table_base i32))
+  (memory 1)
+  (table 4 funcref)
+
+  (global $stack_pointer (mut i32)
+    (i32.add (global.get $memory_base) (i32.const 1024)))
+  (global $scaled i64
+    (i64.mul (i64.const 3) (i64.add (i64.const 1) (i64.const 1))))
+
+  (elem (offset (i32.add (global.get $table_base) (i32.const 1))) $get_stack_pointer)
+  (data (offset (i32.add (global.get $memory_base) (i32.const 16))) "hello")
+
+  (func $get_stack_pointer (result i32)
+    (global.get $stack_pointer))
+)
@@ -0,0 +1,17 @@
+(module
+  (import "env" "memory" (memory 1))
+  (memory $local 2 4)
+  (export "local" (memory $local))
+
+  (func (export "load") (param i32) (result i32)
+    (i32.load (local.get 0)))
+
+  (func (export "store") (param i32 i32)
+    (i32.store (local.get 0) (local.get 1)))
+
+  (func (export "size") (result i32)
+    (memory.size))
+
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0)))
+)